@@ -0,0 +1,65 @@
+// Compares the hand-written `Relax` parser with the pest grammar.
+// Run with `cargo bench --features pest --bench parse`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::Serialize;
+use serde_annotate::{serialize, Annotate, Relax};
+
+#[derive(Serialize, Annotate)]
+struct Register {
+    #[annotate(comment = "Register name")]
+    name: String,
+    #[annotate(format=hex, comment = "Offset from the base address")]
+    offset: u32,
+    #[annotate(format=hex)]
+    reset: u64,
+    fields: Vec<Field>,
+}
+
+#[derive(Serialize, Annotate)]
+struct Field {
+    name: String,
+    #[annotate(comment = "Least significant bit")]
+    lsb: u8,
+    width: u8,
+    description: String,
+}
+
+fn registers() -> Vec<Register> {
+    (0..1000)
+        .map(|i| Register {
+            name: format!("REG{}", i),
+            offset: i * 4,
+            reset: 0xdead_beef_0000_0000 | i as u64,
+            fields: (0..8)
+                .map(|f| Field {
+                    name: format!("FIELD{}", f),
+                    lsb: f * 4,
+                    width: 4,
+                    description: "The field description".into(),
+                })
+                .collect(),
+        })
+        .collect()
+}
+
+fn bench_parse(c: &mut Criterion) {
+    let doc = serialize(&registers()).unwrap();
+    let inputs = [
+        ("json", doc.clone().to_json().to_string(), Relax::json()),
+        ("json5", doc.clone().to_json5().to_string(), Relax::json5()),
+        ("hjson", doc.to_hjson().to_string(), Relax::hjson()),
+    ];
+    for (name, text, relax) in inputs.iter() {
+        let mut group = c.benchmark_group(*name);
+        group.bench_function("pest", |b| {
+            b.iter(|| relax.from_str_pest(black_box(text)).unwrap())
+        });
+        group.bench_function("hand-written", |b| {
+            b.iter(|| relax.from_str(black_box(text)).unwrap())
+        });
+        group.finish();
+    }
+}
+
+criterion_group!(benches, bench_parse);
+criterion_main!(benches);
@@ -0,0 +1,145 @@
+// Compares serializing via a `Document` with streaming directly to a writer.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use serde::Serialize;
+use serde_annotate::{serialize, Annotate, Json, Yaml};
+
+#[derive(Serialize, Annotate)]
+struct Register {
+    #[annotate(comment = "Register name")]
+    name: String,
+    #[annotate(format=hex, comment = "Offset from the base address")]
+    offset: u32,
+    #[annotate(format=hex)]
+    reset: u64,
+    fields: Vec<Field>,
+}
+
+#[derive(Serialize, Annotate)]
+struct Field {
+    name: String,
+    #[annotate(comment = "Least significant bit")]
+    lsb: u8,
+    width: u8,
+    #[annotate(format=block)]
+    description: String,
+}
+
+#[derive(Serialize, Annotate)]
+struct Telemetry {
+    registers: Vec<Register>,
+    #[annotate(format=hexdump)]
+    #[serde(with = "serde_bytes")]
+    image: Vec<u8>,
+}
+
+fn telemetry() -> Telemetry {
+    Telemetry {
+        registers: (0..1000)
+            .map(|i| Register {
+                name: format!("REG{}", i),
+                offset: i * 4,
+                reset: 0xdead_beef_0000_0000 | i as u64,
+                fields: (0..8)
+                    .map(|f| Field {
+                        name: format!("FIELD{}", f),
+                        lsb: f * 4,
+                        width: 4,
+                        description: "The field description\nspans two lines".into(),
+                    })
+                    .collect(),
+            })
+            .collect(),
+        image: (0..65536).map(|i| i as u8).collect(),
+    }
+}
+
+// A flash image, which is far larger than the rest of the document.
+#[derive(Serialize, Annotate)]
+struct FlashImage {
+    #[annotate(format=hexdump)]
+    #[serde(with = "serde_bytes")]
+    image: Vec<u8>,
+}
+
+fn flash_image() -> FlashImage {
+    FlashImage {
+        image: (0..16 << 20).map(|i| (i * 7) as u8).collect(),
+    }
+}
+
+fn bench_serialize(c: &mut Criterion) {
+    let value = telemetry();
+    let mut buf = Vec::with_capacity(32 << 20);
+
+    let mut group = c.benchmark_group("json5");
+    group.bench_function("document", |b| {
+        b.iter(|| {
+            buf.clear();
+            serialize(black_box(&value))
+                .unwrap()
+                .to_json5()
+                .write_to(&mut buf)
+                .unwrap();
+        })
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| {
+            buf.clear();
+            Json::json5()
+                .serialize_to(black_box(&value), &mut buf)
+                .unwrap();
+        })
+    });
+    group.finish();
+
+    let mut group = c.benchmark_group("yaml");
+    group.bench_function("document", |b| {
+        b.iter(|| {
+            buf.clear();
+            serialize(black_box(&value))
+                .unwrap()
+                .to_yaml()
+                .write_to(&mut buf)
+                .unwrap();
+        })
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| {
+            buf.clear();
+            Yaml::default()
+                .serialize_to(black_box(&value), &mut buf)
+                .unwrap();
+        })
+    });
+    group.finish();
+}
+
+fn bench_hexdump(c: &mut Criterion) {
+    let value = flash_image();
+    let mut buf = Vec::with_capacity(80 << 20);
+
+    let mut group = c.benchmark_group("hexdump");
+    group.sample_size(10);
+    group.bench_function("document", |b| {
+        b.iter(|| {
+            buf.clear();
+            serialize(black_box(&value))
+                .unwrap()
+                .to_yaml()
+                .write_to(&mut buf)
+                .unwrap();
+        })
+    });
+    group.bench_function("stream", |b| {
+        b.iter(|| {
+            buf.clear();
+            Yaml::default()
+                .serialize_to(black_box(&value), &mut buf)
+                .unwrap();
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, bench_serialize, bench_hexdump);
+criterion_main!(benches);
@@ -81,7 +81,7 @@ impl Schema {
                 Document::Null => node.null += 1,
                 Document::Boolean(_) => node.boolean += 1,
                 Document::String(s, _) => node.check_str(s.as_str()),
-                Document::StaticStr(s, _) => node.check_str(s),
+                Document::Str(s, _) => node.check_str(s),
                 Document::Int(_) => node.integer += 1,
                 Document::Float(_) => node.float += 1,
                 _ => {
@@ -27,7 +27,10 @@ fn main() -> Result<()> {
     let args = Args::parse();
 
     let text = std::fs::read_to_string(&args.file)?;
-    let document = Document::parse(&text)?;
+    let document = match args.file.extension().and_then(|e| e.to_str()) {
+        Some("yaml" | "yml") => Document::from_yaml(&text)?,
+        _ => Document::parse(&text)?,
+    };
 
     let profile = ColorProfile::basic();
     let s = match args.format {
@@ -0,0 +1,40 @@
+//! The `validate` program checks documents against a JSON Schema and reports
+//! every violation along with the path to the offending value.
+use anyhow::{anyhow, Result};
+use clap::Parser;
+use serde_annotate::{Document, Schema};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+struct Args {
+    #[clap(short, long, value_parser)]
+    schema: PathBuf,
+
+    #[clap(name = "FILES")]
+    files: Vec<PathBuf>,
+}
+
+fn main() -> Result<()> {
+    let args = Args::parse();
+    let schema = Schema::parse(&std::fs::read_to_string(&args.schema)?)?;
+
+    let mut failed = 0;
+    for f in args.files {
+        let text = std::fs::read_to_string(&f)?;
+        let document = match f.extension().and_then(|e| e.to_str()) {
+            Some("yaml" | "yml") => Document::from_yaml(&text)?,
+            Some("toml") => Document::from_toml(&text)?,
+            _ => Document::parse(&text)?,
+        };
+        if let Err(errors) = schema.validate(&document) {
+            for e in errors {
+                println!("{}: {}", f.display(), e);
+            }
+            failed += 1;
+        }
+    }
+    if failed > 0 {
+        return Err(anyhow!("{} file(s) failed validation", failed));
+    }
+    Ok(())
+}
@@ -20,86 +20,295 @@ pub enum Format {
     Xxd,
 }
 
+/// Specifies the zero padding of an integer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Width {
+    /// Pad the integer to at least this many digits.
+    Digits(usize),
+    /// Pad the integer to the number of digits of its type (e.g. 8 hex
+    /// digits for a `u32`).
+    Natural,
+}
+
+/// Specifies how the digits of an integer are written.  Decimal integers
+/// are never zero-padded.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct Digits {
+    /// The zero padding of the integer.
+    pub width: Option<Width>,
+    /// Separates each group of this many digits with `_` (e.g. `0x0000_0040`).
+    pub group: Option<usize>,
+}
+
+/// Specifies where a member's comment is placed relative to the member.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Placement {
+    /// Place the comment on the lines before the member.
+    #[default]
+    Leading,
+    /// Place the comment at the end of the member's line.
+    Trailing,
+}
+
 /// Identifies a field or variant member of a struct/enum.
+///
+/// `Container` identifies the struct/enum itself: its comment precedes the
+/// value and its format and digits are the defaults of the fields which have
+/// none.
 pub enum MemberId<'a> {
     Name(&'a str),
     Index(u32),
     Variant,
+    Container,
 }
 
 /// Trait implemented on structs to inform the serializer about formatting
 /// options and comments.
+///
+/// `#[derive(Annotate)]` implements and registers this trait.  A hand-written
+/// implementation must be registered with [`register!`](crate::register) (or
+/// at runtime with [`register`]) before the serializer will consult it.
 pub trait Annotate {
     fn format(&self, variant: Option<&str>, field: &MemberId) -> Option<Format>;
     fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<String>;
+    /// Returns where the comment on `field` is placed.
+    fn placement(&self, _variant: Option<&str>, _field: &MemberId) -> Placement {
+        Placement::Leading
+    }
+    /// Returns how the digits of the integers in `field` are written.
+    fn digits(&self, _variant: Option<&str>, _field: &MemberId) -> Option<Digits> {
+        None
+    }
+}
+
+/// Registers a hand-written `Annotate` implementation.
+///
+/// `register!(Foo)` registers `Foo` under its own name.  If serde knows the
+/// type by another name (e.g. it has a `#[serde(rename)]` attribute), give
+/// the name as well: `register!(Foo, "Bar")`.
+///
+/// Values are matched to the registered type with their lifetimes erased, so
+/// the implementation must hold for every lifetime of the type.  Write the
+/// lifetime parameters of the type as `'_` (e.g. `register!(Foo<'_>)`) and
+/// implement `Annotate` for `Foo<'_>`; an implementation for `Foo<'static>`
+/// alone is rejected at compile time.  Name the type itself rather than a
+/// type alias, which would hide its lifetimes.  [`register_static`]
+/// registers annotations which don't depend on the value for any type.
+#[macro_export]
+macro_rules! register {
+    ($ty:ident $(<$($lt:lifetime),+>)?) => {
+        $crate::register!($ty $(<$($lt),+>)?, stringify!($ty));
+    };
+    ($($ty:ident)::+ $(<$($lt:lifetime),+>)?, $name:expr) => {
+        $crate::annotate::private::inventory::submit! {
+            $crate::annotate::private::Annotator::with_caster(
+                $name,
+                $crate::caster!($($ty)::+ $(<$($lt),+>)?),
+            )
+        }
+    };
+}
+
+/// Creates the [`Caster`](crate::annotate::Caster) for a type with a
+/// hand-written `Annotate` implementation.  The type is written as for
+/// [`register!`](crate::register).
+#[macro_export]
+macro_rules! caster {
+    (@elided '_) => {};
+    (@elided $lt:lifetime) => {
+        compile_error!(concat!(
+            "write the lifetime `",
+            stringify!($lt),
+            "` of an annotated type as `'_`"
+        ));
+    };
+    ($($ty:ident)::+ $(<$($lt:lifetime),+>)?) => {{
+        $($($crate::caster!(@elided $lt);)+)?
+        // Only compiles if `Annotate` is implemented for every lifetime of
+        // the type.
+        fn check<'a>(object: &'a $($ty)::+ $(<$($lt),+>)?) -> &'a dyn $crate::Annotate {
+            $crate::annotate::private::annotate(object)
+        }
+        fn cast<'a>(
+            object: &$crate::annotate::private::AnyPointer<'a>,
+        ) -> Option<&'a dyn $crate::Annotate> {
+            // Safety: `check` shows that the implementation holds for every
+            // lifetime of the type.
+            unsafe { object.downcast::<$($ty)::+ $(<$($lt),+>)?>() }.map(check)
+        }
+        $crate::annotate::Caster::new(cast)
+    }};
+}
+
+/// Casts a pointer to the value being serialized into the value's `Annotate`
+/// implementation.  Created by [`caster!`](crate::caster).
+#[derive(Clone, Copy)]
+pub struct Caster(private::CastFn);
+
+impl Caster {
+    #[doc(hidden)]
+    pub const fn new(cast: private::CastFn) -> Self {
+        Caster(cast)
+    }
+}
+
+/// Registers the `Annotate` implementation cast by `caster` for the type
+/// serde knows as `name`.
+///
+/// This is the runtime equivalent of [`register!`](crate::register) for types
+/// which aren't known when the program starts (e.g. types from a plugin):
+/// `annotate::register("Foo", caster!(Foo))`.
+pub fn register(name: &'static str, caster: Caster) {
+    private::Annotator::add(Box::leak(Box::new(private::Annotator::with_caster(
+        name, caster,
+    ))));
+}
+
+/// Registers `annotator` for every value serde serializes with the type name
+/// `name`.
+///
+/// Since the annotator is not given the value, this works for values of any
+/// type, including types from other crates.
+pub fn register_static(name: &'static str, annotator: &'static (dyn Annotate + Sync)) {
+    private::Annotator::add(Box::leak(Box::new(private::Annotator::new_static(
+        name, annotator,
+    ))));
 }
 
 pub mod private {
 
-    use super::Annotate;
+    use super::{Annotate, Caster};
     pub use inventory;
     use once_cell::sync::OnceCell;
+    use std::any::TypeId;
     use std::collections::HashMap;
-    use std::sync::Mutex;
+    use std::rc::Rc;
+    use std::sync::{Arc, Mutex, MutexGuard};
+
+    pub type CastFn = for<'a> fn(object: &AnyPointer<'a>) -> Option<&'a dyn Annotate>;
 
-    type CastFn = unsafe fn(object: *const ()) -> &'static dyn Annotate;
-    /// An `Annotator` holds the name of a struct and casting function that can
-    /// cast the named type into a `dyn Annotate` reference.
-    /// See the safety comment for `Annotator::new`.
+    /// An `Annotator` holds the serde name of a type, a function which can
+    /// cast a pointer to that type into a `dyn Annotate` reference and/or an
+    /// annotator for the annotations which don't depend on the value.
+    ///
+    /// The cast checks the type of the pointer, so a value serialized
+    /// through a proxy (e.g. `serialize_with`, `remote` or a custom
+    /// `Serialize` impl) gets the value-independent annotations instead.
     pub struct Annotator {
         name: &'static str,
-        into_annotate: CastFn,
+        into_annotate: Option<CastFn>,
+        statics: Option<&'static (dyn Annotate + Sync)>,
     }
     inventory::collect!(Annotator);
 
-    static ANNOTATORS: OnceCell<Mutex<HashMap<&'static str, CastFn>>> = OnceCell::new();
+    static ANNOTATORS: OnceCell<Mutex<HashMap<&'static str, Vec<&'static Annotator>>>> =
+        OnceCell::new();
+
     impl Annotator {
-        /// Creates a new `Annotator` for the named type.
-        /// Safety: `name` must be the name of a type that implements the
-        /// `Annotate` trait and `into_annotate` must cast the provided
-        /// pointer into a `&dyn Annotate` reference for that type.
-        pub const unsafe fn new(name: &'static str, into_annotate: CastFn) -> Self {
+        /// Creates a new `Annotator` for the type `T` with the serde name
+        /// `name`.  `statics` must provide the annotations of `T` which
+        /// don't depend on the value being serialized.
+        ///
+        /// # Safety
+        ///
+        /// `T` must have no lifetime parameters, or implement `Annotate` for
+        /// every lifetime: values of `T` with any lifetimes are given to the
+        /// implementation for `T`.
+        pub const unsafe fn new<T: Annotate + 'static>(
+            name: &'static str,
+            statics: &'static (dyn Annotate + Sync),
+        ) -> Self {
+            Annotator {
+                name,
+                into_annotate: Some(cast::<T>),
+                statics: Some(statics),
+            }
+        }
+
+        /// Creates a new `Annotator` for the type cast by `caster` with the
+        /// serde name `name` with no value-independent annotations.
+        pub const fn with_caster(name: &'static str, caster: Caster) -> Self {
+            Annotator {
+                name,
+                into_annotate: Some(caster.0),
+                statics: None,
+            }
+        }
+
+        /// Creates a new `Annotator` for a type which can't be identified
+        /// at runtime (e.g. a generic type).  Only the value-independent
+        /// `statics` annotations are available.
+        pub const fn new_static(
+            name: &'static str,
+            statics: &'static (dyn Annotate + Sync),
+        ) -> Self {
             Annotator {
                 name,
-                into_annotate,
+                into_annotate: None,
+                statics: Some(statics),
             }
         }
-        fn lookup(typename: &str) -> Option<CastFn> {
-            let annotators = ANNOTATORS
+
+        fn registry() -> MutexGuard<'static, HashMap<&'static str, Vec<&'static Annotator>>> {
+            ANNOTATORS
                 .get_or_init(|| {
-                    let mut casts = HashMap::new();
+                    let mut names = HashMap::<_, Vec<_>>::new();
                     for a in inventory::iter::<Annotator> {
-                        let previous = casts.insert(a.name, a.into_annotate);
-                        if previous.is_some() {
-                            panic!("Annotator typename {:?} duplicated.", a.name);
-                        }
+                        names.entry(a.name).or_default().push(a);
                     }
-                    Mutex::new(casts)
+                    Mutex::new(names)
                 })
                 .lock()
-                .unwrap();
-            annotators.get(typename).cloned()
+                .unwrap()
+        }
+
+        /// Adds an `Annotator` to the registry at runtime.
+        pub(crate) fn add(annotator: &'static Annotator) {
+            Self::registry()
+                .entry(annotator.name)
+                .or_default()
+                .push(annotator);
+        }
+
+        /// Finds the annotations for the serde type `typename`, the value of
+        /// which is (usually) pointed to by `object`.
+        ///
+        /// If more than one type is registered as `typename` and `object`
+        /// isn't one of them, there is no way to tell which annotations
+        /// apply, so none are used.
+        pub fn find<'a>(typename: &str, object: &AnyPointer<'a>) -> Option<&'a dyn Annotate> {
+            let annotators = Self::registry();
+            let candidates = annotators.get(typename)?;
+            candidates
+                .iter()
+                .find_map(|a| a.into_annotate.and_then(|cast| cast(object)))
+                .or(match candidates.as_slice() {
+                    [a] => a.statics.map(|a| a as &dyn Annotate),
+                    _ => None,
+                })
         }
 
-        /// Cast the `object` into a `dyn Annotate` reference.
-        pub fn cast<'a>(typename: &str, object: &AnyPointer<'a>) -> Option<&'a dyn Annotate> {
-            if object.is_null() {
-                None
-            } else {
-                let a = Self::lookup(typename).map(|cast| unsafe {
-                    // Safety: If we found the type, its safe to cast it to
-                    // dyn Annotate.  Cast the object and use transmute to
-                    // re-attach the lifetime 'a to the result.
-                    std::mem::transmute(cast(object.ptr))
-                });
-                a
+        /// Finds the annotations for the serde type `typename` which don't
+        /// depend on the value, for use when there is no value (e.g. when
+        /// describing the type).
+        pub(crate) fn find_static(typename: &str) -> Option<&'static dyn Annotate> {
+            match Self::registry().get(typename)?.as_slice() {
+                [a] => a.statics.map(|a| a as &dyn Annotate),
+                _ => None,
             }
         }
     }
 
+    fn cast<'a, T: Annotate + 'static>(object: &AnyPointer<'a>) -> Option<&'a dyn Annotate> {
+        // Safety: upheld by the callers of `Annotator::new`.
+        unsafe { object.downcast::<T>() }.map(|object| object as &dyn Annotate)
+    }
+
+    /// A type-erased reference to the value being serialized.
     #[derive(Clone)]
     pub struct AnyPointer<'a> {
         ptr: *const (),
+        type_id: TypeId,
         lifetime: std::marker::PhantomData<&'a ()>,
     }
 
@@ -110,19 +319,96 @@ pub mod private {
         {
             AnyPointer {
                 ptr: object as *const T as *const (),
+                type_id: typeid::of::<T>(),
                 lifetime: std::marker::PhantomData,
             }
         }
+    }
 
-        pub fn null() -> AnyPointer<'static> {
-            AnyPointer {
-                ptr: std::ptr::null(),
-                lifetime: std::marker::PhantomData,
+    /// Returns the `Annotate` implementation of `object`.
+    pub fn annotate<'a, T: Annotate + 'a>(object: &'a T) -> &'a dyn Annotate {
+        object
+    }
+
+    impl<'a> AnyPointer<'a> {
+        /// Returns the value if it is a `T` or one of the smart pointers
+        /// which forward serialization to a `T`.
+        ///
+        /// # Safety
+        ///
+        /// The type ids ignore lifetimes, so the value may be a `T` with
+        /// other lifetimes than `T`'s (e.g. a `Foo<'a>` for `T = Foo<'static>`).
+        /// The caller must only use the value through code which holds for
+        /// every lifetime of `T` (e.g. an `impl Annotate for Foo<'_>`), which
+        /// then sees no lifetime longer than the value's own.
+        pub unsafe fn downcast<T: 'a>(&self) -> Option<&'a T> {
+            let id = self.type_id;
+            // Safety: `self.ptr` was created from a reference to a value with
+            // the type identified by `self.type_id`, which lives for `'a`.
+            unsafe {
+                if id == typeid::of::<T>() {
+                    Some(&*(self.ptr as *const T))
+                } else if id == typeid::of::<&T>() {
+                    Some(*(self.ptr as *const &T))
+                } else if id == typeid::of::<Box<T>>() {
+                    Some(&**(self.ptr as *const Box<T>))
+                } else if id == typeid::of::<Rc<T>>() {
+                    Some(&**(self.ptr as *const Rc<T>))
+                } else if id == typeid::of::<Arc<T>>() {
+                    Some(&**(self.ptr as *const Arc<T>))
+                } else {
+                    None
+                }
             }
         }
+    }
+
+    /// Values which can be shown as ASCII text by the `fourcc` format in
+    /// comment templates.
+    pub trait FourCC {
+        fn fourcc(&self) -> String;
+    }
+
+    macro_rules! impl_fourcc {
+        ($($ty:ty)*) => {$(
+            impl FourCC for $ty {
+                fn fourcc(&self) -> String {
+                    self.to_le_bytes().as_slice().fourcc()
+                }
+            }
+        )*};
+    }
+    impl_fourcc!(u8 u16 u32 u64 u128 i8 i16 i32 i64 i128);
+
+    impl FourCC for [u8] {
+        fn fourcc(&self) -> String {
+            self.iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect()
+        }
+    }
+
+    impl<const N: usize> FourCC for [u8; N] {
+        fn fourcc(&self) -> String {
+            self.as_slice().fourcc()
+        }
+    }
 
-        pub fn is_null(&self) -> bool {
-            self.ptr.is_null()
+    impl<T: FourCC + ?Sized> FourCC for &T {
+        fn fourcc(&self) -> String {
+            (**self).fourcc()
         }
     }
+
+    /// Formats `value` as the ASCII characters of its little-endian bytes
+    /// (e.g. `0x50444653` is `SFDP`).  Unprintable bytes are shown as `.`.
+    pub fn fourcc<T: FourCC + ?Sized>(value: &T) -> String {
+        value.fourcc()
+    }
 }
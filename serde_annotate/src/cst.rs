@@ -0,0 +1,824 @@
+// A lossless syntax tree for the `Relax` json dialects.
+use std::fmt;
+
+use crate::doc_edit::{key_matches, path_string, ToDocPath};
+use crate::doc_iter::DocPath;
+use crate::document::{Document, StrFormat};
+use crate::error::Error;
+use crate::json::Json;
+use crate::relax::{Relax, Token};
+use crate::relax_parser::{is_line_terminator, is_whitespace, Lexer, Parser};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A concrete syntax tree of a json, json5 or hjson document.
+///
+/// Unlike a `Document`, a `Cst` keeps the text of the document exactly as it
+/// was written: whitespace, comments, commas, quotes and the spelling of
+/// every number.  It can be edited by path like a `Document`.  Edited values
+/// are rendered in the tree's `Json` style and indented to match their
+/// neighbours; the rest of the document is written back byte-for-byte.
+///
+/// `to_document` lowers the tree to a `Document`, e.g. for deserialization.
+pub struct Cst {
+    relax: Relax,
+    style: Json<'static>,
+    root: Entry,
+}
+
+// An entry in an object or array (or the root of the document) and the text
+// around it.
+struct Entry {
+    // Whitespace and comments before the entry.
+    leading: String,
+    key: Option<Key>,
+    value: Value,
+    // The comma after the value, if any, and the whitespace and comments up
+    // to the end of the line.
+    trailing: String,
+    // The position of the comma in `trailing`.
+    comma: Option<usize>,
+}
+
+struct Key {
+    text: String,
+    // The decoded key.
+    name: Document<'static>,
+    // The text between the key and its value, including the colon.
+    colon: String,
+}
+
+enum Value {
+    // The text of a scalar and whether it is an unquoted string.
+    Scalar(String, bool),
+    // The entries of an object or array and the text before the closing
+    // bracket.
+    Object(Vec<Entry>, String),
+    Array(Vec<Entry>, String),
+}
+
+impl Entry {
+    // Returns the indentation of the entry if it starts on its own line.
+    fn indent(&self) -> Option<&str> {
+        let (_, line) = self.leading.rsplit_once(is_line_terminator)?;
+        let end = line.find(|ch| !is_whitespace(ch)).unwrap_or(line.len());
+        Some(&line[..end])
+    }
+
+    fn named(&self, name: &str) -> bool {
+        self.key
+            .as_ref()
+            .is_some_and(|k| key_matches(&k.name, name))
+    }
+
+    fn add_comma(&mut self) {
+        // A comma after an unquoted string would be part of the string.
+        if self.comma.is_none() && !matches!(self.value, Value::Scalar(_, true)) {
+            self.trailing.insert(0, ',');
+            self.comma = Some(0);
+        }
+    }
+
+    fn remove_comma(&mut self) {
+        if let Some(i) = self.comma.take() {
+            self.trailing.remove(i);
+        }
+    }
+}
+
+impl Value {
+    fn entries_mut(&mut self) -> Option<&mut Vec<Entry>> {
+        match self {
+            Value::Object(entries, _) | Value::Array(entries, _) => Some(entries),
+            Value::Scalar(..) => None,
+        }
+    }
+}
+
+impl fmt::Display for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.leading)?;
+        if let Some(key) = &self.key {
+            f.write_str(&key.text)?;
+            f.write_str(&key.colon)?;
+        }
+        write!(f, "{}", self.value)?;
+        f.write_str(&self.trailing)
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (open, entries, end, close) = match self {
+            Value::Scalar(text, _) => return f.write_str(text),
+            Value::Object(entries, end) => ("{", entries, end, "}"),
+            Value::Array(entries, end) => ("[", entries, end, "]"),
+        };
+        f.write_str(open)?;
+        for entry in entries {
+            write!(f, "{}", entry)?;
+        }
+        f.write_str(end)?;
+        f.write_str(close)
+    }
+}
+
+// Builds the syntax tree of a document.  The `relax` parser must have
+// accepted (and indexed the lines of) the text.
+struct Builder<'r, 'a> {
+    relax: &'r Relax,
+    lex: Lexer<'a>,
+}
+
+impl<'r, 'a> Builder<'r, 'a> {
+    fn text(&self, start: usize, end: usize) -> String {
+        self.lex.text[start..end].into()
+    }
+
+    fn error<T>(&self, msg: &str, pos: usize) -> Result<T> {
+        Relax::syntax_error_at(true, msg, self.lex.text, pos)?;
+        unreachable!()
+    }
+
+    // Skips whitespace and comments.
+    fn trivia(&self, pos: usize) -> usize {
+        let mut pos = self.lex.match_whitespace(pos);
+        while let Some(end) = self.lex.match_comment(pos) {
+            pos = self.lex.match_whitespace(end);
+        }
+        pos
+    }
+
+    // Skips whitespace and comments up to the end of the line.
+    fn line_trivia(&self, mut pos: usize) -> usize {
+        loop {
+            match self.lex.char_at(pos) {
+                Some(ch) if is_whitespace(ch) && !is_line_terminator(ch) => pos += ch.len_utf8(),
+                Some('/') if self.lex.starts_with(pos, "/*") => match self.lex.match_comment(pos) {
+                    Some(end) => pos = end,
+                    None => return pos,
+                },
+                Some('/') if self.lex.starts_with(pos, "//") => pos = self.lex.line_end(pos),
+                Some('#') => pos = self.lex.line_end(pos),
+                _ => return pos,
+            }
+        }
+    }
+
+    fn root(&self) -> Result<Entry> {
+        let len = self.lex.text.len();
+        let pos = self.trivia(0);
+        let (value, end) = if pos < len {
+            self.value(pos)?
+        } else {
+            (Value::Scalar(String::new(), false), pos)
+        };
+        Ok(Entry {
+            leading: self.text(0, pos),
+            key: None,
+            value,
+            trailing: self.text(end, len),
+            comma: None,
+        })
+    }
+
+    fn key(&self, pos: usize) -> Result<(Key, usize)> {
+        let token = |end| Token {
+            input: self.lex.text,
+            start: pos,
+            end,
+        };
+        let (name, end) = if let Some(end) = self.lex.match_identifier(pos) {
+            (self.relax.handle_identifier(token(end))?, end)
+        } else if let Some(end) = self.lex.match_number(pos) {
+            (self.relax.handle_number(token(end))?, end)
+        } else if let Some(end) = self.lex.match_string(pos) {
+            (self.relax.handle_string(token(end))?, end)
+        } else {
+            return self.error("expected key", pos);
+        };
+        let colon = self.trivia(end);
+        if self.lex.byte(colon) != Some(b':') {
+            return self.error("expected `:`", colon);
+        }
+        let value = self.trivia(colon + 1);
+        let key = Key {
+            text: self.text(pos, end),
+            name: name.into_owned(),
+            colon: self.text(end, value),
+        };
+        Ok((key, value))
+    }
+
+    fn value(&self, pos: usize) -> Result<(Value, usize)> {
+        let scalar = |end| Ok((Value::Scalar(self.text(pos, end), false), end));
+        if self.lex.starts_with(pos, "null") || self.lex.starts_with(pos, "true") {
+            scalar(pos + 4)
+        } else if self.lex.starts_with(pos, "false") {
+            scalar(pos + 5)
+        } else if let Some(end) = self.lex.match_string(pos) {
+            if self.lex.match_unquoted(pos) == Some(end) {
+                // The string is the rest of the line; the line terminator
+                // and indentation which follow it are trivia.
+                let end = self.lex.line_end(pos);
+                Ok((Value::Scalar(self.text(pos, end), true), end))
+            } else {
+                scalar(end)
+            }
+        } else if let Some(end) = self.lex.match_number(pos) {
+            scalar(end)
+        } else if self.lex.byte(pos) == Some(b'{') {
+            let (entries, end, pos) = self.entries(pos + 1, true)?;
+            Ok((Value::Object(entries, end), pos))
+        } else if self.lex.byte(pos) == Some(b'[') {
+            let (entries, end, pos) = self.entries(pos + 1, false)?;
+            Ok((Value::Array(entries, end), pos))
+        } else {
+            self.error("expected value", pos)
+        }
+    }
+
+    // Finds the end of the text after a value: the comma, if any, and the
+    // rest of the line.  Returns the end and the position of the comma
+    // relative to `pos`.
+    fn trailing(&self, pos: usize) -> (usize, Option<usize>) {
+        let comma = self.trivia(pos);
+        if self.lex.byte(comma) == Some(b',') {
+            (self.line_trivia(comma + 1), Some(comma - pos))
+        } else {
+            (self.line_trivia(pos), None)
+        }
+    }
+
+    // Builds the entries of an object or array from the position after the
+    // opening bracket.  Returns the entries, the text before the closing
+    // bracket and the position after it.
+    fn entries(&self, mut pos: usize, object: bool) -> Result<(Vec<Entry>, String, usize)> {
+        let close = if object { b'}' } else { b']' };
+        let mut entries = Vec::new();
+        loop {
+            let start = self.trivia(pos);
+            if self.lex.byte(start) == Some(close) {
+                return Ok((entries, self.text(pos, start), start + 1));
+            }
+            let (key, value) = if object {
+                let (key, value) = self.key(start)?;
+                (Some(key), value)
+            } else {
+                (None, start)
+            };
+            let (value, end) = self.value(value)?;
+            let (next, comma) = self.trailing(end);
+            entries.push(Entry {
+                leading: self.text(pos, start),
+                key,
+                value,
+                trailing: self.text(end, next),
+                comma,
+            });
+            pos = next;
+        }
+    }
+}
+
+// Returns the position of the entry named by `element` in an object or array.
+fn find_entry(value: &Value, element: &DocPath) -> Option<usize> {
+    match (value, element) {
+        (Value::Object(entries, _), DocPath::Name(name)) => {
+            entries.iter().position(|e| e.named(name))
+        }
+        (Value::Array(entries, _), DocPath::Name(name)) => {
+            name.parse::<usize>().ok().filter(|&i| i < entries.len())
+        }
+        (Value::Array(entries, _), DocPath::Index(i)) => (*i < entries.len()).then_some(*i),
+        _ => None,
+    }
+}
+
+// Returns the entry at `path` and the indentation of the line it is on.
+fn entry_mut<'e>(root: &'e mut Entry, path: &[DocPath]) -> Result<(&'e mut Entry, String)> {
+    let mut entry = root;
+    let mut indent = entry.indent().unwrap_or_default().to_string();
+    for (i, element) in path.iter().enumerate() {
+        let n = find_entry(&entry.value, element)
+            .ok_or_else(|| Error::PathError(path_string(&path[..=i]), "not found"))?;
+        entry = &mut entry.value.entries_mut().unwrap()[n];
+        if let Some(s) = entry.indent() {
+            indent = s.to_string();
+        }
+    }
+    Ok((entry, indent))
+}
+
+// Returns the object or array addressed by all but the last element of
+// `path`, the indentation of its line and the last element.
+fn parent_mut<'e, 'p>(
+    root: &'e mut Entry,
+    path: &'p [DocPath<'p>],
+) -> Result<(&'e mut Entry, String, &'p DocPath<'p>)> {
+    let (last, parent) = path
+        .split_last()
+        .ok_or_else(|| Error::PathError(String::new(), "empty path"))?;
+    let (entry, indent) = entry_mut(root, parent)?;
+    match entry.value {
+        Value::Scalar(..) => Err(Error::PathError(
+            path_string(parent),
+            "not a mapping or sequence",
+        )),
+        _ => Ok((entry, indent, last)),
+    }
+}
+
+// Lowers `value`, which starts at `pos` in the text of the tree being parsed
+// by `parser`, to a `Document`.  The tree gives the structure; the parser
+// converts the tokens and groups comments with their values as
+// `Relax::from_str` would.  Returns the document and the position after it.
+fn lower<'a>(
+    parser: &mut Parser<'_, 'a>,
+    value: &Value,
+    pos: usize,
+) -> Result<(Document<'a>, usize)> {
+    let (entries, object) = match value {
+        Value::Scalar(..) => return parser.value(pos),
+        Value::Object(entries, _) => (entries, true),
+        Value::Array(entries, _) => (entries, false),
+    };
+    let mark = parser.mark();
+    let mut pos = parser.skip(pos + 1)?;
+    for entry in entries {
+        if entry.key.is_some() {
+            let (key, end) = parser.key(pos)?;
+            parser.push_value(key, pos, end);
+            // Skip the colon and the trivia around it.
+            pos = parser.skip(end)?;
+            pos = parser.skip(pos + 1)?;
+        }
+        let (value, end) = lower(parser, &entry.value, pos)?;
+        parser.push_spanned(value, pos, end);
+        pos = parser.comma(end)?;
+    }
+    let members = parser.members(mark, object)?;
+    let doc = if object {
+        Document::Mapping(members)
+    } else {
+        Document::Sequence(members)
+    };
+    Ok((doc, pos + 1))
+}
+
+// Renders `value` in `style` and builds its syntax tree.  Each new line of
+// the rendered text starts with `indent`.
+fn render(style: &Json, value: &Document, indent: &str, compact: bool) -> Result<Value> {
+    let compact = compact || matches!(value, Document::Compact(_));
+    let text = style.render(value.as_value()?, indent, compact)?;
+    let relax = Relax::default();
+    relax.from_str(&text)?;
+    let root = Builder {
+        relax: &relax,
+        lex: Lexer { text: &text },
+    }
+    .root()?;
+    // Only hjson multiline strings are preceded by whitespace.
+    match root.value {
+        Value::Scalar(text, unquoted) => Ok(Value::Scalar(root.leading + &text, unquoted)),
+        value => Ok(value),
+    }
+}
+
+impl Cst {
+    /// Parses a document in any of the json dialects accepted by
+    /// `Relax::default()`.  Edits are rendered as json5.
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::new(Relax::default(), Json::json5(), text)
+    }
+
+    /// Parses a strict json document.  Edits are rendered as json.
+    pub fn from_json(text: &str) -> Result<Self> {
+        Self::new(Relax::json(), Json::default(), text)
+    }
+
+    /// Parses a json5 document.  Edits are rendered as json5.
+    pub fn from_json5(text: &str) -> Result<Self> {
+        Self::new(Relax::json5(), Json::json5(), text)
+    }
+
+    /// Parses a hjson document.  Edits are rendered as hjson.
+    pub fn from_hjson(text: &str) -> Result<Self> {
+        Self::new(Relax::hjson(), Json::hjson(), text)
+    }
+
+    fn new(relax: Relax, style: Json<'static>, text: &str) -> Result<Self> {
+        relax.from_str(text)?;
+        let root = Builder {
+            relax: &relax,
+            lex: Lexer { text },
+        }
+        .root()?;
+        Ok(Cst { relax, style, root })
+    }
+
+    /// Sets the style used to render edits.  The style's comment format and
+    /// indentation are used for new entries in empty objects and arrays.
+    pub fn style(mut self, style: Json<'static>) -> Self {
+        self.style = style;
+        self
+    }
+
+    /// Converts the tree to a `Document`.
+    pub fn to_document(&self) -> Result<Document<'static>> {
+        // The text gives the tokens of the tree their positions (and so the
+        // lines which decide where comments belong).
+        let text = self.to_string();
+        self.relax.index_lines(&text);
+        let mut parser = Parser::new(&self.relax, &text);
+        let pos = parser.skip(0)?;
+        if pos < text.len() {
+            let (value, end) = lower(&mut parser, &self.root.value, pos)?;
+            parser.push_spanned(value, pos, end);
+            parser.skip(end)?;
+        }
+        Ok(parser.finish().into_owned())
+    }
+
+    /// Replaces the value at `path` with `value`.  The entry's key, comments
+    /// and comma are retained.
+    pub fn set<P: ToDocPath + ?Sized>(&mut self, path: &P, value: Document) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (entry, indent) = entry_mut(&mut self.root, &path)?;
+        // A value which shares its line with other entries stays on the line.
+        let compact = !path.is_empty() && entry.indent().is_none();
+        entry.value = render(&self.style, &value, &indent, compact)?;
+        Ok(())
+    }
+
+    /// Inserts `value` at `path` with an optional comment.
+    ///
+    /// As with `Document::insert`, a new key is appended to a mapping and a
+    /// value is inserted before the indexed element of a sequence.  The new
+    /// entry is laid out like its neighbours; the comment is omitted if the
+    /// style doesn't permit comments.
+    pub fn insert<P: ToDocPath + ?Sized>(
+        &mut self,
+        path: &P,
+        value: Document,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (parent, indent, last) = parent_mut(&mut self.root, &path)?;
+        let inline_parent = path.len() > 1 && parent.indent().is_none();
+        let (position, name) = match (&parent.value, last) {
+            (Value::Object(entries, _), DocPath::Name(name)) => {
+                if find_entry(&parent.value, last).is_some() {
+                    return Err(Error::PathError(path_string(&path), "already exists"));
+                }
+                (entries.len(), Some(*name))
+            }
+            (Value::Array(entries, _), element) => {
+                let index = match element {
+                    DocPath::Index(i) => Some(*i),
+                    DocPath::Name(n) => n.parse::<usize>().ok(),
+                };
+                match index {
+                    Some(i) if i <= entries.len() => (i, None),
+                    _ => return Err(Error::PathError(path_string(&path), "bad index")),
+                }
+            }
+            _ => return Err(Error::PathError(path_string(&path), "expected a key")),
+        };
+        let (Value::Object(entries, end) | Value::Array(entries, end)) = &mut parent.value else {
+            unreachable!()
+        };
+
+        // Lay the entry out like the one it displaces (or follows).
+        let neighbour = entries.get(position).or(entries.last());
+        let (sep, child_indent, inline) = match neighbour.map(Entry::indent) {
+            Some(Some(i)) => (format!("\n{}", i), i.to_string(), false),
+            Some(None) => (" ".to_string(), indent.clone(), true),
+            None if inline_parent && !end.contains(is_line_terminator) => {
+                (String::new(), indent.clone(), true)
+            }
+            None => {
+                let i = format!("{}{:2$}", indent, "", self.style.indent_width());
+                (format!("\n{}", i), i, false)
+            }
+        };
+        let key = match name {
+            Some(name) => {
+                let colon = neighbour
+                    .and_then(|e| e.key.as_ref())
+                    .map(|k| k.colon.as_str())
+                    .filter(|c| c.trim() == ":")
+                    .unwrap_or(": ");
+                Some(Key {
+                    text: self.style.render_key(name)?,
+                    name: Document::String(name.into(), StrFormat::Standard),
+                    colon: colon.into(),
+                })
+            }
+            None => None,
+        };
+        let commas = entries.len() < 2 || entries.iter().any(|e| e.comma.is_some());
+
+        let comment = match comment {
+            Some(comment) => self.style.render_comment(comment, &child_indent)?,
+            None => String::new(),
+        };
+        let mut leading = match entries.get_mut(position) {
+            // A comment on the line of the entry before it would trail that
+            // entry, so a commented entry in a single-line container starts
+            // on a line of its own.
+            Some(next) if inline && !comment.is_empty() => {
+                if next.leading.is_empty() {
+                    next.leading.push(' ');
+                }
+                format!("\n{}", child_indent)
+            }
+            // The first entry of a single-line container keeps its place
+            // after the bracket.
+            Some(first) if position == 0 && inline => std::mem::replace(&mut first.leading, sep),
+            _ if inline && !comment.is_empty() => format!("\n{}", child_indent),
+            _ => sep,
+        };
+        if !comment.is_empty() {
+            leading.push_str(&comment);
+            if !comment.trim_end_matches([' ', '\t']).ends_with('\n') {
+                leading.push('\n');
+                leading.push_str(&child_indent);
+            }
+        }
+        let comma = if position < entries.len() {
+            commas
+        } else if let Some(last) = entries.last_mut() {
+            let trailing_comma = last.comma.is_some();
+            if commas {
+                last.add_comma();
+            }
+            trailing_comma
+        } else {
+            if !inline && !end.contains(is_line_terminator) {
+                *end = format!("{}\n{}", end.trim_end(), indent);
+            }
+            false
+        };
+        let entry = Entry {
+            leading,
+            key,
+            value: render(&self.style, &value, &child_indent, inline)?,
+            trailing: if comma { ",".into() } else { String::new() },
+            comma: comma.then_some(0),
+        };
+        entries.insert(position, entry);
+        Ok(())
+    }
+
+    /// Removes the entry at `path`, including the comments preceding it.
+    pub fn remove<P: ToDocPath + ?Sized>(&mut self, path: &P) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (parent, _, last) = parent_mut(&mut self.root, &path)?;
+        let position = find_entry(&parent.value, last)
+            .ok_or_else(|| Error::PathError(path_string(&path), "not found"))?;
+        let entries = parent.value.entries_mut().unwrap();
+        let removed = entries.remove(position);
+        if let Some(next) = entries.get_mut(position) {
+            if position == 0 && removed.indent().is_none() && next.indent().is_none() {
+                next.leading = removed.leading;
+            }
+        } else if let Some(last) = entries.last_mut() {
+            // The new last entry takes the removed entry's place before the
+            // closing bracket: its comma only if the removed entry had one,
+            // and the same whitespace.
+            if removed.comma.is_none() {
+                last.remove_comma();
+            }
+            let rest = match removed.comma {
+                Some(i) => &removed.trailing[i + 1..],
+                None => &removed.trailing,
+            };
+            last.trailing.truncate(last.trailing.trim_end().len());
+            if rest.trim().is_empty() {
+                last.trailing.push_str(rest);
+            }
+        }
+        Ok(())
+    }
+
+    /// Renames the mapping key at `path` to `name`.
+    pub fn rename<P: ToDocPath + ?Sized>(&mut self, path: &P, name: &str) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (parent, _, last) = parent_mut(&mut self.root, &path)?;
+        let (Value::Object(..), DocPath::Name(_)) = (&parent.value, last) else {
+            return Err(Error::PathError(path_string(&path), "expected a key"));
+        };
+        if find_entry(&parent.value, &DocPath::Name(name)).is_some() {
+            return Err(Error::PathError(name.into(), "already exists"));
+        }
+        let position = find_entry(&parent.value, last)
+            .ok_or_else(|| Error::PathError(path_string(&path), "not found"))?;
+        let entry = &mut parent.value.entries_mut().unwrap()[position];
+        entry.key = Some(Key {
+            text: self.style.render_key(name)?,
+            name: Document::String(name.into(), StrFormat::Standard),
+            colon: entry.key.take().unwrap().colon,
+        });
+        Ok(())
+    }
+}
+
+impl fmt::Display for Cst {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.root)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const DOCUMENTS: &[&str] = &[
+        r#"{"foo": "bar", baz: 'boo', "n": [1, -2.5e3, 0x1F, 0b101, 0o17, +7, .5, 5., NaN]}"#,
+        r#"
+        // Leading comment
+        {
+          /* block */ key: value with spaces
+          "quoted" : "esc\"aped\n™",   // trailing
+          # hash comment
+          multi:
+            '''
+            first line
+              indented
+            '''
+          nested: { a: [true, false, null], b: {} } // same line
+          list: [
+            1
+            2, // two
+            3
+            // dangling
+          ]
+          0x10: hex
+        }
+        # trailing comment
+        "#,
+        "[1,2,3,]",
+        "[\n  1\n  ,2\n]",
+        "{a: 1 b: 2}",
+        "  \"str\"  ",
+        "1\r\n// crlf\r\n",
+        "# just a comment",
+        "",
+    ];
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        for text in DOCUMENTS {
+            let cst = Cst::parse(text)?;
+            assert_eq!(cst.to_string(), *text);
+            assert_eq!(
+                format!("{:?}", cst.to_document()?),
+                format!("{:?}", Document::parse(text)?.into_owned())
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_dialects() -> Result<()> {
+        assert!(Cst::from_json("[0x10]").is_err());
+        assert_eq!(Cst::from_json5("[0x10]")?.to_string(), "[0x10]");
+        assert!(Cst::from_hjson("[0x10]").is_err());
+        Ok(())
+    }
+
+    const SAMPLE: &str = r#"{
+  // The name of the thing
+  name: "widget",   // keep
+  regs: [
+    {offset: 0X10, size: 4},
+    {
+      offset: 0x20
+    }
+  ],
+  // Obsolete
+  old: true
+}"#;
+
+    #[test]
+    fn test_set() -> Result<()> {
+        let mut cst = Cst::parse(SAMPLE)?;
+        cst.set("regs[0].size", Document::Int(8u8.into()))?;
+        cst.set(
+            "regs[1]",
+            Document::Mapping(vec![Document::Fragment(vec![
+                Document::Str("offset", StrFormat::Standard),
+                Document::Int(crate::Int::new(0x40u32, crate::Base::Hex)),
+            ])]),
+        )?;
+        cst.set(
+            "regs[0]",
+            Document::Compact(Box::new(Document::parse("{a: [1, 2]}")?)),
+        )?;
+        assert_eq!(
+            cst.to_string(),
+            r#"{
+  // The name of the thing
+  name: "widget",   // keep
+  regs: [
+    {a: [1, 2]},
+    {
+      offset: 0x40
+    }
+  ],
+  // Obsolete
+  old: true
+}"#
+        );
+        assert!(cst.set("regs[2]", Document::Null).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_remove_rename() -> Result<()> {
+        let mut cst = Cst::parse(SAMPLE)?;
+        cst.remove("old")?;
+        cst.rename("name", "title")?;
+        cst.insert("size", Document::Int(4u8.into()), Some("Size in bytes"))?;
+        cst.insert("regs[0]", Document::Null, None)?;
+        cst.insert("regs[1].base", Document::Boolean(false), None)?;
+        cst.remove("regs[1].offset")?;
+        cst.insert("empty", Document::Sequence(vec![]), None)?;
+        cst.insert("empty[0]", Document::Null, Some("First"))?;
+        assert!(cst.insert("size", Document::Null, None).is_err());
+        assert!(cst.rename("size", "title").is_err());
+        assert!(cst.insert("regs[9]", Document::Null, None).is_err());
+        assert_eq!(
+            cst.to_string(),
+            r#"{
+  // The name of the thing
+  title: "widget",   // keep
+  regs: [
+    null,
+    {size: 4, base: false},
+    {
+      offset: 0x20
+    }
+  ],
+  // Size in bytes
+  size: 4,
+  empty: [
+    // First
+    null
+  ]
+}"#
+        );
+        assert!(!bool::try_from(cst.to_document()?.get("regs[1].base")?)?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_hjson() -> Result<()> {
+        let mut cst = Cst::from_hjson("{\n  # names\n  a: one\n  b: two\n}")?;
+        cst.insert("c", Document::Str("three", StrFormat::Standard), None)?;
+        cst.remove("a")?;
+        cst.set("b", Document::Int(2u8.into()))?;
+        assert_eq!(cst.to_string(), "{\n  b: 2\n  c: \"three\"\n}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_edit_inline() -> Result<()> {
+        let mut cst = Cst::from_json("[1, 2]")?;
+        cst.insert("[0]", Document::Int(0u8.into()), None)?;
+        cst.insert("[3]", Document::Int(3u8.into()), Some("dropped"))?;
+        assert_eq!(cst.to_string(), "[0, 1, 2, 3]");
+        cst.remove("[0]")?;
+        cst.remove("[2]")?;
+        assert_eq!(cst.to_string(), "[1, 2]");
+
+        let mut cst = Cst::parse("{a: 1, b: [1, 2, 3], c: [4, 5 ,]}")?;
+        cst.remove("b[2]")?;
+        assert_eq!(cst.to_string(), "{a: 1, b: [1, 2], c: [4, 5 ,]}");
+        cst.remove("b[0]")?;
+        cst.remove("c[1]")?;
+        assert_eq!(cst.to_string(), "{a: 1, b: [2], c: [4,]}");
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_comment_inline() -> Result<()> {
+        let mut cst = Cst::parse("{a: 1, b: 2}")?;
+        cst.insert("c", Document::Int(3u8.into()), Some("Three"))?;
+        assert_eq!(cst.to_string(), "{a: 1, b: 2,\n// Three\nc: 3}");
+        // The comment belongs to the new key rather than trailing `b`.
+        let doc = cst.to_document()?;
+        let Document::Mapping(m) = &doc else {
+            panic!("expected a mapping: {:?}", doc);
+        };
+        assert!(m[1].fragments()?.iter().all(|n| n.comment().is_none()));
+        assert!(matches!(&m[2].fragments()?[0], Document::Comment(c, _) if c == "Three"));
+
+        let mut cst = Cst::parse("[1, 2]")?;
+        cst.insert("[0]", Document::Int(0u8.into()), Some("Zero"))?;
+        assert_eq!(cst.to_string(), "[\n// Zero\n0, 1, 2]");
+        Ok(())
+    }
+}
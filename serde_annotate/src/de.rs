@@ -1,27 +1,31 @@
 // Deserializer for serde-annotate `Document`s.
 
+use serde::de::value::BorrowedStrDeserializer;
 use serde::de::{
     self, DeserializeOwned, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess,
     VariantAccess, Visitor,
 };
 
-use crate::doc_iter::DocPath;
-use crate::document::Document;
-use crate::error::Error;
+use crate::doc_iter::{DocPath, OwnedDocPath};
+use crate::document::{Document, Span};
+use crate::error::{Error, Location};
 use crate::hexdump;
+use crate::integer::Int;
+use crate::relax::Relax;
+use std::marker::PhantomData;
 
 type Result<T> = std::result::Result<T, Error>;
 
 /// Deserialize an owned document.
 pub struct Deserialize {
-    doc: Document,
+    doc: Document<'static>,
 }
 
 impl TryFrom<&str> for Deserialize {
     type Error = Error;
     /// Parses a document from a `&str` and returns a `Deserialize`.
     fn try_from(text: &str) -> Result<Self> {
-        let doc = Document::parse(text)?;
+        let doc = Document::parse(text)?.into_owned();
         Ok(Deserialize { doc })
     }
 }
@@ -35,7 +39,7 @@ impl Deserialize {
     /// not modify the document.
     pub fn transform<F>(mut self, f: F) -> Self
     where
-        F: Fn(&[DocPath], &str, &Document) -> Option<Document>,
+        F: Fn(&[DocPath], &str, &Document) -> Option<Document<'static>>,
     {
         for (path, doc) in self.doc.iter_path_mut() {
             let path_str = path
@@ -53,66 +57,237 @@ impl Deserialize {
     /// Converts the owned document into type `T`.
     pub fn into<T: DeserializeOwned>(self) -> Result<T> {
         let mut ds = Deserializer::from_document(&self.doc)?;
-        T::deserialize(&mut ds)
+        T::deserialize(&mut ds).map_err(|e| ds.locate(e))
+    }
+}
+
+// The text a document was parsed from, used to describe error locations.
+#[derive(Clone, Copy)]
+struct Source<'a> {
+    name: Option<&'a str>,
+    text: &'a str,
+}
+
+mod private {
+    use super::*;
+
+    // Decides how a `Deserializer` visits the strings owned by its document.
+    pub trait Strings<'doc, 'de> {
+        fn visit_str<V: Visitor<'de>>(s: &'doc str, v: V) -> Result<V::Value>;
+    }
+}
+use private::Strings;
+
+/// Visits the strings owned by a document as borrowed from the document.
+pub enum Borrowed {}
+
+impl<'de> Strings<'de, 'de> for Borrowed {
+    fn visit_str<V: Visitor<'de>>(s: &'de str, v: V) -> Result<V::Value> {
+        v.visit_borrowed_str(s)
+    }
+}
+
+// Visits the strings owned by a document as transient, for documents which
+// don't outlive the value being deserialized.
+enum Transient {}
+
+impl<'de> Strings<'_, 'de> for Transient {
+    fn visit_str<V: Visitor<'de>>(s: &str, v: V) -> Result<V::Value> {
+        v.visit_str(s)
     }
 }
 
 /// A `Deserializer` deserializes a parsed document.
-pub struct Deserializer<'de> {
-    pub(crate) doc: &'de Document,
+///
+/// `'doc` is the lifetime of the document and `'de` the lifetime of the text
+/// it was parsed from.  Strings the document borrows from the text can always
+/// be deserialized as `&'de str`.  Strings the document owns (e.g. because
+/// they contained escapes) can only be borrowed when the document itself
+/// lives for `'de`, as it does for [`Deserializer::from_document`].
+pub struct Deserializer<'doc, 'de, S = Borrowed> {
+    doc: &'doc Document<'de>,
+    span: Option<Span>,
+    source: Option<Source<'doc>>,
+    wrapping: bool,
+    strings: PhantomData<S>,
 }
 
-impl<'de> Deserializer<'de> {
+impl<'de> Deserializer<'de, 'de> {
     /// Creates a `Deserializer` from a parsed document.
-    pub fn from_document(doc: &'de Document) -> Result<Self> {
+    pub fn from_document(doc: &'de Document<'de>) -> Result<Self> {
+        Self::new(doc)
+    }
+}
+
+impl<'doc, 'de, S> Deserializer<'doc, 'de, S> {
+    fn new(doc: &'doc Document<'de>) -> Result<Self> {
         Ok(Deserializer {
             doc: doc.as_value()?,
+            span: doc.span(),
+            source: None,
+            wrapping: false,
+            strings: PhantomData,
         })
     }
+
+    /// Sets the name and text of the source the document was parsed from.
+    /// If the document was parsed with source spans enabled, errors will
+    /// report their location as `name:line:col` along with an excerpt of the
+    /// offending line.
+    pub fn with_source(mut self, name: Option<&'doc str>, text: &'doc str) -> Self {
+        self.source = Some(Source { name, text });
+        self
+    }
+
+    /// Sets whether integers which are out of range for the type being
+    /// deserialized are converted keeping their bit pattern (e.g. `0xFFFFFFFF`
+    /// deserializes as `-1i32`) rather than reported as errors.
+    pub fn wrapping_integers(mut self, wrapping: bool) -> Self {
+        self.wrapping = wrapping;
+        self
+    }
+
+    // Creates a new `Deserializer` referring to the same node.
+    fn reborrow(&self) -> Self {
+        Deserializer {
+            doc: self.doc,
+            span: self.span,
+            source: self.source,
+            wrapping: self.wrapping,
+            strings: PhantomData,
+        }
+    }
+
+    // Creates a `Deserializer` for a child node of this deserializer's
+    // document.
+    fn child(&self, doc: &'doc Document<'de>) -> Result<Self> {
+        let mut child = Self::new(doc).map_err(|e| self.locate(e))?;
+        child.source = self.source;
+        child.wrapping = self.wrapping;
+        Ok(child)
+    }
+
+    // Attaches this deserializer's source location to `err`, unless the
+    // error already carries a location.
+    fn locate(&self, err: Error) -> Error {
+        if let Error::Located(_, _) = err {
+            return err;
+        }
+        match self.span {
+            None => err,
+            Some(span) => {
+                let source = self.source;
+                let excerpt = source.and_then(|s| s.text.lines().nth(span.line - 1));
+                Error::Located(
+                    Box::new(err),
+                    Location {
+                        file: source.and_then(|s| s.name).map(String::from),
+                        line: span.line,
+                        col: span.col,
+                        excerpt: excerpt.map(|e| e.trim_end().to_string()),
+                    },
+                )
+            }
+        }
+    }
+
+    // Deserializes a child node with `seed`, attaching the child's path
+    // element and location to any errors.  The errors from deeper nodes
+    // gain the rest of their path as they are returned through each parent.
+    fn deserialize_child<T>(
+        &self,
+        doc: &'doc Document<'de>,
+        element: Element<'doc, 'de>,
+        seed: T,
+    ) -> Result<T::Value>
+    where
+        T: DeserializeSeed<'de>,
+        S: Strings<'doc, 'de>,
+    {
+        let mut child = self.child(doc).map_err(|e| element.attach(e))?;
+        seed.deserialize(&mut child)
+            .map_err(|e| element.attach(child.locate(e)))
+    }
+}
+
+// The element of a child node's document path.  The element is only
+// formatted when an error needs it.
+#[derive(Clone, Copy)]
+enum Element<'doc, 'de> {
+    Key(&'doc Document<'de>),
+    Index(usize),
+}
+
+impl Element<'_, '_> {
+    fn attach(self, err: Error) -> Error {
+        let element = match self {
+            Element::Key(k) => OwnedDocPath::Name(key_name(k)),
+            Element::Index(i) => OwnedDocPath::Index(i),
+        };
+        err.at_path(vec![element])
+    }
 }
 
 /// Parses and deserializes a `str` into a `T`.  The parser is
-/// maximally permissive.
-pub fn from_str<T>(text: &str) -> Result<T>
+/// maximally permissive.  Errors report the path to the offending value; use
+/// [`from_str_with_spans`] to also report its location in `text`.
+///
+/// Strings in `text` which need no unescaping are borrowed, so `T` may
+/// contain `&'de str` or `Cow<'de, str>` fields.
+pub fn from_str<'de, T>(text: &'de str) -> Result<T>
 where
-    T: DeserializeOwned,
+    T: de::Deserialize<'de>,
 {
-    let doc = Document::parse(text)?;
-    let mut ds = Deserializer::from_document(&doc)?;
-    T::deserialize(&mut ds)
+    parse_and_deserialize(text, false)
 }
 
-impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
+/// Like [`from_str`], but records the source location of each parsed value
+/// so that errors also report the line and column of the offending value
+/// along with an excerpt of its line.
+pub fn from_str_with_spans<'de, T>(text: &'de str) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    parse_and_deserialize(text, true)
+}
+
+fn parse_and_deserialize<'de, T>(text: &'de str, spans: bool) -> Result<T>
+where
+    T: de::Deserialize<'de>,
+{
+    let mut relax = Relax::default();
+    relax.spans = spans;
+    let doc = relax.from_str(text)?;
+    let mut ds = Deserializer::<Transient>::new(&doc)?.with_source(None, text);
+    T::deserialize(&mut ds).map_err(|e| ds.locate(e))
+}
+
+impl<'doc, 'de, S: Strings<'doc, 'de>> de::Deserializer<'de> for &mut Deserializer<'doc, 'de, S> {
     type Error = Error;
 
     fn deserialize_any<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        match self.doc {
-            Document::String(s, _) => v.visit_str(s.as_str()),
-            Document::StaticStr(s, _) => v.visit_str(s),
+        match self.doc.as_value()? {
+            Document::String(s, _) => S::visit_str(s, v),
+            Document::Str(s, _) => v.visit_borrowed_str(s),
             Document::Boolean(b) => v.visit_bool(*b),
-            Document::Int(i) => v.visit_i64(i.into()),
+            Document::Int(i) => visit_int(i, v),
             Document::Float(f) => v.visit_f64(*f),
-            Document::Mapping(map) => {
-                v.visit_map(Sequence::new(map.iter().filter(|f| f.has_value())))
-            }
-            Document::Sequence(seq) => {
-                v.visit_seq(Sequence::new(seq.iter().filter(|f| f.has_value())))
-            }
+            Document::Mapping(map) => v.visit_map(Sequence::new(self, map)),
+            Document::Sequence(seq) => v.visit_seq(Sequence::new(self, seq)),
             Document::Bytes(b) => v.visit_bytes(b.as_slice()),
             Document::Null => v.visit_unit(),
-            Document::Compact(_) => unimplemented!(),
-            Document::Fragment(_) => unimplemented!(),
-            Document::Comment(_, _) => unimplemented!(),
+            // `as_value` never returns the container or comment nodes.
+            d => Err(Error::StructureError("a value", d.variant())),
         }
     }
-    fn deserialize_ignored_any<V>(self, _v: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!();
+        v.visit_unit()
     }
 
     fn deserialize_bool<V>(self, v: V) -> Result<V::Value>
@@ -125,62 +300,62 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        v.visit_u8(self.doc.try_into()?)
+        v.visit_u8(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_u16<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_u16(self.doc.try_into()?)
+        v.visit_u16(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_u32<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_u32(self.doc.try_into()?)
+        v.visit_u32(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_u64<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_u64(self.doc.try_into()?)
+        v.visit_u64(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_u128<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_u128(self.doc.try_into()?)
+        v.visit_u128(self.doc.to_int(self.wrapping)?)
     }
 
     fn deserialize_i8<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_i8(self.doc.try_into()?)
+        v.visit_i8(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_i16<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_i16(self.doc.try_into()?)
+        v.visit_i16(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_i32<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_i32(self.doc.try_into()?)
+        v.visit_i32(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_i64<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_i64(self.doc.try_into()?)
+        v.visit_i64(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_i128<V>(self, v: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        v.visit_i128(self.doc.try_into()?)
+        v.visit_i128(self.doc.to_int(self.wrapping)?)
     }
     fn deserialize_f32<V>(self, v: V) -> Result<V::Value>
     where
@@ -192,7 +367,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        v.visit_f32(self.doc.try_into()?)
+        v.visit_f64(self.doc.try_into()?)
     }
     fn deserialize_char<V>(self, v: V) -> Result<V::Value>
     where
@@ -204,7 +379,11 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        v.visit_borrowed_str(self.doc.as_str()?)
+        match self.doc.as_value()? {
+            Document::String(s, _) => S::visit_str(s, v),
+            Document::Str(s, _) => v.visit_borrowed_str(s),
+            _ => Err(Error::StructureError("String", self.doc.variant())),
+        }
     }
     fn deserialize_string<V>(self, v: V) -> Result<V::Value>
     where
@@ -226,6 +405,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         match self.doc.as_value()? {
             Document::Bytes(b) => v.visit_byte_buf(b.clone()),
             Document::String(s, _) => v.visit_byte_buf(hexdump::from_str(s)?),
+            Document::Str(s, _) => v.visit_byte_buf(hexdump::from_str(s)?),
             Document::Sequence(_) => self.deserialize_seq(v),
             _ => Err(Error::StructureError(
                 "String or Sequence",
@@ -269,7 +449,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if let Document::Sequence(seq) = self.doc {
-            v.visit_seq(Sequence::new(seq.iter().filter(|f| f.has_value())))
+            v.visit_seq(Sequence::new(self, seq))
         } else {
             Err(Error::StructureError("Sequence", self.doc.variant()))
         }
@@ -292,7 +472,7 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         if let Document::Mapping(map) = self.doc {
-            v.visit_map(Sequence::new(map.iter().filter(|f| f.has_value())))
+            v.visit_map(Sequence::new(self, map))
         } else {
             Err(Error::StructureError("Mapping", self.doc.variant()))
         }
@@ -320,8 +500,8 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         match self.doc.as_value()? {
             Document::String(s, _) => v.visit_enum(s.as_str().into_deserializer()),
-            Document::StaticStr(s, _) => v.visit_enum(s.into_deserializer()),
-            Document::Mapping(frags) => v.visit_enum(Enum::new(frags)?),
+            Document::Str(s, _) => v.visit_enum(BorrowedStrDeserializer::new(s)),
+            Document::Mapping(frags) => v.visit_enum(Enum::new(self, frags)?),
             _ => Err(Error::StructureError(
                 "String or Mapping",
                 self.doc.variant(),
@@ -336,50 +516,93 @@ impl<'de, 'a> de::Deserializer<'de> for &'a mut Deserializer<'de> {
     }
 }
 
+// Visits an integer with the narrowest visit method which can hold it, so
+// that visitors which only accept 64-bit integers (e.g. those buffering
+// content for untagged or flattened types) see them whenever possible.
+fn visit_int<'de, V>(i: &Int, v: V) -> Result<V::Value>
+where
+    V: Visitor<'de>,
+{
+    if let Some(n) = i.checked::<u64>() {
+        v.visit_u64(n)
+    } else if let Some(n) = i.checked::<i64>() {
+        v.visit_i64(n)
+    } else if let Some(n) = i.checked::<u128>() {
+        v.visit_u128(n)
+    } else {
+        v.visit_i128(i128::from(i))
+    }
+}
+
+// Returns the name of a mapping key for use in error paths.
+fn key_name(key: &Document) -> String {
+    match key.as_value() {
+        Ok(Document::String(s, _)) => s.clone(),
+        Ok(Document::Str(s, _)) => s.to_string(),
+        Ok(k @ Document::Int(_)) => {
+            i128::try_from(k).map_or_else(|_| "?".into(), |i| i.to_string())
+        }
+        Ok(k) => format!("<{}>", k.variant()),
+        Err(_) => "?".into(),
+    }
+}
+
 // The `Sequence` struct is used to provide sequence and map access to
 // `Document::Sequence` and `Document::Mapping` nodes.
-struct Sequence<'de, T: Iterator<Item = &'de Document>> {
-    iter: T,
-    value: Option<&'de Document>,
+struct Sequence<'doc, 'de, S> {
+    parent: Deserializer<'doc, 'de, S>,
+    iter: std::slice::Iter<'doc, Document<'de>>,
+    index: usize,
+    value: Option<(&'doc Document<'de>, &'doc Document<'de>)>,
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> Sequence<'de, T> {
-    fn new<I: IntoIterator<Item = T::Item, IntoIter = T>>(ii: I) -> Self {
+impl<'doc, 'de, S> Sequence<'doc, 'de, S> {
+    fn new(parent: &Deserializer<'doc, 'de, S>, items: &'doc [Document<'de>]) -> Self {
         Sequence {
-            iter: ii.into_iter(),
+            parent: parent.reborrow(),
+            iter: items.iter(),
+            index: 0,
             value: None,
         }
     }
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> SeqAccess<'de> for Sequence<'de, T> {
+impl<'doc, 'de, S: Strings<'doc, 'de>> SeqAccess<'de> for Sequence<'doc, 'de, S> {
     type Error = Error;
 
     fn next_element_seed<E>(&mut self, seed: E) -> Result<Option<E::Value>>
     where
         E: DeserializeSeed<'de>,
     {
-        match self.iter.next() {
-            Some(next) => seed
-                .deserialize(&mut Deserializer::from_document(next)?)
-                .map(Some),
+        match self.iter.find(|d| d.has_value()) {
+            Some(next) => {
+                self.index += 1;
+                self.parent
+                    .deserialize_child(next, Element::Index(self.index - 1), seed)
+                    .map(Some)
+            }
             None => Ok(None),
         }
     }
 }
 
-impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T> {
+impl<'doc, 'de, S: Strings<'doc, 'de>> MapAccess<'de> for Sequence<'doc, 'de, S> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
     where
         K: DeserializeSeed<'de>,
     {
-        match self.iter.next() {
+        match self.iter.find(|d| d.has_value()) {
             Some(doc) => {
-                let (k, v) = doc.as_kv()?;
-                self.value = Some(v);
-                seed.deserialize(&mut Deserializer::from_document(k)?)
+                let (k, v) = doc.as_kv().map_err(|e| self.parent.locate(e))?;
+                let element = Element::Key(k);
+                // Keys don't carry spans, so errors are reported at the value.
+                let mut key = self.parent.child(k).map_err(|e| element.attach(e))?;
+                key.span = v.span().or(self.parent.span);
+                self.value = Some((v, k));
+                seed.deserialize(&mut key)
+                    .map_err(|e| element.attach(key.locate(e)))
                     .map(Some)
             }
             None => Ok(None),
@@ -391,7 +614,7 @@ impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T>
         V: DeserializeSeed<'de>,
     {
         match self.value.take() {
-            Some(v) => seed.deserialize(&mut Deserializer::from_document(v)?),
+            Some((v, k)) => self.parent.deserialize_child(v, Element::Key(k), seed),
             None => Err(Error::Unknown("kvpair missing the value".into())),
         }
     }
@@ -399,28 +622,33 @@ impl<'de, T: Iterator<Item = &'de Document>> MapAccess<'de> for Sequence<'de, T>
 
 // The `Enum` struct is used to provide access to the different enum kinds
 // supported by the serde data model.
-struct Enum<'de> {
-    enm: &'de Document,
-    var: &'de Document,
+struct Enum<'doc, 'de, S> {
+    parent: Deserializer<'doc, 'de, S>,
+    enm: &'doc Document<'de>,
+    var: &'doc Document<'de>,
 }
 
-impl<'de> Enum<'de> {
-    fn new(ev: &'de [Document]) -> Result<Self> {
+impl<'doc, 'de, S> Enum<'doc, 'de, S> {
+    fn new(parent: &Deserializer<'doc, 'de, S>, ev: &'doc [Document<'de>]) -> Result<Self> {
         // We expect only one document node will contain a value.
         // Filter out non-value-containing nodes and extract the value.
         let mut values = ev.iter().filter(|&e| Document::has_value(e));
         let ev = values
             .next()
-            .ok_or_else(|| Error::StructureError("one value", "none"))?;
+            .ok_or(Error::StructureError("one value", "none"))?;
         if values.next().is_some() {
             return Err(Error::StructureError("one value", "many"));
         }
         let (e, v) = ev.as_kv()?;
-        Ok(Enum { enm: e, var: v })
+        Ok(Enum {
+            parent: parent.reborrow(),
+            enm: e,
+            var: v,
+        })
     }
 }
 
-impl<'de> EnumAccess<'de> for Enum<'de> {
+impl<'doc, 'de, S: Strings<'doc, 'de>> EnumAccess<'de> for Enum<'doc, 'de, S> {
     type Error = Error;
     type Variant = Self;
 
@@ -428,39 +656,53 @@ impl<'de> EnumAccess<'de> for Enum<'de> {
     where
         V: DeserializeSeed<'de>,
     {
-        Ok((
-            seed.deserialize(&mut Deserializer::from_document(self.enm)?)?,
-            self,
-        ))
+        // The variant name is reported at the enum's own path and location.
+        let mut name = Deserializer::<S>::new(self.enm)?;
+        name.span = self.parent.span;
+        name.source = self.parent.source;
+        let value = seed.deserialize(&mut name).map_err(|e| name.locate(e))?;
+        Ok((value, self))
     }
 }
 
-impl<'de> VariantAccess<'de> for Enum<'de> {
+impl<'doc, 'de, S: Strings<'doc, 'de>> VariantAccess<'de> for Enum<'doc, 'de, S> {
     type Error = Error;
 
     fn unit_variant(self) -> Result<()> {
-        Err(Error::Unknown("unreachable: unit_variant".into()))
+        // A unit variant written as a mapping (e.g. `{"Unit": null}`).
+        self.parent.deserialize_child(
+            self.var,
+            Element::Key(self.enm),
+            std::marker::PhantomData::<()>,
+        )
     }
 
     fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value>
     where
         T: DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut Deserializer::from_document(self.var)?)
+        self.parent
+            .deserialize_child(self.var, Element::Key(self.enm), seed)
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_seq(&mut Deserializer::from_document(self.var)?, visitor)
+        let element = Element::Key(self.enm);
+        let mut var = self.parent.child(self.var).map_err(|e| element.attach(e))?;
+        de::Deserializer::deserialize_seq(&mut var, visitor)
+            .map_err(|e| element.attach(var.locate(e)))
     }
 
     fn struct_variant<V>(self, _fields: &'static [&'static str], visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        de::Deserializer::deserialize_map(&mut Deserializer::from_document(self.var)?, visitor)
+        let element = Element::Key(self.enm);
+        let mut var = self.parent.child(self.var).map_err(|e| element.attach(e))?;
+        de::Deserializer::deserialize_map(&mut var, visitor)
+            .map_err(|e| element.attach(var.locate(e)))
     }
 }
 
@@ -510,4 +752,326 @@ mod tests {
         let expected = E::Struct { a: 1 };
         assert_eq!(expected, from_str(j).unwrap());
     }
+
+    #[test]
+    fn test_error_location() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Reg {
+            name: String,
+            offset: u32,
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Test {
+            regs: Vec<Reg>,
+        }
+
+        let j = "{\n  regs: [\n    {name: \"a\", offset: 0x10},\n    {name: \"b\", offset: [1]}\n  ]\n}";
+        let err = from_str::<Test>(j).unwrap_err();
+        assert!(err.location().is_none());
+        assert_eq!(
+            err.to_string(),
+            "at regs[1].offset: document structure error: expected Int but got Sequence"
+        );
+
+        let err = from_str_with_spans::<Test>(j).unwrap_err();
+        let loc = err.location().unwrap();
+        assert_eq!((loc.line, loc.col), (4, 25));
+        assert_eq!(
+            err.to_string(),
+            "at regs[1].offset: document structure error: expected Int but got Sequence at 4:25\n|     {name: \"b\", offset: [1]}\n|                         ^"
+        );
+
+        let doc = Relax::default().from_str(j).unwrap();
+        let err = Deserializer::from_document(&doc)
+            .and_then(|mut ds| Test::deserialize(&mut ds))
+            .unwrap_err();
+        assert!(err.location().is_none());
+        assert_eq!(
+            err.to_string(),
+            "at regs[1].offset: document structure error: expected Int but got Sequence"
+        );
+
+        let mut relax = Relax::default();
+        relax.spans = true;
+        let doc = relax.from_str(j).unwrap();
+        let mut ds = Deserializer::from_document(&doc)
+            .unwrap()
+            .with_source(Some("test.json5"), j);
+        let err = Test::deserialize(&mut ds).unwrap_err();
+        assert!(err.to_string().contains(" at test.json5:4:25\n"));
+    }
+
+    #[test]
+    fn test_error_path() {
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        #[serde(deny_unknown_fields)]
+        enum E {
+            Struct { a: u32 },
+        }
+        #[derive(Deserialize, Debug)]
+        #[allow(dead_code)]
+        struct Test {
+            items: Vec<E>,
+        }
+
+        let err = from_str::<Test>(r#"{"items": [{"Struct": {"a": 1}}, {"Struct": {"b": 2}}]}"#)
+            .unwrap_err();
+        let path = err.path().unwrap();
+        assert_eq!(
+            path.iter().map(DocPath::to_string).collect::<Vec<_>>(),
+            ["items", "1", "Struct", "b"]
+        );
+        assert!(matches!(err.inner(), Error::Deserialize(_)));
+        assert!(err
+            .to_string()
+            .starts_with("at items[1].Struct.b: deserializer error: unknown field `b`"));
+
+        let err = from_str::<Test>(r#"{"items": [{"Tuple": [1]}]}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("at items[0]: deserializer error: unknown variant `Tuple`"));
+
+        let err = from_str::<Test>("[1]").unwrap_err();
+        assert!(err.path().is_none());
+    }
+
+    #[test]
+    fn test_integer_range() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Test {
+            small: u8,
+            signed: i32,
+        }
+
+        let err = from_str::<Test>("{small: 300, signed: 0}").unwrap_err();
+        assert!(matches!(err.inner(), Error::OutOfRange(_, "u8")));
+        assert_eq!(err.to_string(), "at small: 300 is out of range for u8");
+
+        let err = from_str_with_spans::<u32>("-1").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "-1 is out of range for u32 at 1:1\n| -1\n| ^"
+        );
+        let err = from_str::<u32>("1.7").unwrap_err();
+        assert!(matches!(err.inner(), Error::Inexact(_, "u32")));
+        assert_eq!(from_str::<u32>("7.0").unwrap(), 7);
+        assert_eq!(from_str::<u64>("\"0xFFFFFFFFFFFFFFFF\"").unwrap(), u64::MAX);
+        assert_eq!(
+            from_str::<i128>("-170141183460469231731687303715884105728").unwrap(),
+            i128::MIN
+        );
+        assert!(from_str::<i128>("-340282366920938463463374607431768211455").is_err());
+        assert!(from_str::<i128>("\"-340282366920938463463374607431768211455\"").is_err());
+
+        let j = "{small: 0xFF, signed: 0xFFFFFFFF}";
+        let err = from_str::<Test>(j).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("at signed: 0xFFFFFFFF is out of range for i32"));
+
+        let doc = Document::parse(j).unwrap();
+        let mut ds = Deserializer::from_document(&doc)
+            .unwrap()
+            .wrapping_integers(true);
+        assert_eq!(
+            Test::deserialize(&mut ds).unwrap(),
+            Test {
+                small: 255,
+                signed: -1
+            }
+        );
+    }
+
+    #[test]
+    fn test_untagged() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(untagged)]
+        enum Value {
+            Unsigned(u64),
+            Signed(i64),
+            Text(String),
+            List(Vec<Value>),
+        }
+
+        let j = "[0xFFFFFFFFFFFFFFFF, -5, 'text', [1]]";
+        assert_eq!(
+            from_str::<Value>(j).unwrap(),
+            Value::List(vec![
+                Value::Unsigned(u64::MAX),
+                Value::Signed(-5),
+                Value::Text("text".into()),
+                Value::List(vec![Value::Unsigned(1)]),
+            ])
+        );
+    }
+
+    #[test]
+    fn test_flatten() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Header {
+            #[serde(rename = "type")]
+            kind: u8,
+            length: u64,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Packet {
+            #[serde(flatten)]
+            header: Header,
+            #[serde(flatten)]
+            rest: std::collections::BTreeMap<String, String>,
+        }
+
+        let j = r#"{
+            // The packet type.
+            type: 0x10,
+            length: 0x8000000000000000,
+            payload: "abc",
+        }"#;
+        assert_eq!(
+            from_str::<Packet>(j).unwrap(),
+            Packet {
+                header: Header {
+                    kind: 16,
+                    length: 1 << 63,
+                },
+                rest: [("payload".to_string(), "abc".to_string())].into(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_tagged_enums() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(tag = "type")]
+        enum Internal {
+            Read { addr: u32 },
+            Reset,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        #[serde(tag = "t", content = "c")]
+        enum Adjacent {
+            Write(u32, u8),
+            Reset,
+        }
+
+        let j = "[{type: 'Read', addr: 0x40}, {type: 'Reset'}]";
+        assert_eq!(
+            from_str::<Vec<Internal>>(j).unwrap(),
+            [Internal::Read { addr: 0x40 }, Internal::Reset]
+        );
+        let j = "[{t: 'Write', c: [0x40, 1]}, {t: 'Reset'}]";
+        assert_eq!(
+            from_str::<Vec<Adjacent>>(j).unwrap(),
+            [Adjacent::Write(0x40, 1), Adjacent::Reset]
+        );
+
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum External {
+            Unit,
+        }
+        assert_eq!(
+            from_str::<External>("{Unit: null}").unwrap(),
+            External::Unit
+        );
+        let err = from_str::<External>("{Unit: 1}").unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("at Unit: document structure error"));
+    }
+
+    #[test]
+    fn test_unknown_fields() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Test {
+            a: u32,
+        }
+
+        let j = r#"{
+            a: 1,
+            // An unknown field.
+            b: {c: [1, 2, {d: null}], e: 0xFFFFFFFFFFFFFFFFFFFF},
+            f: "unknown",
+        }"#;
+        assert_eq!(from_str::<Test>(j).unwrap(), Test { a: 1 });
+        let doc = Document::from_yaml("a: 1\nb:\n  - [1, 2]\n").unwrap();
+        let mut ds = Deserializer::from_document(&doc).unwrap();
+        assert_eq!(Test::deserialize(&mut ds).unwrap(), Test { a: 1 });
+    }
+
+    #[test]
+    fn test_any() {
+        let j = r#"{
+            // A comment.
+            big: 0xFFFFFFFFFFFFFFFF,
+            neg: -1,
+            list: [1.5, true, null],
+        }"#;
+        let value = from_str::<serde_json::Value>(j).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "big": u64::MAX,
+                "neg": -1,
+                "list": [1.5, true, null],
+            })
+        );
+
+        // Integers which don't fit in 64 bits are visited as 128-bit integers.
+        let err = from_str::<serde_json::Value>("{huge: 0x100000000000000000}").unwrap_err();
+        assert!(matches!(
+            err.path().as_deref(),
+            Some([DocPath::Name("huge")])
+        ));
+        assert!(matches!(err.inner(), Error::Deserialize(_)));
+    }
+
+    #[test]
+    fn test_borrowed() {
+        use std::borrow::Cow;
+
+        #[derive(Deserialize, Debug)]
+        struct Test<'a> {
+            name: &'a str,
+            #[serde(borrow)]
+            note: Cow<'a, str>,
+            #[serde(borrow)]
+            escaped: Cow<'a, str>,
+            tags: Vec<&'a str>,
+            kind: Kind,
+        }
+        #[derive(Deserialize, Debug, PartialEq)]
+        enum Kind {
+            Register,
+        }
+
+        let j = r#"{
+            name: "ctrl",
+            note: 'Control register',
+            escaped: "a\tb",
+            tags: ["rw", "reset"],
+            kind: "Register",
+        }"#;
+        let t = from_str::<Test>(j).unwrap();
+        assert_eq!(t.name, "ctrl");
+        assert_eq!(t.tags, ["rw", "reset"]);
+        assert_eq!(t.kind, Kind::Register);
+        assert!(matches!(t.note, Cow::Borrowed("Control register")));
+        // Strings with escapes must be unescaped into an owned string.
+        assert!(matches!(&t.escaped, Cow::Owned(s) if s == "a\tb"));
+
+        let err = from_str::<Test>(r#"{name: "a\nb"}"#).unwrap_err();
+        assert!(err
+            .to_string()
+            .starts_with("at name: deserializer error: invalid type: string \"a\\nb\""));
+
+        // A document which owns its strings can still be borrowed from.
+        let doc = Document::parse(j).unwrap().into_owned();
+        let mut ds = Deserializer::from_document(&doc).unwrap();
+        let t = Test::deserialize(&mut ds).unwrap();
+        assert_eq!(t.name, "ctrl");
+        assert!(matches!(t.escaped, Cow::Borrowed("a\tb")));
+    }
 }
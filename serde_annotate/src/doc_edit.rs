@@ -0,0 +1,370 @@
+// Path-addressed editing of `Document`s.
+use crate::doc_iter::DocPath;
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// Types which can address a node within a `Document`.
+///
+/// A path may be given as a slice of `DocPath` elements or as a dotted string
+/// such as `a.b[3].c` (equivalently `a.b.3.c`).  A name element applied to a
+/// sequence is interpreted as an index.
+pub trait ToDocPath {
+    fn to_doc_path(&self) -> Result<Vec<DocPath<'_>>>;
+}
+
+impl ToDocPath for str {
+    fn to_doc_path(&self) -> Result<Vec<DocPath<'_>>> {
+        let mut path = Vec::new();
+        if self.is_empty() {
+            return Ok(path);
+        }
+        for part in self.split('.') {
+            let (name, mut rest) = part.split_at(part.find('[').unwrap_or(part.len()));
+            if !name.is_empty() {
+                path.push(DocPath::Name(name));
+            } else if rest.is_empty() {
+                return Err(Error::PathError(self.into(), "empty path element"));
+            }
+            while !rest.is_empty() {
+                let index = rest
+                    .strip_prefix('[')
+                    .and_then(|r| r.split_once(']'))
+                    .and_then(|(index, r)| {
+                        rest = r;
+                        index.parse::<usize>().ok()
+                    })
+                    .ok_or_else(|| Error::PathError(self.into(), "bad index"))?;
+                path.push(DocPath::Index(index));
+            }
+        }
+        Ok(path)
+    }
+}
+
+impl ToDocPath for String {
+    fn to_doc_path(&self) -> Result<Vec<DocPath<'_>>> {
+        self.as_str().to_doc_path()
+    }
+}
+
+impl ToDocPath for [DocPath<'_>] {
+    fn to_doc_path(&self) -> Result<Vec<DocPath<'_>>> {
+        Ok(self.to_vec())
+    }
+}
+
+impl ToDocPath for Vec<DocPath<'_>> {
+    fn to_doc_path(&self) -> Result<Vec<DocPath<'_>>> {
+        Ok(self.clone())
+    }
+}
+
+pub(crate) fn path_string(path: &[DocPath]) -> String {
+    path.iter()
+        .map(DocPath::to_string)
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// Returns whether the mapping key `key` is named `name`.
+pub(crate) fn key_matches(key: &Document, name: &str) -> bool {
+    match key {
+        Document::String(s, _) => s == name,
+        Document::Str(s, _) => *s == name,
+        Document::Int(_) => i128::try_from(key)
+            .ok()
+            .zip(name.parse::<i128>().ok())
+            .is_some_and(|(a, b)| a == b),
+        _ => false,
+    }
+}
+
+// Returns the position in `items` of the `index`th value-containing node.
+fn nth_value(items: &[Document], index: usize) -> Option<usize> {
+    items
+        .iter()
+        .enumerate()
+        .filter(|(_, item)| item.has_value())
+        .nth(index)
+        .map(|(i, _)| i)
+}
+
+// Returns the position in `entries` of the mapping entry whose key is `name`.
+fn find_key(entries: &[Document], name: &str) -> Option<usize> {
+    entries
+        .iter()
+        .position(|e| e.as_kv().is_ok_and(|(k, _)| key_matches(k, name)))
+}
+
+// Finds the position of the entry named by `element` in the mapping or
+// sequence `node`.
+fn find_entry(node: &Document, element: &DocPath) -> Option<usize> {
+    match (node, element) {
+        (Document::Mapping(entries), DocPath::Name(name)) => find_key(entries, name),
+        (Document::Sequence(items), DocPath::Name(name)) => {
+            nth_value(items, name.parse::<usize>().ok()?)
+        }
+        (Document::Sequence(items), DocPath::Index(index)) => nth_value(items, *index),
+        _ => None,
+    }
+}
+
+// Returns the value of an entry in a mapping or sequence.
+fn entry_value_mut<'a, 'b>(entry: &'a mut Document<'b>) -> Result<&'a mut Document<'b>> {
+    if entry.as_kv().is_ok() {
+        Ok(entry.as_kv_mut()?.1)
+    } else {
+        entry.as_value_mut()
+    }
+}
+
+impl<'a> Document<'a> {
+    /// Returns the value node at `path`.
+    pub fn get<P: ToDocPath + ?Sized>(&self, path: &P) -> Result<&Document<'a>> {
+        let path = path.to_doc_path()?;
+        let mut node = self.as_value()?;
+        for (i, element) in path.iter().enumerate() {
+            let entry = find_entry(node, element)
+                .ok_or_else(|| Error::PathError(path_string(&path[..=i]), "not found"))?;
+            node = match node {
+                Document::Mapping(entries) => entries[entry].as_kv()?.1.as_value()?,
+                Document::Sequence(items) => items[entry].as_value()?,
+                _ => unreachable!(),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Returns a mutable reference to the value node at `path`.
+    pub fn get_mut<P: ToDocPath + ?Sized>(&mut self, path: &P) -> Result<&mut Document<'a>> {
+        let path = path.to_doc_path()?;
+        let mut node = self.as_value_mut()?;
+        for (i, element) in path.iter().enumerate() {
+            let entry = find_entry(node, element)
+                .ok_or_else(|| Error::PathError(path_string(&path[..=i]), "not found"))?;
+            node = match node {
+                Document::Mapping(entries) | Document::Sequence(entries) => {
+                    entry_value_mut(&mut entries[entry])?.as_value_mut()?
+                }
+                _ => unreachable!(),
+            };
+        }
+        Ok(node)
+    }
+
+    /// Replaces the value at `path` with `value`, returning the previous
+    /// value.  Comments attached to the entry are retained.
+    pub fn set<P: ToDocPath + ?Sized>(
+        &mut self,
+        path: &P,
+        value: Document<'a>,
+    ) -> Result<Document<'a>> {
+        let node = self.get_mut(path)?;
+        Ok(std::mem::replace(node, value))
+    }
+
+    // Returns the mapping or sequence addressed by all but the last element of
+    // `path`, along with the last element.
+    fn parent_mut<'p>(
+        &mut self,
+        path: &'p [DocPath<'p>],
+    ) -> Result<(&mut Document<'a>, &'p DocPath<'p>)> {
+        let (last, parent) = path
+            .split_last()
+            .ok_or_else(|| Error::PathError(String::new(), "empty path"))?;
+        match self.get_mut(parent)? {
+            node @ (Document::Mapping(_) | Document::Sequence(_)) => Ok((node, last)),
+            _ => Err(Error::PathError(
+                path_string(parent),
+                "not a mapping or sequence",
+            )),
+        }
+    }
+
+    /// Inserts `value` at `path` with an optional comment.
+    ///
+    /// If the parent of `path` is a mapping, a new key is appended to the
+    /// mapping; it is an error if the key already exists.  If the parent is a
+    /// sequence, the value is inserted before the indexed element, or appended
+    /// if the index is the length of the sequence.
+    pub fn insert<P: ToDocPath + ?Sized>(
+        &mut self,
+        path: &P,
+        value: Document<'a>,
+        comment: Option<&str>,
+    ) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (parent, last) = self.parent_mut(&path)?;
+        let mut entry = Vec::new();
+        if let Some(c) = comment {
+            entry.push(Document::Comment(c.into(), CommentFormat::Standard));
+        }
+        let (items, position) = match (parent, last) {
+            (Document::Mapping(items), DocPath::Name(name)) => {
+                if find_key(items, name).is_some() {
+                    return Err(Error::PathError(path_string(&path), "already exists"));
+                }
+                entry.push(Document::String(name.to_string(), StrFormat::Standard));
+                (items, None)
+            }
+            (Document::Sequence(items), element) => {
+                let index = match element {
+                    DocPath::Index(i) => Some(*i),
+                    DocPath::Name(n) => n.parse::<usize>().ok(),
+                };
+                let count = items.iter().filter(|i| i.has_value()).count();
+                match index {
+                    Some(i) if i == count => (items, None),
+                    Some(i) if i < count => {
+                        let position = nth_value(items, i);
+                        (items, position)
+                    }
+                    _ => return Err(Error::PathError(path_string(&path), "bad index")),
+                }
+            }
+            _ => return Err(Error::PathError(path_string(&path), "expected a key")),
+        };
+        // Appended entries go after the last value so that any trailing
+        // comments stay at the end of the container.
+        let position =
+            position.unwrap_or_else(|| Document::last_value_index(items).map_or(0, |i| i + 1));
+        entry.push(value);
+        if entry.len() == 1 {
+            items.insert(position, entry.pop().unwrap());
+        } else {
+            items.insert(position, Document::Fragment(entry));
+        }
+        Ok(())
+    }
+
+    /// Removes the entry at `path`, including its comments, and returns its value.
+    pub fn remove<P: ToDocPath + ?Sized>(&mut self, path: &P) -> Result<Document<'a>> {
+        let path = path.to_doc_path()?;
+        let (parent, last) = self.parent_mut(&path)?;
+        let position = find_entry(parent, last)
+            .ok_or_else(|| Error::PathError(path_string(&path), "not found"))?;
+        let (Document::Mapping(items) | Document::Sequence(items)) = parent else {
+            unreachable!()
+        };
+        let mut entry = items.remove(position);
+        let value = entry_value_mut(&mut entry)?;
+        Ok(std::mem::replace(value, Document::Null))
+    }
+
+    /// Renames the mapping key at `path` to `name`, retaining the key's
+    /// string format and any comments.
+    pub fn rename<P: ToDocPath + ?Sized>(&mut self, path: &P, name: &str) -> Result<()> {
+        let path = path.to_doc_path()?;
+        let (parent, last) = self.parent_mut(&path)?;
+        let (Document::Mapping(items), DocPath::Name(old)) = (parent, last) else {
+            return Err(Error::PathError(path_string(&path), "expected a key"));
+        };
+        if find_key(items, name).is_some() {
+            return Err(Error::PathError(name.into(), "already exists"));
+        }
+        let position = find_key(items, old)
+            .ok_or_else(|| Error::PathError(path_string(&path), "not found"))?;
+        let (key, _) = items[position].as_kv_mut()?;
+        let format = match key {
+            Document::String(_, f) | Document::Str(_, f) => *f,
+            _ => StrFormat::Standard,
+        };
+        *key = Document::String(name.into(), format);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    const SAMPLE: &str = r#"{
+  // The name of the thing
+  name: "widget",
+  regs: [
+    {
+      // Offset
+      offset: 0x10
+    },
+    {
+      offset: 0x20
+    }
+  ],
+  // Obsolete
+  old: true
+}"#;
+
+    #[test]
+    fn test_get_set() -> Result<()> {
+        let mut doc = Document::parse(SAMPLE)?;
+        assert_eq!(doc.get("name")?.as_str()?, "widget");
+        assert_eq!(u32::try_from(doc.get("regs[1].offset")?)?, 0x20);
+        assert_eq!(u32::try_from(doc.get("regs.0.offset")?)?, 0x10);
+        let path = [
+            DocPath::Name("regs"),
+            DocPath::Index(0),
+            DocPath::Name("offset"),
+        ];
+        assert_eq!(u32::try_from(doc.get(&path[..])?)?, 0x10);
+        assert!(doc.get("regs[2]").is_err());
+        assert!(doc.get("regs[").is_err());
+
+        let old = doc.set("regs[0].offset", Document::Int(0x40u32.into()))?;
+        assert_eq!(u32::try_from(&old)?, 0x10);
+        let int = crate::Int::new(0x40u32, crate::Base::Hex);
+        doc.set("regs[1].offset", Document::Int(int))?;
+        assert_eq!(
+            doc.to_json5().to_string(),
+            SAMPLE.replace("0x10", "64").replace("0x20", "0x40")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_remove_rename() -> Result<()> {
+        let mut doc = Document::parse(SAMPLE)?;
+        doc.remove("old")?;
+        doc.rename("name", "title")?;
+        doc.insert("size", Document::Int(4u8.into()), Some("Size in bytes"))?;
+        doc.insert("regs[0]", Document::Null, None)?;
+        doc.insert("regs[3]", Document::Boolean(false), Some("Last"))?;
+        assert!(doc.insert("size", Document::Null, None).is_err());
+        assert!(doc.rename("size", "title").is_err());
+        assert_eq!(
+            doc.to_json5().to_string(),
+            r#"{
+  // The name of the thing
+  title: "widget",
+  regs: [
+    null,
+    {
+      // Offset
+      offset: 0x10
+    },
+    {
+      offset: 0x20
+    },
+    // Last
+    false
+  ],
+  // Size in bytes
+  size: 4
+}"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_insert_before_dangling_comment() -> Result<()> {
+        let mut doc = Document::parse("{\n  // End of the table\n}")?;
+        doc.insert("a", Document::Int(1u8.into()), None)?;
+        assert_eq!(
+            doc.to_json5().to_string(),
+            "{\n  a: 1\n  // End of the table\n}"
+        );
+        Ok(())
+    }
+}
@@ -1,12 +1,12 @@
 use crate::document::Document;
 
-impl Document {
+impl<'d> Document<'d> {
     /// Returns an iterator over all all document nodes including
     /// comments and fragments.
     ///
     /// When encountering a container node (mapping, sequence or fragment),
     /// the container node is yielded first, then all of its children.
-    pub fn iter(&self) -> DocIter {
+    pub fn iter(&self) -> DocIter<'_, 'd> {
         let v = std::slice::from_ref(self);
         DocIter {
             stack: vec![v.iter()],
@@ -15,7 +15,7 @@ impl Document {
 
     /// Returns an iterator over all value nodes in the document.
     /// The iterator yields tuples of (object-path, value-node).
-    pub fn iter_path(&self) -> DocPathIter {
+    pub fn iter_path(&self) -> DocPathIter<'_, 'd> {
         let v = std::slice::from_ref(self);
         DocPathIter {
             stack: vec![v.iter()],
@@ -26,7 +26,7 @@ impl Document {
 
     /// Returns a mutable iterator over all value nodes in the document.
     /// The iterator yields tuples of (object-path, value-node).
-    pub fn iter_path_mut(&mut self) -> DocPathIterMut {
+    pub fn iter_path_mut(&mut self) -> DocPathIterMut<'_, 'd> {
         let v = std::slice::from_mut(self);
         DocPathIterMut {
             stack: vec![v.iter_mut()],
@@ -36,27 +36,24 @@ impl Document {
     }
 }
 
-impl<'a> IntoIterator for &'a Document {
-    type Item = &'a Document;
-    type IntoIter = DocIter<'a>;
+impl<'a, 'd> IntoIterator for &'a Document<'d> {
+    type Item = &'a Document<'d>;
+    type IntoIter = DocIter<'a, 'd>;
 
     fn into_iter(self) -> Self::IntoIter {
         self.iter()
     }
 }
 
-pub struct DocIter<'a> {
-    stack: Vec<std::slice::Iter<'a, Document>>,
+pub struct DocIter<'a, 'd> {
+    stack: Vec<std::slice::Iter<'a, Document<'d>>>,
 }
 
-impl<'a> Iterator for DocIter<'a> {
-    type Item = &'a Document;
+impl<'a, 'd> Iterator for DocIter<'a, 'd> {
+    type Item = &'a Document<'d>;
     fn next(&mut self) -> Option<Self::Item> {
         let val = loop {
-            let top = match self.stack.last_mut() {
-                Some(top) => top,
-                None => return None,
-            };
+            let top = self.stack.last_mut()?;
             if let Some(val) = top.next() {
                 break val;
             }
@@ -66,6 +63,7 @@ impl<'a> Iterator for DocIter<'a> {
             Document::Mapping(v) => self.stack.push(v.iter()),
             Document::Sequence(v) => self.stack.push(v.iter()),
             Document::Compact(v) => self.stack.push(std::slice::from_ref(&**v).iter()),
+            Document::Spanned(v, _) => self.stack.push(std::slice::from_ref(&**v).iter()),
             Document::Fragment(v) => self.stack.push(v.iter()),
             _ => {}
         };
@@ -73,14 +71,14 @@ impl<'a> Iterator for DocIter<'a> {
     }
 }
 
-pub struct DocPathIter<'a> {
-    stack: Vec<std::slice::Iter<'a, Document>>,
+pub struct DocPathIter<'a, 'd> {
+    stack: Vec<std::slice::Iter<'a, Document<'d>>>,
     aggregate: Vec<bool>,
     path: Vec<DocPath<'a>>,
 }
 
-pub struct DocPathIterMut<'a> {
-    stack: Vec<std::slice::IterMut<'a, Document>>,
+pub struct DocPathIterMut<'a, 'd> {
+    stack: Vec<std::slice::IterMut<'a, Document<'d>>>,
     aggregate: Vec<bool>,
     path: Vec<DocPath<'a>>,
 }
@@ -91,6 +89,32 @@ pub enum DocPath<'a> {
     Index(usize),
 }
 
+/// An owned element of a document path, as reported in errors.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum OwnedDocPath {
+    Name(String),
+    Index(usize),
+}
+
+impl OwnedDocPath {
+    /// Borrows the path element as a `DocPath`.
+    pub fn as_doc_path(&self) -> DocPath<'_> {
+        match self {
+            OwnedDocPath::Name(n) => DocPath::Name(n),
+            OwnedDocPath::Index(i) => DocPath::Index(*i),
+        }
+    }
+}
+
+impl From<DocPath<'_>> for OwnedDocPath {
+    fn from(p: DocPath<'_>) -> Self {
+        match p {
+            DocPath::Name(n) => OwnedDocPath::Name(n.to_string()),
+            DocPath::Index(i) => OwnedDocPath::Index(i),
+        }
+    }
+}
+
 impl std::fmt::Display for DocPath<'_> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -100,8 +124,8 @@ impl std::fmt::Display for DocPath<'_> {
     }
 }
 
-impl<'a> Iterator for DocPathIter<'a> {
-    type Item = (Vec<DocPath<'a>>, &'a Document);
+impl<'a, 'd> Iterator for DocPathIter<'a, 'd> {
+    type Item = (Vec<DocPath<'a>>, &'a Document<'d>);
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(top) = self.stack.last_mut() {
             let val = top.next();
@@ -117,7 +141,7 @@ impl<'a> Iterator for DocPathIter<'a> {
                     self.path.push(DocPath::Index(usize::MAX));
                     self.aggregate.push(true);
                 }
-                Some(Document::Compact(v)) => {
+                Some(Document::Compact(v)) | Some(Document::Spanned(v, _)) => {
                     self.stack.push(std::slice::from_ref(&**v).iter());
                     self.aggregate.push(false);
                 }
@@ -158,8 +182,8 @@ impl<'a> Iterator for DocPathIter<'a> {
     }
 }
 
-impl<'a> Iterator for DocPathIterMut<'a> {
-    type Item = (Vec<DocPath<'a>>, &'a mut Document);
+impl<'a, 'd> Iterator for DocPathIterMut<'a, 'd> {
+    type Item = (Vec<DocPath<'a>>, &'a mut Document<'d>);
     fn next(&mut self) -> Option<Self::Item> {
         while let Some(top) = self.stack.last_mut() {
             let val = top.next();
@@ -175,7 +199,7 @@ impl<'a> Iterator for DocPathIterMut<'a> {
                     self.path.push(DocPath::Index(usize::MAX));
                     self.aggregate.push(true);
                 }
-                Some(Document::Compact(ref mut v)) => {
+                Some(Document::Compact(ref mut v)) | Some(Document::Spanned(ref mut v, _)) => {
                     self.stack.push(std::slice::from_mut(&mut **v).iter_mut());
                     self.aggregate.push(false);
                 }
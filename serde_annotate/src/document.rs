@@ -4,6 +4,8 @@ use std::convert::TryFrom;
 use crate::error::Error;
 use crate::integer::Int;
 use crate::relax::Relax;
+use crate::toml_parser::TomlParser;
+use crate::yaml_parser::YamlParser;
 
 /// Represents possible serialized string formats.
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
@@ -43,14 +45,39 @@ pub enum CommentFormat {
     SlashSlash,
 }
 
+/// The location of a node in the text it was parsed from.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Span {
+    /// The byte offset of the start of the node.
+    pub start: usize,
+    /// The byte offset of the end of the node.
+    pub end: usize,
+    /// The line number of the start of the node (1-based).
+    pub line: usize,
+    /// The column number of the start of the node (1-based).
+    pub col: usize,
+}
+
+/// A document node.
+///
+/// Strings which appear verbatim in the parsed text are borrowed from it as
+/// `Str` nodes; strings which had to be unescaped or otherwise rewritten are
+/// owned.  Use [`Document::into_owned`] to detach a document from its source.
+///
+/// Each member of a `Mapping` or `Sequence` may be a `Fragment` holding
+/// comments along with the member's key and value.  Comments before the key
+/// (or value) are leading comments, emitted on the lines above the member;
+/// comments after the value are trailing comments, emitted on the same line
+/// as the value.  A member holding only comments is a run of dangling
+/// comments, such as those before the closing bracket of an aggregate.
 #[derive(Clone, Debug)]
-pub enum Document {
+pub enum Document<'a> {
     // A comment (emitted for humans, ignored by parsers).
     Comment(String, CommentFormat),
     // A string value and its preferred formatting.
     String(String, StrFormat),
-    // A string reference and its preferred formatting.
-    StaticStr(&'static str, StrFormat),
+    // A string borrowed from the source text and its preferred formatting.
+    Str(&'a str, StrFormat),
     // A boolean value.
     Boolean(bool),
     // An Integer (signed, unsigned, 8 to 128 bits) and its preferred output form.
@@ -58,57 +85,91 @@ pub enum Document {
     // Floating point types.
     Float(f64),
     // A mapping object (e.g. dict/hash/etc)
-    Mapping(Vec<Document>),
+    Mapping(Vec<Document<'a>>),
     // A sequence objecct (e.g. list/array/etc)
-    Sequence(Vec<Document>),
+    Sequence(Vec<Document<'a>>),
     // A special form for bytes objects.
     Bytes(Vec<u8>),
     // A null value.
     Null,
     // A hint to the emitter to emit in compact form.
-    Compact(Box<Document>),
+    Compact(Box<Document<'a>>),
+    // A node and its location in the parsed text.
+    Spanned(Box<Document<'a>>, Span),
     // A fragment holds a set of document nodes that may be useful as an
     // aggregate, such as Key-Value pairs.
-    Fragment(Vec<Document>),
+    Fragment(Vec<Document<'a>>),
 }
 
-impl From<&'static str> for Document {
-    fn from(s: &'static str) -> Self {
-        Document::StaticStr(s, StrFormat::Standard)
+impl<'a> From<&'a str> for Document<'a> {
+    fn from(s: &'a str) -> Self {
+        Document::Str(s, StrFormat::Standard)
     }
 }
 
-impl Document {
+impl<'a> Document<'a> {
     /// Parses a string into a `Document` using the maximally permissive parser.
-    pub fn parse(text: &str) -> Result<Document, Error> {
+    pub fn parse(text: &'a str) -> Result<Document<'a>, Error> {
         let relax = Relax::default();
         relax.from_str(text)
     }
 
     /// Parses a string into a `Document` using strict json.
-    pub fn from_json(text: &str) -> Result<Document, Error> {
+    pub fn from_json(text: &'a str) -> Result<Document<'a>, Error> {
         let relax = Relax::json();
         relax.from_str(text)
     }
 
     /// Parses a string into a `Document` using json5.
-    pub fn from_json5(text: &str) -> Result<Document, Error> {
+    pub fn from_json5(text: &'a str) -> Result<Document<'a>, Error> {
         let relax = Relax::json5();
         relax.from_str(text)
     }
 
     /// Parses a string into a `Document` using hjson.
-    pub fn from_hjson(text: &str) -> Result<Document, Error> {
+    pub fn from_hjson(text: &'a str) -> Result<Document<'a>, Error> {
         let relax = Relax::hjson();
         relax.from_str(text)
     }
 
+    /// Parses a string into a `Document` using yaml.
+    pub fn from_yaml(text: &'a str) -> Result<Document<'a>, Error> {
+        YamlParser::new(text).parse()
+    }
+
+    /// Parses a string into a `Document` using toml.
+    pub fn from_toml(text: &'a str) -> Result<Document<'a>, Error> {
+        TomlParser::new(text).parse()
+    }
+
+    /// Converts this document into one which owns all of its strings.
+    pub fn into_owned(self) -> Document<'static> {
+        fn all(docs: Vec<Document>) -> Vec<Document<'static>> {
+            docs.into_iter().map(Document::into_owned).collect()
+        }
+        match self {
+            Document::Comment(c, f) => Document::Comment(c, f),
+            Document::String(s, f) => Document::String(s, f),
+            Document::Str(s, f) => Document::String(s.to_owned(), f),
+            Document::Boolean(b) => Document::Boolean(b),
+            Document::Int(i) => Document::Int(i),
+            Document::Float(f) => Document::Float(f),
+            Document::Mapping(m) => Document::Mapping(all(m)),
+            Document::Sequence(s) => Document::Sequence(all(s)),
+            Document::Bytes(b) => Document::Bytes(b),
+            Document::Null => Document::Null,
+            Document::Compact(c) => Document::Compact(Box::new(c.into_owned())),
+            Document::Spanned(d, span) => Document::Spanned(Box::new(d.into_owned()), span),
+            Document::Fragment(f) => Document::Fragment(all(f)),
+        }
+    }
+
     /// Returns the variant of this `Document`.
     pub fn variant(&self) -> &'static str {
         match self {
             Document::Comment(_, _) => "Comment",
             Document::String(_, _) => "String",
-            Document::StaticStr(_, _) => "StaticStr",
+            Document::Str(_, _) => "Str",
             Document::Boolean(_) => "Boolean",
             Document::Int(_) => "Int",
             Document::Float(_) => "Float",
@@ -117,12 +178,13 @@ impl Document {
             Document::Bytes(_) => "Bytes",
             Document::Null => "Null",
             Document::Compact(_) => "Compact",
+            Document::Spanned(d, _) => d.variant(),
             Document::Fragment(_) => "Fragment",
         }
     }
 
     /// Returns the list of fragments in this node.
-    pub fn fragments(&self) -> Result<&[Document], Error> {
+    pub fn fragments(&self) -> Result<&[Document<'a>], Error> {
         if let Document::Fragment(f) = self {
             Ok(f)
         } else {
@@ -131,7 +193,7 @@ impl Document {
     }
 
     /// Returns a mutable list of fragments in this node.
-    pub fn fragments_mut(&mut self) -> Result<&mut [Document], Error> {
+    pub fn fragments_mut(&mut self) -> Result<&mut [Document<'a>], Error> {
         if let Document::Fragment(ref mut f) = self {
             Ok(f)
         } else {
@@ -140,7 +202,7 @@ impl Document {
     }
 
     /// Returns this node as a kvpair.
-    pub fn as_kv(&self) -> Result<(&Document, &Document), Error> {
+    pub fn as_kv(&self) -> Result<(&Document<'a>, &Document<'a>), Error> {
         let frags = self.fragments()?;
         let kv = frags.iter().filter(|f| f.has_value()).collect::<Vec<_>>();
         match kv.len() {
@@ -152,7 +214,7 @@ impl Document {
     }
 
     /// Returns this node as a mutable kvpair.
-    pub fn as_kv_mut(&mut self) -> Result<(&mut Document, &mut Document), Error> {
+    pub fn as_kv_mut(&mut self) -> Result<(&mut Document<'a>, &mut Document<'a>), Error> {
         let frags = self.fragments_mut()?;
         let mut kv = frags
             .iter_mut()
@@ -173,15 +235,16 @@ impl Document {
     /// Returns a reference to this node's value-containing `Document`.
     /// A comment node has no value and thus returns an error.
     /// A fragment node must contain exactly one value or it returns an error.
-    pub fn as_value(&self) -> Result<&Document, Error> {
+    pub fn as_value(&self) -> Result<&Document<'a>, Error> {
         match self {
             Document::Comment(_, _) => Err(Error::StructureError("a value", "Comment")),
             Document::Compact(c) => c.as_value(),
+            Document::Spanned(d, _) => d.as_value(),
             Document::Fragment(frags) => {
                 let values = frags.iter().filter(|f| f.has_value()).collect::<Vec<_>>();
                 match values.len() {
                     0 => Err(Error::StructureError("one value", "zero")),
-                    1 => Ok(values[0]),
+                    1 => values[0].as_value(),
                     _ => Err(Error::StructureError("one value", "many")),
                 }
             }
@@ -192,10 +255,11 @@ impl Document {
     /// Returns a mutable reference to this node's value-containing `Document`.
     /// A comment node has no value and thus returns an error.
     /// A fragment node must contain exactly one value or it returns an error.
-    pub fn as_value_mut(&mut self) -> Result<&mut Document, Error> {
+    pub fn as_value_mut(&mut self) -> Result<&mut Document<'a>, Error> {
         match self {
             Document::Comment(_, _) => Err(Error::StructureError("a value", "Comment")),
             Document::Compact(c) => c.as_value_mut(),
+            Document::Spanned(d, _) => d.as_value_mut(),
             Document::Fragment(frags) => {
                 let mut values = frags
                     .iter_mut()
@@ -203,7 +267,7 @@ impl Document {
                     .collect::<Vec<_>>();
                 match values.len() {
                     0 => Err(Error::StructureError("one value", "zero")),
-                    1 => Ok(values.pop().unwrap()),
+                    1 => values.pop().unwrap().as_value_mut(),
                     _ => Err(Error::StructureError("one value", "many")),
                 }
             }
@@ -216,21 +280,68 @@ impl Document {
         match self {
             Document::Comment(_, _) => false,
             Document::Compact(c) => c.has_value(),
+            Document::Spanned(d, _) => d.has_value(),
             Document::Fragment(f) => f.iter().any(Document::has_value),
             _ => true,
         }
     }
 
-    /// Returns the index of the last value containing node in a slice.
-    pub fn last_value_index(sequence: &[Document]) -> usize {
-        let mut last = sequence.len();
-        for (i, frag) in sequence.iter().enumerate().rev() {
-            if frag.has_value() {
-                last = i;
+    /// Returns the index of the last value containing node in a slice, or
+    /// `None` if no node contains a value.
+    pub fn last_value_index(sequence: &[Document]) -> Option<usize> {
+        sequence.iter().rposition(Document::has_value)
+    }
+
+    /// Splits the nodes of a mapping or sequence member into its leading
+    /// comments, its key and value, and its trailing comments.  The comments
+    /// of a member without a value are returned as leading comments.
+    pub(crate) fn member_parts<'d>(
+        nodes: &'d [Document<'a>],
+    ) -> (&'d [Document<'a>], &'d [Document<'a>], &'d [Document<'a>]) {
+        let first = nodes.iter().position(Document::has_value);
+        let last = nodes.iter().rposition(Document::has_value);
+        match (first, last) {
+            (Some(first), Some(last)) => {
+                (&nodes[..first], &nodes[first..=last], &nodes[last + 1..])
+            }
+            _ => (nodes, &[], &[]),
+        }
+    }
+
+    /// Returns the value of a node along with the comments attached to it by
+    /// the fragments enclosing it (e.g. the comment on a unit variant), from
+    /// the outermost fragment in.  Each comment is flagged with whether it
+    /// precedes the value.
+    pub(crate) fn value_comments(&self) -> (&Document<'a>, Vec<(&Document<'a>, bool)>) {
+        let mut node = self;
+        let mut comments = Vec::new();
+        while let Document::Fragment(nodes) = node {
+            let (leading, values, trailing) = Document::member_parts(nodes);
+            let [value] = values else {
                 break;
+            };
+            comments.extend(leading.iter().map(|c| (c, true)));
+            comments.extend(trailing.iter().map(|c| (c, false)));
+            node = value;
+        }
+        (node, comments)
+    }
+
+    /// Returns the source location of this node's value, if the parser
+    /// recorded one.
+    pub fn span(&self) -> Option<Span> {
+        match self {
+            Document::Spanned(_, span) => Some(*span),
+            Document::Compact(c) => c.span(),
+            Document::Fragment(frags) => {
+                let mut values = frags.iter().filter(|f| f.has_value());
+                match (values.next(), values.next()) {
+                    (Some(v), None) => v.span(),
+                    _ => None,
+                }
             }
+            _ => None,
         }
-        last
     }
 
     /// Returns the comment information contained in a node.
@@ -246,7 +357,7 @@ impl Document {
     pub fn as_str(&self) -> Result<&str, Error> {
         match self.as_value()? {
             Document::String(s, _) => Ok(s.as_str()),
-            Document::StaticStr(s, _) => Ok(s),
+            Document::Str(s, _) => Ok(s),
             _ => Err(Error::StructureError("String", self.variant())),
         }
     }
@@ -268,21 +379,77 @@ fn parse_bool(v: &str) -> Result<bool, Error> {
     }
 }
 
+/// A member of a mapping or sequence with its comments in emitting order.
+/// A member without a value holds only dangling comments.
+pub(crate) struct Member<'d> {
+    pub leading: Vec<(&'d str, &'d CommentFormat)>,
+    pub key: Option<&'d Document<'d>>,
+    pub value: Option<&'d Document<'d>>,
+    pub trailing: Vec<(&'d str, &'d CommentFormat)>,
+}
+
+impl<'d> Member<'d> {
+    /// Splits a member of a mapping (if `mapping`) or sequence.  Comments
+    /// between a key and its value lead the member, as do the comments which
+    /// precede the value they're attached to.
+    pub(crate) fn new(member: &'d Document<'d>, mapping: bool) -> Result<Self, Error> {
+        let nodes = match member {
+            Document::Fragment(nodes) => nodes.as_slice(),
+            _ if mapping => member.fragments()?,
+            _ => std::slice::from_ref(member),
+        };
+        let (leading, values, trailing) = Document::member_parts(nodes);
+        let mut member = Member {
+            leading: leading
+                .iter()
+                .chain(values)
+                .filter_map(Document::comment)
+                .collect(),
+            key: None,
+            value: None,
+            trailing: trailing.iter().filter_map(Document::comment).collect(),
+        };
+        let mut values = values.iter().filter(|v| v.has_value());
+        if mapping {
+            member.key = values.next();
+        }
+        let Some(value) = values.next() else {
+            return match member.key {
+                Some(_) => Err(Error::StructureError("kvpair", "one element")),
+                None => Ok(member),
+            };
+        };
+        let (value, attached) = value.value_comments();
+        for (c, precedes) in attached {
+            let Some(c) = c.comment() else {
+                continue;
+            };
+            if precedes {
+                member.leading.push(c);
+            } else {
+                member.trailing.push(c);
+            }
+        }
+        member.value = Some(value);
+        Ok(member)
+    }
+}
+
 /// Tries to convert the document into a boolean value.
-impl TryFrom<&Document> for bool {
+impl TryFrom<&Document<'_>> for bool {
     type Error = Error;
     fn try_from(v: &Document) -> Result<Self, Self::Error> {
         match v.as_value()? {
             Document::Boolean(b) => Ok(*b),
             Document::String(s, _) => parse_bool(s.as_str()),
-            Document::StaticStr(s, _) => parse_bool(s),
+            Document::Str(s, _) => parse_bool(s),
             _ => Err(Error::StructureError("Boolean", v.variant())),
         }
     }
 }
 
 /// Tries to convert the document into a char value.
-impl TryFrom<&Document> for char {
+impl TryFrom<&Document<'_>> for char {
     type Error = Error;
     fn try_from(v: &Document) -> Result<Self, Self::Error> {
         let s = v.as_str()?;
@@ -297,19 +464,46 @@ impl TryFrom<&Document> for char {
     }
 }
 
+impl Document<'_> {
+    /// Converts the document into an integer of type `T`.  Values which don't
+    /// fit in `T` are an error, unless `wrapping` is set, in which case
+    /// integers are converted to `T` keeping their bit pattern (e.g. `0xFFFFFFFF`
+    /// as an `i32` is `-1`).
+    pub(crate) fn to_int<T>(&self, wrapping: bool) -> Result<T, Error>
+    where
+        T: num_traits::NumCast + for<'a> From<&'a Int>,
+    {
+        let name = std::any::type_name::<T>();
+        let int = |i: Int| {
+            if wrapping {
+                Ok(<T as From<&Int>>::from(&i))
+            } else {
+                i.checked()
+                    .ok_or_else(|| Error::OutOfRange(i.to_string(), name))
+            }
+        };
+        match self.as_value()? {
+            Document::Int(v) => int(v.clone()),
+            Document::Float(v) if v.fract() != 0.0 || !v.is_finite() => {
+                Err(Error::Inexact(v.to_string(), name))
+            }
+            Document::Float(v) => <T as num_traits::NumCast>::from(*v)
+                .ok_or_else(|| Error::OutOfRange(v.to_string(), name)),
+            Document::String(s, _) => int(Int::from_str_radix(s.as_str(), 0)?),
+            Document::Str(s, _) => int(Int::from_str_radix(s, 0)?),
+            _ => Err(Error::StructureError("Int", self.variant())),
+        }
+    }
+}
+
 macro_rules! impl_int_conv {
     ($t:ty) => {
-        /// Tries to convert the document into an integer value.
-        impl TryFrom<&Document> for $t {
+        /// Tries to convert the document into an integer value.  Values out of
+        /// range for the integer type are an error.
+        impl TryFrom<&Document<'_>> for $t {
             type Error = Error;
             fn try_from(v: &Document) -> Result<Self, Self::Error> {
-                match v.as_value()? {
-                    Document::Int(v) => Ok(<$t>::from(v)),
-                    Document::Float(v) => Ok(*v as $t),
-                    Document::String(s, _) => Ok(<$t>::from(Int::from_str_radix(s.as_str(), 0)?)),
-                    Document::StaticStr(s, _) => Ok(<$t>::from(Int::from_str_radix(s, 0)?)),
-                    _ => Err(Error::StructureError("Int", v.variant())),
-                }
+                v.to_int(false)
             }
         }
     };
@@ -328,7 +522,7 @@ impl_int_conv!(i128);
 macro_rules! impl_float_conv {
     ($t:ty) => {
         /// Tries to convert the document into a float value.
-        impl TryFrom<&Document> for $t {
+        impl TryFrom<&Document<'_>> for $t {
             type Error = Error;
             fn try_from(v: &Document) -> Result<Self, Self::Error> {
                 match v.as_value()? {
@@ -1,3 +1,5 @@
+use crate::doc_iter::{DocPath, OwnedDocPath};
+#[cfg(feature = "pest")]
 use crate::relax::ParseError;
 use serde::{de, ser};
 use std::char::CharTryFromError;
@@ -18,10 +20,13 @@ pub enum Error {
     EscapeError(char),
     #[error("formatter error: {0:?}")]
     FmtError(#[from] std::fmt::Error),
+    #[error(transparent)]
+    IoError(#[from] std::io::Error),
     #[error("Hexdump error: {0}")]
     HexdumpError(String),
     #[error("Type {0:?} is not valid as a mapping key")]
     KeyTypeError(&'static str),
+    #[cfg(feature = "pest")]
     #[error(transparent)]
     ParseError(Box<ParseError>),
     #[error(transparent)]
@@ -32,8 +37,108 @@ pub enum Error {
     CharTryFromError(#[from] CharTryFromError),
     #[error("document structure error: expected {0} but got {1}")]
     StructureError(&'static str, &'static str),
+    #[error("{0} is out of range for {1}")]
+    OutOfRange(String, &'static str),
+    #[error("{0} is not exactly representable as {1}")]
+    Inexact(String, &'static str),
+    #[error("{0} cannot represent {1}")]
+    Unrepresentable(&'static str, String),
+    #[error("invalid schema: {0}")]
+    InvalidSchema(String),
+    #[error("schema violation: {0}")]
+    SchemaViolation(String),
+    #[error("path error at `{0}`: {1}")]
+    PathError(String, &'static str),
     #[error("syntax error: {0} at {1}:{col}\n| {3}\n| {4:>col$}", col = .2)]
     SyntaxError(String, usize, usize, String, &'static str),
+    #[error("at {}: {1}", dotted(.0))]
+    AtPath(Vec<OwnedDocPath>, Box<Error>),
+    #[error("{0} at {1}")]
+    Located(Box<Error>, Location),
+}
+
+/// The location in the source text of a document node associated with an error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    /// The name of the source file, if known.
+    pub file: Option<String>,
+    /// The 1-based line number.
+    pub line: usize,
+    /// The 1-based column number.
+    pub col: usize,
+    /// The text of the source line, if known.
+    pub excerpt: Option<String>,
+}
+
+impl Display for Location {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{}:", file)?;
+        }
+        write!(f, "{}:{}", self.line, self.col)?;
+        if let Some(excerpt) = &self.excerpt {
+            write!(f, "\n| {}\n| {:>col$}", excerpt, "^", col = self.col)?;
+        }
+        Ok(())
+    }
+}
+
+impl Error {
+    /// Returns the source location associated with this error, if any.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located(_, loc) => Some(loc),
+            Error::AtPath(_, e) => e.location(),
+            _ => None,
+        }
+    }
+
+    /// Returns the document path at which this error occurred, if any.
+    pub fn path(&self) -> Option<Vec<DocPath<'_>>> {
+        match self {
+            Error::AtPath(path, _) => Some(path.iter().map(OwnedDocPath::as_doc_path).collect()),
+            Error::Located(e, _) => e.path(),
+            _ => None,
+        }
+    }
+
+    /// Attaches `path` to the error, within any source location.  If the
+    /// error already has a path, `path` is prepended to it.
+    pub(crate) fn at_path(self, mut path: Vec<OwnedDocPath>) -> Error {
+        match self {
+            _ if path.is_empty() => self,
+            Error::Located(e, loc) => Error::Located(Box::new(e.at_path(path)), loc),
+            Error::AtPath(p, e) => {
+                path.extend(p);
+                Error::AtPath(path, e)
+            }
+            e => Error::AtPath(path, Box::new(e)),
+        }
+    }
+
+    /// Returns the underlying error without any path or location context.
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::AtPath(_, e) | Error::Located(e, _) => e.inner(),
+            _ => self,
+        }
+    }
+}
+
+// Formats a document path as in `items[1].name`.
+fn dotted(path: &[OwnedDocPath]) -> String {
+    let mut s = String::new();
+    for element in path {
+        match element {
+            OwnedDocPath::Name(n) if s.is_empty() => s.push_str(n),
+            OwnedDocPath::Name(n) => {
+                s.push('.');
+                s.push_str(n);
+            }
+            OwnedDocPath::Index(i) => s.push_str(&format!("[{}]", i)),
+        }
+    }
+    s
 }
 
 impl ser::Error for Error {
@@ -48,6 +153,7 @@ impl de::Error for Error {
     }
 }
 
+#[cfg(feature = "pest")]
 impl From<ParseError> for Error {
     fn from(e: ParseError) -> Self {
         Error::ParseError(Box::new(e))
@@ -25,29 +25,34 @@ fn hexdump(data: &[u8]) -> String {
         if i > 0 {
             s.push('\n');
         }
-        write!(s, "{:08x}", i * 16).unwrap();
-        let mut buf = [b'.'; 16];
-        let mut space = 51;
-        for (j, &byte) in chunk.iter().enumerate() {
-            if j % 8 == 0 {
-                s.push(' ');
-                space -= 1;
-            }
+        hexdump_row(&mut s, i * 16, chunk);
+    }
+    s
+}
+
+// Emit one row of up to 16 bytes in the style of `hexdump -vC`.
+fn hexdump_row(s: &mut String, offset: usize, chunk: &[u8]) {
+    write!(s, "{:08x}", offset).unwrap();
+    let mut buf = [b'.'; 16];
+    let mut space = 51;
+    for (j, &byte) in chunk.iter().enumerate() {
+        if j % 8 == 0 {
             s.push(' ');
-            s.push(HEX[(byte >> 4) as usize] as char);
-            s.push(HEX[(byte & 0x0F) as usize] as char);
-            space -= 3;
-            buf[j] = match byte {
-                0x20..=0x7f => byte,
-                _ => b'.',
-            };
+            space -= 1;
         }
-        // Utf8Error is impossible here because all of the codepoints
-        // inside `buf` are ASCII.
-        let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
-        write!(s, "{0:>1$} |{2}|", " ", space, chars).unwrap();
+        s.push(' ');
+        s.push(HEX[(byte >> 4) as usize] as char);
+        s.push(HEX[(byte & 0x0F) as usize] as char);
+        space -= 3;
+        buf[j] = match byte {
+            0x20..=0x7f => byte,
+            _ => b'.',
+        };
     }
-    s
+    // Utf8Error is impossible here because all of the codepoints
+    // inside `buf` are ASCII.
+    let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
+    write!(s, "{0:>1$} |{2}|", " ", space, chars).unwrap();
 }
 
 // Emit bytes as a hexdump in the style of `xxd -g<grouping>``.
@@ -61,30 +66,69 @@ fn xxd(data: &[u8], grouping: usize) -> String {
         if i > 0 {
             s.push('\n');
         }
-        write!(s, "{:08x}:", i * 16).unwrap();
-        let mut buf = [b'.'; 16];
-        let mut space = (16 / grouping) * (grouping * 2 + 1) + 1;
-        for (j, &byte) in chunk.iter().enumerate() {
-            if j % grouping == 0 {
-                s.push(' ');
-                space -= 1;
-            }
-            s.push(HEX[(byte >> 4) as usize] as char);
-            s.push(HEX[(byte & 0x0F) as usize] as char);
-            space -= 2;
-            buf[j] = match byte {
-                0x20..=0x7f => byte,
-                _ => b'.',
-            };
-        }
-        // Utf8Error is impossible here because all of the codepoints
-        // inside `buf` are ASCII.
-        let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
-        write!(s, "{0:>1$} {2}", " ", space, chars).unwrap();
+        xxd_row(&mut s, i * 16, chunk, grouping);
     }
     s
 }
 
+// Emit one row of up to 16 bytes in the style of `xxd -g<grouping>`.
+fn xxd_row(s: &mut String, offset: usize, chunk: &[u8], grouping: usize) {
+    write!(s, "{:08x}:", offset).unwrap();
+    let mut buf = [b'.'; 16];
+    let mut space = (16 / grouping) * (grouping * 2 + 1) + 1;
+    for (j, &byte) in chunk.iter().enumerate() {
+        if j % grouping == 0 {
+            s.push(' ');
+            space -= 1;
+        }
+        s.push(HEX[(byte >> 4) as usize] as char);
+        s.push(HEX[(byte & 0x0F) as usize] as char);
+        space -= 2;
+        buf[j] = match byte {
+            0x20..=0x7f => byte,
+            _ => b'.',
+        };
+    }
+    // Utf8Error is impossible here because all of the codepoints
+    // inside `buf` are ASCII.
+    let chars = std::str::from_utf8(&buf[..chunk.len()]).unwrap();
+    write!(s, "{0:>1$} {2}", " ", space, chars).unwrap();
+}
+
+/// An iterator over the lines of a multiline hexdump, which lets large
+/// buffers be emitted without holding the whole hexdump in memory.
+pub struct Rows<'a> {
+    chunks: std::iter::Enumerate<std::slice::Chunks<'a, u8>>,
+    format: BytesFormat,
+}
+
+impl Iterator for Rows<'_> {
+    type Item = String;
+
+    fn next(&mut self) -> Option<String> {
+        let (i, chunk) = self.chunks.next()?;
+        let mut s = String::with_capacity(80);
+        match self.format {
+            BytesFormat::Hexdump => hexdump_row(&mut s, i * 16, chunk),
+            _ => xxd_row(&mut s, i * 16, chunk, 2),
+        }
+        Some(s)
+    }
+}
+
+/// Returns the lines of `data` in the multiline `format`, or `None` if
+/// `format` isn't a multiline format.  The lines are the same as those of
+/// `to_string`, except that empty data has no lines.
+pub fn rows(data: &[u8], format: BytesFormat) -> Option<Rows<'_>> {
+    match format {
+        BytesFormat::Hexdump | BytesFormat::Xxd => Some(Rows {
+            chunks: data.chunks(16).enumerate(),
+            format,
+        }),
+        _ => None,
+    }
+}
+
 /// Convers a byte buffer to a hexadecimal string in `format`.
 pub fn to_string(data: &[u8], format: BytesFormat) -> Option<String> {
     match format {
@@ -211,9 +255,9 @@ mod tests {
     #[test]
     fn test_xxd() -> Result<()> {
         let buf = TEST_STR;
-        for n in 0..XXD.len() {
+        for (n, expect) in XXD.iter().enumerate() {
             let res = xxd(buf.as_bytes(), 1 << n);
-            assert_eq!(res, XXD[n]);
+            assert_eq!(&res, expect);
         }
         Ok(())
     }
@@ -237,8 +281,8 @@ mod tests {
 
     #[test]
     fn test_from_xxd() -> Result<()> {
-        for n in 0..XXD.len() {
-            let res = from_str(XXD[n])?;
+        for text in XXD {
+            let res = from_str(text)?;
             let s = std::str::from_utf8(&res)?;
             assert_eq!(s, TEST_STR);
         }
@@ -13,7 +13,7 @@ pub enum Base {
     Hex = 16,
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub enum IntValue {
     U8(u8),
     U16(u16),
@@ -67,8 +67,8 @@ impl IntValue {
             let bits = std::mem::size_of_val(&v) * 8;
             width = match base {
                 Base::Bin => bits,
-                Base::Oct => (bits + 2) / 3,
-                Base::Hex => (bits + 3) / 4,
+                Base::Oct => bits.div_ceil(3),
+                Base::Hex => bits.div_ceil(4),
                 Base::Dec => unreachable!(),
             };
         }
@@ -121,18 +121,22 @@ impl IntValue {
         }
     }
 
-    pub fn negate(self) -> Self {
+    /// Negates the value, widening unsigned values to a signed type.
+    /// Returns `None` if the negated value doesn't fit in an `i128`.
+    pub fn checked_neg(self) -> Option<Self> {
         match self {
-            IntValue::U8(v) => IntValue::I16(-(v as i16)),
-            IntValue::U16(v) => IntValue::I32(-(v as i32)),
-            IntValue::U32(v) => IntValue::I64(-(v as i64)),
-            IntValue::U64(v) => IntValue::I128(-(v as i128)),
-            IntValue::U128(v) => IntValue::I128(-(v as i128)),
-            IntValue::I8(v) => IntValue::I8(-v),
-            IntValue::I16(v) => IntValue::I16(-v),
-            IntValue::I32(v) => IntValue::I32(-v),
-            IntValue::I64(v) => IntValue::I64(-v),
-            IntValue::I128(v) => IntValue::I128(-v),
+            IntValue::U8(v) => Some(IntValue::I16(-(v as i16))),
+            IntValue::U16(v) => Some(IntValue::I32(-(v as i32))),
+            IntValue::U32(v) => Some(IntValue::I64(-(v as i64))),
+            IntValue::U64(v) => Some(IntValue::I128(-(v as i128))),
+            // `i128::MIN` has no positive counterpart in an `i128`.
+            IntValue::U128(v) if v == i128::MIN.unsigned_abs() => Some(IntValue::I128(i128::MIN)),
+            IntValue::U128(v) => i128::try_from(v).ok().map(|v| IntValue::I128(-v)),
+            IntValue::I8(v) => v.checked_neg().map(IntValue::I8),
+            IntValue::I16(v) => v.checked_neg().map(IntValue::I16),
+            IntValue::I32(v) => v.checked_neg().map(IntValue::I32),
+            IntValue::I64(v) => v.checked_neg().map(IntValue::I64),
+            IntValue::I128(v) => v.checked_neg().map(IntValue::I128),
         }
     }
 }
@@ -142,6 +146,7 @@ pub struct Int {
     value: IntValue,
     base: Base,
     width: usize,
+    group: usize,
 }
 
 impl Int {
@@ -152,6 +157,7 @@ impl Int {
             value: v.into(),
             base,
             width,
+            group: 0,
         }
     }
     /// Creates an `Int` that will display with no zero padding.
@@ -162,6 +168,13 @@ impl Int {
     pub fn new_padded<T: Into<IntValue>>(v: T, base: Base) -> Int {
         Self::new_with_padding(v, base, usize::MAX)
     }
+    /// Separates each `group` digits of the integer with `_` when it is
+    /// displayed in its preferred base.  A `group` of zero displays the
+    /// digits without separators.
+    pub fn with_grouping(mut self, group: usize) -> Int {
+        self.group = group;
+        self
+    }
 
     /// Returns whether the integer is within the legal range of json integers.
     pub fn is_legal_json(&self) -> bool {
@@ -174,6 +187,39 @@ impl Int {
         }
     }
 
+    /// Returns the smallest and largest values representable by the
+    /// integer's type, unless the type is 128 bits wide.
+    pub(crate) fn limits(&self) -> Option<(i128, i128)> {
+        match self.value {
+            IntValue::U8(_) => Some((0, u8::MAX.into())),
+            IntValue::U16(_) => Some((0, u16::MAX.into())),
+            IntValue::U32(_) => Some((0, u32::MAX.into())),
+            IntValue::U64(_) => Some((0, u64::MAX.into())),
+            IntValue::I8(_) => Some((i8::MIN.into(), i8::MAX.into())),
+            IntValue::I16(_) => Some((i16::MIN.into(), i16::MAX.into())),
+            IntValue::I32(_) => Some((i32::MIN.into(), i32::MAX.into())),
+            IntValue::I64(_) => Some((i64::MIN.into(), i64::MAX.into())),
+            IntValue::U128(_) | IntValue::I128(_) => None,
+        }
+    }
+
+    /// Converts the integer to `T`, or returns `None` if the value is out of
+    /// range for `T`.  Use `T::from` to convert the bit pattern instead.
+    pub fn checked<T: num_traits::NumCast>(&self) -> Option<T> {
+        match self.value {
+            IntValue::U8(v) => T::from(v),
+            IntValue::U16(v) => T::from(v),
+            IntValue::U32(v) => T::from(v),
+            IntValue::U64(v) => T::from(v),
+            IntValue::U128(v) => T::from(v),
+            IntValue::I8(v) => T::from(v),
+            IntValue::I16(v) => T::from(v),
+            IntValue::I32(v) => T::from(v),
+            IntValue::I64(v) => T::from(v),
+            IntValue::I128(v) => T::from(v),
+        }
+    }
+
     /// Returns the preferred base for expressing this integer.
     pub fn base(&self) -> Base {
         self.base
@@ -181,7 +227,26 @@ impl Int {
 
     /// Formats the integer in the requested base, defaulting to the preferred base.
     pub fn format(&self, base: Option<&Base>) -> String {
-        self.value.format(*base.unwrap_or(&Base::Dec), self.width)
+        let base = *base.unwrap_or(&Base::Dec);
+        let s = self.value.format(base, self.width);
+        // The grouping was chosen for the preferred base, so integers
+        // displayed in another base aren't grouped.
+        if self.group == 0 || base != self.base {
+            return s;
+        }
+        let start = match base {
+            Base::Dec => usize::from(s.starts_with('-')),
+            _ => 2,
+        };
+        let (prefix, digits) = s.split_at(start);
+        let mut grouped = prefix.to_string();
+        for (i, ch) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i) % self.group == 0 {
+                grouped.push('_');
+            }
+            grouped.push(ch);
+        }
+        grouped
     }
 
     fn strip_numeric_prefix(src: &str, ch: u8) -> &str {
@@ -214,6 +279,9 @@ impl Int {
     /// - If `radix` is `0`, the base is inferred from the common integer
     ///   prefixes `0x`, `0b` and `0o`.  If there is no prefix, the base defaults
     ///   to base 10.
+    ///
+    /// The digits may be separated into groups with `_` (e.g. `0x0000_0040`).
+    /// Each `_` must be between two digits.
     pub fn from_str_radix(src: &str, radix: u32) -> Result<Int, ParseIntError> {
         let (negative, src) = if let Some(s) = src.strip_prefix('-') {
             (true, s)
@@ -229,9 +297,19 @@ impl Int {
             10 => (Base::Dec, src),
             _ => Self::detect_numeric_prefix(src),
         };
-        let value = IntValue::U128(u128::from_str_radix(text, base as u32)?);
-        let value = if negative { value.negate() } else { value };
-        Ok(Self::new_with_padding(value, base, text.len()))
+        let digits = text.replace('_', "");
+        let separated = !text.starts_with('_') && !text.ends_with('_') && !text.contains("__");
+        // Misplaced separators are left in the text so the parse fails.
+        let value = u128::from_str_radix(if separated { &digits } else { text }, base as u32)?;
+        let value = match IntValue::U128(value).checked_neg() {
+            Some(v) if negative => v,
+            // Parsing the negated magnitude as an `i128` reports the overflow.
+            None if negative => IntValue::I128(format!("-{}", value).parse()?),
+            _ => IntValue::U128(value),
+        };
+        // The size of the last group gives the grouping.
+        let group = text.rfind('_').map(|i| text.len() - i - 1).unwrap_or(0);
+        Ok(Self::new_with_padding(value, base, digits.len()).with_grouping(group))
     }
 }
 
@@ -243,7 +321,8 @@ impl fmt::Display for Int {
 
 macro_rules! impl_from_int {
     ($t:ty) => {
-        /// Consumes the `Int` converting to a primitive type.
+        /// Consumes the `Int` converting to a primitive type.  Values which are
+        /// out of range are converted as with `as`, keeping the bit pattern.
         impl From<Int> for $t {
             fn from(val: Int) -> Self {
                 match val.value {
@@ -260,7 +339,8 @@ macro_rules! impl_from_int {
                 }
             }
         }
-        /// Converts the `Int` to a primitive type.
+        /// Converts the `Int` to a primitive type.  Values which are out of
+        /// range are converted as with `as`, keeping the bit pattern.
         impl From<&Int> for $t {
             fn from(val: &Int) -> Self {
                 match val.value {
@@ -337,6 +417,31 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn negative_overflow() -> Result<()> {
+        let min = Int::from_str_radix("-170141183460469231731687303715884105728", 0)?;
+        assert_eq!(min.checked::<i128>(), Some(i128::MIN));
+        assert!(Int::from_str_radix("-170141183460469231731687303715884105729", 0).is_err());
+        assert!(Int::from_str_radix("-340282366920938463463374607431768211455", 0).is_err());
+        assert!(Int::from_str_radix("-0x80000000000000000000000000000001", 0).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn checked_conversions() -> Result<()> {
+        assert_eq!(Int::from_str_radix("255", 0)?.checked::<u8>(), Some(255));
+        assert_eq!(Int::from_str_radix("300", 0)?.checked::<u8>(), None);
+        assert_eq!(Int::from_str_radix("-1", 0)?.checked::<u32>(), None);
+        assert_eq!(Int::from_str_radix("-128", 0)?.checked::<i8>(), Some(-128));
+        assert_eq!(Int::from_str_radix("0xFFFFFFFF", 0)?.checked::<i32>(), None);
+        assert_eq!(i32::from(Int::from_str_radix("0xFFFFFFFF", 0)?), -1);
+        assert_eq!(
+            Int::new(u128::MAX, Base::Hex).checked::<u128>(),
+            Some(u128::MAX)
+        );
+        Ok(())
+    }
+
     #[test]
     fn basic_roundtrip() -> Result<()> {
         assert_eq!(
@@ -368,6 +473,34 @@ mod tests {
         );
     }
 
+    #[test]
+    fn grouping() -> Result<()> {
+        let hex = Int::new_padded(0x40u32, Base::Hex).with_grouping(4);
+        assert_eq!(hex.to_string(), "0x0000_0040");
+        assert_eq!(hex.format(Some(&Base::Dec)), "64");
+        let bin = Int::new(0xA5u8, Base::Bin).with_grouping(4);
+        assert_eq!(bin.to_string(), "0b1010_0101");
+        let dec = Int::new(-1234567i32, Base::Dec).with_grouping(3);
+        assert_eq!(dec.to_string(), "-1_234_567");
+        let oct = Int::new_with_padding(8u16, Base::Oct, 6).with_grouping(3);
+        assert_eq!(oct.to_string(), "0o000_010");
+
+        assert_eq!(u32::from(Int::from_str_radix("0x0000_0040", 0)?), 0x40);
+        assert_eq!(i32::from(Int::from_str_radix("-1_000", 0)?), -1000);
+        // Grouping and padding are preserved.
+        assert_eq!(
+            Int::from_str_radix("0b0000_1010_0101", 0)?.to_string(),
+            "0b0000_1010_0101"
+        );
+        assert_eq!(Int::from_str_radix("0x1_0000", 0)?.to_string(), "0x1_0000");
+        // Separators must be between digits.
+        assert!(Int::from_str_radix("0x_40", 0).is_err());
+        assert!(Int::from_str_radix("40_", 0).is_err());
+        assert!(Int::from_str_radix("4__0", 0).is_err());
+        assert!(Int::from_str_radix("_", 0).is_err());
+        Ok(())
+    }
+
     #[test]
     fn exceeds_padding() {
         assert_eq!(
@@ -1,10 +1,14 @@
+use crate::annotate::Placement;
 use crate::color::{ColorProfile, PaintExt};
-use crate::document::{CommentFormat, Document, StrFormat};
+use crate::document::{CommentFormat, Document, Member, StrFormat};
 use crate::error::Error;
 use crate::integer::{Base, Int};
+use crate::stream::{self, Emitter, Scalar};
+use crate::writer::IoWriter;
 use once_cell::sync::OnceCell;
 use std::collections::HashSet;
 use std::fmt::{self, Display};
+use std::io;
 
 type Result<T> = std::result::Result<T, Error>;
 
@@ -17,8 +21,8 @@ pub enum Multiline {
 }
 
 /// A JSON document and its formatting properties.
-pub struct Json {
-    document: Document,
+pub struct Json<'a> {
+    document: Document<'a>,
     indent: usize,
     color: ColorProfile,
     comment: HashSet<CommentFormat>,
@@ -26,12 +30,31 @@ pub struct Json {
     bases: HashSet<Base>,
     literals: HashSet<Base>,
     strict_numeric_limits: bool,
+    digit_separators: bool,
     multiline: Multiline,
     bare_keys: bool,
     compact: bool,
+    align_comments: bool,
 }
 
-impl Json {
+impl Default for Json<'_> {
+    /// Creates an empty JSON document.  Together with `serialize_to`, this
+    /// allows values to be streamed to a writer.
+    fn default() -> Self {
+        Document::Null.to_json()
+    }
+}
+
+impl Json<'_> {
+    /// Creates an empty Json5 document.
+    pub fn json5() -> Self {
+        Document::Null.to_json5()
+    }
+    /// Creates an empty Hjson document.
+    pub fn hjson() -> Self {
+        Document::Null.to_hjson()
+    }
+
     /// Set the amount of indentation for each level of nesting.
     pub fn indent(mut self, i: usize) -> Self {
         self.indent = i;
@@ -76,6 +99,13 @@ impl Json {
         self.strict_numeric_limits = b;
         self
     }
+    /// Set whether integer literals may separate groups of digits with `_`.
+    /// When false, integers annotated with digit grouping are emitted without
+    /// separators unless they are emitted as quoted strings.
+    pub fn digit_separators(mut self, b: bool) -> Self {
+        self.digit_separators = b;
+        self
+    }
     /// Set the style of multiline strings to be used in the document.
     pub fn multiline(mut self, m: Multiline) -> Self {
         self.multiline = m;
@@ -92,16 +122,44 @@ impl Json {
         self.compact = b;
         self
     }
+    /// Set whether to align the trailing comments of the members of a
+    /// mapping or sequence to a common column.  Streaming with
+    /// `serialize_to` can't look ahead to the later members, so comments
+    /// are not aligned there.
+    pub fn align_comments(mut self, b: bool) -> Self {
+        self.align_comments = b;
+        self
+    }
 
     pub fn color(mut self, c: ColorProfile) -> Self {
         self.color = c;
         self
     }
-}
 
-impl fmt::Display for Json {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut emitter = JsonEmitter {
+    /// Writes the document to `w`.  The output is streamed to the writer
+    /// rather than being rendered to a `String` first.
+    pub fn write_to<W: io::Write>(&self, w: W) -> Result<()> {
+        let mut w = IoWriter::new(w);
+        let result = self.emitter().emit_node(&mut w, &self.document);
+        w.finish(result)
+    }
+
+    /// Serializes `value` directly to `w` in this document format, without
+    /// first building a `Document`.  Annotations are applied exactly as
+    /// `serialize` would apply them; the document held by this `Json` is
+    /// ignored.
+    pub fn serialize_to<T, W>(&self, value: &T, w: W) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+        W: io::Write,
+    {
+        let mut w = IoWriter::new(w);
+        let result = stream::serialize(value, &mut w, &mut self.emitter());
+        w.finish(result)
+    }
+
+    fn emitter(&self) -> JsonEmitter {
+        JsonEmitter {
             level: 0,
             indent: self.indent,
             color: self.color,
@@ -110,17 +168,63 @@ impl fmt::Display for Json {
             bases: self.bases.clone(),
             literals: self.literals.clone(),
             strict_numeric_limits: self.strict_numeric_limits,
+            digit_separators: self.digit_separators,
             multiline: self.multiline,
             bare_keys: self.bare_keys,
             compact: self.compact,
-        };
-        emitter.emit_node(f, &self.document).map_err(|_| fmt::Error)
+            align_comments: self.align_comments,
+            stack: Vec::new(),
+            key: None,
+            trailing: Vec::new(),
+            margin: String::new(),
+        }
+    }
+
+    // Renders `node` for insertion into an existing document, starting each
+    // new line with `margin`.
+    pub(crate) fn render(&self, node: &Document, margin: &str, compact: bool) -> Result<String> {
+        let mut emitter = self.emitter();
+        emitter.margin = margin.into();
+        emitter.compact |= compact;
+        let mut s = String::new();
+        emitter.emit_node(&mut s, node)?;
+        Ok(s)
+    }
+
+    // Returns the amount of indentation for each level of nesting.
+    pub(crate) fn indent_width(&self) -> usize {
+        self.indent
+    }
+
+    // Renders a mapping key.
+    pub(crate) fn render_key(&self, key: &str) -> Result<String> {
+        let mut s = String::new();
+        self.emitter().emit_key(&mut s, key)?;
+        Ok(s)
+    }
+
+    // Renders a comment, starting each new line with `margin`.  Returns an
+    // empty string if the document doesn't permit comments.
+    pub(crate) fn render_comment(&self, comment: &str, margin: &str) -> Result<String> {
+        let mut emitter = self.emitter();
+        emitter.margin = margin.into();
+        let mut s = String::new();
+        emitter.emit_comment(&mut s, comment, &CommentFormat::Standard)?;
+        Ok(s)
     }
 }
 
-impl Document {
+impl fmt::Display for Json<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.emitter()
+            .emit_node(f, &self.document)
+            .map_err(|_| fmt::Error)
+    }
+}
+
+impl<'a> Document<'a> {
     /// Convert a `Document` to a JSON document.
-    pub fn to_json(self) -> Json {
+    pub fn to_json(self) -> Json<'a> {
         Json {
             document: self,
             indent: 2,
@@ -130,16 +234,18 @@ impl Document {
             bases: HashSet::from([Base::Dec]),
             literals: HashSet::from([Base::Dec]),
             strict_numeric_limits: true,
+            digit_separators: false,
             multiline: Multiline::None,
             bare_keys: false,
             compact: false,
+            align_comments: false,
         }
     }
 
     /// Convert a `Document` to a Json5 document.
-    /// A Json5 document allows `//` comments, hex literals,
-    /// multiline strings and bare keys.
-    pub fn to_json5(self) -> Json {
+    /// A Json5 document allows `//` comments, hex literals, multiline strings
+    /// and bare keys.
+    pub fn to_json5(self) -> Json<'a> {
         self.to_json()
             .comment(&[CommentFormat::Block, CommentFormat::SlashSlash])
             .literals(&[Base::Hex])
@@ -150,7 +256,7 @@ impl Document {
     /// Convert a `Document` to a Hjson document.
     /// A Hjson document allows comments, multiline strings and bare keys.
     /// Defaults to `#` comments, but hjson also supports `//` comments.
-    pub fn to_hjson(self) -> Json {
+    pub fn to_hjson(self) -> Json<'a> {
         self.to_json()
             .comment(&[
                 CommentFormat::Block,
@@ -172,9 +278,27 @@ struct JsonEmitter {
     bases: HashSet<Base>,
     literals: HashSet<Base>,
     strict_numeric_limits: bool,
+    digit_separators: bool,
     multiline: Multiline,
     bare_keys: bool,
     compact: bool,
+    align_comments: bool,
+    // The open aggregates when streaming.
+    stack: Vec<Aggregate>,
+    // When streaming, the key awaiting its value.  The key is written once
+    // the value starts, so the comments attached to the value precede it.
+    key: Option<String>,
+    // When streaming, the trailing comments of the top-level value.
+    trailing: Vec<String>,
+    // Text preceding the indentation of each line.
+    margin: String,
+}
+
+// An aggregate being streamed by the `JsonEmitter`.
+struct Aggregate {
+    empty: bool,
+    // The trailing comments of the current element, which follow its comma.
+    trailing: Vec<String>,
 }
 
 impl Default for JsonEmitter {
@@ -188,9 +312,15 @@ impl Default for JsonEmitter {
             bases: HashSet::new(),
             literals: HashSet::new(),
             strict_numeric_limits: true,
+            digit_separators: false,
             multiline: Multiline::None,
             bare_keys: false,
             compact: false,
+            align_comments: false,
+            stack: Vec::new(),
+            key: None,
+            trailing: Vec::new(),
+            margin: String::new(),
         }
     }
 }
@@ -200,7 +330,7 @@ impl JsonEmitter {
         match node {
             Document::Comment(c, f) => self.emit_comment_newline(w, c, f),
             Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
-            Document::StaticStr(v, f) => self.emit_string(w, v, *f),
+            Document::Str(v, f) => self.emit_string(w, v, *f),
             Document::Boolean(v) => self.emit_boolean(w, *v),
             Document::Int(v) => self.emit_int(w, v),
             Document::Float(v) => self.emit_float(w, *v),
@@ -209,6 +339,19 @@ impl JsonEmitter {
             Document::Bytes(v) => self.emit_bytes(w, v),
             Document::Null => self.emit_null(w),
             Document::Compact(d) => self.emit_compact(w, d),
+            Document::Spanned(d, _) => self.emit_node(w, d),
+            Document::Fragment(ds) if ds.iter().filter(|d| d.has_value()).count() == 1 => {
+                // A value with comments attached, such as a commented unit
+                // variant at the top level.
+                let (value, comments) = node.value_comments();
+                let (leading, trailing): (Vec<_>, Vec<_>) =
+                    comments.into_iter().partition(|(_, precedes)| *precedes);
+                for (c, f) in leading.iter().filter_map(|(c, _)| c.comment()) {
+                    self.emit_comment_newline(w, c, f)?;
+                }
+                self.emit_node(w, value)?;
+                self.emit_trailing_comments(w, trailing.iter().filter_map(|(c, _)| c.comment()), 0)
+            }
             Document::Fragment(ds) => {
                 let mut prior_val = false;
                 for d in ds {
@@ -254,64 +397,8 @@ impl JsonEmitter {
         Ok(())
     }
 
-    // TODO: Can this function be rewritten to be less complex?
     fn emit_sequence<W: fmt::Write>(&mut self, w: &mut W, sequence: &[Document]) -> Result<()> {
-        self.level += 1;
-        self.writeln(w, self.color.aggregate.paint("["))?;
-        if !sequence.is_empty() {
-            self.emit_indent(w)?;
-        }
-        let last = Document::last_value_index(sequence);
-        let mut need_eol = false;
-        for (i, value) in sequence.iter().enumerate() {
-            if i > 0 && need_eol {
-                write!(w, "{}", if self.compact { " " } else { "\n" })?;
-                if i <= last || !self.comment.is_empty() {
-                    self.emit_indent(w)?;
-                }
-                need_eol = false;
-            }
-            if let Document::Fragment(nodes) = value {
-                let mut val_done = false;
-                for node in nodes {
-                    if let Some((c, f)) = node.comment() {
-                        if val_done && need_eol {
-                            write!(w, " ")?;
-                        }
-                        need_eol = self.emit_comment(w, c, f)?;
-                        if need_eol && !val_done {
-                            writeln!(w)?;
-                            self.emit_indent(w)?;
-                        }
-                        need_eol |= i < last;
-                        continue;
-                    }
-                    if !val_done {
-                        self.emit_node(w, node)?;
-                        if i != last {
-                            write!(w, "{}", self.color.punctuation.paint(","))?;
-                        }
-                        val_done = true;
-                        need_eol = true;
-                    } else {
-                        return Err(Error::StructureError("Comment", node.variant()));
-                    }
-                }
-            } else {
-                self.emit_node(w, value)?;
-                if i != last {
-                    write!(w, "{}", self.color.punctuation.paint(","))?;
-                }
-                need_eol = true;
-            }
-        }
-        if need_eol {
-            self.writeln(w, "")?;
-        }
-        self.level -= 1;
-        self.emit_indent(w)?;
-        write!(w, "{}", self.color.aggregate.paint("]"))?;
-        Ok(())
+        self.emit_members(w, sequence, false)
     }
 
     fn emit_key<W: fmt::Write>(&mut self, w: &mut W, s: &str) -> Result<()> {
@@ -329,90 +416,185 @@ impl JsonEmitter {
         Ok(())
     }
 
-    // TODO: Can this function be rewritten to be less complex?
+    fn emit_key_node<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+        let node = match node {
+            Document::Spanned(k, _) => k,
+            _ => node,
+        };
+        match node {
+            Document::String(s, _) => self.emit_key(w, s.as_str())?,
+            Document::Str(s, _) => self.emit_key(w, s)?,
+            Document::Boolean(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Int(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Float(v) => write!(
+                w,
+                "{}{}{}",
+                self.color.punctuation.paint("\""),
+                self.color.key.paint(v),
+                self.color.punctuation.paint("\"")
+            )?,
+            Document::Comment(_, _) => return Err(Error::KeyTypeError("comment")),
+            Document::Mapping(_) => return Err(Error::KeyTypeError("mapping")),
+            Document::Sequence(_) => return Err(Error::KeyTypeError("sequence")),
+            Document::Bytes(_) => return Err(Error::KeyTypeError("bytes")),
+            Document::Compact(_) => return Err(Error::KeyTypeError("compact")),
+            Document::Spanned(_, _) => return Err(Error::KeyTypeError("spanned")),
+            Document::Fragment(_) => return Err(Error::KeyTypeError("fragment")),
+            Document::Null => return Err(Error::KeyTypeError("null")),
+        };
+        Ok(())
+    }
+
     fn emit_mapping<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
+        self.emit_members(w, mapping, true)
+    }
+
+    // Emits the members of a mapping or sequence.  Leading comments are
+    // emitted on the lines above a member, trailing comments after its value
+    // and dangling comments on the lines before the closing bracket.
+    fn emit_members<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        members: &[Document],
+        mapping: bool,
+    ) -> Result<()> {
+        let (open, close) = if mapping { ("{", "}") } else { ("[", "]") };
+        let comments = !self.compact && !self.comment.is_empty();
         self.level += 1;
-        self.writeln(w, self.color.aggregate.paint("{"))?;
-        if !mapping.is_empty() {
-            self.emit_indent(w)?;
-        }
-        let last = Document::last_value_index(mapping);
-        let mut need_eol = false;
-        for (i, frag) in mapping.iter().enumerate() {
-            let nodes = frag.fragments()?;
-            if i > 0 && need_eol {
+        self.writeln(w, self.color.aggregate.paint(open))?;
+        let last = Document::last_value_index(members);
+        let widths = if comments && self.align_comments {
+            self.member_widths(members, mapping, last)?
+        } else {
+            Vec::new()
+        };
+        let column = widths.iter().flatten().max().copied().unwrap_or(0);
+        let mut empty = true;
+        for (i, member) in members.iter().enumerate() {
+            let member = Member::new(member, mapping)?;
+            if member.value.is_none() && !comments {
+                continue;
+            }
+            if !empty {
                 write!(w, "{}", if self.compact { " " } else { "\n" })?;
-                if i <= last || !self.comment.is_empty() {
-                    self.emit_indent(w)?;
-                }
-                need_eol = false;
             }
-            let mut key_done = i > last;
-            let mut val_done = i > last;
-            for node in nodes {
-                if let Some((c, f)) = node.comment() {
-                    if val_done && need_eol {
-                        write!(w, " ")?;
-                    }
-                    need_eol = self.emit_comment(w, c, f)?;
-                    if need_eol && !key_done {
+            self.emit_indent(w)?;
+            empty = false;
+            let Some(value) = member.value else {
+                for (j, (c, f)) in member.leading.into_iter().enumerate() {
+                    if j > 0 {
                         writeln!(w)?;
                         self.emit_indent(w)?;
                     }
-                    need_eol |= i < last;
-                    continue;
-                }
-                if !key_done {
-                    match node {
-                        Document::String(s, _) => self.emit_key(w, s.as_str())?,
-                        Document::StaticStr(s, _) => self.emit_key(w, s)?,
-                        Document::Boolean(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Int(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Float(v) => write!(
-                            w,
-                            "{}{}{}",
-                            self.color.punctuation.paint("\""),
-                            self.color.key.paint(v),
-                            self.color.punctuation.paint("\"")
-                        )?,
-                        Document::Comment(_, _) => return Err(Error::KeyTypeError("comment")),
-                        Document::Mapping(_) => return Err(Error::KeyTypeError("mapping")),
-                        Document::Sequence(_) => return Err(Error::KeyTypeError("sequence")),
-                        Document::Bytes(_) => return Err(Error::KeyTypeError("bytes")),
-                        Document::Compact(_) => return Err(Error::KeyTypeError("compact")),
-                        Document::Fragment(_) => return Err(Error::KeyTypeError("fragment")),
-                        Document::Null => return Err(Error::KeyTypeError("null")),
-                    };
-                    write!(w, "{}", self.color.punctuation.paint(": "))?;
-                    key_done = true;
-                } else if !val_done {
-                    self.emit_node(w, node)?;
-                    if i != last {
-                        write!(w, "{}", self.color.punctuation.paint(","))?;
-                    }
-                    val_done = true;
-                    need_eol = true;
+                    self.emit_comment(w, c, f)?;
                 }
+                continue;
+            };
+            for (c, f) in member.leading {
+                self.emit_comment_newline(w, c, f)?;
             }
+            self.emit_member(w, member.key, value, Some(i) != last)?;
+            let pad = match widths.get(i) {
+                Some(Some(width)) => column - width,
+                _ => 0,
+            };
+            self.emit_trailing_comments(w, member.trailing, pad)?;
         }
-        if need_eol {
+        if !empty {
             self.writeln(w, "")?;
         }
         self.level -= 1;
         self.emit_indent(w)?;
-        write!(w, "{}", self.color.aggregate.paint("}"))?;
+        write!(w, "{}", self.color.aggregate.paint(close))?;
+        Ok(())
+    }
+
+    // Emits the key (if any) and value of a member, followed by a comma if
+    // `comma` is set.
+    fn emit_member<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        key: Option<&Document>,
+        value: &Document,
+        comma: bool,
+    ) -> Result<()> {
+        if let Some(key) = key {
+            self.emit_key_node(w, key)?;
+            write!(w, "{}", self.color.punctuation.paint(": "))?;
+        }
+        self.emit_node(w, value)?;
+        if comma {
+            write!(w, "{}", self.color.punctuation.paint(","))?;
+        }
+        Ok(())
+    }
+
+    // Returns the width of each member with trailing comments, up to its
+    // comments.  Members which span more than one line have no width.
+    fn member_widths(
+        &mut self,
+        members: &[Document],
+        mapping: bool,
+        last: Option<usize>,
+    ) -> Result<Vec<Option<usize>>> {
+        let color = std::mem::take(&mut self.color);
+        let widths = members
+            .iter()
+            .enumerate()
+            .map(|(i, member)| {
+                let member = Member::new(member, mapping)?;
+                let value = match member.value {
+                    Some(v) if !member.trailing.is_empty() => v,
+                    _ => return Ok(None),
+                };
+                if !self.compact && is_aggregate(value) {
+                    return Ok(None);
+                }
+                let mut s = String::new();
+                self.emit_member(&mut s, member.key, value, Some(i) != last)?;
+                Ok((!s.contains('\n')).then(|| s.chars().count()))
+            })
+            .collect();
+        self.color = color;
+        widths
+    }
+
+    // Emits comments on the line after a value, `pad` spaces after the
+    // value.  A comment following a line comment starts a new line.
+    fn emit_trailing_comments<'c, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comments: impl IntoIterator<Item = (&'c str, &'c CommentFormat)>,
+        pad: usize,
+    ) -> Result<()> {
+        if self.compact || self.comment.is_empty() {
+            return Ok(());
+        }
+        let mut newline = false;
+        for (c, f) in comments {
+            if newline {
+                writeln!(w)?;
+                self.emit_indent(w)?;
+            } else {
+                write!(w, "{:pad$} ", "")?;
+            }
+            if !self.emit_comment(w, c, f)? {
+                break;
+            }
+            newline = self.comment_format(f) != CommentFormat::Block;
+        }
         Ok(())
     }
 
@@ -429,6 +611,11 @@ impl JsonEmitter {
         Ok(())
     }
 
+    // Returns the format in which a comment of format `f` is emitted.
+    fn comment_format(&self, f: &CommentFormat) -> CommentFormat {
+        *self.comment.get(f).unwrap_or(&self.standard_comment)
+    }
+
     fn emit_comment<W: fmt::Write>(
         &mut self,
         w: &mut W,
@@ -438,16 +625,25 @@ impl JsonEmitter {
         if self.compact || self.comment.is_empty() {
             return Ok(false);
         }
-        let format = *self.comment.get(format).unwrap_or(&self.standard_comment);
+        let format = self.comment_format(format);
         let leader = match format {
             CommentFormat::SlashSlash | CommentFormat::Standard => "//",
             CommentFormat::Hash => "#",
-            CommentFormat::Block => " *",
+            CommentFormat::Block if !comment.contains('\n') => {
+                write!(
+                    w,
+                    "{}",
+                    self.color.comment.paint(format_args!("/* {} */", comment))
+                )?;
+                return Ok(true);
+            }
+            CommentFormat::Block => {
+                write!(w, "{}", self.color.comment.paint("/*"))?;
+                writeln!(w)?;
+                self.emit_indent(w)?;
+                " *"
+            }
         };
-        if format == CommentFormat::Block {
-            writeln!(w, "/*")?;
-            self.emit_indent(w)?;
-        }
         for (i, line) in comment.split('\n').enumerate() {
             if i > 0 {
                 writeln!(w)?;
@@ -466,8 +662,9 @@ impl JsonEmitter {
             }
         }
         if format == CommentFormat::Block {
-            writeln!(w, "*/")?;
+            writeln!(w)?;
             self.emit_indent(w)?;
+            write!(w, "{}", self.color.comment.paint(" */"))?;
         }
         Ok(true)
     }
@@ -516,6 +713,29 @@ impl JsonEmitter {
     }
 
     fn emit_string_multiline<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
+        self.begin_multiline(w)?;
+        self.emit_multiline_text(w, value)?;
+        self.end_multiline(w)
+    }
+
+    // Emits a multiline string given as its lines, without building the
+    // whole string.
+    fn emit_lines<W, I>(&mut self, w: &mut W, lines: I) -> Result<()>
+    where
+        W: fmt::Write,
+        I: Iterator<Item = String>,
+    {
+        self.begin_multiline(w)?;
+        for (i, line) in lines.enumerate() {
+            if i > 0 {
+                self.emit_multiline_text(w, "\n")?;
+            }
+            self.emit_multiline_text(w, &line)?;
+        }
+        self.end_multiline(w)
+    }
+
+    fn begin_multiline<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
         if self.multiline == Multiline::Hjson {
             writeln!(w)?;
             self.level += 1;
@@ -525,6 +745,10 @@ impl JsonEmitter {
         } else {
             write!(w, "{}", self.color.punctuation.paint("\""))?;
         }
+        Ok(())
+    }
+
+    fn emit_multiline_text<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
         let bytes = value.as_bytes();
         let mut start = 0;
         for (i, &byte) in bytes.iter().enumerate() {
@@ -568,6 +792,10 @@ impl JsonEmitter {
         if start != bytes.len() {
             write!(w, "{}", self.color.string.paint(&value[start..]))?;
         }
+        Ok(())
+    }
+
+    fn end_multiline<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
         if self.multiline == Multiline::Hjson {
             writeln!(w)?;
             self.emit_indent(w)?;
@@ -590,10 +818,14 @@ impl JsonEmitter {
 
     fn emit_int<W: fmt::Write>(&mut self, w: &mut W, i: &Int) -> Result<()> {
         let b = i.base();
-        let s = i.format(self.bases.get(&b));
-        if self.strict_numeric_limits && !i.is_legal_json()
-            || self.bases.get(&b).is_some() && self.literals.get(&b).is_none()
-        {
+        let quoted = self.strict_numeric_limits && !i.is_legal_json()
+            || self.bases.contains(&b) && !self.literals.contains(&b);
+        let s = if quoted || self.digit_separators {
+            i.format(self.bases.get(&b))
+        } else {
+            i.clone().with_grouping(0).format(self.bases.get(&b))
+        };
+        if quoted {
             write!(
                 w,
                 "{}{}{}",
@@ -621,6 +853,7 @@ impl JsonEmitter {
         if self.compact {
             return Ok(());
         }
+        w.write_str(&self.margin)?;
         let mut len = self.level * self.indent;
         while len > 0 {
             let chunk = std::cmp::min(len, SPACE.len());
@@ -640,6 +873,161 @@ impl JsonEmitter {
     }
 }
 
+impl JsonEmitter {
+    // Starts an element of a streamed aggregate, separating it from the prior
+    // element and emitting its comment.
+    fn begin_element<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+    ) -> Result<()> {
+        let top = self
+            .stack
+            .last_mut()
+            .ok_or(Error::StructureError("an aggregate", "none"))?;
+        let trailing = std::mem::take(&mut top.trailing);
+        if !std::mem::replace(&mut top.empty, false) {
+            write!(w, "{}", self.color.punctuation.paint(","))?;
+            self.emit_held_comments(w, &trailing)?;
+            write!(w, "{}", if self.compact { " " } else { "\n" })?;
+        }
+        self.emit_indent(w)?;
+        match comment {
+            Some((c, placement)) => self.comment(w, c, placement),
+            None => Ok(()),
+        }
+    }
+
+    // Writes the key awaiting the value which is starting.
+    fn resolve<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        if let Some(key) = self.key.take() {
+            write!(w, "{}{}", key, self.color.punctuation.paint(": "))?;
+        }
+        Ok(())
+    }
+
+    fn begin_aggregate<W: fmt::Write>(&mut self, w: &mut W, open: &str) -> Result<()> {
+        self.resolve(w)?;
+        self.level += 1;
+        self.writeln(w, self.color.aggregate.paint(open))?;
+        self.stack.push(Aggregate {
+            empty: true,
+            trailing: Vec::new(),
+        });
+        Ok(())
+    }
+
+    fn end_aggregate<W: fmt::Write>(&mut self, w: &mut W, close: &str) -> Result<()> {
+        let top = self
+            .stack
+            .pop()
+            .ok_or(Error::StructureError("an aggregate", "none"))?;
+        if !top.empty {
+            self.emit_held_comments(w, &top.trailing)?;
+            self.writeln(w, "")?;
+        }
+        self.level -= 1;
+        self.emit_indent(w)?;
+        write!(w, "{}", self.color.aggregate.paint(close))?;
+        Ok(())
+    }
+
+    // Emits the trailing comments held for a streamed value.
+    fn emit_held_comments<W: fmt::Write>(&mut self, w: &mut W, comments: &[String]) -> Result<()> {
+        let comments = comments
+            .iter()
+            .map(|c| (c.as_str(), &CommentFormat::Standard));
+        self.emit_trailing_comments(w, comments, 0)
+    }
+}
+
+impl Emitter for JsonEmitter {
+    fn scalar<W: fmt::Write>(&mut self, w: &mut W, value: Scalar) -> Result<()> {
+        self.resolve(w)?;
+        match value {
+            Scalar::Str(v, f) => self.emit_string(w, v, f),
+            Scalar::Boolean(v) => self.emit_boolean(w, v),
+            Scalar::Int(v) => self.emit_int(w, &v),
+            Scalar::Float(v) => self.emit_float(w, v),
+            Scalar::Bytes(v) => self.emit_bytes(w, v),
+            Scalar::Lines(v) => self.emit_lines(w, v),
+            Scalar::Null => self.emit_null(w),
+        }
+    }
+
+    fn begin_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.begin_aggregate(w, "[")
+    }
+
+    fn element<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+    ) -> Result<()> {
+        self.begin_element(w, comment)
+    }
+
+    fn end_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.end_aggregate(w, "]")
+    }
+
+    fn begin_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.begin_aggregate(w, "{")
+    }
+
+    fn key<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+        key: &Document,
+    ) -> Result<()> {
+        self.begin_element(w, comment)?;
+        let mut pending = String::new();
+        self.emit_key_node(&mut pending, key)?;
+        self.key = Some(pending);
+        Ok(())
+    }
+
+    fn end_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.end_aggregate(w, "}")
+    }
+
+    fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    fn comment<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: &str,
+        placement: Placement,
+    ) -> Result<()> {
+        if self.compact {
+            return Ok(());
+        }
+        match placement {
+            Placement::Leading => self.emit_comment_newline(w, comment, &CommentFormat::Standard),
+            Placement::Trailing => {
+                let trailing = match self.stack.last_mut() {
+                    Some(top) => &mut top.trailing,
+                    None => &mut self.trailing,
+                };
+                trailing.push(comment.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn finish<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        let trailing = std::mem::take(&mut self.trailing);
+        self.emit_held_comments(w, &trailing)
+    }
+}
+
 // Taken from serde-json:
 const BB: u8 = b'b'; // \x08
 const TT: u8 = b't'; // \x09
@@ -675,6 +1063,16 @@ const ESCAPE: [u8; 256] = [
 
 const SPACE: &str = "                                                                                                    ";
 
+// Whether a value is emitted as a bracketed aggregate, which spans more than
+// one line unless it is compact.
+fn is_aggregate(node: &Document) -> bool {
+    match node {
+        Document::Spanned(d, _) => is_aggregate(d),
+        Document::Mapping(_) | Document::Sequence(_) | Document::Bytes(_) => true,
+        _ => false,
+    }
+}
+
 // More strict than javascript.
 fn bad_identifier_char(ch: char) -> bool {
     !matches!(ch, '0'..='9' | 'A'..='Z' | 'a'..='z' | '_' | '$')
@@ -748,37 +1146,37 @@ mod tests {
     use super::*;
     use crate::document::CommentFormat;
 
-    fn int(v: i32) -> Document {
+    fn int(v: i32) -> Document<'static> {
         Document::Int(Int::new(v, Base::Dec))
     }
-    fn hex(v: u32) -> Document {
+    fn hex(v: u32) -> Document<'static> {
         Document::Int(Int::new(v, Base::Hex))
     }
-    fn float(v: f64) -> Document {
+    fn float(v: f64) -> Document<'static> {
         Document::Float(v)
     }
-    fn boolean(v: bool) -> Document {
+    fn boolean(v: bool) -> Document<'static> {
         Document::Boolean(v)
     }
-    fn null() -> Document {
+    fn null() -> Document<'static> {
         Document::Null
     }
-    fn string(v: &str) -> Document {
+    fn string(v: &str) -> Document<'static> {
         Document::String(v.to_string(), StrFormat::Standard)
     }
-    fn multistr(v: &str) -> Document {
+    fn multistr(v: &str) -> Document<'static> {
         Document::String(v.to_string(), StrFormat::Multiline)
     }
-    fn comment(v: &str) -> Document {
+    fn comment(v: &str) -> Document<'static> {
         Document::Comment(v.to_string(), CommentFormat::Standard)
     }
-    fn kv(k: &str, v: Document) -> Document {
+    fn kv(k: &str, v: Document<'static>) -> Document<'static> {
         Document::Fragment(vec![string(k), v])
     }
-    fn kvcomment(k: &str, v: Document, c: &str) -> Document {
+    fn kvcomment(k: &str, v: Document<'static>, c: &str) -> Document<'static> {
         Document::Fragment(vec![comment(c), string(k), v])
     }
-    fn nes_address(seg: &str, bank: i32, addr: u32) -> Document {
+    fn nes_address(seg: &str, bank: i32, addr: u32) -> Document<'static> {
         Document::Compact(
             Document::Mapping(vec![kv(
                 seg,
@@ -789,6 +1187,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn basic_document() {
         let c = comment("woohoo!").to_json();
         assert_eq!(c.to_string(), "");
@@ -984,4 +1383,67 @@ No \\n's!",
         println!("{}", map);
         assert_eq!(map.to_string(), expect);
     }
+
+    // A writer which fails after accepting a fixed number of bytes.
+    struct Full(usize);
+    impl std::io::Write for Full {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            if self.0 == 0 {
+                return Err(std::io::ErrorKind::WriteZero.into());
+            }
+            let n = std::cmp::min(self.0, buf.len());
+            self.0 -= n;
+            Ok(n)
+        }
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn write_to() {
+        let map = Document::Mapping(vec![
+            kv("a", int(1)),
+            kvcomment("b", hex(0xdecaf), "Bee"),
+            kv("c", Document::Bytes(vec![1, 2, 3])),
+        ])
+        .to_json5();
+        let mut buf = Vec::new();
+        map.write_to(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), map.to_string());
+
+        let err = map.write_to(Full(10)).unwrap_err();
+        assert!(matches!(err, Error::IoError(e) if e.kind() == std::io::ErrorKind::WriteZero));
+
+        let bad =
+            Document::Mapping(vec![Document::Fragment(vec![Document::Null, int(1)])]).to_json();
+        assert!(matches!(
+            bad.write_to(std::io::sink()),
+            Err(Error::KeyTypeError(_))
+        ));
+    }
+
+    #[test]
+    fn comment_placement() {
+        let text = r#"{
+  // leading
+  addr: 0x40, // MMIO base
+  size: 4, /* block */
+  nested: {
+    a: 1
+  }, // after nested
+  list: [
+    1, // one
+    2
+    // dangling in list
+  ],
+  empty: {
+    // dangling in empty
+  },
+  last: true // last trailing
+  // dangling in map
+}"#;
+        let doc = Document::parse(text).unwrap();
+        assert_eq!(doc.to_json5().to_string(), text);
+    }
 }
@@ -1,6 +1,8 @@
 pub mod annotate;
 mod color;
+mod cst;
 mod de;
+mod doc_edit;
 mod doc_iter;
 mod document;
 mod error;
@@ -9,17 +11,30 @@ mod integer;
 mod json;
 mod partial;
 mod relax;
+mod relax_parser;
+mod schema;
 mod ser;
+mod stream;
+mod toml;
+mod toml_parser;
+mod trace;
+mod writer;
 mod yaml;
+mod yaml_parser;
 
 pub use annotate::Annotate;
 pub use color::ColorProfile;
-pub use de::{from_str, Deserialize, Deserializer};
-pub use doc_iter::DocPath;
-pub use document::{BytesFormat, CommentFormat, Document, StrFormat};
-pub use error::Error;
+pub use cst::Cst;
+pub use de::{from_str, from_str_with_spans, Deserialize, Deserializer};
+pub use doc_edit::ToDocPath;
+pub use doc_iter::{DocPath, OwnedDocPath};
+pub use document::{BytesFormat, CommentFormat, Document, Span, StrFormat};
+pub use error::{Error, Location};
 pub use integer::{Base, Int, IntValue};
 pub use json::Json;
+pub use relax::Relax;
+pub use schema::Schema;
 pub use ser::{serialize, AnnotatedSerializer};
 pub use serde_annotate_derive::*;
+pub use toml::Toml;
 pub use yaml::Yaml;
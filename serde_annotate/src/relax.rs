@@ -1,13 +1,18 @@
+#[cfg(feature = "pest")]
 use pest::error::Error as PestError;
+#[cfg(feature = "pest")]
 use pest::iterators::{Pair, Pairs};
+#[cfg(feature = "pest")]
 use pest::Parser as P;
-use pest::Position;
+#[cfg(feature = "pest")]
 use pest_derive::Parser;
 use std::cell::RefCell;
+use std::iter::Peekable;
 
-use crate::document::{CommentFormat, Document, StrFormat};
+use crate::document::{CommentFormat, Document, Span, StrFormat};
 use crate::error::Error;
 use crate::integer::Int;
+use crate::relax_parser::Parser;
 
 #[derive(Default)]
 struct Inner {
@@ -20,8 +25,7 @@ struct Inner {
 ///
 /// The `Relax` parser is configurable and can allow or disallow each of these
 /// extensions.  The default `Relax` parser is maximally permissive.
-#[derive(Parser)]
-#[grammar = "relax.pest"]
+#[cfg_attr(feature = "pest", derive(Parser), grammar = "relax.pest")]
 pub struct Relax {
     inner: RefCell<Inner>,
     pub comma_trailing: bool,
@@ -31,6 +35,8 @@ pub struct Relax {
     pub number_oct: bool,
     pub number_plus: bool,
     pub number_lax_dec_point: bool,
+    /// Permit `_` between the digits of a number (e.g. `0x0000_0040`).
+    pub number_separators: bool,
     pub string_single_quote: bool,
     pub string_unquoted: bool,
     pub string_ident: bool,
@@ -39,10 +45,66 @@ pub struct Relax {
     pub comment_slash: bool,
     pub comment_hash: bool,
     pub comment_block: bool,
+    /// Wrap each parsed value in a `Document::Spanned` node recording its
+    /// location in the source text.
+    pub spans: bool,
 }
 
+#[cfg(feature = "pest")]
 pub(crate) type ParseError = PestError<Rule>;
 
+/// The text of a token and its position in the parsed input.
+#[derive(Clone, Copy)]
+pub(crate) struct Token<'a> {
+    pub input: &'a str,
+    pub start: usize,
+    pub end: usize,
+}
+
+impl<'a> Token<'a> {
+    fn as_str(&self) -> &'a str {
+        &self.input[self.start..self.end]
+    }
+}
+
+#[cfg(feature = "pest")]
+impl<'a> From<&Pair<'a, Rule>> for Token<'a> {
+    fn from(pair: &Pair<'a, Rule>) -> Self {
+        let span = pair.as_span();
+        Token {
+            input: span.get_input(),
+            start: span.start(),
+            end: span.end(),
+        }
+    }
+}
+
+/// An element of the body of an object or array: the tokens between the
+/// brackets in the order they appear in the text.
+pub(crate) enum Item<'a> {
+    Comma,
+    /// A comment, the line it starts on and its end position.
+    Comment(Document<'a>, usize, usize),
+    /// A key or value, the line it ends on and its end position.
+    Value(Document<'a>, usize, usize),
+}
+
+impl<'a> Item<'a> {
+    fn end(&self) -> usize {
+        match self {
+            Item::Comma => 0,
+            Item::Comment(_, _, end) | Item::Value(_, _, end) => *end,
+        }
+    }
+
+    pub(crate) fn into_document(self) -> Document<'a> {
+        match self {
+            Item::Comma => unreachable!(),
+            Item::Comment(doc, _, _) | Item::Value(doc, _, _) => doc,
+        }
+    }
+}
+
 impl Default for Relax {
     /// Returns a maximally permissive json parser.
     fn default() -> Self {
@@ -55,6 +117,7 @@ impl Default for Relax {
             number_oct: true,
             number_plus: true,
             number_lax_dec_point: true,
+            number_separators: true,
             string_single_quote: true,
             string_unquoted: true,
             string_ident: true,
@@ -63,6 +126,7 @@ impl Default for Relax {
             comment_slash: true,
             comment_hash: true,
             comment_block: true,
+            spans: false,
         }
     }
 }
@@ -78,6 +142,7 @@ impl Relax {
             number_oct: false,
             number_plus: false,
             number_lax_dec_point: false,
+            number_separators: false,
             string_single_quote: false,
             string_unquoted: false,
             string_ident: false,
@@ -99,6 +164,7 @@ impl Relax {
             comment_hash: false,
             number_bin: false,
             number_oct: false,
+            number_separators: false,
             ..Self::default()
         }
     }
@@ -112,12 +178,33 @@ impl Relax {
             number_oct: false,
             number_plus: false,
             number_lax_dec_point: false,
+            number_separators: false,
             ..Self::default()
         }
     }
 
     /// Parses a string into a `Document`.
-    pub fn from_str(&self, text: &str) -> Result<Document, Error> {
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_str<'a>(&self, text: &'a str) -> Result<Document<'a>, Error> {
+        self.index_lines(text);
+        Parser::new(self, text).parse()
+    }
+
+    /// Parses a string into a `Document` using the pest grammar in
+    /// `relax.pest`.
+    ///
+    /// The result is the same as `from_str`, which is considerably faster.
+    /// The grammar is the reference for the syntax `from_str` accepts.
+    /// Requires the `pest` feature.
+    #[cfg(feature = "pest")]
+    #[allow(clippy::wrong_self_convention)]
+    pub fn from_str_pest<'a>(&self, text: &'a str) -> Result<Document<'a>, Error> {
+        self.index_lines(text);
+        let json = Relax::parse(Rule::text, text)?.next().unwrap();
+        self.handle_pair(json)
+    }
+
+    pub(crate) fn index_lines(&self, text: &str) {
         // Iterate over the input text and remember the line breaks. Since we use
         // positioning information to infer which comments belong with which json
         // items, caching the line-number information speeds up parsing
@@ -131,11 +218,9 @@ impl Relax {
         }
         inner.lines.push(usize::MAX);
         self.inner.replace(inner);
-        let json = Relax::parse(Rule::text, text)?.next().unwrap();
-        self.handle_pair(json)
     }
 
-    fn line_col(&self, pos: usize) -> (usize, usize) {
+    pub(crate) fn line_col(&self, pos: usize) -> (usize, usize) {
         let inner = self.inner.borrow();
         let line = match inner.lines.binary_search(&pos) {
             Ok(i) => i,
@@ -145,6 +230,36 @@ impl Relax {
         (line, col)
     }
 
+    // Returns the line of the last character of a token other than
+    // whitespace.  The token of an unquoted string includes the line
+    // terminator after it.
+    pub(crate) fn end_line(&self, token: Token) -> usize {
+        let len = token.as_str().trim_end().len();
+        let (line, _) = self.line_col(token.start + len.saturating_sub(1));
+        line
+    }
+
+    fn span(&self, token: Token) -> Span {
+        let (line, col) = self.line_col(token.start);
+        // The line table records the position of each newline, so columns
+        // are already 1-based on every line except the first.
+        Span {
+            start: token.start,
+            end: token.end,
+            line: line + 1,
+            col: if line == 0 { col + 1 } else { col },
+        }
+    }
+
+    // Wraps a value with its source span if requested.
+    pub(crate) fn spanned<'a>(&self, doc: Document<'a>, token: Token) -> Document<'a> {
+        if self.spans {
+            Document::Spanned(Box::new(doc), self.span(token))
+        } else {
+            doc
+        }
+    }
+
     fn unhex(ch: char) -> u32 {
         match ch {
             '0'..='9' => (ch as u8 - b'0') as u32,
@@ -195,47 +310,40 @@ impl Relax {
         Ok(s)
     }
 
-    fn from_str_radix(text: &str, radix: u32) -> Result<Document, Error> {
+    fn from_str_radix(text: &str, radix: u32) -> Result<Document<'static>, Error> {
         match Int::from_str_radix(text, radix) {
             Ok(val) => Ok(Document::Int(val)),
             Err(_) => Ok(Document::String(text.into(), StrFormat::Standard)),
         }
     }
 
-    fn handle_number(&self, pair: Pair<Rule>) -> Result<Document, Error> {
-        let text = pair.as_str();
+    pub(crate) fn handle_number<'a>(&self, token: Token<'a>) -> Result<Document<'a>, Error> {
+        let text = token.as_str();
         let t = if let Some(t) = text.strip_prefix('+') {
-            Self::syntax_error(!self.number_plus, "leading `+`", pair.as_span().start_pos())?;
+            Self::syntax_error(!self.number_plus, "leading `+`", token)?;
             t
         } else if let Some(t) = text.strip_prefix('-') {
             t
         } else {
             text
         };
+        Self::syntax_error(
+            !self.number_separators && t.contains('_'),
+            "digit separator",
+            token,
+        )?;
         if t.starts_with("0x") || t.starts_with("0X") {
             // Hexadecimal integer.
-            Self::syntax_error(
-                !self.number_hex,
-                "hexadecimal literal",
-                pair.as_span().start_pos(),
-            )?;
+            Self::syntax_error(!self.number_hex, "hexadecimal literal", token)?;
             Self::from_str_radix(text, 16)
         } else if t.starts_with("0b") || t.starts_with("0B") {
             // Binary integer.
-            Self::syntax_error(
-                !self.number_bin,
-                "binary literal",
-                pair.as_span().start_pos(),
-            )?;
-            return Self::from_str_radix(text, 2);
+            Self::syntax_error(!self.number_bin, "binary literal", token)?;
+            Self::from_str_radix(text, 2)
         } else if t.starts_with("0o") || t.starts_with("0O") {
             // Octal integer.
-            Self::syntax_error(
-                !self.number_oct,
-                "octal literal",
-                pair.as_span().start_pos(),
-            )?;
-            return Self::from_str_radix(text, 8);
+            Self::syntax_error(!self.number_oct, "octal literal", token)?;
+            Self::from_str_radix(text, 8)
         } else if t.contains('.')
             || t.contains('e')
             || t.contains('E')
@@ -246,110 +354,174 @@ impl Relax {
             Self::syntax_error(
                 !self.number_lax_dec_point && (t.starts_with('.') || t.ends_with('.')),
                 "bad float literal",
-                pair.as_span().start_pos(),
+                token,
             )?;
-            return Ok(Document::Float(text.parse().unwrap()));
+            Ok(Document::Float(text.replace('_', "").parse().unwrap()))
         } else {
             // Decimal integer.
-            return Self::from_str_radix(text, 10);
+            Self::from_str_radix(text, 10)
         }
     }
 
-    fn handle_kvpair(&self, pairs: &mut Pairs<Rule>) -> Result<(Document, bool), Error> {
+    fn kvpair<'a>(
+        &self,
+        items: &mut Peekable<impl Iterator<Item = Item<'a>>>,
+    ) -> (Document<'a>, bool) {
         let mut k = usize::MAX;
         let mut v = usize::MAX;
         let mut kv = vec![];
         let mut comma = false;
-        while let Some(pair) = pairs.peek() {
-            let rule = pair.as_rule();
-            if rule == Rule::comma {
-                comma = true;
-                let _ = pairs.next();
-                continue;
-            }
-            let (line, _) = self.line_col(pair.as_span().start());
-            if rule == Rule::COMMENT {
-                if v != usize::MAX {
-                    if v == line {
-                        // Comment on the same line as the value,
-                        // keep the comment.
-                    } else {
+        while let Some(item) = items.peek() {
+            match *item {
+                Item::Comma => {
+                    comma = true;
+                    let _ = items.next();
+                    continue;
+                }
+                Item::Comment(_, line, _) => {
+                    if v != usize::MAX && v != line {
                         // Comment is not on the same line as the value,
                         // so exit the loop; the comment belongs to the
                         // next value.
                         break;
                     }
-                } else {
-                    // Comment before the value, keep the comment.
+                    // Otherwise, the comment is before the value or on
+                    // the same line as the value; keep the comment.
+                }
+                Item::Value(_, line, _) => {
+                    if k == usize::MAX {
+                        // If the item isn't a comment or comma, and we haven't
+                        // seen the key, then it must be the key.
+                        k = line;
+                    } else if v == usize::MAX {
+                        // If we haven't seen the value, then it must be the
+                        // value.
+                        v = line;
+                    } else {
+                        // We've seen both the key and value, so it must be
+                        // part of the next kvpair.  Exit the loop.
+                        break;
+                    }
                 }
-            } else if k == usize::MAX {
-                // If the pair isn't a comment or comma, and we haven't seen
-                // the key, then it must be the key.
-                // Keep it.
-                k = line;
-            } else if v == usize::MAX {
-                // If the pair isn't a comment or comma, and we haven't seen
-                // the value, then it must be the value.
-                // Keep it.
-                v = line;
-            } else {
-                // If the pair is a not a comment or comma and we've seen both
-                // the key and value, it must be part of the next kvpair.
-                // Exit the loop.
-                break;
             }
-            kv.push(self.handle_pair(pair)?);
-            // Advance the iterator.
-            let _ = pairs.next();
+            kv.push(items.next().unwrap().into_document());
         }
-        Ok((Document::Fragment(kv), comma))
+        (Document::Fragment(kv), comma)
     }
 
-    fn handle_array_elem(&self, pairs: &mut Pairs<Rule>) -> Result<(Document, bool), Error> {
+    fn array_elem<'a>(
+        &self,
+        items: &mut Peekable<impl Iterator<Item = Item<'a>>>,
+    ) -> (Document<'a>, bool) {
         let mut i = usize::MAX;
         let mut item = vec![];
         let mut comma = false;
-        let mut saw_value = false;
-        while let Some(pair) = pairs.peek() {
-            let rule = pair.as_rule();
-            if rule == Rule::comma {
-                let _ = pairs.next();
-                comma = true;
-                continue;
-            }
-            let (line, _) = self.line_col(pair.as_span().start());
-            if rule == Rule::COMMENT {
-                if saw_value {
-                    if i == line {
-                        // Comment is on the same line as the value,
-                        // keep the comment.
-                    } else {
+        while let Some(it) = items.peek() {
+            match *it {
+                Item::Comma => {
+                    comma = true;
+                    let _ = items.next();
+                    continue;
+                }
+                Item::Comment(_, line, _) => {
+                    if i != usize::MAX && i != line {
                         // Comment is not on the same line as the value,
                         // so exit the loop; the comment belongs to the
                         // next value.
                         break;
                     }
-                } else {
-                    // Comment is before the value, keep the comment.
+                    // Otherwise, the comment is before the value or on
+                    // the same line as the value; keep the comment.
+                }
+                Item::Value(_, line, _) => {
+                    if i == usize::MAX {
+                        // If the item isn't a comment or comma, it must be
+                        // a value.  Keep the value.
+                        i = line;
+                    } else {
+                        // If we've already seen a value, its the next value.
+                        // Exit the loop.
+                        break;
+                    }
                 }
-            } else if !saw_value {
-                // If the pair isn't a comment or comma, it must be a value.
-                // Keep the value.
-                i = line;
-                saw_value = true;
-            } else {
-                // If the pair is a value, but we've already seen a value,
-                // its the next value.  Exit the loop.
-                break;
             }
-            item.push(self.handle_pair(pair)?);
-            let _ = pairs.next();
+            item.push(items.next().unwrap().into_document());
         }
         if item.len() == 1 && item[0].comment().is_none() {
-            Ok((item.pop().unwrap(), comma))
+            (item.pop().unwrap(), comma)
         } else {
-            Ok((Document::Fragment(item), comma))
+            (Document::Fragment(item), comma)
+        }
+    }
+
+    /// Groups the items in the body of an object or array into its members,
+    /// attaching each comment to the member it belongs to.
+    ///
+    /// Comments before a value are leading comments of its member, comments
+    /// starting on the line the value ends on are trailing comments, and
+    /// comments after the last value are dangling comments, which form a
+    /// member of their own.
+    pub(crate) fn aggregate<'a>(
+        &self,
+        input: &str,
+        items: impl Iterator<Item = Item<'a>>,
+        object: bool,
+    ) -> Result<Vec<Document<'a>>, Error> {
+        // A run of line comments starting after a value is a single token;
+        // only its first line trails the value.
+        let mut value_line = usize::MAX;
+        let items = items.flat_map(|item| {
+            let mut rest = None;
+            let item = match item {
+                Item::Value(_, line, _) => {
+                    value_line = line;
+                    item
+                }
+                Item::Comment(Document::Comment(c, f), line, end)
+                    if line == value_line && f != CommentFormat::Block && c.contains('\n') =>
+                {
+                    let (first, second) = c.split_once('\n').unwrap();
+                    let second = Document::Comment(second.into(), f);
+                    rest = Some(Item::Comment(second, line + 1, end));
+                    Item::Comment(Document::Comment(first.into(), f), line, end)
+                }
+                item => item,
+            };
+            std::iter::once(item).chain(rest)
+        });
+        let mut items = items.peekable();
+        let mut end = items.peek().map(Item::end);
+        let mut members = Vec::new();
+        let mut saw_comma = false;
+        let mut need_comma = false;
+        while items.peek().is_some() {
+            if !self.comma_optional {
+                Self::syntax_error_at(
+                    need_comma ^ saw_comma,
+                    "expected comma",
+                    input,
+                    end.unwrap(),
+                )?;
+            }
+            end = items.peek().map(Item::end);
+            let (node, comma) = if object {
+                self.kvpair(&mut items)
+            } else {
+                self.array_elem(&mut items)
+            };
+            members.push(node);
+            saw_comma = comma;
+            need_comma = true;
+        }
+        if let Some(end) = end {
+            Self::syntax_error_at(
+                !self.comma_trailing && saw_comma,
+                "no comma expected",
+                input,
+                end,
+            )?;
         }
+        Ok(members)
     }
 
     fn strip_leading_prefix<'a>(lines: &[&'a str], prefix: char) -> Vec<&'a str> {
@@ -367,14 +539,28 @@ impl Relax {
             .collect::<Vec<_>>()
     }
 
-    fn syntax_error(err: bool, msg: &str, pos: Position) -> Result<(), Error> {
+    fn syntax_error(err: bool, msg: &str, token: Token) -> Result<(), Error> {
+        Self::syntax_error_at(err, msg, token.input, token.start)
+    }
+
+    pub(crate) fn syntax_error_at(
+        err: bool,
+        msg: &str,
+        input: &str,
+        pos: usize,
+    ) -> Result<(), Error> {
         if err {
-            let (ln, col) = pos.line_col();
+            let start = input[..pos].rfind('\n').map_or(0, |i| i + 1);
+            let end = input[pos..].find('\n').map_or(input.len(), |i| pos + i);
+            let line = input.as_bytes()[..start]
+                .iter()
+                .filter(|&&b| b == b'\n')
+                .count();
             Err(Error::SyntaxError(
                 msg.into(),
-                ln,
-                col,
-                pos.line_of().trim_end().into(),
+                line + 1,
+                input[start..pos].chars().count() + 1,
+                input[start..end].trim_end().into(),
                 "^",
             ))
         } else {
@@ -382,14 +568,10 @@ impl Relax {
         }
     }
 
-    fn handle_comment(&self, pair: Pair<Rule>) -> Result<Document, Error> {
-        let comment = pair.as_str();
+    pub(crate) fn handle_comment<'a>(&self, token: Token) -> Result<Document<'a>, Error> {
+        let comment = token.as_str();
         if let Some(c) = comment.strip_prefix("/*") {
-            Self::syntax_error(
-                !self.comment_block,
-                "block comment",
-                pair.as_span().start_pos(),
-            )?;
+            Self::syntax_error(!self.comment_block, "block comment", token)?;
             let c = c.strip_suffix("*/").unwrap().trim_end();
             let lines = c.split('\n').map(str::trim).collect::<Vec<_>>();
             let lines = Self::strip_leading_prefix(&lines, '*');
@@ -402,11 +584,7 @@ impl Relax {
             let c = lines[start..].join("\n");
             Ok(Document::Comment(c, CommentFormat::Block))
         } else if comment.starts_with("//") {
-            Self::syntax_error(
-                !self.comment_slash,
-                "slash comment",
-                pair.as_span().start_pos(),
-            )?;
+            Self::syntax_error(!self.comment_slash, "slash comment", token)?;
             let lines = comment.split('\n').map(str::trim).collect::<Vec<_>>();
             let lines = Self::strip_leading_prefix(&lines, '/');
             let lines = Self::strip_leading_prefix(&lines, ' ');
@@ -419,11 +597,7 @@ impl Relax {
             let c = lines[..end].join("\n");
             Ok(Document::Comment(c, CommentFormat::SlashSlash))
         } else if comment.starts_with('#') {
-            Self::syntax_error(
-                !self.comment_hash,
-                "hash comment",
-                pair.as_span().start_pos(),
-            )?;
+            Self::syntax_error(!self.comment_hash, "hash comment", token)?;
             let lines = comment.split('\n').map(str::trim).collect::<Vec<_>>();
             let lines = Self::strip_leading_prefix(&lines, '#');
             let lines = Self::strip_leading_prefix(&lines, ' ');
@@ -440,16 +614,24 @@ impl Relax {
         }
     }
 
-    fn handle_string(&self, pair: Pair<Rule>) -> Result<Document, Error> {
-        let s = pair.as_str();
+    // Whether a string token is quoted.  An unquoted hjson string may start
+    // with a quote which isn't closed on the same line, but it never ends
+    // with one.
+    fn is_quoted(s: &str) -> bool {
+        let b = s.as_bytes();
+        b.len() > 1 && matches!(b[0], b'\'' | b'"') && b[b.len() - 1] == b[0]
+    }
+
+    pub(crate) fn handle_string<'a>(&self, token: Token<'a>) -> Result<Document<'a>, Error> {
+        let s = token.as_str();
         if s.starts_with("'''") {
             Self::syntax_error(
                 !self.string_hjson_multiline,
                 "unexpected hjson multiline string",
-                pair.as_span().start_pos(),
+                token,
             )?;
             let s = &s[3..(s.len() - 3)].trim();
-            let (_, column) = self.line_col(pair.as_span().start());
+            let (_, column) = self.line_col(token.start);
             let split = column - 1;
             let mut value = Vec::new();
             for line in s.split('\n') {
@@ -463,11 +645,11 @@ impl Relax {
                 }
             }
             Ok(Document::String(value.join("\n"), StrFormat::Multiline))
-        } else if s.starts_with('\'') || s.starts_with('"') {
+        } else if Self::is_quoted(s) {
             Self::syntax_error(
                 !self.string_single_quote && s.starts_with('\''),
                 "single quote",
-                pair.as_span().start_pos(),
+                token,
             )?;
             let s = &s[1..(s.len() - 1)];
             let json5_line_cont = s.contains("\\\r\n")
@@ -478,117 +660,95 @@ impl Relax {
             Self::syntax_error(
                 !self.string_json5_multiline && json5_line_cont,
                 "unexpected end of line",
-                pair.as_span().start_pos(),
+                token,
             )?;
             let format = if json5_line_cont {
                 StrFormat::Multiline
             } else {
                 StrFormat::Standard
             };
-            Ok(Document::String(Self::unescape(s)?, format))
+            if s.contains('\\') {
+                Ok(Document::String(Self::unescape(s)?, format))
+            } else {
+                Ok(Document::Str(s, format))
+            }
         } else {
-            Self::syntax_error(
-                !self.string_unquoted,
-                "missing quotes",
-                pair.as_span().start_pos(),
-            )?;
-            Ok(Document::String(s.trim().into(), StrFormat::Unquoted))
+            Self::syntax_error(!self.string_unquoted, "missing quotes", token)?;
+            Ok(Document::Str(s.trim(), StrFormat::Unquoted))
         }
     }
 
-    fn handle_pair(&self, pair: Pair<Rule>) -> Result<Document, Error> {
+    pub(crate) fn handle_identifier<'a>(&self, token: Token<'a>) -> Result<Document<'a>, Error> {
+        Self::syntax_error(!self.string_ident, "missing quotes", token)?;
+        Ok(Document::Str(token.as_str(), StrFormat::Unquoted))
+    }
+
+    // Converts the pairs in the body of an object or array into `Item`s.
+    #[cfg(feature = "pest")]
+    fn handle_items<'a>(
+        &self,
+        pairs: Pairs<'a, Rule>,
+        object: bool,
+    ) -> Result<Vec<Item<'a>>, Error> {
+        // The keys and values of an object alternate.
+        let mut key = object;
+        pairs
+            .map(|pair| {
+                let token = Token::from(&pair);
+                Ok(match pair.as_rule() {
+                    Rule::comma => Item::Comma,
+                    Rule::COMMENT => {
+                        let (line, _) = self.line_col(token.start);
+                        Item::Comment(self.handle_comment(token)?, line, token.end)
+                    }
+                    _ if key => {
+                        key = false;
+                        let line = self.end_line(token);
+                        Item::Value(self.handle_pair(pair)?, line, token.end)
+                    }
+                    _ => {
+                        key = object;
+                        let line = self.end_line(token);
+                        let doc = self.handle_pair(pair)?;
+                        Item::Value(self.spanned(doc, token), line, token.end)
+                    }
+                })
+            })
+            .collect()
+    }
+
+    #[cfg(feature = "pest")]
+    fn handle_pair<'a>(&self, pair: Pair<'a, Rule>) -> Result<Document<'a>, Error> {
+        let token = Token::from(&pair);
         match pair.as_rule() {
             Rule::null => Ok(Document::Null),
             Rule::boolean => Ok(Document::Boolean(pair.as_str().parse().unwrap())),
-            Rule::string => self.handle_string(pair),
-            Rule::hjson_key => {
-                Self::syntax_error(
-                    !self.string_ident,
-                    "missing quotes",
-                    pair.as_span().start_pos(),
-                )?;
-                Ok(Document::String(pair.as_str().into(), StrFormat::Unquoted))
-            }
-            Rule::identifier => {
-                Self::syntax_error(
-                    !self.string_ident,
-                    "missing quotes",
-                    pair.as_span().start_pos(),
-                )?;
-                // TODO: add StrFormat::Unquoted
-                Ok(Document::String(pair.as_str().into(), StrFormat::Unquoted))
-            }
-            Rule::number => self.handle_number(pair),
+            Rule::string => self.handle_string(token),
+            Rule::hjson_key | Rule::identifier => self.handle_identifier(token),
+            Rule::number => self.handle_number(token),
             Rule::object => {
-                let mut pairs = pair.into_inner();
-                let mut npair = pairs.peek();
-                let mut kvs = Vec::new();
-                let mut saw_comma = false;
-                let mut need_comma = false;
-                while pairs.peek().is_some() {
-                    if !self.comma_optional {
-                        Self::syntax_error(
-                            need_comma ^ saw_comma,
-                            "expected comma",
-                            npair.unwrap().as_span().end_pos(),
-                        )?;
-                    }
-                    npair = pairs.peek();
-                    let (node, comma) = self.handle_kvpair(&mut pairs)?;
-                    kvs.push(node);
-                    saw_comma = comma;
-                    need_comma = true;
-                }
-                if npair.is_some() {
-                    Self::syntax_error(
-                        !self.comma_trailing && saw_comma,
-                        "no comma expected",
-                        npair.unwrap().as_span().end_pos(),
-                    )?;
-                }
+                let items = self.handle_items(pair.into_inner(), true)?;
+                let kvs = self.aggregate(token.input, items.into_iter(), true)?;
                 Ok(Document::Mapping(kvs))
             }
             Rule::array => {
-                let mut pairs = pair.into_inner();
-                let mut npair = pairs.peek();
-                let mut values = Vec::new();
-                let mut saw_comma = false;
-                let mut need_comma = false;
-                while pairs.peek().is_some() {
-                    if !self.comma_optional {
-                        Self::syntax_error(
-                            need_comma ^ saw_comma,
-                            "expected comma",
-                            npair.unwrap().as_span().end_pos(),
-                        )?;
-                    }
-
-                    npair = pairs.peek();
-                    let (node, comma) = self.handle_array_elem(&mut pairs)?;
-                    values.push(node);
-                    saw_comma = comma;
-                    need_comma = true;
-                }
-                if npair.is_some() {
-                    Self::syntax_error(
-                        !self.comma_trailing && saw_comma,
-                        "no comma expected",
-                        npair.unwrap().as_span().end_pos(),
-                    )?;
-                }
-
+                let items = self.handle_items(pair.into_inner(), false)?;
+                let values = self.aggregate(token.input, items.into_iter(), false)?;
                 Ok(Document::Sequence(values))
             }
-            Rule::COMMENT => self.handle_comment(pair),
-            Rule::EOI => Ok(Document::Null),
+            Rule::COMMENT => self.handle_comment(token),
             Rule::text => {
                 let mut doc = pair
                     .into_inner()
-                    .map(|p| self.handle_pair(p))
+                    .filter(|p| p.as_rule() != Rule::EOI)
+                    .map(|p| match p.as_rule() {
+                        Rule::COMMENT => self.handle_pair(p),
+                        _ => {
+                            let token = Token::from(&p);
+                            Ok(self.spanned(self.handle_pair(p)?, token))
+                        }
+                    })
                     .collect::<Result<Vec<_>, _>>()?;
-                // Since we explicitly handled EOI, remove the dummy Null node
-                // from the end of the vector.
-                let _ = doc.pop();
                 // A single node, or a sequence?
                 if doc.len() == 1 {
                     Ok(doc.pop().unwrap())
@@ -626,10 +786,10 @@ mod tests {
     }
 
     fn parse_string(r: &Relax, text: &str) -> Result<String> {
-        if let Document::String(s, _) = r.from_str(text)? {
-            Ok(s)
-        } else {
-            Err(anyhow!("Didn't return Document::String()"))
+        match r.from_str(text)? {
+            Document::String(s, _) => Ok(s),
+            Document::Str(s, _) => Ok(s.into()),
+            _ => Err(anyhow!("Didn't return Document::String()")),
         }
     }
 
@@ -650,6 +810,37 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_string_borrowed() -> Result<()> {
+        let relax = Relax::default();
+        let text = r#"{
+          "foo": "bar",
+          baz: 'a\tb',
+          unquoted: hello world
+        }"#;
+        let doc = relax.from_str(text)?;
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Didn't return Document::Mapping()"));
+        };
+        let kv = m
+            .iter()
+            .map(Document::as_kv)
+            .collect::<Result<Vec<_>, _>>()?;
+        // Strings without escapes are slices of the input text.
+        let range = text.as_bytes().as_ptr_range();
+        let borrowed =
+            |d: &Document| matches!(d, Document::Str(s, _) if range.contains(&s.as_ptr()));
+        assert!(borrowed(kv[0].0) && borrowed(kv[0].1));
+        assert!(borrowed(kv[1].0));
+        assert!(matches!(kv[1].1, Document::String(s, _) if s == "a\tb"));
+        assert!(borrowed(kv[2].0));
+        assert!(matches!(
+            kv[2].1,
+            Document::Str("hello world", StrFormat::Unquoted)
+        ));
+        Ok(())
+    }
+
     fn parse_integer(r: &Relax, text: &str) -> Result<i128> {
         if let Document::Int(int) = r.from_str(text)? {
             Ok(int.into())
@@ -704,6 +895,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_number_separators() -> Result<()> {
+        let relax = Relax::default();
+        let i = parse_integer(&relax, "0x0000_0040")?;
+        assert_eq!(i, 0x40);
+        let i = parse_integer(&relax, "-0b1010_0101")?;
+        assert_eq!(i, -0xA5);
+        let i = parse_integer(&relax, "1_000_000")?;
+        assert_eq!(i, 1000000);
+        let f = parse_float(&relax, "1_000.5")?;
+        assert_eq!(f, 1000.5);
+        assert!(Relax::json().from_str("1_000").is_err());
+        assert!(Relax::json5().from_str("0x0000_0040").is_err());
+        Ok(())
+    }
+
     #[test]
     fn test_number_float() -> Result<()> {
         let relax = Relax::default();
@@ -716,7 +923,7 @@ mod tests {
         Ok(())
     }
 
-    fn parse_mapping(r: &Relax, text: &str) -> Result<Vec<Document>> {
+    fn parse_mapping<'a>(r: &Relax, text: &'a str) -> Result<Vec<Document<'a>>> {
         let doc = r.from_str(text)?;
         if let Document::Mapping(m) = doc {
             Ok(m)
@@ -725,11 +932,9 @@ mod tests {
         }
     }
 
-    fn kv_extract(kv: Option<&Document>) -> Result<(&str, &str)> {
-        if let Some((Document::String(k, _), Document::String(v, _))) =
-            kv.map(Document::as_kv).transpose()?
-        {
-            Ok((k.as_str(), v.as_str()))
+    fn kv_extract<'a>(kv: Option<&'a Document>) -> Result<(&'a str, &'a str)> {
+        if let Some((k, v)) = kv.map(Document::as_kv).transpose()? {
+            Ok((k.as_str()?, v.as_str()?))
         } else {
             Err(anyhow!("Expected KeyValue(String, String), not {:?}", kv))
         }
@@ -750,7 +955,7 @@ mod tests {
         Ok(())
     }
 
-    fn parse_sequence(r: &Relax, text: &str) -> Result<Vec<Document>> {
+    fn parse_sequence<'a>(r: &Relax, text: &'a str) -> Result<Vec<Document<'a>>> {
         let doc = r.from_str(text);
         if let Ok(Document::Sequence(s)) = doc {
             Ok(s)
@@ -829,12 +1034,12 @@ mod tests {
             [Document::Fragment(a), Document::Fragment(b)] => {
                 let mut i = a.iter();
                 assert!(matches!(i.next(), Some(Document::Comment(_, _))));
-                assert!(matches!(i.next(), Some(Document::String(_, _))));
-                assert!(matches!(i.next(), Some(Document::String(_, _))));
+                assert!(matches!(i.next(), Some(Document::Str(_, _))));
+                assert!(matches!(i.next(), Some(Document::Str(_, _))));
                 assert!(i.next().is_none());
                 let mut i = b.iter();
-                assert!(matches!(i.next(), Some(Document::String(_, _))));
-                assert!(matches!(i.next(), Some(Document::String(_, _))));
+                assert!(matches!(i.next(), Some(Document::Str(_, _))));
+                assert!(matches!(i.next(), Some(Document::Str(_, _))));
                 assert!(matches!(i.next(), Some(Document::Comment(_, _))));
                 assert!(i.next().is_none());
             }
@@ -843,6 +1048,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_comment_placement() -> Result<()> {
+        let relax = Relax::default();
+        let sequence = parse_sequence(
+            &relax,
+            r#"[
+            [
+              1
+            ], // trailing a multi-line value
+            // leading
+            2 // trailing
+            // dangling
+        ]"#,
+        )?;
+        let comment = |d: &Document| d.comment().map(|(c, _)| c.to_string());
+        match &sequence[..] {
+            [Document::Fragment(a), Document::Fragment(b), Document::Fragment(c)] => {
+                assert!(matches!(a[0], Document::Sequence(_)));
+                assert_eq!(
+                    comment(&a[1]).as_deref(),
+                    Some("trailing a multi-line value")
+                );
+                assert_eq!(comment(&b[0]).as_deref(), Some("leading"));
+                assert!(matches!(b[1], Document::Int(_)));
+                assert_eq!(comment(&b[2]).as_deref(), Some("trailing"));
+                assert_eq!(comment(&c[0]).as_deref(), Some("dangling"));
+                assert_eq!((a.len(), b.len(), c.len()), (2, 3, 1));
+            }
+            _ => return Err(anyhow!("Unexpected structure")),
+        };
+        Ok(())
+    }
+
     #[test]
     fn test_json_comment() -> Result<()> {
         let relax = Relax::json();
@@ -1055,4 +1293,40 @@ mod tests {
         .is_ok());
         Ok(())
     }
+
+    #[test]
+    fn test_spans() -> Result<()> {
+        let relax = Relax {
+            spans: true,
+            ..Default::default()
+        };
+        let doc = relax.from_str("{\n  // Comment\n  a: [1, \"two\"]\n}")?;
+        assert_eq!(
+            doc.span(),
+            Some(Span {
+                start: 0,
+                end: 32,
+                line: 1,
+                col: 1
+            })
+        );
+        let a = doc.get("a")?;
+        let Document::Sequence(items) = a else {
+            return Err(anyhow!("expected a sequence"));
+        };
+        assert_eq!(
+            items[1].span(),
+            Some(Span {
+                start: 24,
+                end: 29,
+                line: 3,
+                col: 10
+            })
+        );
+        assert_eq!(items[1].as_str()?, "two");
+
+        let doc = Relax::default().from_str("[1]")?;
+        assert_eq!(doc.span(), None);
+        Ok(())
+    }
 }
@@ -0,0 +1,647 @@
+// Hand-written parser for the `Relax` json dialects.
+use crate::document::Document;
+use crate::error::Error;
+use crate::relax::{Item, Relax, Token};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// `Lexer` recognizes the tokens of the grammar in `relax.pest`.
+///
+/// Each `match_*` function recognizes the grammar rule of the same name at a
+/// position in the text, returning the position after the match.
+#[derive(Clone, Copy)]
+pub(crate) struct Lexer<'a> {
+    pub text: &'a str,
+}
+
+/// `Parser` is a recursive-descent parser for the syntax described by the
+/// grammar in `relax.pest`.
+///
+/// Like the grammar, the parser accepts every extension and leaves checking
+/// the `Relax` dialect flags to the functions which convert tokens into
+/// `Document` nodes.  The conversion, and the grouping of comments with the
+/// values they describe, is shared with `Relax::from_str_pest` (built with
+/// the `pest` feature) so both produce the same documents.
+pub(crate) struct Parser<'r, 'a> {
+    relax: &'r Relax,
+    lex: Lexer<'a>,
+    // The items of the objects and arrays being parsed.  Each aggregate
+    // pushes its items on top of those of its parents.
+    items: Vec<Item<'a>>,
+}
+
+pub(crate) fn is_line_terminator(ch: char) -> bool {
+    matches!(ch, '\n' | '\r' | '\u{2028}' | '\u{2029}')
+}
+
+pub(crate) fn is_whitespace(ch: char) -> bool {
+    match ch {
+        '\t' | '\u{b}' | '\u{c}' | ' ' | '\n' | '\r' => true,
+        _ if ch.is_ascii() => false,
+        _ => {
+            // The line and paragraph separators and the characters of the
+            // Unicode `Space_Separator` category.
+            matches!(
+                ch,
+                '\u{a0}' | '\u{feff}' | '\u{1680}' | '\u{2000}'
+                    ..='\u{200a}' | '\u{2028}' | '\u{2029}' | '\u{202f}' | '\u{205f}' | '\u{3000}'
+            )
+        }
+    }
+}
+
+fn is_punctuator(ch: char) -> bool {
+    matches!(ch, '{' | '}' | '[' | ']' | ',' | ':')
+}
+
+fn is_identifier_start(ch: char) -> bool {
+    match ch {
+        '$' | '_' => true,
+        _ if ch.is_ascii() => ch.is_ascii_alphabetic(),
+        _ => ch.is_alphabetic(),
+    }
+}
+
+impl<'a> Lexer<'a> {
+    pub(crate) fn byte(&self, pos: usize) -> Option<u8> {
+        self.text.as_bytes().get(pos).copied()
+    }
+
+    pub(crate) fn char_at(&self, pos: usize) -> Option<char> {
+        match self.byte(pos) {
+            Some(b) if b.is_ascii() => Some(b as char),
+            Some(_) => self.text[pos..].chars().next(),
+            None => None,
+        }
+    }
+
+    pub(crate) fn starts_with(&self, pos: usize, s: &str) -> bool {
+        self.text.as_bytes()[pos..].starts_with(s.as_bytes())
+    }
+
+    // Matches `s` ignoring ASCII case.
+    pub(crate) fn starts_with_ci(&self, pos: usize, s: &str) -> bool {
+        self.text.as_bytes()[pos..]
+            .get(..s.len())
+            .is_some_and(|t| t.eq_ignore_ascii_case(s.as_bytes()))
+    }
+
+    // Returns the position after the run of bytes matching `f`.
+    pub(crate) fn match_while(&self, mut pos: usize, f: impl Fn(u8) -> bool) -> usize {
+        while self.byte(pos).is_some_and(&f) {
+            pos += 1;
+        }
+        pos
+    }
+
+    // Matches a run of digits which may be separated into groups by `_`.
+    pub(crate) fn match_digits(&self, pos: usize, f: impl Fn(u8) -> bool) -> usize {
+        let mut end = self.match_while(pos, &f);
+        while end > pos && self.byte(end) == Some(b'_') && self.byte(end + 1).is_some_and(&f) {
+            end = self.match_while(end + 1, &f);
+        }
+        end
+    }
+
+    // Returns the position of the next line terminator, or the end of the
+    // text.
+    pub(crate) fn line_end(&self, pos: usize) -> usize {
+        let bytes = self.text.as_bytes();
+        let mut i = pos;
+        while i < bytes.len() {
+            match bytes[i] {
+                b'\n' | b'\r' => return i,
+                // U+2028 and U+2029 are encoded as E2 80 A8 and E2 80 A9.
+                0xE2 if bytes[i + 1] == 0x80 && matches!(bytes[i + 2], 0xA8 | 0xA9) => return i,
+                _ => i += 1,
+            }
+        }
+        i
+    }
+
+    pub(crate) fn match_line_terminator_sequence(&self, pos: usize) -> Option<usize> {
+        if self.starts_with(pos, "\r\n") {
+            return Some(pos + 2);
+        }
+        self.char_at(pos)
+            .filter(|&ch| is_line_terminator(ch))
+            .map(|ch| pos + ch.len_utf8())
+    }
+
+    pub(crate) fn match_whitespace(&self, mut pos: usize) -> usize {
+        while let Some(ch) = self.char_at(pos).filter(|&ch| is_whitespace(ch)) {
+            pos += ch.len_utf8();
+        }
+        pos
+    }
+
+    // Matches a run of line comments starting with `prefix`.  Comments on
+    // consecutive lines (separated only by whitespace) are a single token.
+    pub(crate) fn match_line_comments(&self, pos: usize, prefix: &str) -> Option<usize> {
+        let comment = |pos| {
+            self.starts_with(pos, prefix)
+                .then(|| self.line_end(pos + prefix.len()))
+        };
+        let comment_eol = |pos| {
+            comment(pos)
+                .and_then(|end| self.match_line_terminator_sequence(end))
+                .map(|end| self.match_whitespace(end))
+        };
+        if let Some(mut end) = comment_eol(pos) {
+            let mut n = 0;
+            while let Some(e) = comment_eol(end).or_else(|| comment(end)) {
+                end = e;
+                n += 1;
+            }
+            if n > 0 {
+                return Some(end);
+            }
+        }
+        comment(pos)
+    }
+
+    pub(crate) fn match_comment(&self, pos: usize) -> Option<usize> {
+        match self.byte(pos) {
+            Some(b'/') if self.starts_with(pos, "/*") => {
+                self.text[pos + 2..].find("*/").map(|end| pos + 2 + end + 2)
+            }
+            Some(b'/') => self.match_line_comments(pos, "//"),
+            Some(b'#') => self.match_line_comments(pos, "#"),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn match_decimal_integer(&self, pos: usize) -> Option<usize> {
+        match self.byte(pos)? {
+            b'0' => Some(pos + 1),
+            b'1'..=b'9' => Some(self.match_digits(pos, |b| b.is_ascii_digit())),
+            _ => None,
+        }
+    }
+
+    pub(crate) fn match_exponent(&self, pos: usize) -> Option<usize> {
+        if !matches!(self.byte(pos)?, b'e' | b'E') {
+            return None;
+        }
+        let mut pos = pos + 1;
+        if matches!(self.byte(pos), Some(b'+' | b'-')) {
+            pos += 1;
+        }
+        let end = self.match_while(pos, |b| b.is_ascii_digit());
+        (end > pos).then_some(end)
+    }
+
+    pub(crate) fn match_decimal(&self, pos: usize) -> Option<usize> {
+        let digits = |pos| self.match_while(pos, |b| b.is_ascii_digit());
+        let exponent = |pos| self.match_exponent(pos).unwrap_or(pos);
+        if let Some(end) = self.match_decimal_integer(pos) {
+            if self.byte(end) == Some(b'.') {
+                return Some(exponent(digits(end + 1)));
+            }
+            return Some(exponent(end));
+        }
+        if self.byte(pos) == Some(b'.') {
+            let end = digits(pos + 1);
+            if end > pos + 1 {
+                return Some(exponent(end));
+            }
+        }
+        None
+    }
+
+    pub(crate) fn match_number(&self, pos: usize) -> Option<usize> {
+        let pos = match self.byte(pos)? {
+            b'+' | b'-' => pos + 1,
+            _ => pos,
+        };
+        let radix = |prefix, f: fn(u8) -> bool| {
+            if self.starts_with_ci(pos, prefix) {
+                let end = self.match_digits(pos + 2, f);
+                (end > pos + 2).then_some(end)
+            } else {
+                None
+            }
+        };
+        radix("0b", |b| matches!(b, b'0' | b'1'))
+            .or_else(|| radix("0x", |b| b.is_ascii_hexdigit()))
+            .or_else(|| radix("0o", |b| matches!(b, b'0'..=b'7')))
+            .or_else(|| self.match_decimal(pos))
+            .or_else(|| self.starts_with(pos, "Infinity").then_some(pos + 8))
+            .or_else(|| self.starts_with(pos, "NaN").then_some(pos + 3))
+    }
+
+    pub(crate) fn is_punctuator(&self, pos: usize) -> bool {
+        self.byte(pos).is_some_and(|b| is_punctuator(b as char))
+    }
+
+    // A number which hjson doesn't treat as the start of an unquoted string.
+    pub(crate) fn is_hjson_number(&self, pos: usize) -> bool {
+        match self.match_number(pos) {
+            Some(end) => {
+                self.is_punctuator(end)
+                    || self.char_at(end).is_some_and(is_whitespace)
+                    || self.match_comment(end).is_some()
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) fn match_hex(&self, pos: usize, n: usize) -> Option<usize> {
+        let end = pos + n;
+        self.text
+            .as_bytes()
+            .get(pos..end)
+            .filter(|digits| digits.iter().all(u8::is_ascii_hexdigit))
+            .map(|_| end)
+    }
+
+    pub(crate) fn match_escape_sequence(&self, pos: usize) -> Option<usize> {
+        match self.char_at(pos)? {
+            '0' => Some(pos + 1),
+            'x' => self.match_hex(pos + 1, 2),
+            'u' => self.match_hex(pos + 1, 4),
+            ch if ch.is_ascii_digit() || is_line_terminator(ch) => None,
+            ch => Some(pos + ch.len_utf8()),
+        }
+    }
+
+    pub(crate) fn match_quoted(&self, pos: usize, quote: char) -> Option<usize> {
+        let mut pos = pos + 1;
+        loop {
+            match self.char_at(pos)? {
+                '\\' => {
+                    pos = self
+                        .match_escape_sequence(pos + 1)
+                        .or_else(|| self.match_line_terminator_sequence(pos + 1))?;
+                }
+                ch if ch == quote => return Some(pos + 1),
+                ch if is_line_terminator(ch) => return None,
+                ch => pos += ch.len_utf8(),
+            }
+        }
+    }
+
+    pub(crate) fn match_unquoted(&self, pos: usize) -> Option<usize> {
+        if self.is_hjson_number(pos) || self.is_punctuator(pos) || self.match_comment(pos).is_some()
+        {
+            return None;
+        }
+        let end = self.line_end(pos);
+        if end == pos {
+            return None;
+        }
+        self.match_line_terminator_sequence(end)
+            .map(|end| self.match_whitespace(end))
+    }
+
+    pub(crate) fn match_string(&self, pos: usize) -> Option<usize> {
+        if self.starts_with(pos, "'''") {
+            if let Some(end) = self.text[pos + 3..].find("'''") {
+                return Some(pos + 3 + end + 3);
+            }
+        }
+        match self.byte(pos)? {
+            b'\'' => self.match_quoted(pos, '\''),
+            b'"' => self.match_quoted(pos, '"'),
+            _ => None,
+        }
+        .or_else(|| self.match_unquoted(pos))
+    }
+
+    pub(crate) fn match_identifier(&self, pos: usize) -> Option<usize> {
+        let escape = |pos| {
+            self.starts_with(pos, "\\u")
+                .then(|| self.match_hex(pos + 2, 4))
+                .flatten()
+        };
+        let mut pos = match self.char_at(pos)? {
+            '\\' => escape(pos)?,
+            ch if is_identifier_start(ch) => pos + ch.len_utf8(),
+            _ => return None,
+        };
+        loop {
+            pos = match self.char_at(pos) {
+                Some('\\') => match escape(pos) {
+                    Some(end) => end,
+                    None => return Some(pos),
+                },
+                Some(ch) if !is_punctuator(ch) && !is_line_terminator(ch) => pos + ch.len_utf8(),
+                _ => return Some(pos),
+            }
+        }
+    }
+}
+
+impl<'r, 'a> Parser<'r, 'a> {
+    /// Creates a parser for `text`.  The `relax` parser must have indexed
+    /// the lines of `text`.
+    pub fn new(relax: &'r Relax, text: &'a str) -> Self {
+        Parser {
+            relax,
+            lex: Lexer { text },
+            items: Vec::new(),
+        }
+    }
+
+    /// Parses the text into a `Document`.
+    pub fn parse(mut self) -> Result<Document<'a>> {
+        let mut pos = self.skip(0)?;
+        if pos < self.lex.text.len() {
+            let (value, end) = self.value(pos)?;
+            self.push_spanned(value, pos, end);
+            pos = self.skip(end)?;
+        }
+        if pos < self.lex.text.len() {
+            return self.error("expected end of input", pos);
+        }
+        Ok(self.finish())
+    }
+
+    /// Returns the document formed by the items at the top level.
+    pub(crate) fn finish(mut self) -> Document<'a> {
+        let mut doc = self
+            .items
+            .drain(..)
+            .map(Item::into_document)
+            .collect::<Vec<_>>();
+        // A single node, or a sequence?
+        if doc.len() == 1 {
+            doc.pop().unwrap()
+        } else {
+            Document::Fragment(doc)
+        }
+    }
+
+    fn error<T>(&self, msg: &str, pos: usize) -> Result<T> {
+        Relax::syntax_error_at(true, msg, self.lex.text, pos)?;
+        unreachable!()
+    }
+
+    fn token(&self, start: usize, end: usize) -> Token<'a> {
+        Token {
+            input: self.lex.text,
+            start,
+            end,
+        }
+    }
+
+    // Skips whitespace and comments, adding the comments to the items.
+    pub(crate) fn skip(&mut self, pos: usize) -> Result<usize> {
+        let mut pos = self.lex.match_whitespace(pos);
+        while let Some(end) = self.lex.match_comment(pos) {
+            let token = self.token(pos, end);
+            let doc = self.relax.handle_comment(token)?;
+            let (line, _) = self.relax.line_col(pos);
+            self.items.push(Item::Comment(doc, line, end));
+            pos = self.lex.match_whitespace(end);
+        }
+        Ok(pos)
+    }
+
+    pub(crate) fn key(&self, pos: usize) -> Result<(Document<'a>, usize)> {
+        if let Some(end) = self.lex.match_identifier(pos) {
+            Ok((self.relax.handle_identifier(self.token(pos, end))?, end))
+        } else if let Some(end) = self.lex.match_number(pos) {
+            Ok((self.relax.handle_number(self.token(pos, end))?, end))
+        } else if let Some(end) = self.lex.match_string(pos) {
+            Ok((self.relax.handle_string(self.token(pos, end))?, end))
+        } else {
+            self.error("expected key", pos)
+        }
+    }
+
+    pub(crate) fn value(&mut self, pos: usize) -> Result<(Document<'a>, usize)> {
+        if self.lex.starts_with(pos, "null") {
+            Ok((Document::Null, pos + 4))
+        } else if self.lex.starts_with(pos, "true") {
+            Ok((Document::Boolean(true), pos + 4))
+        } else if self.lex.starts_with(pos, "false") {
+            Ok((Document::Boolean(false), pos + 5))
+        } else if let Some(end) = self.lex.match_string(pos) {
+            Ok((self.relax.handle_string(self.token(pos, end))?, end))
+        } else if let Some(end) = self.lex.match_number(pos) {
+            Ok((self.relax.handle_number(self.token(pos, end))?, end))
+        } else if self.lex.byte(pos) == Some(b'{') {
+            self.object(pos)
+        } else if self.lex.byte(pos) == Some(b'[') {
+            self.array(pos)
+        } else {
+            self.error("expected value", pos)
+        }
+    }
+
+    // Adds a key or value to the items.
+    pub(crate) fn push_value(&mut self, doc: Document<'a>, pos: usize, end: usize) {
+        let line = self.relax.end_line(self.token(pos, end));
+        self.items.push(Item::Value(doc, line, end));
+    }
+
+    // Adds a value, with its span if requested, to the items.
+    pub(crate) fn push_spanned(&mut self, doc: Document<'a>, pos: usize, end: usize) {
+        let doc = self.relax.spanned(doc, self.token(pos, end));
+        self.push_value(doc, pos, end);
+    }
+
+    // Returns a mark for the items of an object or array about to be parsed.
+    pub(crate) fn mark(&self) -> usize {
+        self.items.len()
+    }
+
+    // Groups the items since `mark` into the members of an object or array.
+    pub(crate) fn members(&mut self, mark: usize, object: bool) -> Result<Vec<Document<'a>>> {
+        self.relax
+            .aggregate(self.lex.text, self.items.drain(mark..), object)
+    }
+
+    // Skips to the next item after a value, adding the comma (if any) to the
+    // items.
+    pub(crate) fn comma(&mut self, pos: usize) -> Result<usize> {
+        let pos = self.skip(pos)?;
+        if self.lex.byte(pos) == Some(b',') {
+            self.items.push(Item::Comma);
+            self.skip(pos + 1)
+        } else {
+            Ok(pos)
+        }
+    }
+
+    fn object(&mut self, pos: usize) -> Result<(Document<'a>, usize)> {
+        let mark = self.mark();
+        let mut pos = self.skip(pos + 1)?;
+        while self.lex.byte(pos) != Some(b'}') {
+            let (key, end) = self.key(pos)?;
+            self.push_value(key, pos, end);
+            pos = self.skip(end)?;
+            if self.lex.byte(pos) != Some(b':') {
+                return self.error("expected `:`", pos);
+            }
+            pos = self.skip(pos + 1)?;
+            let (value, end) = self.value(pos)?;
+            self.push_spanned(value, pos, end);
+            pos = self.comma(end)?;
+        }
+        let kvs = self.members(mark, true)?;
+        Ok((Document::Mapping(kvs), pos + 1))
+    }
+
+    fn array(&mut self, pos: usize) -> Result<(Document<'a>, usize)> {
+        let mark = self.mark();
+        let mut pos = self.skip(pos + 1)?;
+        while self.lex.byte(pos) != Some(b']') {
+            let (value, end) = self.value(pos)?;
+            self.push_spanned(value, pos, end);
+            pos = self.comma(end)?;
+        }
+        let values = self.members(mark, false)?;
+        Ok((Document::Sequence(values), pos + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(feature = "pest")]
+    use anyhow::anyhow;
+    use anyhow::Result;
+
+    // Documents which `from_str` and `from_str_pest` must parse alike.
+    #[cfg(feature = "pest")]
+    const DOCUMENTS: &[&str] = &[
+        r#"{"foo": "bar", baz: 'boo', "n": [1, -2.5e3, 0x1F, 0b101, 0o17, +7, .5, 5., NaN, -Infinity]}"#,
+        r#"
+        // Leading comment
+        // spanning lines
+
+        // after a blank line
+        {
+          /* block
+           * comment */
+          key: value with spaces
+          "quoted": "esc\"aped\n\u2122\x41", // trailing
+          'single': 'it\'s',
+          # hash comment
+          multi:
+            '''
+            first line
+              indented
+            last line
+            '''
+          cont: "line\
+continued"
+          nested: { a: [true, false, null], b: {} } // same line
+          list: [
+            // first
+            1
+            2, // two
+            3
+            // dangling
+          ]
+          time: 01/02/03 04:05:06AM
+          num_then_text: 123abc
+          nullish: "null"
+          foo bar: 1
+          $id_2: 0
+          -5: neg
+          0x10: hex
+        }
+        # trailing comment
+        "#,
+        "[1,2,3,]",
+        "[1 2 3]",
+        "[\n  1\n  2\n]",
+        "{a: 1 b: 2}",
+        "{\"a\": 1 // c\n, \"b\": 2}",
+        "{a: 1\n // last\n}",
+        "[1, // one\n 2 /* two */, 3]",
+        "  \"str\"  ",
+        "\n'''\n  x\n  '''",
+        "\"\\0\" ",
+        "'\\v'",
+        "{a : 1, 1a: 2}",
+        "[0123\n, 123 abc\n, \"open\n, 'open\n]",
+        "{\"\\u00e9\": \"caf\u{e9}\"}",
+        "{ caf\u{e9}: \u{3b1}\u{3b2}\u{3b3}\n }",
+        "[\u{a0}1,\u{2003}2\u{feff}]",
+        "[0x0000_0040, 0b1010_0101, 0o7_777, 1_000, -1_000.5, 1__0]",
+        "{a: 1_, b: 0x_1}",
+        "1\r\n// crlf\r\n",
+        "nullable\n",
+        "true story\n",
+        "'unterminated\n",
+        "{a: 'b' \n",
+        "# just a comment",
+        "// one\n// two",
+        "",
+    ];
+
+    #[cfg(feature = "pest")]
+    fn parsers() -> Vec<Relax> {
+        let mut spans = Relax::default();
+        spans.spans = true;
+        vec![
+            Relax::default(),
+            Relax::json(),
+            Relax::json5(),
+            Relax::hjson(),
+            spans,
+        ]
+    }
+
+    #[cfg(feature = "pest")]
+    fn check(relax: &Relax, text: &str) -> Result<()> {
+        let fast = relax.from_str(text).map(|d| format!("{:?}", d));
+        let pest = relax.from_str_pest(text).map(|d| format!("{:?}", d));
+        match (fast, pest) {
+            (Ok(a), Ok(b)) if a == b => Ok(()),
+            (Err(_), Err(_)) => Ok(()),
+            (a, b) => Err(anyhow!(
+                "parsers disagree on {:?}:\n  from_str: {:?}\n  from_str_pest: {:?}",
+                text,
+                a,
+                b
+            )),
+        }
+    }
+
+    #[cfg(feature = "pest")]
+    #[test]
+    fn test_same_as_pest() -> Result<()> {
+        for relax in parsers() {
+            for text in DOCUMENTS {
+                check(&relax, text)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[cfg(feature = "pest")]
+    #[test]
+    fn test_same_as_pest_truncated() -> Result<()> {
+        // Every prefix of a document is either an error for both parsers or
+        // the same document.
+        for relax in parsers() {
+            for text in DOCUMENTS {
+                for (i, _) in text.char_indices() {
+                    check(&relax, &text[..i])?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let relax = Relax::json();
+        let err = relax.from_str("{\"a\": 1,\n \"b\": [1, 2,]}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: no comma expected at 2:12\n|  \"b\": [1, 2,]}\n|            ^"
+        );
+        let err = relax.from_str("{\"a\" 1}").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: expected `:` at 1:6\n| {\"a\" 1}\n|      ^"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,1118 @@
+// Validation of `Document`s against a JSON Schema.
+use regex::Regex;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::annotate::private::Annotator;
+use crate::annotate::{Annotate, Format, MemberId};
+
+use crate::doc_iter::OwnedDocPath;
+use crate::document::{BytesFormat, CommentFormat, Document, Span, StrFormat};
+use crate::error::{Error, Location};
+use crate::integer::{Base, Int};
+use crate::trace::{Container, Shape, Variant};
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// The value types understood by the `type` keyword.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Type {
+    Null,
+    Boolean,
+    Object,
+    Array,
+    Number,
+    String,
+    Integer,
+}
+
+impl Type {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "null" => Some(Type::Null),
+            "boolean" => Some(Type::Boolean),
+            "object" => Some(Type::Object),
+            "array" => Some(Type::Array),
+            "number" => Some(Type::Number),
+            "string" => Some(Type::String),
+            "integer" => Some(Type::Integer),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            Type::Null => "null",
+            Type::Boolean => "boolean",
+            Type::Object => "object",
+            Type::Array => "array",
+            Type::Number => "number",
+            Type::String => "string",
+            Type::Integer => "integer",
+        }
+    }
+
+    // Returns whether `node` is an instance of this type.
+    fn matches(&self, node: &Document) -> bool {
+        match self {
+            Type::Null => matches!(node, Document::Null),
+            Type::Boolean => matches!(node, Document::Boolean(_)),
+            Type::Object => matches!(node, Document::Mapping(_)),
+            Type::Array => matches!(node, Document::Sequence(_) | Document::Bytes(_)),
+            Type::Number => Number::of(node).is_some(),
+            Type::String => string(node).is_some(),
+            Type::Integer => Number::of(node).is_some_and(|n| n.is_integer()),
+        }
+    }
+}
+
+/// A numeric value, which may come from an `Int`, a `Float` or a string
+/// holding an integer too large for a 64-bit integer.
+#[derive(Clone, Copy, Debug)]
+enum Number {
+    Int(i128),
+    // An integer larger than `i128::MAX`.
+    U128(u128),
+    Float(f64),
+}
+
+impl Number {
+    fn of(node: &Document) -> Option<Self> {
+        match node {
+            Document::Int(i) => Self::int(i),
+            Document::Float(f) => Some(Number::Float(*f)),
+            // Integers may be emitted as strings by serializers which
+            // can't represent them as numbers.
+            Document::String(s, _) => Self::parse(s),
+            Document::Str(s, _) => Self::parse(s),
+            _ => None,
+        }
+    }
+
+    fn int(i: &Int) -> Option<Self> {
+        i.checked::<i128>()
+            .map(Number::Int)
+            .or_else(|| i.checked::<u128>().map(Number::U128))
+    }
+
+    // Emitters quote integers which a json number can't hold exactly, so any
+    // string holding an integer is read as one.
+    fn parse(s: &str) -> Option<Self> {
+        Int::from_str_radix(s, 0).ok().and_then(|i| Self::int(&i))
+    }
+
+    fn is_integer(&self) -> bool {
+        match self {
+            Number::Int(_) | Number::U128(_) => true,
+            Number::Float(f) => f.fract() == 0.0,
+        }
+    }
+
+    fn as_f64(&self) -> f64 {
+        match self {
+            Number::Int(i) => *i as f64,
+            Number::U128(u) => *u as f64,
+            Number::Float(f) => *f,
+        }
+    }
+
+    fn compare(&self, other: &Number) -> Option<Ordering> {
+        match (self, other) {
+            (Number::Int(a), Number::Int(b)) => Some(a.cmp(b)),
+            (Number::U128(a), Number::U128(b)) => Some(a.cmp(b)),
+            // A `U128` is larger than any `Int`.
+            (Number::U128(_), Number::Int(_)) => Some(Ordering::Greater),
+            (Number::Int(_), Number::U128(_)) => Some(Ordering::Less),
+            (a, b) => a.as_f64().partial_cmp(&b.as_f64()),
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Number::Int(i) => write!(f, "{}", i),
+            Number::U128(u) => write!(f, "{}", u),
+            Number::Float(v) => write!(f, "{}", v),
+        }
+    }
+}
+
+// Returns the json type name of `node`.
+fn type_of(node: &Document) -> &'static str {
+    match node {
+        Document::Null => "null",
+        Document::Boolean(_) => "boolean",
+        Document::Int(_) => "integer",
+        Document::Float(_) => "number",
+        Document::String(_, _) | Document::Str(_, _) => "string",
+        Document::Sequence(_) | Document::Bytes(_) => "array",
+        Document::Mapping(_) => "object",
+        _ => node.variant(),
+    }
+}
+
+fn string<'a>(node: &'a Document) -> Option<&'a str> {
+    match node {
+        Document::String(s, _) => Some(s.as_str()),
+        Document::Str(s, _) => Some(s),
+        _ => None,
+    }
+}
+
+// Returns the value of `node`, along with its source location if the
+// parser recorded one.
+fn value<'a, 'b>(node: &'a Document<'b>) -> Result<(&'a Document<'b>, Option<Span>)> {
+    let span = node.span();
+    let mut node = node.as_value()?;
+    while let Document::Spanned(d, _) | Document::Compact(d) = node {
+        node = d.as_value()?;
+    }
+    Ok((node, span))
+}
+
+// Returns the value-containing entries of a mapping as (key, value) pairs.
+fn entries<'a, 'b>(
+    mapping: &'a [Document<'b>],
+) -> impl Iterator<Item = (&'a Document<'b>, &'a Document<'b>)> {
+    mapping.iter().filter_map(|e| e.as_kv().ok())
+}
+
+// Returns the name of a mapping key.
+fn key_name(key: &Document) -> String {
+    match value(key) {
+        Ok((Document::String(s, _), _)) => s.clone(),
+        Ok((Document::Str(s, _), _)) => s.to_string(),
+        Ok((Document::Int(i), _)) => i128::from(i).to_string(),
+        Ok((Document::Boolean(b), _)) => b.to_string(),
+        Ok((k, _)) => format!("<{}>", k.variant()),
+        Err(_) => "?".into(),
+    }
+}
+
+// Returns whether two nodes hold equal values.  Integers are compared by
+// value regardless of their base.
+fn equal(a: &Document, b: &Document) -> bool {
+    let (Ok((a, _)), Ok((b, _))) = (value(a), value(b)) else {
+        return false;
+    };
+    if let (Some(a), Some(b)) = (string(a), string(b)) {
+        return a == b;
+    }
+    if let (Some(a), Some(b)) = (Number::of(a), Number::of(b)) {
+        return a.compare(&b) == Some(Ordering::Equal);
+    }
+    match (a, b) {
+        (Document::Null, Document::Null) => true,
+        (Document::Boolean(a), Document::Boolean(b)) => a == b,
+        (Document::Sequence(a), Document::Sequence(b)) => {
+            let a = a.iter().filter(|n| n.has_value()).collect::<Vec<_>>();
+            let b = b.iter().filter(|n| n.has_value()).collect::<Vec<_>>();
+            a.len() == b.len() && a.iter().zip(b).all(|(a, b)| equal(a, b))
+        }
+        (Document::Bytes(a), Document::Bytes(b)) => a == b,
+        (Document::Mapping(a), Document::Mapping(b)) => {
+            entries(a).count() == entries(b).count()
+                && entries(a).all(|(ka, va)| {
+                    let name = key_name(ka);
+                    entries(b).any(|(kb, vb)| key_name(kb) == name && equal(va, vb))
+                })
+        }
+        _ => false,
+    }
+}
+
+/// A `Schema` validates `Document`s against a JSON Schema.
+///
+/// A subset of the 2020-12 draft is supported: the `type`, `properties`,
+/// `required`, `items`, `enum`, `minimum`, `maximum` and `pattern`
+/// keywords.  Other keywords are ignored.
+///
+/// Documents written for humans often express integers in hex, binary or
+/// octal, and json emitters quote integers too large for a json number.  Such
+/// nodes, and strings holding an integer, are treated as integers, so they
+/// satisfy `"type": "integer"` and are checked against `minimum` and
+/// `maximum`.
+#[derive(Debug, Default)]
+pub struct Schema {
+    // A `false` schema, which no value satisfies.
+    reject: bool,
+    types: Option<Vec<Type>>,
+    properties: Vec<(String, Schema)>,
+    required: Vec<String>,
+    items: Option<Box<Schema>>,
+    enumeration: Option<Vec<Document<'static>>>,
+    minimum: Option<Number>,
+    maximum: Option<Number>,
+    pattern: Option<Regex>,
+}
+
+impl Schema {
+    /// Parses a schema from text in any of the formats accepted by
+    /// `Document::parse`.
+    pub fn parse(text: &str) -> Result<Self> {
+        Self::from_document(&Document::parse(text)?)
+    }
+
+    /// Creates a schema from a parsed JSON Schema document.
+    pub fn from_document(doc: &Document) -> Result<Self> {
+        Self::compile(doc, &mut Vec::new())
+    }
+
+    fn compile(doc: &Document, path: &mut Vec<OwnedDocPath>) -> Result<Self> {
+        let invalid =
+            |path: &[OwnedDocPath], msg: String| Error::InvalidSchema(msg).at_path(path.to_vec());
+        let mapping = match value(doc)?.0 {
+            Document::Boolean(b) => {
+                return Ok(Schema {
+                    reject: !b,
+                    ..Default::default()
+                })
+            }
+            Document::Mapping(m) => m,
+            d => {
+                return Err(invalid(
+                    path,
+                    format!("expected a schema but got {}", d.variant()),
+                ))
+            }
+        };
+        let mut schema = Schema::default();
+        for (k, v) in entries(mapping) {
+            let keyword = key_name(k);
+            path.push(OwnedDocPath::Name(keyword.clone()));
+            let v = value(v)?.0;
+            match keyword.as_str() {
+                "type" => {
+                    let names = match v {
+                        Document::Sequence(s) => s.iter().map(value).collect::<Result<Vec<_>>>()?,
+                        _ => vec![(v, None)],
+                    };
+                    let types = names
+                        .into_iter()
+                        .map(|(n, _)| string(n).and_then(Type::from_name))
+                        .collect::<Option<Vec<_>>>()
+                        .ok_or_else(|| invalid(path, "unknown type".into()))?;
+                    schema.types = Some(types);
+                }
+                "properties" => {
+                    let Document::Mapping(props) = v else {
+                        return Err(invalid(path, "expected a mapping".into()));
+                    };
+                    for (name, prop) in entries(props) {
+                        let name = key_name(name);
+                        path.push(OwnedDocPath::Name(name.clone()));
+                        let prop = Self::compile(prop, path)?;
+                        path.pop();
+                        schema.properties.push((name, prop));
+                    }
+                }
+                "required" => {
+                    let Document::Sequence(names) = v else {
+                        return Err(invalid(path, "expected a sequence".into()));
+                    };
+                    for name in names.iter().filter(|n| n.has_value()) {
+                        let name = string(value(name)?.0)
+                            .ok_or_else(|| invalid(path, "expected a property name".into()))?;
+                        schema.required.push(name.into());
+                    }
+                }
+                "items" => schema.items = Some(Box::new(Self::compile(v, path)?)),
+                "enum" => {
+                    let Document::Sequence(values) = v else {
+                        return Err(invalid(path, "expected a sequence".into()));
+                    };
+                    schema.enumeration = Some(
+                        values
+                            .iter()
+                            .filter(|n| n.has_value())
+                            .map(|n| n.clone().into_owned())
+                            .collect(),
+                    );
+                }
+                "minimum" | "maximum" => {
+                    let n =
+                        Number::of(v).ok_or_else(|| invalid(path, "expected a number".into()))?;
+                    if keyword == "minimum" {
+                        schema.minimum = Some(n);
+                    } else {
+                        schema.maximum = Some(n);
+                    }
+                }
+                "pattern" => {
+                    let pattern =
+                        string(v).ok_or_else(|| invalid(path, "expected a string".into()))?;
+                    let re = Regex::new(pattern).map_err(|e| invalid(path, e.to_string()))?;
+                    schema.pattern = Some(re);
+                }
+                _ => {}
+            }
+            path.pop();
+        }
+        Ok(schema)
+    }
+
+    /// Validates `doc` against the schema, returning every violation found.
+    /// Each violation carries the document path of the offending node and,
+    /// if the document was parsed with source spans, its location.
+    pub fn validate(&self, doc: &Document) -> std::result::Result<(), Vec<Error>> {
+        let mut errors = Vec::new();
+        self.check(doc, &mut Vec::new(), &mut errors);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn check(&self, doc: &Document, path: &mut Vec<OwnedDocPath>, errors: &mut Vec<Error>) {
+        let (node, span) = match value(doc) {
+            Ok(v) => v,
+            Err(e) => return errors.push(e),
+        };
+        let mut violation = |msg: String| {
+            let mut err = Error::SchemaViolation(msg).at_path(path.clone());
+            if let Some(span) = span {
+                let location = Location {
+                    file: None,
+                    line: span.line,
+                    col: span.col,
+                    excerpt: None,
+                };
+                err = Error::Located(Box::new(err), location);
+            }
+            errors.push(err);
+        };
+
+        if self.reject {
+            violation("no value is permitted here".into());
+            return;
+        }
+        if let Some(types) = &self.types {
+            if !types.iter().any(|t| t.matches(node)) {
+                let names = types.iter().map(Type::name).collect::<Vec<_>>();
+                let expected = match names.as_slice() {
+                    [name] => name.to_string(),
+                    _ => format!("one of {}", names.join(", ")),
+                };
+                violation(format!("expected {} but got {}", expected, type_of(node)));
+            }
+        }
+        if let Some(values) = &self.enumeration {
+            if !values.iter().any(|v| equal(v, node)) {
+                violation("value is not one of the permitted values".into());
+            }
+        }
+        if let Some(n) = Number::of(node) {
+            if let Some(min) = &self.minimum {
+                if n.compare(min) == Some(Ordering::Less) {
+                    violation(format!("{} is less than the minimum {}", n, min));
+                }
+            }
+            if let Some(max) = &self.maximum {
+                if n.compare(max) == Some(Ordering::Greater) {
+                    violation(format!("{} is greater than the maximum {}", n, max));
+                }
+            }
+        }
+        if let (Some(re), Some(s)) = (&self.pattern, string(node)) {
+            if !re.is_match(s) {
+                violation(format!(
+                    "{:?} does not match the pattern {:?}",
+                    s,
+                    re.as_str()
+                ));
+            }
+        }
+        match node {
+            Document::Mapping(m) => {
+                for name in &self.required {
+                    if !entries(m).any(|(k, _)| key_name(k) == *name) {
+                        violation(format!("missing required property `{}`", name));
+                    }
+                }
+                for (k, v) in entries(m) {
+                    let name = key_name(k);
+                    if let Some((_, prop)) = self.properties.iter().find(|(n, _)| *n == name) {
+                        path.push(OwnedDocPath::Name(name));
+                        prop.check(v, path, errors);
+                        path.pop();
+                    }
+                }
+            }
+            Document::Sequence(s) => {
+                if let Some(items) = &self.items {
+                    for (i, v) in s.iter().filter(|n| n.has_value()).enumerate() {
+                        path.push(OwnedDocPath::Index(i));
+                        items.check(v, path, errors);
+                        path.pop();
+                    }
+                }
+            }
+            Document::Bytes(b) => {
+                if let Some(items) = &self.items {
+                    for (i, v) in b.iter().enumerate() {
+                        path.push(OwnedDocPath::Index(i));
+                        items.check(&Document::Int((*v).into()), path, errors);
+                        path.pop();
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+// Returns a mapping entry for a schema keyword.
+fn keyword(name: &'static str, value: Document<'static>) -> Document<'static> {
+    Document::Fragment(vec![Document::from(name), value])
+}
+
+fn type_name(name: &'static str) -> Document<'static> {
+    keyword("type", Document::from(name))
+}
+
+// Returns the name of a mapping entry created by `keyword`.
+fn keyword_name<'a>(node: &'a Document) -> Option<&'a str> {
+    match node {
+        Document::Fragment(f) => f.first().and_then(string),
+        _ => None,
+    }
+}
+
+fn description(text: String) -> Document<'static> {
+    keyword("description", Document::String(text, StrFormat::Standard))
+}
+
+// Returns the entry of the `properties` mapping describing the property
+// `name`.  The description is also emitted as a comment on the property.
+fn property(
+    name: String,
+    comment: Option<String>,
+    mut schema: Vec<Document<'static>>,
+) -> Document<'static> {
+    let mut prop = Vec::new();
+    if let Some(comment) = comment {
+        schema.retain(|n| keyword_name(n) != Some("description"));
+        schema.insert(0, description(comment.clone()));
+        prop.push(Document::Comment(comment, CommentFormat::Standard));
+    }
+    prop.push(Document::String(name, StrFormat::Standard));
+    prop.push(if schema.is_empty() {
+        Document::Compact(Document::Mapping(schema).into())
+    } else {
+        Document::Mapping(schema)
+    });
+    Document::Fragment(prop)
+}
+
+fn compact_list(items: Vec<Document<'static>>) -> Document<'static> {
+    Document::Compact(Document::Sequence(items).into())
+}
+
+impl Schema {
+    /// Describes the sample `value` as a JSON Schema.
+    ///
+    /// The schema is derived from the annotated document that `serialize`
+    /// would produce for `value`: field comments become `description`s
+    /// (and comments in the schema itself), and integers annotated as `hex`,
+    /// `bin` or `oct` carry a matching `format` and also permit strings in
+    /// that form, since json cannot express them as literals.
+    ///
+    /// The schema describes the value, not its type, so it is a starting
+    /// point rather than the schema of `T`: `None` fields are permitted but
+    /// neither required nor typed, sequences are described by their first
+    /// element and only the variant present in the sample is described for
+    /// enums.  Use `describe_type` to describe `T` itself.
+    pub fn describe_value<T: ?Sized + serde::Serialize>(value: &T) -> Result<Document<'static>> {
+        let doc = crate::ser::serialize(value)?;
+        let mut nodes = Self::header::<T>();
+        nodes.extend(Self::describe(&doc)?);
+        Ok(Document::Mapping(nodes))
+    }
+
+    /// Describes the type `T` as a JSON Schema.
+    ///
+    /// The shape of `T` is traced through its `Deserialize` implementation,
+    /// so every field and enum variant is described, optional fields (e.g.
+    /// `Option`s and `#[serde(default)]` fields) are not required and
+    /// recursive types refer back to themselves with `$ref`.  The comments
+    /// and formats of `#[derive(Annotate)]` which don't depend on the value
+    /// are used as for `describe_value`.
+    ///
+    /// Only types which deserialize without looking at the document can be
+    /// traced: untagged and internally tagged enums, flattened fields and
+    /// types which reject the zero or empty value of their contents
+    /// (e.g. `NonZeroU32`) return an error.
+    pub fn describe_type<'de, T: serde::Deserialize<'de>>() -> Result<Document<'static>> {
+        let trace = crate::trace::trace::<T>()?;
+        let mut describer = Describer {
+            containers: &trace.containers,
+            stack: Vec::new(),
+        };
+        let mut nodes = Self::header::<T>();
+        nodes.extend(describer.shape(&trace.root, Formats::default(), "#")?);
+        Ok(Document::Mapping(nodes))
+    }
+
+    // Returns the `$schema` and `title` keywords of a schema for `T`.
+    fn header<T: ?Sized>() -> Vec<Document<'static>> {
+        let name = std::any::type_name::<T>();
+        let name = name.split('<').next().unwrap_or(name);
+        let name = name.rsplit("::").next().unwrap_or(name);
+        vec![
+            keyword(
+                "$schema",
+                Document::from("https://json-schema.org/draft/2020-12/schema"),
+            ),
+            keyword("title", Document::String(name.into(), StrFormat::Standard)),
+        ]
+    }
+
+    // Returns the schema keywords describing `node`.
+    fn describe(node: &Document) -> Result<Vec<Document<'static>>> {
+        let (node, _) = value(node)?;
+        let nodes = match node {
+            Document::Null => Vec::new(),
+            Document::Boolean(_) => vec![type_name("boolean")],
+            Document::Float(_) => vec![type_name("number")],
+            Document::String(_, _) | Document::Str(_, _) => vec![type_name("string")],
+            Document::Int(i) => Self::describe_int(i),
+            Document::Bytes(_) => {
+                let byte = Int::from(0u8);
+                vec![
+                    type_name("array"),
+                    keyword("items", Document::Mapping(Self::describe_int(&byte))),
+                ]
+            }
+            Document::Sequence(s) => {
+                let mut nodes = vec![type_name("array")];
+                if let Some(first) = s.iter().find(|n| n.has_value()) {
+                    let items = Self::describe(first)?;
+                    nodes.push(keyword("items", Document::Mapping(items)));
+                }
+                nodes
+            }
+            Document::Mapping(m) => {
+                let mut properties = Vec::new();
+                let mut required = Vec::new();
+                for entry in m {
+                    let Ok((k, v)) = entry.as_kv() else {
+                        continue;
+                    };
+                    let name = key_name(k);
+                    let comments = entry
+                        .fragments()?
+                        .iter()
+                        .take_while(|n| !n.has_value())
+                        .filter_map(Document::comment)
+                        .map(|(c, _)| c)
+                        .collect::<Vec<_>>();
+                    let description = (!comments.is_empty()).then(|| comments.join("\n"));
+                    if !matches!(value(v)?.0, Document::Null) {
+                        required.push(Document::String(name.clone(), StrFormat::Standard));
+                    }
+                    properties.push(property(name, description, Self::describe(v)?));
+                }
+                vec![
+                    type_name("object"),
+                    keyword("properties", Document::Mapping(properties)),
+                    keyword(
+                        "required",
+                        Document::Compact(Document::Sequence(required).into()),
+                    ),
+                ]
+            }
+            _ => return Err(Error::StructureError("a value", node.variant())),
+        };
+        Ok(nodes)
+    }
+
+    fn describe_int(i: &Int) -> Vec<Document<'static>> {
+        let (format, pattern) = match i.base() {
+            Base::Dec => ("", ""),
+            Base::Hex => ("hex", "^[+-]?0[xX][0-9A-Fa-f]+(_[0-9A-Fa-f]+)*$"),
+            Base::Bin => ("bin", "^[+-]?0[bB][01]+(_[01]+)*$"),
+            Base::Oct => ("oct", "^[+-]?0[oO][0-7]+(_[0-7]+)*$"),
+        };
+        let mut nodes = Vec::new();
+        if format.is_empty() {
+            nodes.push(type_name("integer"));
+        } else {
+            let types = vec![Document::from("integer"), Document::from("string")];
+            nodes.push(keyword(
+                "type",
+                Document::Compact(Document::Sequence(types).into()),
+            ));
+            nodes.push(keyword("format", Document::from(format)));
+            nodes.push(keyword("pattern", Document::from(pattern)));
+        }
+        if let Some((min, max)) = i.limits() {
+            // Bounds are plain decimal json numbers.  The json emitter quotes
+            // numbers a json number can't hold exactly, so such bounds (e.g.
+            // those of a `u64`) are left out.
+            for (name, bound) in [("minimum", min), ("maximum", max)] {
+                let bound = Int::from(bound);
+                if bound.is_legal_json() {
+                    nodes.push(keyword(name, Document::Int(bound)));
+                }
+            }
+        }
+        nodes
+    }
+}
+
+// The formats which an annotated member passes on to the values within it.
+#[derive(Clone, Copy)]
+struct Formats {
+    base: Base,
+    bytes: BytesFormat,
+}
+
+impl Default for Formats {
+    fn default() -> Self {
+        Formats {
+            base: Base::Dec,
+            bytes: BytesFormat::Standard,
+        }
+    }
+}
+
+impl Formats {
+    // Applies the format annotated on `field`, as `AnnotatedSerializer` does.
+    fn annotated(
+        self,
+        annotator: Option<&dyn Annotate>,
+        variant: Option<&str>,
+        field: &MemberId,
+    ) -> Self {
+        let format = annotator.and_then(|a| {
+            a.format(variant, field).or_else(|| match field {
+                MemberId::Name(_) | MemberId::Index(_) => a.format(None, &MemberId::Container),
+                _ => None,
+            })
+        });
+        let mut formats = self;
+        match format {
+            Some(Format::Binary) => formats.base = Base::Bin,
+            Some(Format::Decimal) => formats.base = Base::Dec,
+            Some(Format::Hex) => formats.base = Base::Hex,
+            Some(Format::Octal) => formats.base = Base::Oct,
+            Some(Format::HexStr) => formats.bytes = BytesFormat::HexStr,
+            Some(Format::Hexdump) => formats.bytes = BytesFormat::Hexdump,
+            Some(Format::Xxd) => formats.bytes = BytesFormat::Xxd,
+            _ => {}
+        }
+        formats
+    }
+}
+
+// Renders the schema of a traced type.
+struct Describer<'t> {
+    containers: &'t HashMap<&'static str, Container>,
+    // The named types being described and the JSON pointers to their schemas.
+    stack: Vec<(&'static str, String)>,
+}
+
+impl Describer<'_> {
+    // Returns the schema keywords describing `shape`, the schema of which
+    // is at `pointer`.
+    fn shape(
+        &mut self,
+        shape: &Shape,
+        formats: Formats,
+        pointer: &str,
+    ) -> Result<Vec<Document<'static>>> {
+        let nodes = match shape {
+            Shape::Bool => vec![type_name("boolean")],
+            Shape::Int(v) => Schema::describe_int(&Int::new(v.clone(), formats.base)),
+            Shape::Float => vec![type_name("number")],
+            Shape::Char | Shape::Str => vec![type_name("string")],
+            Shape::Bytes if formats.bytes == BytesFormat::Standard => {
+                let byte = Int::from(0u8);
+                vec![
+                    type_name("array"),
+                    keyword("items", Document::Mapping(Schema::describe_int(&byte))),
+                ]
+            }
+            Shape::Bytes => vec![type_name("string")],
+            Shape::Unit => vec![type_name("null")],
+            Shape::Option(inner) => {
+                let nodes = self.shape(inner, formats, pointer)?;
+                if nodes.iter().any(|n| keyword_name(n) == Some("type")) {
+                    nullable(nodes)
+                } else {
+                    let inner = self.shape(inner, formats, &format!("{}/anyOf/0", pointer))?;
+                    let null = Document::Mapping(vec![type_name("null")]);
+                    vec![keyword(
+                        "anyOf",
+                        Document::Sequence(vec![Document::Mapping(inner), null]),
+                    )]
+                }
+            }
+            Shape::Seq(item) => {
+                let items = self.shape(item, formats, &format!("{}/items", pointer))?;
+                vec![
+                    type_name("array"),
+                    keyword("items", Document::Mapping(items)),
+                ]
+            }
+            Shape::Tuple(shapes) => self.tuple(shapes, None, None, formats, pointer)?,
+            Shape::Map(_, value) => {
+                let values =
+                    self.shape(value, formats, &format!("{}/additionalProperties", pointer))?;
+                vec![
+                    type_name("object"),
+                    keyword("additionalProperties", Document::Mapping(values)),
+                ]
+            }
+            Shape::Named(name) => self.named(name, formats, pointer)?,
+        };
+        Ok(nodes)
+    }
+
+    fn named(
+        &mut self,
+        name: &'static str,
+        formats: Formats,
+        pointer: &str,
+    ) -> Result<Vec<Document<'static>>> {
+        if let Some((_, target)) = self.stack.iter().find(|(n, _)| *n == name) {
+            let target = Document::String(target.clone(), StrFormat::Standard);
+            return Ok(vec![keyword("$ref", target)]);
+        }
+        let container = self
+            .containers
+            .get(name)
+            .ok_or_else(|| Error::Unknown(format!("no shape was traced for `{}`", name)))?;
+        let annotator = Annotator::find_static(name);
+        self.stack.push((name, pointer.to_string()));
+        let nodes = self.container(container, annotator, None, formats, pointer);
+        self.stack.pop();
+        let mut nodes = nodes?;
+        if let Some(comment) = annotator.and_then(|a| a.comment(None, &MemberId::Container)) {
+            nodes.insert(0, description(comment));
+        }
+        Ok(nodes)
+    }
+
+    // Describes a named type, or the variant `variant` of an enum.
+    fn container(
+        &mut self,
+        container: &Container,
+        annotator: Option<&dyn Annotate>,
+        variant: Option<&str>,
+        formats: Formats,
+        pointer: &str,
+    ) -> Result<Vec<Document<'static>>> {
+        match container {
+            Container::Unit => Ok(vec![type_name("null")]),
+            Container::Newtype(shape) => {
+                let formats = formats.annotated(annotator, variant, &MemberId::Index(0));
+                self.shape(shape, formats, pointer)
+            }
+            Container::Tuple(shapes) => self.tuple(shapes, annotator, variant, formats, pointer),
+            Container::Struct(fields) => {
+                let mut properties = Vec::new();
+                let mut required = Vec::new();
+                for f in fields {
+                    let field = MemberId::Name(f.name);
+                    let formats = formats.annotated(annotator, variant, &field);
+                    let pointer = format!("{}/properties/{}", pointer, escape(f.name));
+                    let schema = self.shape(&f.shape, formats, &pointer)?;
+                    let comment = annotator.and_then(|a| a.comment(variant, &field));
+                    properties.push(property(f.name.into(), comment, schema));
+                    if f.required {
+                        required.push(Document::from(f.name));
+                    }
+                }
+                Ok(vec![
+                    type_name("object"),
+                    keyword("properties", Document::Mapping(properties)),
+                    keyword("required", compact_list(required)),
+                ])
+            }
+            Container::Enum(variants) => self.variants(variants, annotator, formats, pointer),
+        }
+    }
+
+    fn tuple(
+        &mut self,
+        shapes: &[Shape],
+        annotator: Option<&dyn Annotate>,
+        variant: Option<&str>,
+        formats: Formats,
+        pointer: &str,
+    ) -> Result<Vec<Document<'static>>> {
+        let mut nodes = vec![type_name("array")];
+        if annotator.is_none() && shapes.windows(2).all(|w| w[0] == w[1]) {
+            // Arrays and tuples of a single type.
+            if let Some(shape) = shapes.first() {
+                let items = self.shape(shape, formats, &format!("{}/items", pointer))?;
+                nodes.push(keyword("items", Document::Mapping(items)));
+            }
+        } else {
+            let mut items = Vec::new();
+            for (i, shape) in shapes.iter().enumerate() {
+                let field = MemberId::Index(i as u32);
+                let formats = formats.annotated(annotator, variant, &field);
+                let item = self.shape(shape, formats, &format!("{}/prefixItems/{}", pointer, i))?;
+                let item = Document::Mapping(item);
+                items.push(match annotator.and_then(|a| a.comment(variant, &field)) {
+                    Some(c) => Document::Fragment(vec![
+                        Document::Comment(c, CommentFormat::Standard),
+                        item,
+                    ]),
+                    None => item,
+                });
+            }
+            nodes.push(keyword("prefixItems", Document::Sequence(items)));
+        }
+        let len = Document::Int(Int::from(shapes.len() as u64));
+        nodes.push(keyword("minItems", len.clone()));
+        nodes.push(keyword("maxItems", len));
+        Ok(nodes)
+    }
+
+    // Describes the variants of an externally tagged enum.
+    fn variants(
+        &mut self,
+        variants: &[Variant],
+        annotator: Option<&dyn Annotate>,
+        formats: Formats,
+        pointer: &str,
+    ) -> Result<Vec<Document<'static>>> {
+        let comments = variants
+            .iter()
+            .map(|v| annotator.and_then(|a| a.comment(Some(v.name), &MemberId::Variant)))
+            .collect::<Vec<_>>();
+        let unit = variants.iter().all(|v| v.shape == Some(Container::Unit));
+        if unit && comments.iter().all(Option::is_none) {
+            let names = variants.iter().map(|v| Document::from(v.name)).collect();
+            return Ok(vec![
+                type_name("string"),
+                keyword("enum", compact_list(names)),
+            ]);
+        }
+        let mut schemas = Vec::new();
+        for (i, (v, comment)) in variants.iter().zip(comments).enumerate() {
+            let pointer = format!("{}/oneOf/{}", pointer, i);
+            let mut schema = match &v.shape {
+                Some(Container::Unit) => vec![
+                    type_name("string"),
+                    keyword("enum", compact_list(vec![Document::from(v.name)])),
+                ],
+                Some(c) => {
+                    let inner = format!("{}/properties/{}", pointer, escape(v.name));
+                    let content = self.container(c, annotator, Some(v.name), formats, &inner)?;
+                    vec![
+                        type_name("object"),
+                        keyword(
+                            "properties",
+                            Document::Mapping(vec![property(v.name.into(), None, content)]),
+                        ),
+                        keyword("required", compact_list(vec![Document::from(v.name)])),
+                        keyword("additionalProperties", Document::Boolean(false)),
+                    ]
+                }
+                None => {
+                    return Err(Error::Unknown(format!(
+                        "variant `{}` was not traced",
+                        v.name
+                    )))
+                }
+            };
+            schemas.push(match comment {
+                Some(c) => {
+                    schema.insert(0, description(c.clone()));
+                    Document::Fragment(vec![
+                        Document::Comment(c, CommentFormat::Standard),
+                        Document::Mapping(schema),
+                    ])
+                }
+                None => Document::Mapping(schema),
+            });
+        }
+        Ok(vec![keyword("oneOf", Document::Sequence(schemas))])
+    }
+}
+
+// Permits `null` in addition to the types and values permitted by the
+// schema `nodes`.
+fn nullable(nodes: Vec<Document<'static>>) -> Vec<Document<'static>> {
+    nodes
+        .into_iter()
+        .map(|node| {
+            let (name, null) = match keyword_name(&node) {
+                Some("type") => ("type", Document::from("null")),
+                Some("enum") => ("enum", Document::Null),
+                _ => return node,
+            };
+            let mut values = match node {
+                Document::Fragment(mut f) => match f.pop() {
+                    Some(Document::Compact(d)) => match *d {
+                        Document::Sequence(s) => s,
+                        d => vec![d],
+                    },
+                    Some(d) => vec![d],
+                    None => Vec::new(),
+                },
+                _ => Vec::new(),
+            };
+            values.push(null);
+            keyword(name, compact_list(values))
+        })
+        .collect()
+}
+
+// Escapes `name` for use in a JSON pointer.
+fn escape(name: &str) -> String {
+    name.replace('~', "~0").replace('/', "~1")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::doc_iter::DocPath;
+    use crate::relax::Relax;
+    use anyhow::Result;
+
+    const SCHEMA: &str = r#"{
+        "type": "object",
+        "required": ["name", "registers"],
+        "properties": {
+            "name": {"type": "string", "pattern": "^[a-z_]+$"},
+            "mode": {"enum": ["fast", "slow", 3]},
+            "registers": {
+                "type": "array",
+                "items": {
+                    "type": "object",
+                    "required": ["offset"],
+                    "properties": {
+                        "offset": {"type": "integer", "minimum": 0, "maximum": 255},
+                        "reset": {"type": ["integer", "null"]}
+                    }
+                }
+            }
+        }
+    }"#;
+
+    fn violations(text: &str) -> Result<Vec<String>> {
+        let schema = Schema::parse(SCHEMA)?;
+        let doc = Document::parse(text)?;
+        Ok(match schema.validate(&doc) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(Error::to_string).collect(),
+        })
+    }
+
+    #[test]
+    fn test_valid() -> Result<()> {
+        let text = r#"
+        {
+            name: uart_ctrl
+            mode: 3
+            registers: [
+                {offset: 0x10, reset: "0xFFFFFFFFFFFFFFFFFFFF"}
+                {offset: 0b1, reset: null}
+                {offset: 255, reset: "340282366920938463463374607431768211455"}
+                {offset: "255", reset: "1152921504606846976"}
+            ]
+        }"#;
+        assert!(violations(text)?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_violations() -> Result<()> {
+        let text = r#"
+        {
+            name: "Bad Name"
+            mode: "medium"
+            registers: [
+                {offset: 0x100}
+                {offset: -1, reset: true}
+                {reset: 1.5}
+                {offset: "ff"}
+            ]
+        }"#;
+        assert_eq!(
+            violations(text)?,
+            vec![
+                r#"at name: schema violation: "Bad Name" does not match the pattern "^[a-z_]+$""#,
+                "at mode: schema violation: value is not one of the permitted values",
+                "at registers[0].offset: schema violation: 256 is greater than the maximum 255",
+                "at registers[1].offset: schema violation: -1 is less than the minimum 0",
+                "at registers[1].reset: schema violation: expected one of integer, null but got boolean",
+                "at registers[2]: schema violation: missing required property `offset`",
+                "at registers[2].reset: schema violation: expected one of integer, null but got number",
+                "at registers[3].offset: schema violation: expected integer but got string",
+            ]
+        );
+        assert_eq!(
+            violations("[]")?,
+            vec!["schema violation: expected object but got array"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_large_integers() -> Result<()> {
+        let schema = Schema::parse(r#"{"minimum": 0, "maximum": 1}"#)?;
+        let max = Document::parse("340282366920938463463374607431768211455")?;
+        assert_eq!(
+            schema.validate(&max).unwrap_err()[0].to_string(),
+            "schema violation: 340282366920938463463374607431768211455 is greater than the maximum 1"
+        );
+        // Strings which hold no integer in the 128-bit range are strings.
+        for text in [
+            r#""-170141183460469231731687303715884105729""#,
+            r#""-340282366920938463463374607431768211455""#,
+        ] {
+            assert!(schema.validate(&Document::parse(text)?).is_ok());
+        }
+        let min = Document::parse(r#""-170141183460469231731687303715884105728""#)?;
+        assert_eq!(
+            schema.validate(&min).unwrap_err()[0].to_string(),
+            "schema violation: -170141183460469231731687303715884105728 is less than the minimum 0"
+        );
+        let min = Document::parse(r#""-0x8000000000000001""#)?;
+        assert_eq!(
+            schema.validate(&min).unwrap_err()[0].to_string(),
+            "schema violation: -9223372036854775809 is less than the minimum 0"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_violation_location() -> Result<()> {
+        let schema = Schema::parse(SCHEMA)?;
+        let mut relax = Relax::default();
+        relax.spans = true;
+        let doc = relax.from_str("{\n  name: \"x\",\n  registers: [{offset: true}]\n}")?;
+        let errors = schema.validate(&doc).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        let location = errors[0].location().unwrap();
+        assert_eq!((location.line, location.col), (3, 24));
+        let path = errors[0].path().unwrap();
+        assert!(matches!(
+            path.as_slice(),
+            [
+                DocPath::Name("registers"),
+                DocPath::Index(0),
+                DocPath::Name("offset")
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_invalid_schema() {
+        let err = Schema::parse(r#"{"properties": {"a": {"type": "int"}}}"#).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at properties.a.type: invalid schema: unknown type"
+        );
+        assert!(Schema::parse(r#"{"pattern": "("}"#).is_err());
+    }
+}
@@ -1,13 +1,13 @@
 use serde::ser;
 
 use crate::annotate::private::{Annotator, AnyPointer};
-use crate::annotate::{Annotate, Format, MemberId};
+use crate::annotate::{Annotate, Digits, Format, MemberId, Placement, Width};
 use crate::document::{BytesFormat, CommentFormat, Document, StrFormat};
 use crate::error::Error;
 use crate::hexdump;
-use crate::integer::{Base, Int};
+use crate::integer::{Base, Int, IntValue};
 
-pub fn serialize<T>(value: &T) -> Result<Document, Error>
+pub fn serialize<T>(value: &T) -> Result<Document<'static>, Error>
 where
     T: ?Sized + ser::Serialize,
 {
@@ -20,10 +20,12 @@ where
 pub struct AnnotatedSerializer<'a> {
     ptr: AnyPointer<'a>,
     annotator: Option<&'a dyn Annotate>,
-    base: Base,
-    strformat: StrFormat,
-    bytesformat: BytesFormat,
-    compact: bool,
+    pub(crate) base: Base,
+    width: usize,
+    group: usize,
+    pub(crate) strformat: StrFormat,
+    pub(crate) bytesformat: BytesFormat,
+    pub(crate) compact: bool,
 }
 
 impl<'a> AnnotatedSerializer<'a> {
@@ -35,6 +37,8 @@ impl<'a> AnnotatedSerializer<'a> {
             ptr: AnyPointer::new(object),
             annotator: None,
             base: Base::Dec,
+            width: 0,
+            group: 0,
             strformat: StrFormat::Standard,
             bytesformat: BytesFormat::Standard,
             compact: false,
@@ -47,6 +51,17 @@ impl<'a> AnnotatedSerializer<'a> {
         x
     }
 
+    fn with_digits(&self, d: Digits) -> Self {
+        let mut x = self.clone();
+        x.width = match d.width {
+            Some(Width::Digits(n)) => n,
+            Some(Width::Natural) => usize::MAX,
+            None => 0,
+        };
+        x.group = d.group.unwrap_or(0);
+        x
+    }
+
     fn with_bytesformat(&self, b: BytesFormat) -> Self {
         let mut x = self.clone();
         x.bytesformat = b;
@@ -65,8 +80,30 @@ impl<'a> AnnotatedSerializer<'a> {
         x
     }
 
-    fn annotate(&self, variant: Option<&str>, field: &MemberId) -> Option<Self> {
-        match self.annotator.and_then(|a| a.format(variant, field)) {
+    // Looks up the annotations for the named type being serialized.  Every
+    // serializer method for a named type goes through here: `self.ptr`
+    // points to the value being serialized, which `Annotator::find` casts to
+    // the registered type under the lifetime contract of `Annotator::new`
+    // (or the check made by `caster!`).
+    pub(crate) fn cast(&mut self, name: &str) {
+        self.annotator = Annotator::find(name, &self.ptr);
+    }
+
+    pub(crate) fn annotate(&self, variant: Option<&str>, field: &MemberId) -> Option<Self> {
+        let format = self.annotator.and_then(|a| {
+            a.format(variant, field).or_else(|| match field {
+                // Fields without a format of their own take the container's.
+                MemberId::Name(_) | MemberId::Index(_) => a.format(None, &MemberId::Container),
+                _ => None,
+            })
+        });
+        let digits = self.annotator.and_then(|a| {
+            a.digits(variant, field).or_else(|| match field {
+                MemberId::Name(_) | MemberId::Index(_) => a.digits(None, &MemberId::Container),
+                _ => None,
+            })
+        });
+        let annotated = match format {
             Some(Format::Block) => Some(self.with_strformat(StrFormat::Multiline)),
             Some(Format::Binary) => Some(self.with_base(Base::Bin)),
             Some(Format::Decimal) => Some(self.with_base(Base::Dec)),
@@ -77,28 +114,101 @@ impl<'a> AnnotatedSerializer<'a> {
             Some(Format::Hexdump) => Some(self.with_bytesformat(BytesFormat::Hexdump)),
             Some(Format::Xxd) => Some(self.with_bytesformat(BytesFormat::Xxd)),
             None => None,
+        };
+        match digits {
+            Some(d) => Some(annotated.as_ref().unwrap_or(self).with_digits(d)),
+            None => annotated,
         }
     }
 
-    fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<Document> {
-        self.annotator
-            .and_then(|a| a.comment(variant, field))
+    // Creates an `Int` in the annotated base and digits.
+    pub(crate) fn int<T: Into<IntValue>>(&self, v: T) -> Int {
+        Int::new_with_padding(v, self.base, self.width).with_grouping(self.group)
+    }
+
+    pub(crate) fn comment_text(&self, variant: Option<&str>, field: &MemberId) -> Option<String> {
+        self.annotator.and_then(|a| a.comment(variant, field))
+    }
+
+    fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<Document<'static>> {
+        self.comment_text(variant, field)
             .map(|c| Document::Comment(c, CommentFormat::Standard))
     }
 
-    fn serialize<T>(&self, value: &T, ser: Option<AnnotatedSerializer>) -> Result<Document, Error>
+    pub(crate) fn placement(&self, variant: Option<&str>, field: &MemberId) -> Placement {
+        self.annotator
+            .map(|a| a.placement(variant, field))
+            .unwrap_or_default()
+    }
+
+    // Attaches the comment on the container being serialized, if any, to
+    // its value `node`.
+    fn contained(&self, node: Document<'static>) -> Document<'static> {
+        self.commented(None, &MemberId::Container, node)
+    }
+
+    // Attaches the comment on `field`, if any, to `node`.
+    fn commented(
+        &self,
+        variant: Option<&str>,
+        field: &MemberId,
+        node: Document<'static>,
+    ) -> Document<'static> {
+        match self.comment(variant, field) {
+            Some(c) => match self.placement(variant, field) {
+                Placement::Leading => Document::Fragment(vec![c, node]),
+                Placement::Trailing => Document::Fragment(vec![node, c]),
+            },
+            None => node,
+        }
+    }
+
+    // Adds the comment on `field`, if any, to the `nodes` of a member.
+    fn add_comment(
+        &self,
+        variant: Option<&str>,
+        field: &MemberId,
+        nodes: &mut Vec<Document<'static>>,
+    ) {
+        if let Some(c) = self.comment(variant, field) {
+            match self.placement(variant, field) {
+                Placement::Leading => nodes.insert(0, c),
+                Placement::Trailing => nodes.push(c),
+            }
+        }
+    }
+
+    // Returns the serializer to use for a child `value`.  If `ser` holds
+    // the annotated format for the child, it is used in place of `self`.
+    pub(crate) fn child<'c, T>(
+        &self,
+        value: &'c T,
+        ser: Option<AnnotatedSerializer<'a>>,
+    ) -> AnnotatedSerializer<'c>
     where
         T: ?Sized + ser::Serialize,
+        'a: 'c,
     {
         let mut ser = ser.unwrap_or(self.clone());
         ser.ptr = AnyPointer::new(value);
         ser.annotator = None;
-        value.serialize(&mut ser)
+        ser
+    }
+
+    fn serialize<T>(
+        &self,
+        value: &T,
+        ser: Option<AnnotatedSerializer>,
+    ) -> Result<Document<'static>, Error>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(&mut self.child(value, ser))
     }
 }
 
 impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     type SerializeSeq = SerializeSeq<'s, 'a>;
@@ -114,43 +224,43 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
     }
 
     fn serialize_i8(self, v: i8) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_i16(self, v: i16) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_i32(self, v: i32) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_i64(self, v: i64) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_i128(self, v: i128) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_u8(self, v: u8) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_u16(self, v: u16) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_u32(self, v: u32) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_u64(self, v: u64) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_u128(self, v: u128) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Int(Int::new(v, self.base)))
+        Ok(Document::Int(self.int(v)))
     }
 
     fn serialize_f32(self, v: f32) -> Result<Self::Ok, Self::Error> {
@@ -199,8 +309,9 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         Ok(Document::Null)
     }
 
-    fn serialize_unit_struct(self, _name: &'static str) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Null)
+    fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
+        self.cast(name);
+        Ok(self.contained(Document::Null))
     }
 
     fn serialize_unit_variant(
@@ -209,17 +320,10 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         _variant_index: u32,
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         let node = self.serialize_str(variant)?;
-        // TODO(serde-annotate#6): currently, placing a comment on a unit variant results in
-        // ugly (json) or bad (yaml) documents.  For now, omit comments on
-        // unit variants until we refactor comment emitting.
-        //if let Some(c) = self.comment(Some(variant), &MemberId::Variant) {
-        //    Ok(Document::Fragment(vec![c, node]))
-        //} else {
-        //    Ok(node)
-        //}
-        Ok(node)
+        let node = self.commented(Some(variant), &MemberId::Variant, node);
+        Ok(self.contained(node))
     }
 
     fn serialize_newtype_struct<T>(
@@ -230,18 +334,11 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         let field = MemberId::Index(0);
         let node = self.serialize(value, self.annotate(None, &field))?;
-        // TODO(serde-annotate#6): currently, placing a comment on a newtype structs results in
-        // ugly (json) or bad (yaml) documents.  For now, omit comments on
-        // unit variants until we refactor comment emitting.
-        //if let Some(c) = self.comment(None, &field) {
-        //    Ok(Document::Fragment(vec![c, node]))
-        //} else {
-        //    Ok(node)
-        //}
-        Ok(node)
+        let node = self.commented(None, &field, node);
+        Ok(self.contained(node))
     }
 
     fn serialize_newtype_variant<T>(
@@ -254,7 +351,7 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
     where
         T: ?Sized + ser::Serialize,
     {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         let a = self.annotate(Some(variant), &MemberId::Variant);
         let compact = a.map(|a| a.compact).unwrap_or(false);
         let v = self.serialize(value, self.annotate(Some(variant), &MemberId::Index(0)))?;
@@ -263,14 +360,9 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         } else {
             v
         };
-        let mut nodes = vec![];
-        if let Some(c) = self.comment(Some(variant), &MemberId::Variant) {
-            nodes.push(c);
-        }
-        nodes.push(Document::from(variant));
-        nodes.push(v);
-
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        let mut nodes = vec![Document::from(variant), v];
+        self.add_comment(Some(variant), &MemberId::Variant, &mut nodes);
+        Ok(self.contained(Document::Mapping(vec![Document::Fragment(nodes)])))
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
@@ -286,7 +378,7 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         Ok(SerializeTupleStruct::new(self))
     }
 
@@ -297,7 +389,7 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         Ok(SerializeTupleVariant::new(self, variant))
     }
 
@@ -310,7 +402,7 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         Ok(SerializeStruct::new(self))
     }
 
@@ -321,14 +413,14 @@ impl<'s, 'a> ser::Serializer for &'s mut AnnotatedSerializer<'a> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        self.annotator = Annotator::cast(name, &self.ptr);
+        self.cast(name);
         Ok(SerializeStructVariant::new(self, variant))
     }
 }
 
 pub struct SerializeSeq<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
-    sequence: Vec<Document>,
+    sequence: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeSeq<'s, 'a> {
@@ -341,7 +433,7 @@ impl<'s, 'a> SerializeSeq<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeSeq for SerializeSeq<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
@@ -359,7 +451,7 @@ impl<'s, 'a> ser::SerializeSeq for SerializeSeq<'s, 'a> {
 
 pub struct SerializeTuple<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
-    sequence: Vec<Document>,
+    sequence: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeTuple<'s, 'a> {
@@ -372,7 +464,7 @@ impl<'s, 'a> SerializeTuple<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeTuple for SerializeTuple<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn serialize_element<T>(&mut self, value: &T) -> Result<(), Self::Error>
@@ -391,7 +483,7 @@ impl<'s, 'a> ser::SerializeTuple for SerializeTuple<'s, 'a> {
 pub struct SerializeTupleStruct<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
     index: u32,
-    sequence: Vec<Document>,
+    sequence: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeTupleStruct<'s, 'a> {
@@ -405,7 +497,7 @@ impl<'s, 'a> SerializeTupleStruct<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeTupleStruct for SerializeTupleStruct<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
@@ -416,17 +508,14 @@ impl<'s, 'a> ser::SerializeTupleStruct for SerializeTupleStruct<'s, 'a> {
         let node = self
             .serializer
             .serialize(value, self.serializer.annotate(None, &field))?;
-        if let Some(c) = self.serializer.comment(None, &field) {
-            self.sequence.push(Document::Fragment(vec![c, node]));
-        } else {
-            self.sequence.push(node);
-        }
+        self.sequence
+            .push(self.serializer.commented(None, &field, node));
         self.index += 1;
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Sequence(self.sequence))
+        Ok(self.serializer.contained(Document::Sequence(self.sequence)))
     }
 }
 
@@ -434,7 +523,7 @@ pub struct SerializeTupleVariant<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
     variant: &'static str,
     index: u32,
-    sequence: Vec<Document>,
+    sequence: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeTupleVariant<'s, 'a> {
@@ -449,7 +538,7 @@ impl<'s, 'a> SerializeTupleVariant<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeTupleVariant for SerializeTupleVariant<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn serialize_field<T>(&mut self, value: &T) -> Result<(), Self::Error>
@@ -460,11 +549,8 @@ impl<'s, 'a> ser::SerializeTupleVariant for SerializeTupleVariant<'s, 'a> {
         let node = self
             .serializer
             .serialize(value, self.serializer.annotate(Some(self.variant), &field))?;
-        if let Some(c) = self.serializer.comment(Some(self.variant), &field) {
-            self.sequence.push(Document::Fragment(vec![c, node]));
-        } else {
-            self.sequence.push(node);
-        }
+        self.sequence
+            .push(self.serializer.commented(Some(self.variant), &field, node));
 
         self.index += 1;
         Ok(())
@@ -480,23 +566,18 @@ impl<'s, 'a> ser::SerializeTupleVariant for SerializeTupleVariant<'s, 'a> {
         } else {
             Document::Sequence(self.sequence)
         };
-        let mut nodes = vec![];
-        if let Some(c) = self
-            .serializer
-            .comment(Some(self.variant), &MemberId::Variant)
-        {
-            nodes.push(c);
-        }
-        nodes.push(Document::from(self.variant));
-        nodes.push(sequence);
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        let mut nodes = vec![Document::from(self.variant), sequence];
+        self.serializer
+            .add_comment(Some(self.variant), &MemberId::Variant, &mut nodes);
+        let mapping = Document::Mapping(vec![Document::Fragment(nodes)]);
+        Ok(self.serializer.contained(mapping))
     }
 }
 
 pub struct SerializeMap<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
-    next_key: Option<Document>,
-    mapping: Vec<Document>,
+    next_key: Option<Document<'static>>,
+    mapping: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeMap<'s, 'a> {
@@ -510,7 +591,7 @@ impl<'s, 'a> SerializeMap<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeMap for SerializeMap<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -531,10 +612,8 @@ impl<'s, 'a> ser::SerializeMap for SerializeMap<'s, 'a> {
     {
         match self.next_key.take() {
             Some(key) => {
-                self.mapping.push(Document::Fragment(vec![
-                    key,
-                    self.serializer.serialize(value, None)?,
-                ]));
+                let value = self.serializer.serialize(value, None)?;
+                self.mapping.push(map_member(key, value));
             }
             None => panic!("serialize_value called before serialize_key"),
         };
@@ -546,17 +625,34 @@ impl<'s, 'a> ser::SerializeMap for SerializeMap<'s, 'a> {
         K: ?Sized + ser::Serialize,
         V: ?Sized + ser::Serialize,
     {
-        self.mapping.push(Document::Fragment(vec![
-            key.serialize(&mut *self.serializer)?,
-            self.serializer.serialize(value, None)?,
-        ]));
+        let key = key.serialize(&mut *self.serializer)?;
+        let value = self.serializer.serialize(value, None)?;
+        self.mapping.push(map_member(key, value));
         Ok(())
     }
 }
 
+// Builds a mapping member from `key` and `value`.  Comments on the key become
+// comments on the member so that emitters need not render commented keys.
+fn map_member(key: Document<'static>, value: Document<'static>) -> Document<'static> {
+    let (k, comments) = key.value_comments();
+    if comments.is_empty() {
+        return Document::Fragment(vec![key, value]);
+    }
+    let (leading, trailing): (Vec<_>, Vec<_>) = comments.into_iter().partition(|(_, p)| *p);
+    let mut nodes = leading
+        .into_iter()
+        .map(|(c, _)| c.clone())
+        .collect::<Vec<_>>();
+    nodes.push(k.clone());
+    nodes.push(value);
+    nodes.extend(trailing.into_iter().map(|(c, _)| c.clone()));
+    Document::Fragment(nodes)
+}
+
 pub struct SerializeStruct<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
-    mapping: Vec<Document>,
+    mapping: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeStruct<'s, 'a> {
@@ -569,11 +665,11 @@ impl<'s, 'a> SerializeStruct<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeStruct for SerializeStruct<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        Ok(Document::Mapping(self.mapping))
+        Ok(self.serializer.contained(Document::Mapping(self.mapping)))
     }
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
@@ -581,15 +677,11 @@ impl<'s, 'a> ser::SerializeStruct for SerializeStruct<'s, 'a> {
         T: ?Sized + ser::Serialize,
     {
         let field = MemberId::Name(key);
-        let mut nodes = vec![];
-        if let Some(c) = self.serializer.comment(None, &field) {
-            nodes.push(c);
-        }
-        nodes.push(Document::from(key));
-        nodes.push(
-            self.serializer
-                .serialize(value, self.serializer.annotate(None, &field))?,
-        );
+        let value = self
+            .serializer
+            .serialize(value, self.serializer.annotate(None, &field))?;
+        let mut nodes = vec![Document::from(key), value];
+        self.serializer.add_comment(None, &field, &mut nodes);
         self.mapping.push(Document::Fragment(nodes));
         Ok(())
     }
@@ -598,7 +690,7 @@ impl<'s, 'a> ser::SerializeStruct for SerializeStruct<'s, 'a> {
 pub struct SerializeStructVariant<'s, 'a> {
     serializer: &'s mut AnnotatedSerializer<'a>,
     variant: &'static str,
-    mapping: Vec<Document>,
+    mapping: Vec<Document<'static>>,
 }
 
 impl<'s, 'a> SerializeStructVariant<'s, 'a> {
@@ -612,7 +704,7 @@ impl<'s, 'a> SerializeStructVariant<'s, 'a> {
 }
 
 impl<'s, 'a> ser::SerializeStructVariant for SerializeStructVariant<'s, 'a> {
-    type Ok = Document;
+    type Ok = Document<'static>;
     type Error = Error;
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
@@ -625,16 +717,11 @@ impl<'s, 'a> ser::SerializeStructVariant for SerializeStructVariant<'s, 'a> {
         } else {
             Document::Mapping(self.mapping)
         };
-        let mut nodes = vec![];
-        if let Some(c) = self
-            .serializer
-            .comment(Some(self.variant), &MemberId::Variant)
-        {
-            nodes.push(c);
-        }
-        nodes.push(Document::from(self.variant));
-        nodes.push(mapping);
-        Ok(Document::Mapping(vec![Document::Fragment(nodes)]))
+        let mut nodes = vec![Document::from(self.variant), mapping];
+        self.serializer
+            .add_comment(Some(self.variant), &MemberId::Variant, &mut nodes);
+        let mapping = Document::Mapping(vec![Document::Fragment(nodes)]);
+        Ok(self.serializer.contained(mapping))
     }
 
     fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error>
@@ -642,15 +729,12 @@ impl<'s, 'a> ser::SerializeStructVariant for SerializeStructVariant<'s, 'a> {
         T: ?Sized + ser::Serialize,
     {
         let field = MemberId::Name(key);
-        let mut nodes = vec![];
-        if let Some(c) = self.serializer.comment(None, &field) {
-            nodes.push(c);
-        }
-        nodes.push(Document::from(key));
-        nodes.push(
-            self.serializer
-                .serialize(value, self.serializer.annotate(None, &field))?,
-        );
+        let value = self
+            .serializer
+            .serialize(value, self.serializer.annotate(Some(self.variant), &field))?;
+        let mut nodes = vec![Document::from(key), value];
+        self.serializer
+            .add_comment(Some(self.variant), &field, &mut nodes);
         self.mapping.push(Document::Fragment(nodes));
         Ok(())
     }
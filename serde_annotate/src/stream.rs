@@ -0,0 +1,598 @@
+// Serializer which emits annotated documents directly to a writer.
+//
+// The `AnnotatedSerializer` builds a complete `Document` which is then
+// rendered by an emitter.  The `StreamSerializer` applies the same
+// annotations, but drives the emitter with a stream of events as the value is
+// serialized, so no intermediate `Document` is built.
+use serde::ser;
+use std::fmt;
+
+use crate::annotate::{MemberId, Placement};
+use crate::document::{BytesFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::hexdump;
+use crate::integer::Int;
+use crate::ser::AnnotatedSerializer;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// A scalar value to be emitted.
+pub(crate) enum Scalar<'a> {
+    Str(&'a str, StrFormat),
+    Boolean(bool),
+    Int(Int),
+    Float(f64),
+    Bytes(&'a [u8]),
+    /// A multiline string given as its lines.
+    Lines(hexdump::Rows<'a>),
+    Null,
+}
+
+/// An emitter which renders a document from a stream of events.
+///
+/// Sequences are emitted as `begin_sequence`, followed by an `element` event
+/// before each element's value, followed by `end_sequence`.  Mappings are
+/// emitted similarly, with a `key` event before each value.  The comment on a
+/// member is given with the event which starts the member, along with where
+/// the comment is placed.
+pub(crate) trait Emitter {
+    fn scalar<W: fmt::Write>(&mut self, w: &mut W, value: Scalar) -> Result<()>;
+    fn begin_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()>;
+    fn element<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+    ) -> Result<()>;
+    fn end_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()>;
+    fn begin_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()>;
+    fn key<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+        key: &Document,
+    ) -> Result<()>;
+    fn end_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()>;
+    fn is_compact(&self) -> bool;
+    fn set_compact(&mut self, compact: bool);
+    /// Attaches a comment to the value which follows.
+    fn comment<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: &str,
+        placement: Placement,
+    ) -> Result<()>;
+    /// Completes the document.
+    fn finish<W: fmt::Write>(&mut self, w: &mut W) -> Result<()>;
+}
+
+/// Serializes `value` to `w` via `emitter`.
+pub(crate) fn serialize<T, W, E>(value: &T, w: &mut W, emitter: &mut E) -> Result<()>
+where
+    T: ?Sized + ser::Serialize,
+    W: fmt::Write,
+    E: Emitter,
+{
+    let ser = StreamSerializer {
+        state: AnnotatedSerializer::new(value),
+        w: &mut *w,
+        emitter: &mut *emitter,
+    };
+    value.serialize(ser)?;
+    emitter.finish(w)
+}
+
+pub(crate) struct StreamSerializer<'s, 'a, W, E> {
+    state: AnnotatedSerializer<'a>,
+    w: &'s mut W,
+    emitter: &'s mut E,
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> StreamSerializer<'s, 'a, W, E> {
+    fn scalar(self, value: Scalar) -> Result<()> {
+        self.emitter.scalar(self.w, value)
+    }
+
+    // Serializes a child value with the annotated format `ser`.
+    fn serialize<T>(&mut self, value: &T, ser: Option<AnnotatedSerializer<'a>>) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        value.serialize(StreamSerializer {
+            state: self.state.child(value, ser),
+            w: &mut *self.w,
+            emitter: &mut *self.emitter,
+        })
+    }
+
+    // Looks up the annotations for the named type being serialized and
+    // emits the comment on the type.
+    fn cast(&mut self, name: &str) -> Result<()> {
+        self.state.cast(name);
+        self.value_comment(None, &MemberId::Container)
+    }
+
+    // Emits the comment attached to a value which has no member of its own,
+    // such as a unit variant or the field of a newtype struct.
+    fn value_comment(&mut self, variant: Option<&str>, field: &MemberId) -> Result<()> {
+        match self.state.comment_text(variant, field) {
+            Some(comment) => {
+                let placement = self.state.placement(variant, field);
+                self.emitter.comment(self.w, &comment, placement)
+            }
+            None => Ok(()),
+        }
+    }
+
+    // Emits the key for a variant and enters compact mode if the variant is
+    // annotated as compact.  Returns the previous compact mode.
+    fn variant_key(&mut self, variant: &'static str) -> Result<bool> {
+        let compact = self
+            .state
+            .annotate(Some(variant), &MemberId::Variant)
+            .map(|a| a.compact)
+            .unwrap_or(false);
+        let comment = self.state.comment_text(Some(variant), &MemberId::Variant);
+        let placement = self.state.placement(Some(variant), &MemberId::Variant);
+        self.emitter.begin_mapping(self.w)?;
+        self.emitter.key(
+            self.w,
+            comment.as_deref().map(|c| (c, placement)),
+            &Document::from(variant),
+        )?;
+        let prior = self.emitter.is_compact();
+        self.emitter.set_compact(prior || compact);
+        Ok(prior)
+    }
+
+    fn end_variant(&mut self, prior: bool) -> Result<()> {
+        self.emitter.set_compact(prior);
+        self.emitter.end_mapping(self.w)
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::Serializer for StreamSerializer<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Compound<'s, 'a, W, E>;
+    type SerializeTupleVariant = Compound<'s, 'a, W, E>;
+    type SerializeMap = SerializeMap<'s, 'a, W, E>;
+    type SerializeStruct = Compound<'s, 'a, W, E>;
+    type SerializeStructVariant = Compound<'s, 'a, W, E>;
+
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.scalar(Scalar::Boolean(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<()> {
+        let int = self.state.int(v);
+        self.scalar(Scalar::Int(int))
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.scalar(Scalar::Float(v as f64))
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.scalar(Scalar::Float(v))
+    }
+
+    fn serialize_char(self, v: char) -> Result<()> {
+        let mut buf = [0u8; 4];
+        let f = self.state.strformat;
+        self.scalar(Scalar::Str(v.encode_utf8(&mut buf), f))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<()> {
+        let f = self.state.strformat;
+        self.scalar(Scalar::Str(v, f))
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        let bytesformat = self.state.bytesformat;
+        // Hexdumps of large buffers are emitted a line at a time.
+        if let Some(rows) = hexdump::rows(v, bytesformat).filter(|_| !v.is_empty()) {
+            self.scalar(Scalar::Lines(rows))
+        } else if let Some(string) = hexdump::to_string(v, bytesformat) {
+            let f = if bytesformat == BytesFormat::HexStr {
+                StrFormat::Standard
+            } else {
+                StrFormat::Multiline
+            };
+            self.scalar(Scalar::Str(&string, f))
+        } else {
+            self.scalar(Scalar::Bytes(v))
+        }
+    }
+
+    fn serialize_none(self) -> Result<()> {
+        self.scalar(Scalar::Null)
+    }
+
+    fn serialize_some<T>(mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.serialize(value, None)
+    }
+
+    fn serialize_unit(self) -> Result<()> {
+        self.scalar(Scalar::Null)
+    }
+
+    fn serialize_unit_struct(mut self, name: &'static str) -> Result<()> {
+        self.cast(name)?;
+        self.scalar(Scalar::Null)
+    }
+
+    fn serialize_unit_variant(
+        mut self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<()> {
+        self.cast(name)?;
+        self.value_comment(Some(variant), &MemberId::Variant)?;
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(mut self, name: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.cast(name)?;
+        self.value_comment(None, &MemberId::Index(0))?;
+        let format = self.state.annotate(None, &MemberId::Index(0));
+        self.serialize(value, format)
+    }
+
+    fn serialize_newtype_variant<T>(
+        mut self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.cast(name)?;
+        let prior = self.variant_key(variant)?;
+        let format = self.state.annotate(Some(variant), &MemberId::Index(0));
+        self.serialize(value, format)?;
+        self.end_variant(prior)
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self> {
+        self.emitter.begin_sequence(self.w)?;
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        mut self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W, E>> {
+        self.cast(name)?;
+        self.emitter.begin_sequence(self.w)?;
+        Ok(Compound::new(self, None, None))
+    }
+
+    fn serialize_tuple_variant(
+        mut self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W, E>> {
+        self.cast(name)?;
+        let prior = self.variant_key(variant)?;
+        self.emitter.begin_sequence(self.w)?;
+        Ok(Compound::new(self, Some(variant), Some(prior)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMap<'s, 'a, W, E>> {
+        self.emitter.begin_mapping(self.w)?;
+        Ok(SerializeMap {
+            serializer: self,
+            next_key: None,
+        })
+    }
+
+    fn serialize_struct(
+        mut self,
+        name: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W, E>> {
+        self.cast(name)?;
+        self.emitter.begin_mapping(self.w)?;
+        Ok(Compound::new(self, None, None))
+    }
+
+    fn serialize_struct_variant(
+        mut self,
+        name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'s, 'a, W, E>> {
+        self.cast(name)?;
+        let prior = self.variant_key(variant)?;
+        self.emitter.begin_mapping(self.w)?;
+        Ok(Compound::new(self, Some(variant), Some(prior)))
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeSeq for StreamSerializer<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.emitter.element(self.w, None)?;
+        self.serialize(value, None)
+    }
+
+    fn end(self) -> Result<()> {
+        self.emitter.end_sequence(self.w)
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeTuple for StreamSerializer<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        ser::SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<()> {
+        ser::SerializeSeq::end(self)
+    }
+}
+
+// The `Compound` struct serializes the annotated members of structs, tuple
+// structs and their enum variant counterparts.
+pub(crate) struct Compound<'s, 'a, W, E> {
+    serializer: StreamSerializer<'s, 'a, W, E>,
+    variant: Option<&'static str>,
+    // The compact mode in effect before entering a variant.
+    prior: Option<bool>,
+    index: u32,
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> Compound<'s, 'a, W, E> {
+    fn new(
+        serializer: StreamSerializer<'s, 'a, W, E>,
+        variant: Option<&'static str>,
+        prior: Option<bool>,
+    ) -> Self {
+        Compound {
+            serializer,
+            variant,
+            prior,
+            index: 0,
+        }
+    }
+
+    fn tuple_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let s = &mut self.serializer;
+        let field = MemberId::Index(self.index);
+        let comment = s.state.comment_text(self.variant, &field);
+        let placement = s.state.placement(self.variant, &field);
+        s.emitter
+            .element(s.w, comment.as_deref().map(|c| (c, placement)))?;
+        let format = s.state.annotate(self.variant, &field);
+        s.serialize(value, format)?;
+        self.index += 1;
+        Ok(())
+    }
+
+    fn struct_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let s = &mut self.serializer;
+        let field = MemberId::Name(key);
+        let comment = s.state.comment_text(self.variant, &field);
+        let placement = s.state.placement(self.variant, &field);
+        s.emitter.key(
+            s.w,
+            comment.as_deref().map(|c| (c, placement)),
+            &Document::from(key),
+        )?;
+        let format = s.state.annotate(self.variant, &field);
+        s.serialize(value, format)
+    }
+
+    fn end_sequence(mut self) -> Result<()> {
+        self.serializer.emitter.end_sequence(self.serializer.w)?;
+        match self.prior {
+            Some(prior) => self.serializer.end_variant(prior),
+            None => Ok(()),
+        }
+    }
+
+    fn end_mapping(mut self) -> Result<()> {
+        self.serializer.emitter.end_mapping(self.serializer.w)?;
+        match self.prior {
+            Some(prior) => self.serializer.end_variant(prior),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeTupleStruct for Compound<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.tuple_field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeTupleVariant for Compound<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.tuple_field(value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_sequence()
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeStruct for Compound<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.struct_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_mapping()
+    }
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeStructVariant for Compound<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        self.struct_field(key, value)
+    }
+
+    fn end(self) -> Result<()> {
+        self.end_mapping()
+    }
+}
+
+pub(crate) struct SerializeMap<'s, 'a, W, E> {
+    serializer: StreamSerializer<'s, 'a, W, E>,
+    next_key: Option<Document<'static>>,
+}
+
+impl<'s, 'a, W: fmt::Write, E: Emitter> ser::SerializeMap for SerializeMap<'s, 'a, W, E> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        // Keys are small, so they're serialized into a `Document` node which
+        // the emitter can inspect to decide how to render the key.
+        self.next_key = Some(key.serialize(&mut self.serializer.state.clone())?);
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<()>
+    where
+        T: ?Sized + ser::Serialize,
+    {
+        let s = &mut self.serializer;
+        let key = match self.next_key.take() {
+            Some(key) => key,
+            None => panic!("serialize_value called before serialize_key"),
+        };
+        // Comments on the key become comments on the member.
+        let (key, comments) = key.value_comments();
+        let leading = comments
+            .iter()
+            .filter(|(_, precedes)| *precedes)
+            .filter_map(|(c, _)| c.comment())
+            .map(|(c, _)| c)
+            .collect::<Vec<_>>()
+            .join("\n");
+        let comment = (!leading.is_empty()).then_some((leading.as_str(), Placement::Leading));
+        s.emitter.key(s.w, comment, key)?;
+        for (c, _) in comments.iter().filter(|(_, precedes)| !*precedes) {
+            if let Some((c, _)) = c.comment() {
+                s.emitter.comment(s.w, c, Placement::Trailing)?;
+            }
+        }
+        s.serialize(value, None)
+    }
+
+    fn end(self) -> Result<()> {
+        self.serializer.emitter.end_mapping(self.serializer.w)
+    }
+}
@@ -0,0 +1,888 @@
+use crate::color::{ColorProfile, PaintExt};
+use crate::doc_iter::OwnedDocPath;
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::integer::{Base, Int};
+use crate::writer::IoWriter;
+use std::fmt;
+use std::io;
+
+type Result<T> = std::result::Result<T, Error>;
+
+/// `Toml` renders a `Document` as TOML.
+///
+/// The top level of the document must be a mapping.  Mappings become
+/// tables and sequences of mappings become arrays of tables, unless they
+/// are marked compact, in which case they are rendered as inline tables.
+/// TOML has no null value, so documents containing nulls cannot be
+/// rendered.
+pub struct Toml<'a> {
+    document: Document<'a>,
+    indent: usize,
+    color: ColorProfile,
+}
+
+impl Toml<'_> {
+    /// Set the amount of indentation for the elements of multi-line arrays.
+    pub fn indent(mut self, i: usize) -> Self {
+        self.indent = i;
+        self
+    }
+    /// Set the color profile used to highlight the output.
+    pub fn color(mut self, c: ColorProfile) -> Self {
+        self.color = c;
+        self
+    }
+
+    /// Writes the document to `w`.  The output is streamed to the writer
+    /// rather than being rendered to a `String` first.
+    pub fn write_to<W: io::Write>(&self, w: W) -> Result<()> {
+        let mut w = IoWriter::new(w);
+        let result = self.emit(&mut w);
+        w.finish(result)
+    }
+
+    /// Renders the document to a `String`, returning an error if the
+    /// document cannot be represented in TOML.  `Toml` doesn't implement
+    /// `Display`, since formatting can't report such errors.
+    pub fn try_to_string(&self) -> Result<String> {
+        let mut s = String::new();
+        self.emit(&mut s)?;
+        Ok(s)
+    }
+
+    fn emit<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
+        let mut emitter = TomlEmitter {
+            indent: self.indent,
+            color: self.color,
+            ..Default::default()
+        };
+        emitter.emit_document(w, &self.document)
+    }
+}
+
+impl<'a> Document<'a> {
+    pub fn to_toml(self) -> Toml<'a> {
+        Toml {
+            document: self,
+            indent: 2,
+            color: ColorProfile::default(),
+        }
+    }
+}
+
+#[derive(Default)]
+struct TomlEmitter {
+    level: usize,
+    indent: usize,
+    color: ColorProfile,
+    compact: bool,
+    // Whether anything has been written yet.  Every line but the first is
+    // preceded by a newline.
+    started: bool,
+    // The keys of the table currently being emitted, formatted for use in
+    // a table header.
+    keys: Vec<String>,
+    // The document path of the node currently being emitted, for errors.
+    path: Vec<OwnedDocPath>,
+}
+
+impl TomlEmitter {
+    const SPACE: &'static str = "                                                                                                    ";
+
+    fn emit_document<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+        match node {
+            Document::Comment(c, f) => self.emit_comment_line(w, c, f),
+            Document::Mapping(m) => self.emit_table(w, m),
+            Document::Compact(d) | Document::Spanned(d, _) => self.emit_document(w, d),
+            Document::Fragment(ds) => {
+                for d in ds {
+                    self.emit_document(w, d)?;
+                }
+                Ok(())
+            }
+            _ => Err(Error::Unrepresentable(
+                "TOML",
+                format!(
+                    "a top-level {}; the top level must be a mapping",
+                    node.variant()
+                ),
+            )),
+        }
+    }
+
+    // Returns whether `node` should be emitted as a table or an array of
+    // tables rather than as a `key = value` pair.
+    fn is_section(node: &Document) -> bool {
+        match Self::unspan(node.value_comments().0) {
+            Document::Mapping(_) => true,
+            Document::Sequence(s) => !s.is_empty() && s.iter().all(|v| Self::table(v).is_some()),
+            _ => false,
+        }
+    }
+
+    // Returns the entries of `node` if it is a (non-compact) mapping.
+    fn table<'a>(node: &'a Document) -> Option<&'a [Document<'a>]> {
+        match node {
+            Document::Spanned(d, _) => Self::table(d),
+            Document::Mapping(m) => Some(m),
+            Document::Fragment(f) => {
+                let mut values = f.iter().filter(|n| n.has_value());
+                match (values.next(), values.next()) {
+                    (Some(v), None) => Self::table(v),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // Returns the comments attached to the value of the member `nodes`
+    // which precede and follow the value.
+    fn attached<'a>(nodes: &'a [Document<'a>]) -> (Vec<&'a Document<'a>>, Vec<&'a Document<'a>>) {
+        let value = nodes.iter().filter(|n| n.has_value()).nth(1);
+        let comments = value.map(|v| v.value_comments().1).unwrap_or_default();
+        let (leading, trailing): (Vec<_>, Vec<_>) = comments.into_iter().partition(|(_, p)| *p);
+        (
+            leading.into_iter().map(|(c, _)| c).collect(),
+            trailing.into_iter().map(|(c, _)| c).collect(),
+        )
+    }
+
+    fn unspan<'a>(node: &'a Document) -> &'a Document<'a> {
+        match node {
+            Document::Spanned(d, _) => Self::unspan(d),
+            _ => node,
+        }
+    }
+
+    // Emits the entries of a table.  TOML requires the `key = value` pairs of
+    // a table to precede its sub-tables, so sub-tables are emitted last.
+    fn emit_table<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
+        let mut sections = Vec::new();
+        for entry in mapping {
+            let nodes = entry.fragments()?;
+            match entry.as_kv() {
+                Ok((_, v)) if Self::is_section(v) => sections.push(nodes),
+                _ => self.emit_pair(w, nodes)?,
+            }
+        }
+        for nodes in sections {
+            self.emit_section(w, nodes)?;
+        }
+        Ok(())
+    }
+
+    // Emits a `key = value` pair along with its comments.
+    fn emit_pair<W: fmt::Write>(&mut self, w: &mut W, nodes: &[Document]) -> Result<()> {
+        let mut key_done = false;
+        let mut val_done = false;
+        // Comments attached to the value (e.g. on a unit variant) follow the
+        // member's own comments.
+        let (leading, attached) = Self::attached(nodes);
+        let mark = self.path.len();
+        for node in nodes {
+            if let Some((c, f)) = node.comment() {
+                if val_done {
+                    self.emit_trailing_comment(w, c, f)?;
+                } else {
+                    self.emit_comment_line(w, c, f)?;
+                }
+            } else if !key_done {
+                for (c, f) in leading.iter().filter_map(|c| c.comment()) {
+                    self.emit_comment_line(w, c, f)?;
+                }
+                self.newline(w)?;
+                let key = self.key(node)?;
+                self.push_name(&key);
+                write!(w, "{}", self.color.key.paint(format_key(&key)))?;
+                key_done = true;
+            } else if !val_done {
+                write!(w, " {} ", self.color.punctuation.paint("="))?;
+                self.emit_value(w, node.value_comments().0)?;
+                val_done = true;
+            }
+        }
+        for (c, f) in attached.iter().filter_map(|c| c.comment()) {
+            self.emit_trailing_comment(w, c, f)?;
+        }
+        self.path.truncate(mark);
+        if key_done && !val_done {
+            return Err(Error::StructureError("a node", "none"));
+        }
+        Ok(())
+    }
+
+    // Emits a table (`[key]`) or an array of tables (`[[key]]`) along with
+    // its comments.
+    fn emit_section<W: fmt::Write>(&mut self, w: &mut W, nodes: &[Document]) -> Result<()> {
+        let (key, value) = nodes.iter().fold((None, None), |(k, v), node| match node {
+            Document::Comment(_, _) => (k, v),
+            _ if k.is_none() => (Some(node), v),
+            _ => (k, Some(node)),
+        });
+        // Unwraps are ok: the caller has checked that `nodes` is a kvpair.
+        let (key, value) = (key.unwrap(), value.unwrap());
+        let key = self.key(key)?;
+        let mark = self.path.len();
+        self.push_name(&key);
+        self.keys.push(format_key(&key));
+        let (attached_leading, attached_trailing) = Self::attached(nodes);
+        let mut leading = nodes
+            .iter()
+            .take_while(|n| n.comment().is_some())
+            .chain(attached_leading.iter().copied());
+        let trailing = nodes
+            .iter()
+            .skip_while(|n| !std::ptr::eq(*n, value))
+            .skip(1)
+            .chain(attached_trailing.iter().copied());
+        match Self::unspan(value.value_comments().0) {
+            Document::Mapping(m) => {
+                // A table holding nothing but sub-tables needs no header of
+                // its own unless it has comments.
+                let implicit = !m.is_empty()
+                    && nodes.len() == 2
+                    && attached_leading.is_empty()
+                    && attached_trailing.is_empty()
+                    && m.iter()
+                        .all(|e| matches!(e.as_kv(), Ok((_, v)) if Self::is_section(v)));
+                if !implicit {
+                    self.blank_line(w)?;
+                    for (c, f) in leading.filter_map(Document::comment) {
+                        self.emit_comment_line(w, c, f)?;
+                    }
+                    self.emit_header(w, "[", "]")?;
+                    for (c, f) in trailing.filter_map(Document::comment) {
+                        self.emit_trailing_comment(w, c, f)?;
+                    }
+                }
+                self.emit_table(w, m)?;
+            }
+            Document::Sequence(s) => {
+                let trailing = trailing.collect::<Vec<_>>();
+                for (i, element) in s.iter().enumerate() {
+                    let frags = match element {
+                        Document::Fragment(f) => f.as_slice(),
+                        _ => std::slice::from_ref(element),
+                    };
+                    let mark = self.path.len();
+                    self.path.push(OwnedDocPath::Index(i));
+                    self.blank_line(w)?;
+                    for (c, f) in leading.by_ref().filter_map(Document::comment) {
+                        self.emit_comment_line(w, c, f)?;
+                    }
+                    let mut table = None;
+                    for node in frags {
+                        match node {
+                            Document::Comment(c, f) if table.is_none() => {
+                                self.emit_comment_line(w, c, f)?;
+                            }
+                            Document::Comment(_, _) => {}
+                            _ => {
+                                self.emit_header(w, "[[", "]]")?;
+                                table = Some(node);
+                            }
+                        }
+                    }
+                    let after = frags
+                        .iter()
+                        .skip_while(|n| !table.is_some_and(|t| std::ptr::eq(*n, t)))
+                        .skip(1);
+                    let after = if i == 0 {
+                        trailing.iter().copied().chain(after).collect::<Vec<_>>()
+                    } else {
+                        after.collect::<Vec<_>>()
+                    };
+                    for (c, f) in after.into_iter().filter_map(Document::comment) {
+                        self.emit_trailing_comment(w, c, f)?;
+                    }
+                    if let Some(m) = table.and_then(Self::table) {
+                        self.emit_table(w, m)?;
+                    }
+                    self.path.truncate(mark);
+                }
+            }
+            _ => unreachable!(),
+        }
+        self.keys.pop();
+        self.path.truncate(mark);
+        Ok(())
+    }
+
+    fn emit_header<W: fmt::Write>(&mut self, w: &mut W, open: &str, close: &str) -> Result<()> {
+        self.newline(w)?;
+        write!(w, "{}", self.color.aggregate.paint(open))?;
+        for (i, key) in self.keys.iter().enumerate() {
+            if i > 0 {
+                write!(w, "{}", self.color.punctuation.paint("."))?;
+            }
+            write!(w, "{}", self.color.key.paint(key))?;
+        }
+        write!(w, "{}", self.color.aggregate.paint(close))?;
+        Ok(())
+    }
+
+    // Emits a value in the right-hand side of a `key = value` pair or as an
+    // element of an inline array.
+    fn emit_value<W: fmt::Write>(&mut self, w: &mut W, node: &Document) -> Result<()> {
+        match node {
+            Document::Comment(_, _) => Err(Error::StructureError("a value", "Comment")),
+            Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
+            Document::Str(v, f) => self.emit_string(w, v, *f),
+            Document::Boolean(v) => {
+                write!(w, "{}", self.color.boolean.paint(v))?;
+                Ok(())
+            }
+            Document::Int(v) => self.emit_int(w, v),
+            Document::Float(v) => self.emit_float(w, *v),
+            Document::Mapping(m) => self.emit_inline_table(w, m),
+            Document::Sequence(s) => self.emit_array(w, s),
+            Document::Bytes(v) => self.emit_bytes(w, v),
+            Document::Null => Err(self.unrepresentable("null")),
+            Document::Compact(d) => {
+                let compact = self.compact;
+                self.compact = true;
+                let result = self.emit_value(w, d);
+                self.compact = compact;
+                result
+            }
+            Document::Spanned(d, _) => self.emit_value(w, d),
+            Document::Fragment(_) => self.emit_value(w, node.as_value()?),
+        }
+    }
+
+    fn emit_inline_table<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
+        if mapping.is_empty() {
+            write!(w, "{}", self.color.aggregate.paint("{}"))?;
+            return Ok(());
+        }
+        // Inline tables cannot hold comments or newlines.
+        let compact = self.compact;
+        self.compact = true;
+        write!(w, "{} ", self.color.aggregate.paint("{"))?;
+        for (i, entry) in mapping.iter().filter(|e| e.has_value()).enumerate() {
+            if i > 0 {
+                write!(w, "{} ", self.color.punctuation.paint(","))?;
+            }
+            let (k, v) = entry.as_kv()?;
+            let key = self.key(k)?;
+            let mark = self.path.len();
+            self.push_name(&key);
+            write!(
+                w,
+                "{} {} ",
+                self.color.key.paint(format_key(&key)),
+                self.color.punctuation.paint("=")
+            )?;
+            self.emit_value(w, v)?;
+            self.path.truncate(mark);
+        }
+        write!(w, " {}", self.color.aggregate.paint("}"))?;
+        self.compact = compact;
+        Ok(())
+    }
+
+    // Returns whether an array element requires the array to be emitted
+    // across multiple lines.
+    fn is_multiline(node: &Document) -> bool {
+        match node {
+            Document::Fragment(f) => f
+                .iter()
+                .any(|n| n.comment().is_some() || Self::is_multiline(n)),
+            Document::Spanned(d, _) => Self::is_multiline(d),
+            Document::Mapping(v) | Document::Sequence(v) => !v.is_empty(),
+            Document::Bytes(v) => !v.is_empty(),
+            _ => false,
+        }
+    }
+
+    fn emit_array<W: fmt::Write>(&mut self, w: &mut W, sequence: &[Document]) -> Result<()> {
+        let mark = self.path.len();
+        if self.compact || !sequence.iter().any(Self::is_multiline) {
+            write!(w, "{}", self.color.aggregate.paint("["))?;
+            for (i, v) in sequence.iter().filter(|v| v.has_value()).enumerate() {
+                if i > 0 {
+                    write!(w, "{} ", self.color.punctuation.paint(","))?;
+                }
+                self.path.push(OwnedDocPath::Index(i));
+                self.emit_value(w, v)?;
+                self.path.truncate(mark);
+            }
+            write!(w, "{}", self.color.aggregate.paint("]"))?;
+            return Ok(());
+        }
+        write!(w, "{}", self.color.aggregate.paint("["))?;
+        self.level += 1;
+        for (i, value) in sequence.iter().enumerate() {
+            self.path.push(OwnedDocPath::Index(i));
+            let frags = match value {
+                Document::Fragment(f) => f.as_slice(),
+                _ => std::slice::from_ref(value),
+            };
+            let mut val_done = false;
+            for node in frags {
+                if let Some((c, f)) = node.comment() {
+                    if val_done {
+                        self.emit_trailing_comment(w, c, f)?;
+                    } else {
+                        self.emit_comment_line(w, c, f)?;
+                    }
+                } else if !val_done {
+                    self.newline(w)?;
+                    self.emit_value(w, node)?;
+                    write!(w, "{}", self.color.punctuation.paint(","))?;
+                    val_done = true;
+                }
+            }
+            self.path.truncate(mark);
+        }
+        self.level -= 1;
+        self.newline(w)?;
+        write!(w, "{}", self.color.aggregate.paint("]"))?;
+        Ok(())
+    }
+
+    fn emit_bytes<W: fmt::Write>(&mut self, w: &mut W, bytes: &[u8]) -> Result<()> {
+        write!(w, "{}", self.color.aggregate.paint("["))?;
+        if self.compact || bytes.is_empty() {
+            for (i, b) in bytes.iter().enumerate() {
+                if i > 0 {
+                    write!(w, "{} ", self.color.punctuation.paint(","))?;
+                }
+                write!(
+                    w,
+                    "{}",
+                    self.color.integer.paint(format_args!("0x{:02X}", b))
+                )?;
+            }
+        } else {
+            self.level += 1;
+            for chunk in bytes.chunks(16) {
+                self.newline(w)?;
+                for (i, b) in chunk.iter().enumerate() {
+                    if i > 0 {
+                        write!(w, " ")?;
+                    }
+                    write!(
+                        w,
+                        "{}",
+                        self.color.integer.paint(format_args!("0x{:02X}", b))
+                    )?;
+                    write!(w, "{}", self.color.punctuation.paint(","))?;
+                }
+            }
+            self.level -= 1;
+            self.newline(w)?;
+        }
+        write!(w, "{}", self.color.aggregate.paint("]"))?;
+        Ok(())
+    }
+
+    fn emit_string<W: fmt::Write>(&mut self, w: &mut W, value: &str, f: StrFormat) -> Result<()> {
+        match f {
+            StrFormat::Multiline if value.contains('\n') => self.emit_string_multiline(w, value),
+            // Datetimes are parsed into unquoted strings; emit them back as
+            // datetime literals.
+            StrFormat::Unquoted if is_datetime(value) => {
+                write!(w, "{}", self.color.string.paint(value))?;
+                Ok(())
+            }
+            _ => {
+                write!(w, "{}", self.color.punctuation.paint("\""))?;
+                self.escape_str(w, value, false)?;
+                write!(w, "{}", self.color.punctuation.paint("\""))?;
+                Ok(())
+            }
+        }
+    }
+
+    fn emit_string_multiline<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
+        // The newline immediately following the opening delimiter is not
+        // part of the string.
+        writeln!(w, "{}", self.color.punctuation.paint("\"\"\""))?;
+        self.escape_str(w, value, true)?;
+        write!(w, "{}", self.color.punctuation.paint("\"\"\""))?;
+        Ok(())
+    }
+
+    fn escape_str<W: fmt::Write>(&self, w: &mut W, v: &str, multiline: bool) -> Result<()> {
+        let bytes = v.as_bytes();
+        let mut start = 0;
+        for (i, &byte) in bytes.iter().enumerate() {
+            let escaped = match byte {
+                // In a multiline string, only quotes which could be mistaken
+                // for the closing delimiter need to be escaped.
+                b'"' if !multiline || i + 1 == bytes.len() || bytes[i + 1] == b'"' => "\\\"",
+                b'\\' => "\\\\",
+                b'\x08' => "\\b",
+                b'\t' if !multiline => "\\t",
+                b'\n' if !multiline => "\\n",
+                b'\x0c' => "\\f",
+                b'\r' => "\\r",
+                b'\x00'..=b'\x1f' | b'\x7f' if byte != b'\t' && byte != b'\n' => {
+                    if start < i {
+                        write!(w, "{}", self.color.string.paint(&v[start..i]))?;
+                    }
+                    let e = format!("\\u{:04X}", byte);
+                    write!(w, "{}", self.color.escape.paint(e))?;
+                    start = i + 1;
+                    continue;
+                }
+                _ => continue,
+            };
+            if start < i {
+                write!(w, "{}", self.color.string.paint(&v[start..i]))?;
+            }
+            write!(w, "{}", self.color.escape.paint(escaped))?;
+            start = i + 1;
+        }
+        if start != v.len() {
+            write!(w, "{}", self.color.string.paint(&v[start..]))?;
+        }
+        Ok(())
+    }
+
+    fn emit_int<W: fmt::Write>(&mut self, w: &mut W, i: &Int) -> Result<()> {
+        // TOML integers are 64-bit signed integers.
+        if i.checked::<i64>().is_none() {
+            return Err(self.unrepresentable(&format!("the integer {}", i)));
+        }
+        let dec = i.format(Some(&Base::Dec));
+        // TOML doesn't permit a sign on hex, octal or binary literals.
+        let s = if dec.starts_with('-') {
+            dec
+        } else {
+            i.to_string()
+        };
+        write!(w, "{}", self.color.integer.paint(s))?;
+        Ok(())
+    }
+
+    fn emit_float<W: fmt::Write>(&mut self, w: &mut W, f: f64) -> Result<()> {
+        let s = if f.is_nan() {
+            "nan".to_string()
+        } else if f.is_infinite() {
+            if f < 0.0 { "-inf" } else { "inf" }.to_string()
+        } else {
+            // Debug formatting always includes a fractional part or an
+            // exponent, which distinguishes floats from integers.
+            format!("{:?}", f)
+        };
+        write!(w, "{}", self.color.float.paint(s))?;
+        Ok(())
+    }
+
+    fn emit_comment_line<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: &str,
+        _format: &CommentFormat,
+    ) -> Result<()> {
+        if self.compact {
+            return Ok(());
+        }
+        for line in comment.split('\n') {
+            self.newline(w)?;
+            self.emit_comment_text(w, line)?;
+        }
+        Ok(())
+    }
+
+    fn emit_trailing_comment<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: &str,
+        _format: &CommentFormat,
+    ) -> Result<()> {
+        if self.compact {
+            return Ok(());
+        }
+        for (i, line) in comment.split('\n').enumerate() {
+            if i == 0 {
+                write!(w, " ")?;
+            } else {
+                self.newline(w)?;
+            }
+            self.emit_comment_text(w, line)?;
+        }
+        Ok(())
+    }
+
+    fn emit_comment_text<W: fmt::Write>(&mut self, w: &mut W, line: &str) -> Result<()> {
+        if line.is_empty() {
+            write!(w, "{}", self.color.comment.paint("#"))?;
+        } else {
+            write!(
+                w,
+                "{}",
+                self.color.comment.paint(format_args!("# {}", line))
+            )?;
+        }
+        Ok(())
+    }
+
+    // Starts a new line at the current indentation level.
+    fn newline<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        if self.started {
+            writeln!(w)?;
+        }
+        self.started = true;
+        let mut len = self.level * self.indent;
+        while len > 0 {
+            let chunk = std::cmp::min(len, Self::SPACE.len());
+            write!(w, "{}", &Self::SPACE[..chunk])?;
+            len -= chunk;
+        }
+        Ok(())
+    }
+
+    // Separates a table header from whatever precedes it.
+    fn blank_line<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        if self.started {
+            writeln!(w)?;
+        }
+        Ok(())
+    }
+
+    // Returns a mapping key formatted for use in a `key = value` pair.
+    fn key(&self, node: &Document) -> Result<String> {
+        let key = match node.as_value()? {
+            Document::String(s, _) => s.clone(),
+            Document::Str(s, _) => s.to_string(),
+            Document::Int(i) => i.format(Some(&Base::Dec)),
+            Document::Boolean(b) => b.to_string(),
+            k => return Err(Error::KeyTypeError(k.variant())),
+        };
+        Ok(key)
+    }
+
+    fn push_name(&mut self, name: &str) {
+        self.path.push(OwnedDocPath::Name(name.into()));
+    }
+
+    fn unrepresentable(&self, what: &str) -> Error {
+        Error::Unrepresentable("TOML", what.into()).at_path(self.path.clone())
+    }
+}
+
+// Returns `key` as a bare key if possible, otherwise as a quoted key.
+fn format_key(key: &str) -> String {
+    if is_bare_key(key) {
+        key.to_string()
+    } else {
+        let mut s = String::new();
+        let emitter = TomlEmitter::default();
+        // Writing to a String cannot fail.
+        emitter.escape_str(&mut s, key, false).unwrap();
+        format!("\"{}\"", s)
+    }
+}
+
+pub(crate) fn is_bare_key(key: &str) -> bool {
+    !key.is_empty()
+        && key
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+}
+
+/// Returns whether `s` is a TOML offset datetime, local datetime, local date
+/// or local time.
+pub(crate) fn is_datetime(s: &str) -> bool {
+    fn digits(s: &[u8], n: usize) -> bool {
+        s.len() >= n && s[..n].iter().all(u8::is_ascii_digit)
+    }
+    fn time(s: &[u8]) -> Option<&[u8]> {
+        if !(digits(s, 2) && s.get(2) == Some(&b':') && digits(&s[3..], 2)) {
+            return None;
+        }
+        if s.get(5) != Some(&b':') || !digits(&s[6..], 2) {
+            return None;
+        }
+        let mut rest = &s[8..];
+        if rest.first() == Some(&b'.') {
+            let n = rest[1..].iter().take_while(|b| b.is_ascii_digit()).count();
+            if n == 0 {
+                return None;
+            }
+            rest = &rest[1 + n..];
+        }
+        Some(rest)
+    }
+    let s = s.as_bytes();
+    let date = digits(s, 4)
+        && s.get(4) == Some(&b'-')
+        && digits(&s[5..], 2)
+        && s.get(7) == Some(&b'-')
+        && digits(&s[8..], 2);
+    if !date {
+        return time(s) == Some(&[]);
+    }
+    let rest = &s[10..];
+    if rest.is_empty() {
+        return true;
+    }
+    if !matches!(rest[0], b'T' | b't' | b' ') {
+        return false;
+    }
+    match time(&rest[1..]) {
+        Some([]) | Some([b'Z' | b'z']) => true,
+        Some([b'+' | b'-', offset @ ..]) => {
+            offset.len() == 5 && digits(offset, 2) && offset[2] == b':' && digits(&offset[3..], 2)
+        }
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+
+    fn kv(key: &'static str, value: Document<'static>) -> Document<'static> {
+        Document::Fragment(vec![Document::from(key), value])
+    }
+
+    #[test]
+    fn test_tables() -> Result<()> {
+        let doc = Document::Mapping(vec![
+            kv(
+                "server",
+                Document::Mapping(vec![
+                    kv("host", Document::from("localhost")),
+                    kv("port", Document::Int(Int::new(0x1F90u32, Base::Hex))),
+                ]),
+            ),
+            Document::Fragment(vec![
+                Document::Comment("The name".into(), CommentFormat::Standard),
+                Document::from("name"),
+                Document::from("example"),
+            ]),
+            kv(
+                "items",
+                Document::Sequence(vec![
+                    Document::Mapping(vec![kv("id", Document::Int(1u8.into()))]),
+                    Document::Mapping(vec![kv("id", Document::Int(2u8.into()))]),
+                ]),
+            ),
+        ]);
+        assert_eq!(
+            doc.to_toml().try_to_string()?,
+            r#"# The name
+name = "example"
+
+[server]
+host = "localhost"
+port = 0x1F90
+
+[[items]]
+id = 1
+
+[[items]]
+id = 2"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_values() -> Result<()> {
+        let doc = Document::Mapping(vec![
+            kv("neg", Document::Int(Int::new(-5i8, Base::Hex))),
+            kv("float", Document::Float(1.0)),
+            kv("inf", Document::Float(f64::NEG_INFINITY)),
+            kv(
+                "text",
+                Document::String("a \"quoted\"\nline\\".into(), StrFormat::Standard),
+            ),
+            kv(
+                "block",
+                Document::String("one\ntwo\"".into(), StrFormat::Multiline),
+            ),
+            kv(
+                "date",
+                Document::String("1979-05-27T07:32:00Z".into(), StrFormat::Unquoted),
+            ),
+            kv(
+                "point",
+                Document::Compact(Box::new(Document::Mapping(vec![
+                    kv("x", Document::Int(1u8.into())),
+                    kv("y", Document::Int(2u8.into())),
+                ]))),
+            ),
+            kv("empty", Document::Sequence(vec![])),
+            kv("a b", Document::Boolean(true)),
+        ]);
+        assert_eq!(
+            doc.to_toml().try_to_string()?,
+            r#"neg = -5
+float = 1.0
+inf = -inf
+text = "a \"quoted\"\nline\\"
+block = """
+one
+two\""""
+date = 1979-05-27T07:32:00Z
+point = { x = 1, y = 2 }
+empty = []
+"a b" = true"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_unrepresentable() -> Result<()> {
+        let doc = Document::Mapping(vec![kv(
+            "a",
+            Document::Mapping(vec![kv(
+                "b",
+                Document::Sequence(vec![Document::Int(1u8.into()), Document::Null]),
+            )]),
+        )]);
+        let err = doc.to_toml().try_to_string().unwrap_err();
+        assert_eq!(err.to_string(), "at a.b[1]: TOML cannot represent null");
+
+        let doc = Document::Sequence(vec![Document::Int(1u8.into())]);
+        let err = doc.to_toml().try_to_string().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "TOML cannot represent a top-level Sequence; the top level must be a mapping"
+        );
+
+        // TOML integers are 64-bit signed integers.
+        let doc = Document::Mapping(vec![kv("a", Document::Int(u64::MAX.into()))]);
+        let err = doc.to_toml().try_to_string().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at a: TOML cannot represent the integer 18446744073709551615"
+        );
+        let doc = Document::Mapping(vec![kv(
+            "b",
+            Document::Sequence(vec![Document::Int(Int::new(u128::MAX, Base::Hex))]),
+        )]);
+        let err = doc.to_toml().try_to_string().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "at b[0]: TOML cannot represent the integer 0xFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFF"
+        );
+        let doc = Document::Mapping(vec![
+            kv("min", Document::Int(i64::MIN.into())),
+            kv("max", Document::Int((i64::MAX as u64).into())),
+        ]);
+        assert_eq!(
+            doc.to_toml().try_to_string()?,
+            "min = -9223372036854775808\nmax = 9223372036854775807"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_is_datetime() {
+        assert!(is_datetime("1979-05-27T07:32:00Z"));
+        assert!(is_datetime("1979-05-27 07:32:00.999-07:00"));
+        assert!(is_datetime("1979-05-27"));
+        assert!(is_datetime("07:32:00"));
+        assert!(!is_datetime("1979-05-27T"));
+        assert!(!is_datetime("07:32"));
+        assert!(!is_datetime("07:32:00Z"));
+    }
+}
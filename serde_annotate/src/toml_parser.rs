@@ -0,0 +1,924 @@
+// Parser for TOML documents into serde-annotate `Document`s.
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::integer::Int;
+use crate::toml::is_datetime;
+
+type Result<T> = std::result::Result<T, Error>;
+
+// TOML builds its tables incrementally: a table header or dotted key may
+// add to a table defined earlier in the document.  The parser collects
+// the tables into this intermediate form and converts them into a
+// `Document` once the whole text has been read.
+#[derive(Default)]
+struct Table {
+    entries: Vec<Entry>,
+    // Whether the table has been defined by a `[header]`.
+    explicit: bool,
+    // Whether the table has been defined by a dotted key.
+    dotted: bool,
+}
+
+struct Entry {
+    key: String,
+    before: Vec<Document<'static>>,
+    value: Value,
+    after: Vec<Document<'static>>,
+}
+
+enum Value {
+    Document(Document<'static>),
+    Table(Table),
+    Array(Vec<Element>),
+}
+
+// An element of an array of tables.
+struct Element {
+    before: Vec<Document<'static>>,
+    table: Table,
+    after: Vec<Document<'static>>,
+}
+
+impl Table {
+    fn position(&self, key: &str) -> Option<usize> {
+        self.entries.iter().position(|e| e.key == key)
+    }
+
+    fn into_document(self) -> Document<'static> {
+        Document::Mapping(self.entries.into_iter().map(Entry::into_document).collect())
+    }
+}
+
+impl Entry {
+    fn new(key: &str, value: Value) -> Self {
+        Entry {
+            key: key.into(),
+            before: Vec::new(),
+            value,
+            after: Vec::new(),
+        }
+    }
+
+    fn into_document(self) -> Document<'static> {
+        let value = match self.value {
+            Value::Document(d) => d,
+            Value::Table(t) => t.into_document(),
+            Value::Array(elements) => Document::Sequence(
+                elements
+                    .into_iter()
+                    .map(|e| commented(e.before, e.table.into_document(), e.after))
+                    .collect(),
+            ),
+        };
+        let mut nodes = self.before;
+        nodes.push(Document::String(self.key, StrFormat::Standard));
+        nodes.push(value);
+        nodes.extend(self.after);
+        Document::Fragment(nodes)
+    }
+}
+
+// Wraps `value` in a fragment with its comments, if it has any.
+fn commented(
+    before: Vec<Document<'static>>,
+    value: Document<'static>,
+    after: Vec<Document<'static>>,
+) -> Document<'static> {
+    if before.is_empty() && after.is_empty() {
+        value
+    } else {
+        let mut nodes = before;
+        nodes.push(value);
+        nodes.extend(after);
+        Document::Fragment(nodes)
+    }
+}
+
+/// `TomlParser` parses TOML into a `Document`, retaining comments, integer
+/// bases and multi-line strings.
+///
+/// Tables and arrays of tables become mappings and sequences of mappings;
+/// inline tables become compact mappings.  `Document` has no datetime type,
+/// so datetimes are parsed into unquoted strings.
+pub struct TomlParser<'a> {
+    text: &'a str,
+    pos: usize,
+    lines: Vec<usize>,
+}
+
+impl<'a> TomlParser<'a> {
+    /// Creates a parser for `text`.
+    pub fn new(text: &'a str) -> Self {
+        let mut lines = vec![0];
+        for (i, ch) in text.char_indices() {
+            if ch == '\n' {
+                lines.push(i + 1);
+            }
+        }
+        TomlParser {
+            text,
+            pos: 0,
+            lines,
+        }
+    }
+
+    /// Parses the text into a `Document`.
+    pub fn parse(mut self) -> Result<Document<'static>> {
+        let mut root = Table::default();
+        // The keys of the table named by the most recent header.
+        let mut current = Vec::new();
+        let mut comments = Vec::new();
+        loop {
+            self.skip_to_content(&mut comments);
+            if self.at_eof() {
+                break;
+            }
+            let start = self.pos;
+            if self.peek() == Some(b'[') {
+                let array = self.peek_at(1) == Some(b'[');
+                self.pos += if array { 2 } else { 1 };
+                let keys = self.parse_keys()?;
+                let close: &[u8] = if array { b"]]" } else { b"]" };
+                if !self.bytes()[self.pos..].starts_with(close) {
+                    return self.error("expected `]`", self.pos);
+                }
+                self.pos += close.len();
+                let mut after = Vec::new();
+                self.end_of_line(&mut after)?;
+                let before = std::mem::take(&mut comments);
+                self.define(&mut root, &keys, array, before, after, start)?;
+                current = keys;
+            } else {
+                let (keys, value) = self.parse_pair()?;
+                let mut after = Vec::new();
+                self.end_of_line(&mut after)?;
+                let table = self.descend(&mut root, &current, false, start)?;
+                let entry = self.insert(table, &keys, value, start)?;
+                entry.before = std::mem::take(&mut comments);
+                entry.after = after;
+            }
+        }
+        let doc = root.into_document();
+        if comments.is_empty() {
+            Ok(doc)
+        } else {
+            comments.insert(0, doc);
+            Ok(Document::Fragment(comments))
+        }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        self.text.as_bytes()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.bytes().get(self.pos + n).copied()
+    }
+
+    fn at_eof(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn at_eol(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\r' | b'\n'))
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        match self.lines.binary_search(&pos) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        }
+    }
+
+    fn error<T>(&self, msg: &str, pos: usize) -> Result<T> {
+        let line = self.line_of(pos);
+        let start = self.lines[line];
+        let end = self.text[start..]
+            .find('\n')
+            .map(|e| start + e)
+            .unwrap_or(self.text.len());
+        Err(Error::SyntaxError(
+            msg.into(),
+            line + 1,
+            pos - start + 1,
+            self.text[start..end].trim_end().into(),
+            "^",
+        ))
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_newline(&mut self) {
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+    }
+
+    // Reads a `#` comment through the end of the line.
+    fn read_comment(&mut self) -> &'a str {
+        let start = self.pos + 1;
+        while !self.at_eol() {
+            self.pos += 1;
+        }
+        let c = &self.text[start..self.pos];
+        c.strip_prefix(' ').unwrap_or(c).trim_end()
+    }
+
+    // Skips blank lines and comment lines.  Consecutive comment lines are
+    // gathered into a single comment node.
+    fn skip_to_content(&mut self, comments: &mut Vec<Document<'static>>) {
+        let mut group = Vec::new();
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some(b'#') => {
+                    group.push(self.read_comment());
+                    self.skip_newline();
+                }
+                Some(b'\r' | b'\n') => {
+                    if !group.is_empty() {
+                        comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+                        group.clear();
+                    }
+                    self.skip_newline();
+                }
+                _ => break,
+            }
+        }
+        if !group.is_empty() {
+            comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+        }
+    }
+
+    // Consumes the remainder of the current line, which may only hold a comment.
+    fn end_of_line(&mut self, nodes: &mut Vec<Document<'static>>) -> Result<()> {
+        self.skip_space();
+        if self.peek() == Some(b'#') {
+            let c = self.read_comment();
+            nodes.push(Document::Comment(c.into(), CommentFormat::Hash));
+        }
+        if !self.at_eol() {
+            return self.error("unexpected character", self.pos);
+        }
+        self.skip_newline();
+        Ok(())
+    }
+
+    // Returns the table named by `keys`, creating tables as needed.  Arrays
+    // of tables resolve to their most recently defined element.  When
+    // `dotted` is set, `keys` are the parents of a dotted key, which may not
+    // reach into tables defined by a header.
+    fn descend<'t>(
+        &self,
+        mut table: &'t mut Table,
+        keys: &[String],
+        dotted: bool,
+        pos: usize,
+    ) -> Result<&'t mut Table> {
+        for key in keys {
+            let i = match table.position(key) {
+                Some(i) => i,
+                None => {
+                    let entry = Entry::new(
+                        key,
+                        Value::Table(Table {
+                            dotted,
+                            ..Default::default()
+                        }),
+                    );
+                    table.entries.push(entry);
+                    table.entries.len() - 1
+                }
+            };
+            table = match &mut table.entries[i].value {
+                Value::Table(t) if dotted && t.explicit => {
+                    return self.error(&format!("key `{}` is already defined", key), pos);
+                }
+                Value::Table(t) => t,
+                // Unwrap is ok: arrays of tables always have an element.
+                Value::Array(a) => &mut a.last_mut().unwrap().table,
+                Value::Document(_) => {
+                    return self.error(&format!("key `{}` is not a table", key), pos);
+                }
+            };
+        }
+        Ok(table)
+    }
+
+    // Defines the table or array of tables named by a header.
+    fn define(
+        &self,
+        root: &mut Table,
+        keys: &[String],
+        array: bool,
+        before: Vec<Document<'static>>,
+        after: Vec<Document<'static>>,
+        pos: usize,
+    ) -> Result<()> {
+        // Unwrap is ok: `parse_keys` returns at least one key.
+        let (key, parents) = keys.split_last().unwrap();
+        let table = self.descend(root, parents, false, pos)?;
+        let explicit = Table {
+            explicit: true,
+            ..Default::default()
+        };
+        let i = match table.position(key) {
+            Some(i) => i,
+            None if array => {
+                table
+                    .entries
+                    .push(Entry::new(key, Value::Array(Vec::new())));
+                table.entries.len() - 1
+            }
+            None => {
+                let mut entry = Entry::new(key, Value::Table(explicit));
+                entry.before = before;
+                entry.after = after;
+                table.entries.push(entry);
+                return Ok(());
+            }
+        };
+        let entry = &mut table.entries[i];
+        match &mut entry.value {
+            Value::Array(a) if array => a.push(Element {
+                before,
+                table: explicit,
+                after,
+            }),
+            Value::Table(t) if !array && !t.explicit && !t.dotted => {
+                t.explicit = true;
+                entry.before.extend(before);
+                entry.after.extend(after);
+            }
+            _ => return self.error(&format!("key `{}` is already defined", key), pos),
+        }
+        Ok(())
+    }
+
+    // Inserts a value at the dotted key `keys` within `table`.
+    fn insert<'t>(
+        &self,
+        table: &'t mut Table,
+        keys: &[String],
+        value: Document<'static>,
+        pos: usize,
+    ) -> Result<&'t mut Entry> {
+        // Unwrap is ok: `parse_keys` returns at least one key.
+        let (key, parents) = keys.split_last().unwrap();
+        let table = self.descend(table, parents, true, pos)?;
+        if table.position(key).is_some() {
+            return self.error(&format!("key `{}` is already defined", key), pos);
+        }
+        table.entries.push(Entry::new(key, Value::Document(value)));
+        // Unwrap is ok: we just pushed an entry.
+        Ok(table.entries.last_mut().unwrap())
+    }
+
+    // Parses a possibly dotted key.
+    fn parse_keys(&mut self) -> Result<Vec<String>> {
+        let mut keys = Vec::new();
+        loop {
+            self.skip_space();
+            let key = match self.peek() {
+                Some(b'"') => self.parse_basic_string()?,
+                Some(b'\'') => self.parse_literal_string()?,
+                _ => {
+                    let start = self.pos;
+                    while matches!(self.peek(), Some(b) if b.is_ascii_alphanumeric() || b == b'_' || b == b'-')
+                    {
+                        self.pos += 1;
+                    }
+                    if start == self.pos {
+                        return self.error("expected a key", self.pos);
+                    }
+                    self.text[start..self.pos].to_string()
+                }
+            };
+            keys.push(key);
+            self.skip_space();
+            if self.peek() != Some(b'.') {
+                return Ok(keys);
+            }
+            self.pos += 1;
+        }
+    }
+
+    // Parses `key = value`.
+    fn parse_pair(&mut self) -> Result<(Vec<String>, Document<'static>)> {
+        let keys = self.parse_keys()?;
+        if self.peek() != Some(b'=') {
+            return self.error("expected `=`", self.pos);
+        }
+        self.pos += 1;
+        self.skip_space();
+        let value = self.parse_value()?;
+        Ok((keys, value))
+    }
+
+    fn parse_value(&mut self) -> Result<Document<'static>> {
+        let rest = &self.bytes()[self.pos..];
+        match self.peek() {
+            Some(b'"') if rest.starts_with(b"\"\"\"") => Ok(Document::String(
+                self.parse_multiline_string(b'"')?,
+                StrFormat::Multiline,
+            )),
+            Some(b'\'') if rest.starts_with(b"'''") => Ok(Document::String(
+                self.parse_multiline_string(b'\'')?,
+                StrFormat::Multiline,
+            )),
+            Some(b'"') => Ok(Document::String(
+                self.parse_basic_string()?,
+                StrFormat::Standard,
+            )),
+            Some(b'\'') => Ok(Document::String(
+                self.parse_literal_string()?,
+                StrFormat::Standard,
+            )),
+            Some(b'[') => self.parse_array(),
+            Some(b'{') => self.parse_inline_table(),
+            Some(_) => self.parse_scalar(),
+            None => self.error("expected a value", self.pos),
+        }
+    }
+
+    // Parses booleans, numbers and datetimes.
+    fn parse_scalar(&mut self) -> Result<Document<'static>> {
+        let start = self.pos;
+        while matches!(self.peek(), Some(b) if b.is_ascii_alphanumeric() || b"_+-.:".contains(&b)) {
+            self.pos += 1;
+        }
+        // A space may separate the date and time of a datetime.
+        if self.pos - start == 10
+            && self.peek() == Some(b' ')
+            && is_datetime(&self.text[start..self.pos])
+            && matches!(
+                self.bytes().get(self.pos + 1..self.pos + 4),
+                Some([b'0'..=b'9', b'0'..=b'9', b':'])
+            )
+        {
+            self.pos += 1;
+            while matches!(self.peek(), Some(b) if b.is_ascii_alphanumeric() || b"+-.:".contains(&b))
+            {
+                self.pos += 1;
+            }
+        }
+        let token = &self.text[start..self.pos];
+        let value = match token {
+            "" => return self.error("expected a value", start),
+            "true" => Document::Boolean(true),
+            "false" => Document::Boolean(false),
+            "inf" | "+inf" => Document::Float(f64::INFINITY),
+            "-inf" => Document::Float(f64::NEG_INFINITY),
+            "nan" | "+nan" | "-nan" => Document::Float(f64::NAN),
+            _ if is_datetime(token) => Document::String(token.into(), StrFormat::Unquoted),
+            _ => match Self::parse_number(token) {
+                // TOML integers are 64-bit signed integers.
+                Some(Document::Int(i)) if i.checked::<i64>().is_none() => {
+                    return self.error("integer out of range", start)
+                }
+                Some(value) => value,
+                None => return self.error("invalid value", start),
+            },
+        };
+        Ok(value)
+    }
+
+    fn parse_number(token: &str) -> Option<Document<'static>> {
+        // Underscores must be between digits.
+        let bytes = token.as_bytes();
+        for (i, &b) in bytes.iter().enumerate() {
+            if b == b'_'
+                && !(i > 0
+                    && bytes[i - 1].is_ascii_hexdigit()
+                    && bytes.get(i + 1).is_some_and(u8::is_ascii_hexdigit))
+            {
+                return None;
+            }
+        }
+        let text = token.replace('_', "");
+        if let Some(digits) = text
+            .strip_prefix("0x")
+            .or_else(|| text.strip_prefix("0o"))
+            .or_else(|| text.strip_prefix("0b"))
+        {
+            if digits.is_empty() || digits.starts_with(['+', '-']) {
+                return None;
+            }
+            return Int::from_str_radix(&text, 0).ok().map(Document::Int);
+        }
+        let unsigned = text.strip_prefix(['+', '-']).unwrap_or(&text);
+        let digits = unsigned
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(unsigned.len());
+        // Leading zeros are not permitted.
+        if digits == 0 || (digits > 1 && unsigned.starts_with('0')) {
+            return None;
+        }
+        if digits == unsigned.len() {
+            Int::from_str_radix(&text, 10).ok().map(Document::Int)
+        } else if unsigned[digits..].starts_with(['.', 'e', 'E'])
+            && !unsigned.ends_with(['.', 'e', 'E', '+', '-'])
+            && !unsigned.contains(".e")
+            && !unsigned.contains(".E")
+        {
+            text.parse::<f64>().ok().map(Document::Float)
+        } else {
+            None
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.text[self.pos..].chars().next()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    fn unicode_escape(&mut self, digits: usize) -> Result<char> {
+        let start = self.pos;
+        let hex = self.text.get(start..start + digits).unwrap_or("");
+        let value = match u32::from_str_radix(hex, 16) {
+            Ok(v) if hex.len() == digits => v,
+            _ => return self.error("invalid unicode escape", start),
+        };
+        self.pos += digits;
+        match char::from_u32(value) {
+            Some(ch) => Ok(ch),
+            None => self.error("invalid unicode escape", start),
+        }
+    }
+
+    // Parses the escape sequence following a backslash.
+    fn escape(&mut self, s: &mut String) -> Result<()> {
+        match self.next_char() {
+            Some('b') => s.push('\x08'),
+            Some('t') => s.push('\t'),
+            Some('n') => s.push('\n'),
+            Some('f') => s.push('\x0c'),
+            Some('r') => s.push('\r'),
+            Some('e') => s.push('\x1b'),
+            Some('"') => s.push('"'),
+            Some('\\') => s.push('\\'),
+            Some('u') => s.push(self.unicode_escape(4)?),
+            Some('U') => s.push(self.unicode_escape(8)?),
+            Some(ch) => return Err(Error::EscapeError(ch)),
+            None => return self.error("unterminated string", self.pos),
+        }
+        Ok(())
+    }
+
+    fn parse_basic_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.next_char() {
+                Some('"') => return Ok(s),
+                Some('\\') => self.escape(&mut s)?,
+                Some('\n') | None => return self.error("unterminated string", start),
+                Some(ch) => s.push(ch),
+            }
+        }
+    }
+
+    fn parse_literal_string(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        match self.text[self.pos..].find(['\'', '\n']) {
+            Some(end) if self.bytes()[self.pos + end] == b'\'' => {
+                let s = self.text[self.pos..self.pos + end].to_string();
+                self.pos += end + 1;
+                Ok(s)
+            }
+            _ => self.error("unterminated string", start),
+        }
+    }
+
+    // Parses a `"""` or `'''` delimited string.
+    fn parse_multiline_string(&mut self, quote: u8) -> Result<String> {
+        let start = self.pos;
+        self.pos += 3;
+        // A newline immediately following the opening delimiter is trimmed.
+        self.skip_newline();
+        let mut s = String::new();
+        loop {
+            if self.peek() == Some(quote)
+                && self.peek_at(1) == Some(quote)
+                && self.peek_at(2) == Some(quote)
+            {
+                // Up to two quotes may immediately precede the closing delimiter.
+                let mut n = 3;
+                while n < 5 && self.peek_at(n) == Some(quote) {
+                    n += 1;
+                }
+                for _ in 3..n {
+                    s.push(quote as char);
+                }
+                self.pos += n;
+                return Ok(s);
+            }
+            match self.next_char() {
+                Some('\\') if quote == b'"' => {
+                    // A backslash at the end of a line trims the following
+                    // whitespace and newlines.
+                    let mark = self.pos;
+                    self.skip_space();
+                    if self.at_eol() && !self.at_eof() {
+                        while matches!(self.peek(), Some(b' ' | b'\t' | b'\r' | b'\n')) {
+                            self.pos += 1;
+                        }
+                    } else {
+                        self.pos = mark;
+                        self.escape(&mut s)?;
+                    }
+                }
+                Some(ch) => s.push(ch),
+                None => return self.error("unterminated string", start),
+            }
+        }
+    }
+
+    // Skips whitespace, newlines and comments within an array.
+    fn skip_array_space(&mut self, comments: &mut Vec<Document<'static>>) {
+        loop {
+            self.skip_to_content(comments);
+            if !self.at_eol() || self.at_eof() {
+                break;
+            }
+        }
+    }
+
+    fn parse_array(&mut self) -> Result<Document<'static>> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut items = Vec::new();
+        loop {
+            let mut before = Vec::new();
+            self.skip_array_space(&mut before);
+            match self.peek() {
+                Some(b']') => {
+                    self.pos += 1;
+                    return Ok(Document::Sequence(items));
+                }
+                None => return self.error("unterminated array", start),
+                _ => {}
+            }
+            let value = self.parse_value()?;
+            let mut after = Vec::new();
+            self.skip_space();
+            if self.peek() == Some(b',') {
+                self.pos += 1;
+                self.skip_space();
+                if self.peek() == Some(b'#') {
+                    let c = self.read_comment();
+                    after.push(Document::Comment(c.into(), CommentFormat::Hash));
+                }
+                items.push(commented(before, value, after));
+            } else {
+                if self.peek() == Some(b'#') {
+                    let c = self.read_comment();
+                    after.push(Document::Comment(c.into(), CommentFormat::Hash));
+                }
+                items.push(commented(before, value, after));
+                let mut comments = Vec::new();
+                self.skip_array_space(&mut comments);
+                if self.peek() != Some(b']') {
+                    return self.error("expected `,` or `]`", self.pos);
+                }
+                self.pos += 1;
+                return Ok(Document::Sequence(items));
+            }
+        }
+    }
+
+    fn parse_inline_table(&mut self) -> Result<Document<'static>> {
+        self.pos += 1;
+        let mut table = Table::default();
+        self.skip_space();
+        if self.peek() == Some(b'}') {
+            self.pos += 1;
+        } else {
+            loop {
+                self.skip_space();
+                let start = self.pos;
+                let (keys, value) = self.parse_pair()?;
+                self.insert(&mut table, &keys, value, start)?;
+                self.skip_space();
+                match self.peek() {
+                    Some(b',') => self.pos += 1,
+                    Some(b'}') => {
+                        self.pos += 1;
+                        break;
+                    }
+                    _ => return self.error("expected `,` or `}`", self.pos),
+                }
+            }
+        }
+        Ok(Document::Compact(table.into_document().into()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer::Base;
+    use anyhow::{anyhow, Result};
+
+    fn parse(text: &str) -> Result<Document<'static>> {
+        Ok(TomlParser::new(text).parse()?)
+    }
+
+    // Parses `v = <text>` and returns the value.
+    fn value(text: &str) -> Result<Document<'static>> {
+        match parse(&format!("v = {}", text))? {
+            Document::Mapping(m) => Ok(m[0].as_kv()?.1.clone()),
+            _ => Err(anyhow!("Didn't return Document::Mapping()")),
+        }
+    }
+
+    fn parse_integer(text: &str) -> Result<(i128, Base)> {
+        if let Document::Int(int) = value(text)? {
+            Ok((int.clone().into(), int.base()))
+        } else {
+            Err(anyhow!("Didn't return Document::Int()"))
+        }
+    }
+
+    fn parse_string(text: &str) -> Result<(String, StrFormat)> {
+        if let Document::String(s, f) = value(text)? {
+            Ok((s, f))
+        } else {
+            Err(anyhow!("Didn't return Document::String()"))
+        }
+    }
+
+    #[test]
+    fn test_scalars() -> Result<()> {
+        assert!(matches!(value("true")?, Document::Boolean(true)));
+        assert!(matches!(value("false")?, Document::Boolean(false)));
+        assert!(matches!(value("3.5")?, Document::Float(f) if f == 3.5));
+        assert!(matches!(value("-1_0.5e+2")?, Document::Float(f) if f == -1050.0));
+        assert!(matches!(value("-inf")?, Document::Float(f) if f == f64::NEG_INFINITY));
+        assert!(matches!(value("nan")?, Document::Float(f) if f.is_nan()));
+        assert_eq!(
+            parse_string(r#""a\tb\u2122\"""#)?,
+            ("a\tb\u{2122}\"".into(), StrFormat::Standard)
+        );
+        assert_eq!(parse_string(r"'C:\path'")?.0, r"C:\path");
+        assert_eq!(
+            parse_string("\"\"\"\none\ntwo \\\n    three\"\"\"\"")?,
+            ("one\ntwo three\"".into(), StrFormat::Multiline)
+        );
+        assert_eq!(parse_string("'''\nraw\\n'''")?.0, "raw\\n");
+        assert_eq!(
+            parse_string("1979-05-27 07:32:00Z")?,
+            ("1979-05-27 07:32:00Z".into(), StrFormat::Unquoted)
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_integers() -> Result<()> {
+        assert_eq!(parse_integer("1_234")?, (1234, Base::Dec));
+        assert_eq!(parse_integer("-5678")?, (-5678, Base::Dec));
+        assert_eq!(parse_integer("+0")?, (0, Base::Dec));
+        assert_eq!(parse_integer("0xdead_BEEF")?, (0xdeadbeef, Base::Hex));
+        assert_eq!(parse_integer("0o755")?, (0o755, Base::Oct));
+        assert_eq!(parse_integer("0b1010")?, (10, Base::Bin));
+        assert!(value("012").is_err());
+        assert!(value("1__2").is_err());
+        assert!(value("-0x10").is_err());
+        // Integers must fit in an `i64`.
+        assert_eq!(
+            parse_integer("9223372036854775807")?,
+            (i64::MAX as i128, Base::Dec)
+        );
+        assert_eq!(
+            parse_integer("-9223372036854775808")?,
+            (i64::MIN as i128, Base::Dec)
+        );
+        assert_eq!(
+            parse_integer("0x7FFFFFFFFFFFFFFF")?,
+            (i64::MAX as i128, Base::Hex)
+        );
+        let err = parse("a = 9223372036854775808").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: integer out of range at 1:5\n| a = 9223372036854775808\n|     ^"
+        );
+        assert!(value("-9223372036854775809").is_err());
+        assert!(value("0xFFFFFFFFFFFFFFFF").is_err());
+        assert!(value("0o1777777777777777777777").is_err());
+        assert!(value("340282366920938463463374607431768211455").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_tables() -> Result<()> {
+        let doc = parse(
+            r#"
+# Title
+title = "example"
+a.b = 1
+
+[server]
+host = "localhost" # Host
+
+[a.c]
+d = 2
+
+[[items]]
+id = 1
+
+# Second
+[[items]]
+id = 2
+point = { x = 1, y.z = 2 }
+"#,
+        )?;
+        assert_eq!(
+            doc.to_toml().try_to_string()?,
+            r#"# Title
+title = "example"
+
+[a]
+b = 1
+
+[a.c]
+d = 2
+
+[server]
+host = "localhost" # Host
+
+[[items]]
+id = 1
+
+# Second
+[[items]]
+id = 2
+point = { x = 1, y = { z = 2 } }"#
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        assert!(parse("a = 1\na = 2").is_err());
+        assert!(parse("[a]\n[a]").is_err());
+        assert!(parse("a = 1\n[a]").is_err());
+        assert!(parse("a = 1\n[[a]]").is_err());
+        assert!(parse("a.b.c = 1\n[a]").is_err());
+        assert!(parse("a.b.c = 1\n[a.b]").is_err());
+        assert!(parse("[a.b]\nc = 1\n[a]\nb.d = 2").is_err());
+        assert!(parse("a = ").is_err());
+        assert!(parse("a = [1, 2").is_err());
+        assert!(parse("a = \"unterminated").is_err());
+        assert!(parse("a = 1 b = 2").is_err());
+        let err = parse("a = 1\nb = @").unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "syntax error: expected a value at 2:5\n| b = @\n|     ^"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let text = r#"# Signature
+signature = 0x50444653
+minor = 6 # Minor
+list = [
+  0b0101,
+  # Quoted
+  "needs: quotes",
+]
+bytes = [1, 2, 3]
+hexdump = """
+00000000  45 76 65 72 79 77 68 65  72 65 20 74 68 61 74 20  |Everywhere that |
+00000010  4d 61 72 79 20 77 65 6e  74                       |Mary went|"""
+when = 1979-05-27T07:32:00-08:00
+nes = { prg = [1, 0x8000] }
+
+# Empty
+[empty]
+
+[[a.b]]
+c = "d"
+
+[[a.b]]
+c = "e""#;
+        let doc = parse(text)?;
+        assert_eq!(doc.to_toml().try_to_string()?, text);
+        Ok(())
+    }
+}
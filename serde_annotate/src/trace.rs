@@ -0,0 +1,788 @@
+// Tracing the shape of a type through its `Deserialize` implementation.
+//
+// The tracer is a `Deserializer` which hands a sample value to every visitor
+// and records what the visitor asked for.  One pass over `T::deserialize`
+// sees a single variant of each enum, so the type is deserialized until
+// every variant has been seen, then once more for each struct field with
+// the field left out to learn whether it is required.
+use serde::de::{
+    self, DeserializeSeed, EnumAccess, IntoDeserializer, MapAccess, SeqAccess, VariantAccess,
+    Visitor,
+};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::marker::PhantomData;
+
+use crate::error::Error;
+use crate::integer::IntValue;
+
+/// The shape of a value, as requested by its `Deserialize` implementation.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Shape {
+    Bool,
+    Int(IntValue),
+    Float,
+    Char,
+    Str,
+    Bytes,
+    Unit,
+    Option(Box<Shape>),
+    Seq(Box<Shape>),
+    Tuple(Vec<Shape>),
+    Map(Box<Shape>, Box<Shape>),
+    /// A struct or enum, the shape of which is in `Trace::containers`.
+    Named(&'static str),
+}
+
+/// The shape of a named struct or enum, or of an enum variant.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Container {
+    Unit,
+    Newtype(Shape),
+    Tuple(Vec<Shape>),
+    Struct(Vec<Field>),
+    Enum(Vec<Variant>),
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Field {
+    pub name: &'static str,
+    pub shape: Shape,
+    pub required: bool,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) struct Variant {
+    pub name: &'static str,
+    /// The shape of the variant, or `None` if it hasn't been seen yet.
+    pub shape: Option<Container>,
+    // Whether the variant contains its own enum.
+    recursive: bool,
+}
+
+/// The shape of a type and of the named types it contains.
+pub(crate) struct Trace {
+    pub root: Shape,
+    pub containers: HashMap<&'static str, Container>,
+}
+
+/// Traces the shape of `T`.
+pub(crate) fn trace<'de, T: de::Deserialize<'de>>() -> std::result::Result<Trace, Error> {
+    let mut tracer = Tracer::default();
+    let root = loop {
+        tracer.chosen.clear();
+        tracer.choices.clear();
+        tracer.route = tracer
+            .incomplete()
+            .and_then(|name| tracer.enum_routes.get(name))
+            .cloned()
+            .unwrap_or_default();
+        let (result, shape) = tracer.trace(PhantomData::<T>);
+        match result {
+            Ok(_) => tracer.tried.clear(),
+            Err(TraceError::Recursion) if !tracer.chosen.is_empty() => {
+                let chosen = std::mem::take(&mut tracer.chosen);
+                tracer.tried.extend(chosen);
+                continue;
+            }
+            Err(e) => return Err(e.into()),
+        }
+        match tracer.incomplete() {
+            None => break shape.unwrap_or(Shape::Unit),
+            Some(name) if tracer.chosen.is_empty() => {
+                return Err(TraceError::Message(format!(
+                    "the variants of `{}` can't all be reached",
+                    name
+                ))
+                .into());
+            }
+            Some(_) => {}
+        }
+    };
+
+    // A field is required if `T` can't be deserialized without it.
+    tracer.exploring = false;
+    let structs = tracer.struct_routes.keys().copied().collect::<Vec<_>>();
+    for id in structs {
+        let fields = match tracer.fields(id) {
+            Some(fields) => fields.iter().map(|f| f.name).collect::<Vec<_>>(),
+            None => continue,
+        };
+        for field in fields {
+            tracer.choices.clear();
+            tracer.route = tracer.struct_routes[&id].clone();
+            tracer.omit = Some((id, field));
+            tracer.omitted = false;
+            let (result, _) = tracer.trace(PhantomData::<T>);
+            let optional = result.is_ok() && tracer.omitted;
+            if let Some(f) = tracer
+                .fields_mut(id)
+                .and_then(|fields| fields.iter_mut().find(|f| f.name == field))
+            {
+                f.required = !optional;
+            }
+        }
+    }
+    Ok(Trace {
+        root,
+        containers: tracer.containers,
+    })
+}
+
+#[derive(Debug)]
+enum TraceError {
+    // The value can't be built without recursing forever.
+    Recursion,
+    Message(String),
+}
+
+impl Display for TraceError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TraceError::Recursion => write!(f, "the type can't be traced: it contains itself"),
+            TraceError::Message(m) => write!(f, "{}", m),
+        }
+    }
+}
+
+impl std::error::Error for TraceError {}
+
+impl de::Error for TraceError {
+    fn custom<T: Display>(msg: T) -> Self {
+        TraceError::Message(msg.to_string())
+    }
+}
+
+impl From<TraceError> for Error {
+    fn from(e: TraceError) -> Self {
+        Error::Deserialize(e.to_string())
+    }
+}
+
+type Result<T> = std::result::Result<T, TraceError>;
+
+// Identifies a struct or a struct variant of an enum.
+type StructId = (&'static str, Option<&'static str>);
+
+// The enum variants chosen on the way to a container.
+type Route = HashMap<&'static str, usize>;
+
+struct Tracer {
+    containers: HashMap<&'static str, Container>,
+    // The names of the containers in the order they were first seen.
+    order: Vec<&'static str>,
+    // The containers being deserialized, along with the chosen variant.
+    stack: Vec<(&'static str, Option<usize>)>,
+    // Whether unseen variants are chosen and shapes are recorded.  The
+    // passes leaving out a field only look at whether `T` deserializes.
+    exploring: bool,
+    // The unseen variants chosen in this pass.
+    chosen: Vec<(&'static str, usize)>,
+    // Unseen variants which couldn't be traced since the last complete pass.
+    tried: HashSet<(&'static str, usize)>,
+    // The variants chosen in this pass.
+    choices: Route,
+    // The variants to choose in this pass.
+    route: Route,
+    enum_routes: HashMap<&'static str, Route>,
+    struct_routes: HashMap<StructId, Route>,
+    // The field to leave out in this pass, and whether it was.
+    omit: Option<(StructId, &'static str)>,
+    omitted: bool,
+}
+
+impl Default for Tracer {
+    fn default() -> Self {
+        Tracer {
+            containers: HashMap::new(),
+            order: Vec::new(),
+            stack: Vec::new(),
+            exploring: true,
+            chosen: Vec::new(),
+            tried: HashSet::new(),
+            choices: Route::new(),
+            route: Route::new(),
+            enum_routes: HashMap::new(),
+            struct_routes: HashMap::new(),
+            omit: None,
+            omitted: false,
+        }
+    }
+}
+
+impl Tracer {
+    // Deserializes a value from `seed`, returning it with its shape.
+    fn trace<'de, S: DeserializeSeed<'de>>(
+        &mut self,
+        seed: S,
+    ) -> (Result<S::Value>, Option<Shape>) {
+        let mut probe = Probe {
+            tracer: self,
+            shape: None,
+        };
+        let result = seed.deserialize(&mut probe);
+        (result, probe.shape)
+    }
+
+    // Whether the value being deserialized is inside another value of the
+    // same type.  Such values are built as small as possible and their
+    // shapes aren't recorded.
+    fn replaying(&self) -> bool {
+        self.stack
+            .iter()
+            .enumerate()
+            .any(|(i, (name, _))| self.stack[..i].iter().any(|(n, _)| n == name))
+    }
+
+    fn enter(&mut self, name: &'static str, variant: Option<usize>) -> Result<()> {
+        let mut depth = 0;
+        for (n, v) in self.stack.iter() {
+            if *n == name {
+                depth += 1;
+                if let (Some(v), Some(Container::Enum(variants))) = (v, self.containers.get_mut(n))
+                {
+                    variants[*v].recursive = true;
+                }
+            }
+        }
+        if depth > 1 {
+            return Err(TraceError::Recursion);
+        }
+        self.stack.push((name, variant));
+        Ok(())
+    }
+
+    fn leave(&mut self) {
+        self.stack.pop();
+    }
+
+    // Records the shape of the container `name`.
+    fn record(&mut self, name: &'static str, container: Container) -> Result<()> {
+        if !self.exploring || self.replaying() {
+            return Ok(());
+        }
+        match self.containers.get(name) {
+            None => {
+                if matches!(container, Container::Struct(_)) {
+                    self.struct_routes
+                        .insert((name, None), self.choices.clone());
+                }
+                self.containers.insert(name, container);
+                self.order.push(name);
+                Ok(())
+            }
+            Some(c) if *c == container => Ok(()),
+            Some(_) => Err(conflict(name)),
+        }
+    }
+
+    // Records the shape of the variant `index` of the enum `name`.
+    fn record_variant(
+        &mut self,
+        name: &'static str,
+        index: usize,
+        container: Container,
+    ) -> Result<()> {
+        if !self.exploring || self.replaying() {
+            return Ok(());
+        }
+        let Some(Container::Enum(variants)) = self.containers.get_mut(name) else {
+            return Err(conflict(name));
+        };
+        let variant = &mut variants[index];
+        match &variant.shape {
+            None => {
+                if matches!(container, Container::Struct(_)) {
+                    self.struct_routes
+                        .insert((name, Some(variant.name)), self.choices.clone());
+                }
+                variant.shape = Some(container);
+                Ok(())
+            }
+            Some(c) if *c == container => Ok(()),
+            Some(_) => Err(conflict(name)),
+        }
+    }
+
+    // Chooses the variant of the enum `name` to deserialize.
+    fn choose(&mut self, name: &'static str, names: &'static [&'static str]) -> Result<usize> {
+        if !self.containers.contains_key(name) {
+            let variants = names
+                .iter()
+                .map(|&name| Variant {
+                    name,
+                    shape: None,
+                    recursive: false,
+                })
+                .collect();
+            self.containers.insert(name, Container::Enum(variants));
+            self.order.push(name);
+            self.enum_routes.insert(name, self.choices.clone());
+        }
+        let Some(Container::Enum(variants)) = self.containers.get(name) else {
+            return Err(conflict(name));
+        };
+        let seen = |v: &&Variant| v.shape.is_some();
+        let index = if self.replaying() || self.stack.iter().any(|(n, _)| *n == name) {
+            // Build the inner value with a variant which doesn't recurse.
+            variants
+                .iter()
+                .position(|v| seen(&v) && !v.recursive)
+                .ok_or(TraceError::Recursion)?
+        } else if let Some(i) = variants
+            .iter()
+            .enumerate()
+            .position(|(i, v)| self.exploring && !seen(&v) && !self.tried.contains(&(name, i)))
+        {
+            self.chosen.push((name, i));
+            i
+        } else if let Some(&i) = self.route.get(name) {
+            i
+        } else {
+            variants
+                .iter()
+                .position(|v| seen(&v))
+                .ok_or(TraceError::Recursion)?
+        };
+        self.choices.insert(name, index);
+        Ok(index)
+    }
+
+    // Returns the first enum with a variant which hasn't been seen.
+    fn incomplete(&self) -> Option<&'static str> {
+        self.order.iter().copied().find(|name| {
+            matches!(&self.containers[name], Container::Enum(variants)
+                if variants.iter().any(|v| v.shape.is_none()))
+        })
+    }
+
+    fn fields(&self, (name, variant): StructId) -> Option<&Vec<Field>> {
+        match (self.containers.get(name)?, variant) {
+            (Container::Struct(fields), None) => Some(fields),
+            (Container::Enum(variants), Some(variant)) => {
+                match &variants.iter().find(|v| v.name == variant)?.shape {
+                    Some(Container::Struct(fields)) => Some(fields),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    fn fields_mut(&mut self, (name, variant): StructId) -> Option<&mut Vec<Field>> {
+        match (self.containers.get_mut(name)?, variant) {
+            (Container::Struct(fields), None) => Some(fields),
+            (Container::Enum(variants), Some(variant)) => {
+                match &mut variants.iter_mut().find(|v| v.name == variant)?.shape {
+                    Some(Container::Struct(fields)) => Some(fields),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+}
+
+fn conflict(name: &str) -> TraceError {
+    TraceError::Message(format!("different types are named `{}`", name))
+}
+
+// Deserializes a sample value and records its shape.
+struct Probe<'t> {
+    tracer: &'t mut Tracer,
+    shape: Option<Shape>,
+}
+
+macro_rules! deserialize_int {
+    ($($method:ident $visit:ident $variant:ident)*) => {$(
+        fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+            self.shape = Some(Shape::Int(IntValue::$variant(0)));
+            visitor.$visit(0)
+        }
+    )*};
+}
+
+impl<'de, 'a, 't> de::Deserializer<'de> for &'a mut Probe<'t> {
+    type Error = TraceError;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value> {
+        Err(TraceError::Message(
+            "types which depend on the document's structure (e.g. untagged or internally \
+             tagged enums) can't be traced"
+                .into(),
+        ))
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Bool);
+        visitor.visit_bool(false)
+    }
+
+    deserialize_int! {
+        deserialize_i8 visit_i8 I8
+        deserialize_i16 visit_i16 I16
+        deserialize_i32 visit_i32 I32
+        deserialize_i64 visit_i64 I64
+        deserialize_i128 visit_i128 I128
+        deserialize_u8 visit_u8 U8
+        deserialize_u16 visit_u16 U16
+        deserialize_u32 visit_u32 U32
+        deserialize_u64 visit_u64 U64
+        deserialize_u128 visit_u128 U128
+    }
+
+    fn deserialize_f32<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Float);
+        visitor.visit_f32(0.0)
+    }
+
+    fn deserialize_f64<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Float);
+        visitor.visit_f64(0.0)
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Char);
+        visitor.visit_char('0')
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Str);
+        visitor.visit_borrowed_str("")
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Bytes);
+        visitor.visit_borrowed_bytes(b"")
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        if self.tracer.replaying() {
+            self.shape = Some(Shape::Option(Box::new(Shape::Unit)));
+            return visitor.visit_none();
+        }
+        let mut probe = Probe {
+            tracer: &mut *self.tracer,
+            shape: None,
+        };
+        let result = visitor.visit_some(&mut probe);
+        let inner = probe.shape.unwrap_or(Shape::Unit);
+        self.shape = Some(Shape::Option(Box::new(inner)));
+        result
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.shape = Some(Shape::Unit);
+        visitor.visit_unit()
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.shape = Some(Shape::Named(name));
+        self.tracer.record(name, Container::Unit)?;
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.shape = Some(Shape::Named(name));
+        self.tracer.enter(name, None)?;
+        let mut probe = Probe {
+            tracer: &mut *self.tracer,
+            shape: None,
+        };
+        let result = visitor.visit_newtype_struct(&mut probe);
+        let inner = probe.shape.unwrap_or(Shape::Unit);
+        let result = result.and_then(|v| {
+            self.tracer.record(name, Container::Newtype(inner))?;
+            Ok(v)
+        });
+        self.tracer.leave();
+        result
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = if self.tracer.replaying() { 0 } else { 1 };
+        let mut seq = Seq::new(self.tracer, len);
+        let result = visitor.visit_seq(&mut seq);
+        let item = seq.shapes.pop().unwrap_or(Shape::Unit);
+        self.shape = Some(Shape::Seq(Box::new(item)));
+        result
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        let mut seq = Seq::new(self.tracer, len);
+        let result = visitor.visit_seq(&mut seq);
+        self.shape = Some(Shape::Tuple(seq.shapes));
+        result
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.shape = Some(Shape::Named(name));
+        self.tracer.enter(name, None)?;
+        let mut seq = Seq::new(self.tracer, len);
+        let result = visitor.visit_seq(&mut seq);
+        let shapes = seq.shapes;
+        let result = result.and_then(|v| {
+            self.tracer.record(name, Container::Tuple(shapes))?;
+            Ok(v)
+        });
+        self.tracer.leave();
+        result
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        let len = if self.tracer.replaying() { 0 } else { 1 };
+        let mut map = Map {
+            tracer: self.tracer,
+            remaining: len,
+            key: None,
+            value: None,
+        };
+        let result = visitor.visit_map(&mut map);
+        self.shape = Some(Shape::Map(
+            Box::new(map.key.unwrap_or(Shape::Unit)),
+            Box::new(map.value.unwrap_or(Shape::Unit)),
+        ));
+        result
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.shape = Some(Shape::Named(name));
+        self.tracer.enter(name, None)?;
+        let mut access = Struct::new(self.tracer, (name, None), fields);
+        let result = visitor.visit_map(&mut access);
+        let fields = access.shapes;
+        let result = result.and_then(|v| {
+            self.tracer.record(name, Container::Struct(fields))?;
+            Ok(v)
+        });
+        self.tracer.leave();
+        result
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.shape = Some(Shape::Named(name));
+        let index = self.tracer.choose(name, variants)?;
+        self.tracer.enter(name, Some(index))?;
+        let result = visitor.visit_enum(Enum {
+            tracer: &mut *self.tracer,
+            name,
+            variant: variants[index],
+            index,
+        });
+        self.tracer.leave();
+        result
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_unit(visitor)
+    }
+}
+
+// The elements of a sequence, tuple or tuple struct.
+struct Seq<'a> {
+    tracer: &'a mut Tracer,
+    remaining: usize,
+    shapes: Vec<Shape>,
+}
+
+impl<'a> Seq<'a> {
+    fn new(tracer: &'a mut Tracer, len: usize) -> Self {
+        Seq {
+            tracer,
+            remaining: len,
+            shapes: Vec::new(),
+        }
+    }
+}
+
+impl<'de> SeqAccess<'de> for Seq<'_> {
+    type Error = TraceError;
+
+    fn next_element_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        self.remaining -= 1;
+        let (result, shape) = self.tracer.trace(seed);
+        self.shapes.push(shape.unwrap_or(Shape::Unit));
+        result.map(Some)
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+// The entries of a map.
+struct Map<'a> {
+    tracer: &'a mut Tracer,
+    remaining: usize,
+    key: Option<Shape>,
+    value: Option<Shape>,
+}
+
+impl<'de> MapAccess<'de> for Map<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        if self.remaining == 0 {
+            return Ok(None);
+        }
+        let (result, shape) = self.tracer.trace(seed);
+        self.key = shape;
+        result.map(Some)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        self.remaining = self.remaining.saturating_sub(1);
+        let (result, shape) = self.tracer.trace(seed);
+        self.value = shape;
+        result
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.remaining)
+    }
+}
+
+// The fields of a struct or struct variant.
+struct Struct<'a> {
+    tracer: &'a mut Tracer,
+    id: StructId,
+    fields: &'static [&'static str],
+    index: usize,
+    shapes: Vec<Field>,
+}
+
+impl<'a> Struct<'a> {
+    fn new(tracer: &'a mut Tracer, id: StructId, fields: &'static [&'static str]) -> Self {
+        Struct {
+            tracer,
+            id,
+            fields,
+            index: 0,
+            shapes: Vec::new(),
+        }
+    }
+}
+
+impl<'de> MapAccess<'de> for Struct<'_> {
+    type Error = TraceError;
+
+    fn next_key_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<Option<S::Value>> {
+        while let Some(&field) = self.fields.get(self.index) {
+            if self.tracer.omit == Some((self.id, field)) {
+                self.tracer.omitted = true;
+                self.index += 1;
+                continue;
+            }
+            return seed.deserialize(field.into_deserializer()).map(Some);
+        }
+        Ok(None)
+    }
+
+    fn next_value_seed<S: DeserializeSeed<'de>>(&mut self, seed: S) -> Result<S::Value> {
+        let name = self.fields[self.index];
+        self.index += 1;
+        let (result, shape) = self.tracer.trace(seed);
+        self.shapes.push(Field {
+            name,
+            shape: shape.unwrap_or(Shape::Unit),
+            required: true,
+        });
+        result
+    }
+}
+
+// The chosen variant of an enum.
+struct Enum<'a> {
+    tracer: &'a mut Tracer,
+    name: &'static str,
+    variant: &'static str,
+    index: usize,
+}
+
+impl<'de, 'a> EnumAccess<'de> for Enum<'a> {
+    type Error = TraceError;
+    type Variant = Self;
+
+    fn variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<(S::Value, Self)> {
+        let value = seed.deserialize(self.variant.into_deserializer())?;
+        Ok((value, self))
+    }
+}
+
+impl<'de, 'a> VariantAccess<'de> for Enum<'a> {
+    type Error = TraceError;
+
+    fn unit_variant(self) -> Result<()> {
+        self.tracer
+            .record_variant(self.name, self.index, Container::Unit)
+    }
+
+    fn newtype_variant_seed<S: DeserializeSeed<'de>>(self, seed: S) -> Result<S::Value> {
+        let (result, shape) = self.tracer.trace(seed);
+        let value = result?;
+        let shape = shape.unwrap_or(Shape::Unit);
+        self.tracer
+            .record_variant(self.name, self.index, Container::Newtype(shape))?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        let mut seq = Seq::new(&mut *self.tracer, len);
+        let value = visitor.visit_seq(&mut seq)?;
+        let shapes = seq.shapes;
+        self.tracer
+            .record_variant(self.name, self.index, Container::Tuple(shapes))?;
+        Ok(value)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let mut access = Struct::new(&mut *self.tracer, (self.name, Some(self.variant)), fields);
+        let value = visitor.visit_map(&mut access)?;
+        let fields = access.shapes;
+        self.tracer
+            .record_variant(self.name, self.index, Container::Struct(fields))?;
+        Ok(value)
+    }
+}
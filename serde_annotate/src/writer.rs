@@ -0,0 +1,36 @@
+// Adapts an `io::Write` for use by the `fmt::Write`-based emitters.
+use crate::error::Error;
+use std::fmt;
+use std::io;
+
+pub(crate) struct IoWriter<W: io::Write> {
+    inner: W,
+    error: Option<io::Error>,
+}
+
+impl<W: io::Write> IoWriter<W> {
+    pub(crate) fn new(inner: W) -> Self {
+        IoWriter { inner, error: None }
+    }
+
+    // Flushes the underlying writer and returns the result of emitting the
+    // document.  Since `fmt::Write` can only report `fmt::Error`, any I/O
+    // error encountered while emitting takes precedence over `result`.
+    pub(crate) fn finish(mut self, result: Result<(), Error>) -> Result<(), Error> {
+        if let Some(e) = self.error.take() {
+            return Err(Error::IoError(e));
+        }
+        result?;
+        self.inner.flush()?;
+        Ok(())
+    }
+}
+
+impl<W: io::Write> fmt::Write for IoWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        self.inner.write_all(s.as_bytes()).map_err(|e| {
+            self.error = Some(e);
+            fmt::Error
+        })
+    }
+}
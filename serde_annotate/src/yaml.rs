@@ -1,20 +1,34 @@
+use crate::annotate::Placement;
 use crate::color::{ColorProfile, PaintExt};
-use crate::document::{CommentFormat, Document, StrFormat};
+use crate::document::{CommentFormat, Document, Member, StrFormat};
 use crate::error::Error;
 use crate::integer::Int;
+use crate::stream::{self, Emitter, Scalar};
+use crate::writer::IoWriter;
 use std::fmt::{self, Display};
+use std::io;
 
 type Result<T> = std::result::Result<T, Error>;
 
-pub struct Yaml {
-    document: Document,
+pub struct Yaml<'a> {
+    document: Document<'a>,
     indent: usize,
     color: ColorProfile,
     compact: bool,
     header: bool,
+    align_comments: bool,
+    digit_separators: bool,
 }
 
-impl Yaml {
+impl Default for Yaml<'_> {
+    /// Creates an empty YAML document.  Together with `serialize_to`, this
+    /// allows values to be streamed to a writer.
+    fn default() -> Self {
+        Document::Null.to_yaml()
+    }
+}
+
+impl Yaml<'_> {
     pub fn indent(mut self, i: usize) -> Self {
         self.indent = i;
         self
@@ -27,36 +41,95 @@ impl Yaml {
         self.header = b;
         self
     }
+    /// Set whether to align the trailing comments of the members of a
+    /// mapping or sequence to a common column.  Streaming with
+    /// `serialize_to` can't look ahead to the later members, so comments
+    /// are not aligned there.
+    pub fn align_comments(mut self, b: bool) -> Self {
+        self.align_comments = b;
+        self
+    }
     pub fn color(mut self, c: ColorProfile) -> Self {
         self.color = c;
         self
     }
-}
+    /// Set whether integers annotated with digit grouping are emitted with
+    /// `_` separators (e.g. `0x0000_1000`).  The YAML 1.2 core schema reads
+    /// such integers as strings, so this is off by default; the
+    /// `Deserializer` reads the strings back as integers.
+    pub fn digit_separators(mut self, b: bool) -> Self {
+        self.digit_separators = b;
+        self
+    }
 
-impl fmt::Display for Yaml {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        let mut emitter = YamlEmitter {
+    /// Writes the document to `w`.  The output is streamed to the writer
+    /// rather than being rendered to a `String` first.
+    pub fn write_to<W: io::Write>(&self, w: W) -> Result<()> {
+        let mut w = IoWriter::new(w);
+        let result = self.emit(&mut w);
+        w.finish(result)
+    }
+
+    /// Serializes `value` directly to `w` as YAML, without first building
+    /// a `Document`.  Annotations are applied exactly as `serialize` would
+    /// apply them; the document held by this `Yaml` is ignored.
+    pub fn serialize_to<T, W>(&self, value: &T, w: W) -> Result<()>
+    where
+        T: ?Sized + serde::Serialize,
+        W: io::Write,
+    {
+        let mut w = IoWriter::new(w);
+        let result = self
+            .emit_header(&mut w)
+            .and_then(|_| stream::serialize(value, &mut w, &mut self.emitter()));
+        w.finish(result)
+    }
+
+    fn emitter(&self) -> YamlEmitter {
+        YamlEmitter {
             level: -1,
             indent: self.indent,
             color: self.color,
             compact: self.compact,
+            align_comments: self.align_comments,
+            digit_separators: self.digit_separators,
             is_key: false,
-        };
+            pending: None,
+            key: None,
+            trailing: Vec::new(),
+            stack: Vec::new(),
+        }
+    }
+
+    fn emit_header<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
         if self.header {
-            writeln!(f, "---")?;
+            writeln!(w, "---")?;
         }
-        emitter.emit_node(f, &self.document).map_err(|_| fmt::Error)
+        Ok(())
+    }
+
+    fn emit<W: fmt::Write>(&self, w: &mut W) -> Result<()> {
+        self.emit_header(w)?;
+        self.emitter().emit_node(w, &self.document)
+    }
+}
+
+impl fmt::Display for Yaml<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.emit(f).map_err(|_| fmt::Error)
     }
 }
 
-impl Document {
-    pub fn to_yaml(self) -> Yaml {
+impl<'a> Document<'a> {
+    pub fn to_yaml(self) -> Yaml<'a> {
         Yaml {
             document: self,
             indent: 2,
             color: ColorProfile::default(),
             compact: false,
             header: true,
+            align_comments: false,
+            digit_separators: false,
         }
     }
 }
@@ -66,7 +139,31 @@ pub struct YamlEmitter {
     indent: usize,
     color: ColorProfile,
     compact: bool,
+    align_comments: bool,
+    digit_separators: bool,
     is_key: bool,
+    // When streaming, the `-` or `:` indicator awaiting the next value.  The
+    // indicator is followed by a newline if the value is a non-empty
+    // aggregate, which isn't known until the value starts.
+    pending: Option<&'static str>,
+    // When streaming, the key awaiting its value.  The key is written once
+    // the value starts, so the comments attached to the value precede it.
+    key: Option<String>,
+    // When streaming, the trailing comments of the current member.  They
+    // follow the indicator if the value is a non-empty aggregate, or else the
+    // value.
+    trailing: Vec<String>,
+    // The open aggregates when streaming.
+    stack: Vec<Aggregate>,
+}
+
+// An aggregate being streamed by the `YamlEmitter`.
+struct Aggregate {
+    compact: bool,
+    empty: bool,
+    // The trailing comments of the member holding the aggregate, which are
+    // emitted once the aggregate's position is known.
+    saved: Vec<String>,
 }
 
 impl Default for YamlEmitter {
@@ -76,7 +173,13 @@ impl Default for YamlEmitter {
             indent: 2,
             color: ColorProfile::default(),
             compact: false,
+            align_comments: false,
+            digit_separators: false,
             is_key: false,
+            pending: None,
+            key: None,
+            trailing: Vec::new(),
+            stack: Vec::new(),
         }
     }
 }
@@ -87,7 +190,7 @@ impl YamlEmitter {
         match node {
             Document::Comment(c, f) => self.emit_comment_newline(w, c, f),
             Document::String(v, f) => self.emit_string(w, v.as_str(), *f),
-            Document::StaticStr(v, f) => self.emit_string(w, v, *f),
+            Document::Str(v, f) => self.emit_string(w, v, *f),
             Document::Boolean(v) => self.emit_boolean(w, *v),
             Document::Int(v) => self.emit_int(w, v),
             Document::Float(v) => self.emit_float(w, *v),
@@ -96,6 +199,36 @@ impl YamlEmitter {
             Document::Bytes(v) => self.emit_bytes(w, v),
             Document::Null => self.emit_null(w),
             Document::Compact(d) => self.emit_compact(w, d),
+            Document::Spanned(d, _) => self.emit_node(w, d),
+            Document::Fragment(ds) if ds.iter().filter(|d| d.has_value()).count() == 1 => {
+                // A value with comments attached, such as a commented unit
+                // variant at the top level.
+                let (value, comments) = node.value_comments();
+                let (leading, trailing): (Vec<_>, Vec<_>) =
+                    comments.into_iter().partition(|(_, precedes)| *precedes);
+                let mut trailing: Vec<_> =
+                    trailing.iter().filter_map(|(c, _)| c.comment()).collect();
+                let leading = leading.iter().filter_map(|(c, _)| c.comment());
+                for (c, f) in leading.chain(lead_block_scalar(value, &mut trailing)) {
+                    self.emit_comment_newline(w, c, f)?;
+                }
+                if let Some(text) = block_scalar(value) {
+                    return self.emit_block_scalar(w, text, trailing, 0);
+                }
+                self.emit_node(w, value)?;
+                if !self.is_block(value) {
+                    return self.emit_trailing_comments(w, trailing, 0);
+                }
+                // The last line of a block aggregate belongs to its last
+                // member (or is a dangling comment), so comments after the
+                // aggregate go on their own lines.
+                for (c, f) in trailing {
+                    writeln!(w)?;
+                    self.emit_indent(w)?;
+                    self.emit_comment(w, c, f)?;
+                }
+                Ok(())
+            }
             Document::Fragment(ds) => {
                 let mut prior_val = false;
                 for d in ds {
@@ -141,132 +274,222 @@ impl YamlEmitter {
         Ok(())
     }
 
+    // Returns whether `node` is emitted as a block aggregate.
+    fn is_block(&self, node: &Document) -> bool {
+        match node {
+            Document::Spanned(d, _) => self.is_block(d),
+            Document::Sequence(v) | Document::Mapping(v) => !self.compact && has_values(v),
+            _ => false,
+        }
+    }
+
+    // Emits the prefix of a value (e.g. a key's colon) and returns whether
+    // the value starts on the next line.  The trailing comments of such a
+    // value are emitted after the prefix, `pad` spaces after it.
     fn emit_helper<W: fmt::Write>(
         &mut self,
         w: &mut W,
         prefix: impl Display,
         value: &Document,
+        trailing: &[(&str, &CommentFormat)],
+        pad: usize,
+    ) -> Result<bool> {
+        let block = self.is_block(value);
+        if block {
+            write!(w, "{}", prefix)?;
+            self.emit_trailing_comments(w, trailing.iter().copied(), pad)?;
+            writeln!(w)?;
+            self.emit_indent_extra(w, 1)?;
+        } else {
+            write!(w, "{} ", prefix)?;
+        }
+        Ok(block)
+    }
+
+    // Emits the members of a mapping or sequence as a flow aggregate.  The
+    // dangling comments of an aggregate without values follow it.
+    fn emit_flow<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        members: &[Document],
+        mapping: bool,
     ) -> Result<()> {
-        match value {
-            Document::Sequence(v) => {
-                if self.compact || v.is_empty() {
-                    write!(w, "{} ", prefix)?;
-                } else {
-                    writeln!(w, "{}", prefix)?;
-                    self.emit_indent_extra(w, 1)?
-                }
+        let (open, close) = if mapping { ("{", "}") } else { ("[", "]") };
+        write!(w, "{}", self.color.aggregate.paint(open))?;
+        for (i, member) in members.iter().filter(|m| m.has_value()).enumerate() {
+            if i > 0 {
+                write!(w, "{}", self.color.punctuation.paint(", "))?;
             }
-            Document::Mapping(v) => {
-                if self.compact || v.is_empty() {
-                    write!(w, "{} ", prefix)?;
-                } else {
-                    writeln!(w, "{}", prefix)?;
-                    self.emit_indent_extra(w, 1)?
+            if mapping {
+                let (k, v) = member.as_kv()?;
+                let key = self.is_key;
+                self.is_key = true;
+                self.emit_node(w, k)?;
+                self.is_key = key;
+                write!(w, "{} ", self.color.punctuation.paint(":"))?;
+                self.emit_node(w, v)?;
+            } else {
+                self.emit_node(w, member)?;
+            }
+        }
+        write!(w, "{}", self.color.aggregate.paint(close))?;
+        if !has_values(members) {
+            for member in members {
+                if let Document::Fragment(nodes) = member {
+                    self.emit_trailing_comments(w, nodes.iter().filter_map(Document::comment), 0)?;
                 }
             }
-            _ => write!(w, "{} ", prefix)?,
-        };
+        }
         Ok(())
     }
 
-    fn emit_sequence<W: fmt::Write>(&mut self, w: &mut W, sequence: &[Document]) -> Result<()> {
-        if self.compact || sequence.is_empty() {
-            write!(w, "{}", self.color.aggregate.paint("["))?;
-            for (i, v) in sequence.iter().enumerate() {
-                if i > 0 {
-                    write!(w, "{}", self.color.punctuation.paint(", "))?;
-                }
-                self.emit_node(w, v)?;
-            }
-            write!(w, "{}", self.color.aggregate.paint("]"))?;
+    // Emits the members of a mapping or sequence in block style.  Leading
+    // comments are emitted on the lines above a member, trailing comments
+    // after its value and dangling comments on the lines after the last
+    // member.
+    fn emit_block<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        members: &[Document],
+        mapping: bool,
+    ) -> Result<()> {
+        self.level += 1;
+        let widths = if self.align_comments {
+            self.member_widths(members, mapping)?
         } else {
-            self.level += 1;
-            for (i, value) in sequence.iter().enumerate() {
-                if i > 0 {
-                    writeln!(w)?;
-                    self.emit_indent(w)?
-                }
-                if let Document::Fragment(frags) = value {
-                    let mut val_done = false;
-                    let mut it = frags.iter().peekable();
-                    while let Some(node) = it.next() {
-                        let next = it.peek();
-                        if let Some((c, f)) = node.comment() {
-                            if val_done {
-                                write!(w, " ")?;
-                            }
-                            if self.emit_comment(w, c, f)? && next.is_some() {
-                                self.writeln(w, "")?;
-                                self.emit_indent(w)?;
-                            }
-                            continue;
-                        }
-                        self.emit_helper(w, self.color.punctuation.paint("-"), node)?;
-                        self.emit_node(w, node)?;
-                        val_done = true;
+            Vec::new()
+        };
+        let column = widths.iter().flatten().max().copied().unwrap_or(0);
+        for (i, member) in members.iter().enumerate() {
+            let member = Member::new(member, mapping)?;
+            if i > 0 {
+                writeln!(w)?;
+                self.emit_indent(w)?;
+            }
+            let Some(value) = member.value else {
+                for (j, (c, f)) in member.leading.into_iter().enumerate() {
+                    if j > 0 {
+                        writeln!(w)?;
+                        self.emit_indent(w)?;
                     }
-                } else {
-                    self.emit_helper(w, self.color.punctuation.paint("-"), value)?;
-                    self.emit_node(w, value)?;
+                    self.emit_comment(w, c, f)?;
                 }
+                continue;
+            };
+            let mut trailing = member.trailing;
+            let moved = lead_block_scalar(value, &mut trailing);
+            for (c, f) in member.leading.into_iter().chain(moved) {
+                self.emit_comment_newline(w, c, f)?;
+            }
+            let pad = match widths.get(i) {
+                Some(Some(width)) => column - width,
+                _ => 0,
+            };
+            let block = self.emit_prefix(w, member.key, value, &trailing, pad)?;
+            if let Some(text) = block_scalar(value) {
+                self.emit_block_scalar(w, text, trailing, pad)?;
+                continue;
+            }
+            self.emit_node(w, value)?;
+            if !block {
+                self.emit_trailing_comments(w, trailing, pad)?;
             }
-            self.level -= 1;
         }
+        self.level -= 1;
         Ok(())
     }
 
-    fn emit_mapping<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
-        if self.compact || mapping.is_empty() {
-            write!(w, "{}", self.color.aggregate.paint("{"))?;
-        } else {
-            self.level += 1;
+    // Emits the key (if any) and indicator of a member.  Returns whether the
+    // value starts on the next line.
+    fn emit_prefix<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        key: Option<&Document>,
+        value: &Document,
+        trailing: &[(&str, &CommentFormat)],
+        pad: usize,
+    ) -> Result<bool> {
+        match key {
+            Some(key) => {
+                let k = self.is_key;
+                self.is_key = true;
+                self.emit_node(w, key)?;
+                self.is_key = k;
+                let prefix = self.color.punctuation.paint(":");
+                self.emit_helper(w, prefix, value, trailing, pad)
+            }
+            None => {
+                let prefix = self.color.punctuation.paint("-");
+                self.emit_helper(w, prefix, value, trailing, pad)
+            }
         }
-        for (i, frag) in mapping.iter().enumerate() {
-            let nodes = frag.fragments()?;
-            if i > 0 {
-                if self.compact {
-                    write!(w, ", ")?;
+    }
+
+    // Returns the width of each member with trailing comments, up to its
+    // comments.  Members which span more than one line have no width.
+    fn member_widths(&mut self, members: &[Document], mapping: bool) -> Result<Vec<Option<usize>>> {
+        let color = std::mem::take(&mut self.color);
+        let widths = members
+            .iter()
+            .map(|member| {
+                let member = Member::new(member, mapping)?;
+                let value = match member.value {
+                    Some(v) if !member.trailing.is_empty() => v,
+                    _ => return Ok(None),
+                };
+                let mut s = String::new();
+                if self.emit_prefix(&mut s, member.key, value, &[], 0)? {
+                    // The comments follow the indicator.
+                    s.truncate(s.find('\n').unwrap_or(s.len()));
                 } else {
-                    self.writeln(w, "")?;
-                    self.emit_indent(w)?;
-                }
-            }
-            let mut key_done = false;
-            let mut val_done = false;
-            let mut it = nodes.iter().peekable();
-            while let Some(node) = it.next() {
-                let next = it.peek();
-                if let Some((c, f)) = node.comment() {
-                    if val_done {
-                        write!(w, " ")?;
-                    }
-                    if self.emit_comment(w, c, f)? && next.is_some() {
-                        self.writeln(w, "")?;
-                        self.emit_indent(w)?;
+                    self.emit_node(&mut s, value)?;
+                    if block_scalar(value).is_some() {
+                        // As do the comments of a block scalar.
+                        s.truncate(s.find('\n').unwrap_or(s.len()));
                     }
-                    continue;
                 }
-                if !key_done {
-                    if next.is_none() {
-                        return Err(Error::StructureError("a node", "none"));
-                    }
-                    let k = self.is_key;
-                    self.is_key = true;
-                    self.emit_node(w, node)?;
-                    self.is_key = k;
-                    key_done = true;
-                    self.emit_helper(w, self.color.punctuation.paint(":"), next.unwrap())?;
-                } else if !val_done {
-                    self.emit_node(w, node)?;
-                    val_done = true;
-                    continue;
-                }
-            }
+                Ok((!s.contains('\n')).then(|| s.chars().count()))
+            })
+            .collect();
+        self.color = color;
+        widths
+    }
+
+    fn emit_sequence<W: fmt::Write>(&mut self, w: &mut W, sequence: &[Document]) -> Result<()> {
+        if self.compact || !has_values(sequence) {
+            self.emit_flow(w, sequence, false)
+        } else {
+            self.emit_block(w, sequence, false)
         }
-        if self.compact || mapping.is_empty() {
-            write!(w, "{}", self.color.aggregate.paint("}"))?;
+    }
+
+    fn emit_mapping<W: fmt::Write>(&mut self, w: &mut W, mapping: &[Document]) -> Result<()> {
+        if self.compact || !has_values(mapping) {
+            self.emit_flow(w, mapping, true)
         } else {
-            self.level -= 1;
+            self.emit_block(w, mapping, true)
+        }
+    }
+
+    // Emits comments after a value on the same line, `pad` spaces after the
+    // value.
+    fn emit_trailing_comments<'c, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comments: impl IntoIterator<Item = (&'c str, &'c CommentFormat)>,
+        pad: usize,
+    ) -> Result<()> {
+        if self.compact {
+            return Ok(());
+        }
+        for (i, (c, f)) in comments.into_iter().enumerate() {
+            if i > 0 {
+                writeln!(w)?;
+                self.emit_indent(w)?;
+            } else {
+                write!(w, "{:pad$} ", "")?;
+            }
+            self.emit_comment(w, c, f)?;
         }
         Ok(())
     }
@@ -323,18 +546,58 @@ impl YamlEmitter {
         Ok(())
     }
 
-    fn emit_string_multiline<W: fmt::Write>(&mut self, w: &mut W, mut value: &str) -> Result<()> {
-        if value.ends_with('\n') {
-            write!(w, "{}", self.color.punctuation.paint("|+"))?;
-            value = &value[..value.len() - 1];
-        } else {
-            write!(w, "{}", self.color.punctuation.paint("|-"))?;
+    fn emit_string_multiline<W: fmt::Write>(&mut self, w: &mut W, value: &str) -> Result<()> {
+        let value = self.emit_block_indicator(w, value)?;
+        self.emit_block_lines(w, value.split('\n'))
+    }
+
+    // Emits the indicator of a block scalar holding `value` and returns the
+    // text of its lines.
+    fn emit_block_indicator<'s, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        value: &'s str,
+    ) -> Result<&'s str> {
+        match value.strip_suffix('\n') {
+            Some(value) => {
+                write!(w, "{}", self.color.punctuation.paint("|+"))?;
+                Ok(value)
+            }
+            None => {
+                write!(w, "{}", self.color.punctuation.paint("|-"))?;
+                Ok(value)
+            }
         }
+    }
+
+    // Emits a block scalar with its trailing comments.  Every line after
+    // the indicator belongs to the scalar, so the comments follow the
+    // indicator, `pad` spaces after it.
+    fn emit_block_scalar<'c, W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        value: &str,
+        trailing: impl IntoIterator<Item = (&'c str, &'c CommentFormat)>,
+        pad: usize,
+    ) -> Result<()> {
+        let value = self.emit_block_indicator(w, value)?;
+        self.emit_trailing_comments(w, trailing, pad)?;
+        self.emit_block_lines(w, value.split('\n'))
+    }
+
+    // Emits the lines of a block scalar, which may be given without building
+    // the whole string.
+    fn emit_block_lines<W, I>(&mut self, w: &mut W, lines: I) -> Result<()>
+    where
+        W: fmt::Write,
+        I: Iterator,
+        I::Item: AsRef<str>,
+    {
         self.level += 1;
-        for line in value.split('\n') {
+        for line in lines {
             writeln!(w)?;
             self.emit_indent(w)?;
-            self.escape_str(w, line, false)?;
+            self.escape_str(w, line.as_ref(), false)?;
         }
         self.level -= 1;
         Ok(())
@@ -360,7 +623,12 @@ impl YamlEmitter {
         } else {
             self.color.integer
         };
-        write!(w, "{}", color.paint(i))?;
+        if self.digit_separators {
+            write!(w, "{}", color.paint(i))?;
+        } else {
+            // The core schema has no digit separators: `1_000` is a string.
+            write!(w, "{}", color.paint(i.clone().with_grouping(0)))?;
+        }
         Ok(())
     }
 
@@ -370,7 +638,14 @@ impl YamlEmitter {
         } else {
             self.color.float
         };
-        write!(w, "{}", color.paint(f))?;
+        if f.is_nan() {
+            write!(w, "{}", color.paint(".nan"))?;
+        } else if f.is_infinite() {
+            let sign = if f < 0.0 { "-" } else { "" };
+            write!(w, "{}", color.paint(format!("{sign}.inf")))?;
+        } else {
+            write!(w, "{}", color.paint(f))?;
+        }
         Ok(())
     }
 
@@ -500,6 +775,261 @@ impl YamlEmitter {
 // * When the string is null or ~ (otherwise, it would be considered as a null value);
 // * When the string looks like a number, such as integers (e.g. 2, 14, etc.), floats (e.g. 2.6, 14.9) and exponential numbers (e.g. 12e7, etc.) (otherwise, it would be treated as a numeric value);
 // * When the string looks like a date (e.g. 2014-12-31) (otherwise it would be automatically converted into a Unix timestamp).
+impl YamlEmitter {
+    // Emits the pending indicator for a value, as `emit_helper` would.
+    fn resolve<W: fmt::Write>(&mut self, w: &mut W, nonempty_aggregate: bool) -> Result<()> {
+        if let Some(key) = self.key.take() {
+            w.write_str(&key)?;
+        }
+        if let Some(indicator) = self.pending.take() {
+            let indicator = self.color.punctuation.paint(indicator);
+            if nonempty_aggregate && !self.compact {
+                write!(w, "{}", indicator)?;
+                self.emit_pending_comments(w)?;
+                writeln!(w)?;
+                self.emit_indent_extra(w, 1)?;
+            } else {
+                write!(w, "{} ", indicator)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn begin_aggregate<W: fmt::Write>(&mut self, w: &mut W, open: &str) -> Result<()> {
+        if self.compact {
+            self.resolve(w, false)?;
+            write!(w, "{}", self.color.aggregate.paint(open))?;
+        }
+        self.stack.push(Aggregate {
+            compact: self.compact,
+            empty: true,
+            saved: std::mem::take(&mut self.trailing),
+        });
+        Ok(())
+    }
+
+    // Emits the trailing comments of the current member.
+    fn emit_pending_comments<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        let trailing = std::mem::take(&mut self.trailing);
+        let comments = trailing
+            .iter()
+            .map(|c| (c.as_str(), &CommentFormat::Standard));
+        self.emit_trailing_comments(w, comments, 0)
+    }
+
+    // Starts an element of a streamed aggregate, separating it from the prior
+    // element and emitting its comment.  A trailing comment is held until
+    // the position of the comment is known.
+    fn begin_element<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+    ) -> Result<()> {
+        let top = self
+            .stack
+            .last_mut()
+            .ok_or(Error::StructureError("an aggregate", "none"))?;
+        let (compact, empty) = (top.compact, std::mem::replace(&mut top.empty, false));
+        if compact {
+            if !empty {
+                write!(w, "{}", self.color.punctuation.paint(", "))?;
+            }
+            return Ok(());
+        }
+        if empty {
+            // The comments of the enclosing member follow its indicator.
+            if self.pending.is_some() {
+                self.trailing = std::mem::take(&mut top.saved);
+            }
+            self.resolve(w, true)?;
+            self.level += 1;
+        } else {
+            self.emit_pending_comments(w)?;
+            writeln!(w)?;
+            self.emit_indent(w)?;
+        }
+        match comment {
+            Some((c, placement)) => self.comment(w, c, placement),
+            None => Ok(()),
+        }
+    }
+
+    fn end_aggregate<W: fmt::Write>(&mut self, w: &mut W, open: &str, close: &str) -> Result<()> {
+        let top = self
+            .stack
+            .pop()
+            .ok_or(Error::StructureError("an aggregate", "none"))?;
+        if top.compact {
+            write!(w, "{}", self.color.aggregate.paint(close))?;
+        } else if top.empty {
+            self.resolve(w, false)?;
+            write!(w, "{}", self.color.aggregate.paint(open))?;
+            write!(w, "{}", self.color.aggregate.paint(close))?;
+        } else {
+            self.emit_pending_comments(w)?;
+            self.level -= 1;
+            // As in `emit_node`, comments after a block aggregate go on
+            // their own lines.
+            for c in top.saved {
+                writeln!(w)?;
+                self.emit_indent(w)?;
+                self.emit_comment(w, &c, &CommentFormat::Standard)?;
+            }
+            return Ok(());
+        }
+        // Comments not yet emitted follow the aggregate.
+        self.trailing.extend(top.saved);
+        if top.empty && !top.compact {
+            self.emit_pending_comments(w)?;
+        }
+        Ok(())
+    }
+}
+
+impl Emitter for YamlEmitter {
+    fn scalar<W: fmt::Write>(&mut self, w: &mut W, value: Scalar) -> Result<()> {
+        if matches!(
+            value,
+            Scalar::Str(_, StrFormat::Multiline) | Scalar::Lines(_)
+        ) {
+            // As in `emit_block`, the comments of a block scalar follow its
+            // indicator if they fit on one line, or else lead it.
+            if !one_line(self.trailing.iter().map(String::as_str)) {
+                for c in std::mem::take(&mut self.trailing) {
+                    self.emit_comment_newline(w, &c, &CommentFormat::Standard)?;
+                }
+            }
+        }
+        self.resolve(w, false)?;
+        match value {
+            Scalar::Str(v, StrFormat::Multiline) => {
+                let v = self.emit_block_indicator(w, v)?;
+                self.emit_pending_comments(w)?;
+                self.emit_block_lines(w, v.split('\n'))
+            }
+            Scalar::Str(v, f) => self.emit_string(w, v, f),
+            Scalar::Boolean(v) => self.emit_boolean(w, v),
+            Scalar::Int(v) => self.emit_int(w, &v),
+            Scalar::Float(v) => self.emit_float(w, v),
+            Scalar::Bytes(v) => self.emit_bytes(w, v),
+            Scalar::Lines(v) => {
+                write!(w, "{}", self.color.punctuation.paint("|-"))?;
+                self.emit_pending_comments(w)?;
+                self.emit_block_lines(w, v)
+            }
+            Scalar::Null => self.emit_null(w),
+        }
+    }
+
+    fn begin_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.begin_aggregate(w, "[")
+    }
+
+    fn element<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+    ) -> Result<()> {
+        self.begin_element(w, comment)?;
+        if !self.compact {
+            self.pending = Some("-");
+        }
+        Ok(())
+    }
+
+    fn end_sequence<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.end_aggregate(w, "[", "]")
+    }
+
+    fn begin_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.begin_aggregate(w, "{")
+    }
+
+    fn key<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: Option<(&str, Placement)>,
+        key: &Document,
+    ) -> Result<()> {
+        self.begin_element(w, comment)?;
+        let mut pending = String::new();
+        let k = self.is_key;
+        self.is_key = true;
+        self.emit_node(&mut pending, key)?;
+        self.is_key = k;
+        self.key = Some(pending);
+        self.pending = Some(":");
+        Ok(())
+    }
+
+    fn end_mapping<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.end_aggregate(w, "{", "}")
+    }
+
+    fn is_compact(&self) -> bool {
+        self.compact
+    }
+
+    fn set_compact(&mut self, compact: bool) {
+        self.compact = compact;
+    }
+
+    fn comment<W: fmt::Write>(
+        &mut self,
+        w: &mut W,
+        comment: &str,
+        placement: Placement,
+    ) -> Result<()> {
+        if self.compact {
+            return Ok(());
+        }
+        match placement {
+            Placement::Leading => self.emit_comment_newline(w, comment, &CommentFormat::Standard),
+            Placement::Trailing => {
+                self.trailing.push(comment.to_string());
+                Ok(())
+            }
+        }
+    }
+
+    fn finish<W: fmt::Write>(&mut self, w: &mut W) -> Result<()> {
+        self.emit_pending_comments(w)
+    }
+}
+
+// Returns the text of `node` if it is emitted as a block scalar.
+fn block_scalar<'d>(node: &'d Document) -> Option<&'d str> {
+    match node {
+        Document::Spanned(d, _) => block_scalar(d),
+        Document::String(v, StrFormat::Multiline) => Some(v),
+        Document::Str(v, StrFormat::Multiline) => Some(v),
+        _ => None,
+    }
+}
+
+// Whether `comments` fit on one line.
+fn one_line<'c>(comments: impl IntoIterator<Item = &'c str>) -> bool {
+    let lines: usize = comments.into_iter().map(|c| c.split('\n').count()).sum();
+    lines <= 1
+}
+
+// Takes the trailing comments of `value` if it is a block scalar and they
+// don't fit on its indicator line, to be emitted as leading comments instead.
+fn lead_block_scalar<'c>(
+    value: &Document,
+    trailing: &mut Vec<(&'c str, &'c CommentFormat)>,
+) -> Vec<(&'c str, &'c CommentFormat)> {
+    if block_scalar(value).is_none() || one_line(trailing.iter().map(|(c, _)| *c)) {
+        return Vec::new();
+    }
+    std::mem::take(trailing)
+}
+
+// Whether any member of a mapping or sequence has a value.
+fn has_values(members: &[Document]) -> bool {
+    members.iter().any(Document::has_value)
+}
+
 fn need_quotes(string: &str) -> bool {
     fn need_quotes_spaces(string: &str) -> bool {
         string.starts_with(' ') || string.ends_with(' ')
@@ -507,7 +1037,7 @@ fn need_quotes(string: &str) -> bool {
 
     string.is_empty()
         || need_quotes_spaces(string)
-        || string.starts_with(&['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
+        || string.starts_with(['&', '*', '?', '|', '-', '<', '>', '=', '!', '%', '@'])
         || string.contains(|character: char| {
             matches!(character,
             ':'
@@ -545,6 +1075,9 @@ fn need_quotes(string: &str) -> bool {
         || string.starts_with("0o")
         || string.parse::<i64>().is_ok()
         || string.parse::<f64>().is_ok()
+        // Integers with digit separators (e.g. `1_000`), which are strings in
+        // YAML 1.2 but integers in YAML 1.1.
+        || Int::from_str_radix(string, 0).is_ok()
 }
 
 #[cfg(test)]
@@ -553,37 +1086,40 @@ mod tests {
     use crate::document::CommentFormat;
     use crate::integer::Base;
 
-    fn int(v: i32) -> Document {
+    fn int(v: i32) -> Document<'static> {
         Document::Int(Int::new(v, Base::Dec))
     }
-    fn hex(v: u32) -> Document {
+    fn hex(v: u32) -> Document<'static> {
         Document::Int(Int::new(v, Base::Hex))
     }
-    fn float(v: f64) -> Document {
+    fn float(v: f64) -> Document<'static> {
         Document::Float(v)
     }
-    fn boolean(v: bool) -> Document {
+    fn boolean(v: bool) -> Document<'static> {
         Document::Boolean(v)
     }
-    fn null() -> Document {
+    fn null() -> Document<'static> {
         Document::Null
     }
-    fn string(v: &str) -> Document {
+    fn string(v: &str) -> Document<'static> {
         Document::String(v.to_string(), StrFormat::Standard)
     }
-    fn multistr(v: &str) -> Document {
+    fn multistr(v: &str) -> Document<'static> {
         Document::String(v.to_string(), StrFormat::Multiline)
     }
-    fn comment(v: &str) -> Document {
+    fn comment(v: &str) -> Document<'static> {
         Document::Comment(v.to_string(), CommentFormat::Standard)
     }
-    fn kv(k: &str, v: Document) -> Document {
+    fn kv(k: &str, v: Document<'static>) -> Document<'static> {
         Document::Fragment(vec![string(k), v])
     }
-    fn kvcomment(k: &str, v: Document, c: &str) -> Document {
+    fn kvcomment(k: &str, v: Document<'static>, c: &str) -> Document<'static> {
         Document::Fragment(vec![comment(c), string(k), v])
     }
-    fn nes_address(seg: &str, bank: i32, addr: u32) -> Document {
+    fn kvtrailing(k: &str, v: Document<'static>, c: &str) -> Document<'static> {
+        Document::Fragment(vec![string(k), v, comment(c)])
+    }
+    fn nes_address(seg: &str, bank: i32, addr: u32) -> Document<'static> {
         Document::Compact(
             Document::Mapping(vec![kv(
                 seg,
@@ -594,6 +1130,7 @@ mod tests {
     }
 
     #[test]
+    #[allow(clippy::approx_constant)]
     fn basic_document() {
         let c = comment("woohoo!").to_yaml().header(false);
         assert_eq!(c.to_string(), "# woohoo!\n");
@@ -607,6 +1144,9 @@ mod tests {
         assert_eq!(i.to_string(), "0x10");
         let s = string("hello").to_yaml().header(false);
         assert_eq!(s.to_string(), "hello");
+        // Strings which would read back as integers are quoted.
+        let s = string("1_000").to_yaml().header(false);
+        assert_eq!(s.to_string(), "\"1_000\"");
         let f = float(3.14159).to_yaml().header(false);
         assert_eq!(f.to_string(), "3.14159");
     }
@@ -733,4 +1273,132 @@ backwardsCompatible: with JSON"#;
         println!("{}", map);
         assert_eq!(map.to_string(), expect);
     }
+
+    #[test]
+    fn write_to() {
+        let map =
+            Document::Mapping(vec![kv("a", int(1)), kvcomment("b", hex(0xdecaf), "Bee")]).to_yaml();
+        let mut buf = Vec::new();
+        map.write_to(&mut buf).unwrap();
+        assert_eq!(String::from_utf8(buf).unwrap(), map.to_string());
+    }
+
+    #[test]
+    fn comment_placement() {
+        let doc = Document::parse(
+            r#"{
+              // leading
+              addr: 0x40, // MMIO base
+              nested: {
+                a: 1
+              }, // after nested
+              list: [
+                1 /* one */
+                {b: 2} // two
+                // dangling in list
+              ],
+              empty: [
+                // dangling in empty
+              ],
+              last: true
+              // dangling in map
+            }"#,
+        )
+        .unwrap();
+        let expect = r#"---
+# leading
+addr: 0x40 # MMIO base
+nested: # after nested
+  a: 1
+list:
+  - 1 # one
+  - # two
+    b: 2
+  # dangling in list
+empty: [] # dangling in empty
+last: true
+# dangling in map"#;
+        assert_eq!(doc.to_yaml().to_string(), expect);
+    }
+
+    #[test]
+    fn dangling_comments_roundtrip() -> Result<()> {
+        let doc = Document::Fragment(vec![
+            Document::Mapping(vec![
+                kv(
+                    "a",
+                    Document::Sequence(vec![
+                        int(1),
+                        int(2),
+                        Document::Fragment(vec![comment("end of a")]),
+                    ]),
+                ),
+                kv("b", int(2)),
+                Document::Fragment(vec![comment("dangling end")]),
+            ]),
+            comment("eof"),
+        ]);
+        let expect = r#"---
+a:
+  - 1
+  - 2
+  # end of a
+b: 2
+# dangling end
+# eof"#;
+        let once = doc.to_yaml().to_string();
+        assert_eq!(once, expect);
+        let twice = Document::from_yaml(&once)?.to_yaml().to_string();
+        assert_eq!(twice, expect);
+        Ok(())
+    }
+
+    #[test]
+    fn block_scalar_comments_roundtrip() -> Result<()> {
+        let dump = "00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|\n00000010";
+        let doc = Document::Mapping(vec![
+            kvtrailing("text", multistr("Hello\nWorld"), "a block"),
+            kvtrailing("kept", multistr("line\n"), "keeps the newline"),
+            kvtrailing("dump", multistr(dump), "a hexdump"),
+            kvtrailing("long", multistr("one\ntwo"), "does not fit\non one line"),
+            kv(
+                "list",
+                Document::Sequence(vec![
+                    Document::Fragment(vec![multistr("a\nb"), comment("element")]),
+                    Document::Fragment(vec![
+                        Document::Mapping(vec![kv("c", int(1))]),
+                        comment("nested"),
+                    ]),
+                ]),
+            ),
+            kvtrailing("nested", Document::Mapping(vec![kv("d", int(2))]), "after"),
+        ]);
+        let expect = r#"---
+text: |- # a block
+  Hello
+  World
+kept: |+ # keeps the newline
+  line
+dump: |- # a hexdump
+  00000000  00 01 02 03 04 05 06 07  08 09 0a 0b 0c 0d 0e 0f  |................|
+  00000010
+# does not fit
+# on one line
+long: |-
+  one
+  two
+list:
+  - |- # element
+    a
+    b
+  - # nested
+    c: 1
+nested: # after
+  d: 2"#;
+        let once = doc.to_yaml().to_string();
+        assert_eq!(once, expect);
+        let twice = Document::from_yaml(&once)?.to_yaml().to_string();
+        assert_eq!(twice, expect);
+        Ok(())
+    }
 }
@@ -0,0 +1,1217 @@
+// Parser for YAML documents into serde-annotate `Document`s.
+use crate::document::{CommentFormat, Document, StrFormat};
+use crate::error::Error;
+use crate::integer::Int;
+use once_cell::sync::OnceCell;
+use regex::Regex;
+
+type Result<T> = std::result::Result<T, Error>;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Chomp {
+    Strip,
+    Clip,
+    Keep,
+}
+
+/// `YamlParser` parses the block and flow styles of YAML into a `Document`,
+/// retaining comments, integer bases and block string formatting.
+///
+/// The parser understands the documents emitted by `Yaml` and most of what
+/// people write by hand: block and flow collections, plain and quoted scalars
+/// (including plain scalars continued on more deeply indented lines), literal
+/// and folded block scalars and `#` comments.  Anchors, aliases, tags,
+/// complex keys and multi-document streams are not supported.
+pub struct YamlParser<'a> {
+    text: &'a str,
+    pos: usize,
+    lines: Vec<usize>,
+}
+
+impl<'a> YamlParser<'a> {
+    /// Creates a parser for `text`.
+    pub fn new(text: &'a str) -> Self {
+        // Remember the start of each line so we can compute columns and
+        // report errors with line numbers.
+        let mut lines = vec![0];
+        for (i, ch) in text.char_indices() {
+            if ch == '\n' {
+                lines.push(i + 1);
+            }
+        }
+        YamlParser {
+            text,
+            pos: 0,
+            lines,
+        }
+    }
+
+    /// Parses the text into a `Document`.
+    pub fn parse(mut self) -> Result<Document<'static>> {
+        let mut nodes = Vec::new();
+        let mark = self.pos;
+        self.skip_to_content(&mut nodes);
+        if !(self.at_marker(b"---") || self.at_directive()) {
+            // Without a document header, leading comments belong to the
+            // root node.
+            nodes.clear();
+            self.pos = mark;
+        }
+        while self.at_directive() {
+            // Skip directives such as `%YAML 1.2`.
+            self.skip_line();
+            self.skip_to_content(&mut nodes);
+        }
+        let mut found = false;
+        if self.at_marker(b"---") {
+            self.pos += 3;
+            self.skip_space();
+            if self.peek() != Some(b'#') && !self.at_eol() {
+                self.parse_block_node(-1, &mut nodes)?;
+                found = true;
+            } else {
+                self.end_of_line(&mut nodes)?;
+            }
+        }
+        if !found {
+            found = self.parse_nested(-1, false, &mut nodes)?;
+        }
+        self.skip_to_content(&mut nodes);
+        if self.at_marker(b"...") {
+            self.pos += 3;
+            self.end_of_line(&mut nodes)?;
+            self.skip_to_content(&mut nodes);
+        }
+        if !self.at_eof() {
+            return self.error("expected end of document", self.pos);
+        }
+        if !found && nodes.is_empty() {
+            Ok(Document::Null)
+        } else if nodes.len() == 1 {
+            Ok(nodes.pop().unwrap())
+        } else {
+            Ok(Document::Fragment(nodes))
+        }
+    }
+
+    fn bytes(&self) -> &'a [u8] {
+        self.text.as_bytes()
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes().get(self.pos).copied()
+    }
+
+    fn peek_at(&self, n: usize) -> Option<u8> {
+        self.bytes().get(self.pos + n).copied()
+    }
+
+    fn is_blank(byte: Option<u8>) -> bool {
+        matches!(byte, None | Some(b' ' | b'\t' | b'\r' | b'\n'))
+    }
+
+    fn at_eof(&self) -> bool {
+        self.pos >= self.text.len()
+    }
+
+    fn at_eol(&self) -> bool {
+        matches!(self.peek(), None | Some(b'\r' | b'\n'))
+    }
+
+    fn at_marker(&self, marker: &[u8]) -> bool {
+        self.column() == 0
+            && self.bytes()[self.pos..].starts_with(marker)
+            && Self::is_blank(self.peek_at(marker.len()))
+    }
+
+    fn at_directive(&self) -> bool {
+        self.peek() == Some(b'%') && self.column() == 0
+    }
+
+    // Returns whether the parser is positioned at the end of the document.
+    fn at_end(&self) -> bool {
+        self.at_eof() || self.at_marker(b"---") || self.at_marker(b"...")
+    }
+
+    fn at_sequence_entry(&self) -> bool {
+        self.peek() == Some(b'-') && Self::is_blank(self.peek_at(1))
+    }
+
+    fn line_of(&self, pos: usize) -> usize {
+        match self.lines.binary_search(&pos) {
+            Ok(i) => i,
+            Err(i) => i - 1,
+        }
+    }
+
+    fn column(&self) -> usize {
+        self.pos - self.lines[self.line_of(self.pos)]
+    }
+
+    fn error<T>(&self, msg: &str, pos: usize) -> Result<T> {
+        let line = self.line_of(pos);
+        let start = self.lines[line];
+        let end = self.text[start..]
+            .find('\n')
+            .map(|e| start + e)
+            .unwrap_or(self.text.len());
+        Err(Error::SyntaxError(
+            msg.into(),
+            line + 1,
+            pos - start + 1,
+            self.text[start..end].trim_end().into(),
+            "^",
+        ))
+    }
+
+    fn skip_space(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t')) {
+            self.pos += 1;
+        }
+    }
+
+    fn skip_line(&mut self) {
+        while !self.at_eol() {
+            self.pos += 1;
+        }
+        self.skip_newline();
+    }
+
+    fn skip_newline(&mut self) {
+        if self.peek() == Some(b'\r') {
+            self.pos += 1;
+        }
+        if self.peek() == Some(b'\n') {
+            self.pos += 1;
+        }
+    }
+
+    // Reads a `#` comment through the end of the line.
+    fn read_comment(&mut self) -> &'a str {
+        let start = self.pos + 1;
+        while !self.at_eol() {
+            self.pos += 1;
+        }
+        let c = &self.text[start..self.pos];
+        c.strip_prefix(' ').unwrap_or(c).trim_end()
+    }
+
+    // Skips blank lines and comment lines, leaving the parser at the first
+    // content character of the next content line.  Consecutive comment lines
+    // are gathered into a single comment node.
+    fn skip_to_content(&mut self, comments: &mut Vec<Document<'static>>) {
+        let mut group = Vec::new();
+        loop {
+            self.skip_space();
+            match self.peek() {
+                Some(b'#') => {
+                    group.push(self.read_comment());
+                    self.skip_newline();
+                }
+                Some(b'\r' | b'\n') => {
+                    if !group.is_empty() {
+                        comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+                        group.clear();
+                    }
+                    self.skip_newline();
+                }
+                _ => break,
+            }
+        }
+        if !group.is_empty() {
+            comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+        }
+    }
+
+    // Gathers the comments between the last member of a block collection at
+    // `indent` and the less indented content which follows it.  Comments
+    // indented to the collection (and deeper than its parent at `parent`)
+    // are the collection's dangling comments; the rest are left for the
+    // content which follows.
+    fn end_block(&mut self, indent: usize, parent: isize, members: &mut Vec<Document<'static>>) {
+        let min_col = indent.max((parent + 1) as usize);
+        let mut comments = Vec::new();
+        let mut group = Vec::new();
+        loop {
+            let line = self.pos;
+            self.skip_space();
+            match self.peek() {
+                Some(b'#') if self.column() >= min_col => {
+                    group.push(self.read_comment());
+                    self.skip_newline();
+                }
+                Some(b'\r' | b'\n') => {
+                    if !group.is_empty() {
+                        comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+                        group.clear();
+                    }
+                    self.skip_newline();
+                }
+                _ => {
+                    self.pos = line;
+                    break;
+                }
+            }
+        }
+        if !group.is_empty() {
+            comments.push(Document::Comment(group.join("\n"), CommentFormat::Hash));
+        }
+        if !comments.is_empty() {
+            members.push(Document::Fragment(comments));
+        }
+    }
+
+    // Consumes the remainder of the current line, which may only hold a comment.
+    fn end_of_line(&mut self, nodes: &mut Vec<Document<'static>>) -> Result<()> {
+        self.skip_space();
+        if self.peek() == Some(b'#') {
+            let c = self.read_comment();
+            nodes.push(Document::Comment(c.into(), CommentFormat::Hash));
+        }
+        if !self.at_eol() {
+            return self.error("unexpected character", self.pos);
+        }
+        self.skip_newline();
+        Ok(())
+    }
+
+    // Parses a node starting on the line after its parent's key or sequence
+    // entry.  Returns false if there is no such node (ie: the value is empty).
+    fn parse_nested(
+        &mut self,
+        indent: isize,
+        same_indent_seq: bool,
+        nodes: &mut Vec<Document<'static>>,
+    ) -> Result<bool> {
+        let mark = self.pos;
+        let mut comments = Vec::new();
+        self.skip_to_content(&mut comments);
+        let col = self.column() as isize;
+        let nested = !self.at_end()
+            && (col > indent || (same_indent_seq && col == indent && self.at_sequence_entry()));
+        if !nested {
+            // Leave the comments for whatever node follows.
+            self.pos = mark;
+            return Ok(false);
+        }
+        if self.at_sequence_entry() {
+            // Collections gather the comments preceding their first entry.
+            self.pos = mark;
+            nodes.push(self.parse_block_sequence(col as usize, indent)?);
+        } else if self.is_key()? {
+            self.pos = mark;
+            nodes.push(self.parse_block_mapping(col as usize, indent)?);
+        } else {
+            nodes.extend(comments);
+            self.parse_inline(indent, nodes)?;
+        }
+        Ok(true)
+    }
+
+    // Parses a node starting at the current position.
+    fn parse_block_node(
+        &mut self,
+        indent: isize,
+        nodes: &mut Vec<Document<'static>>,
+    ) -> Result<()> {
+        let col = self.column();
+        if self.at_sequence_entry() {
+            nodes.push(self.parse_block_sequence(col, indent)?);
+        } else if self.is_key()? {
+            nodes.push(self.parse_block_mapping(col, indent)?);
+        } else {
+            self.parse_inline(indent, nodes)?;
+        }
+        Ok(())
+    }
+
+    // Parses a scalar, flow collection or block scalar followed by the end of the line.
+    fn parse_inline(&mut self, indent: isize, nodes: &mut Vec<Document<'static>>) -> Result<()> {
+        match self.peek() {
+            Some(b'|' | b'>') => return self.parse_block_scalar(indent, nodes),
+            Some(b'[' | b'{') => {
+                let node = self.parse_flow()?;
+                let empty =
+                    matches!(&node, Document::Sequence(v) | Document::Mapping(v) if v.is_empty());
+                nodes.push(if empty {
+                    node
+                } else {
+                    Document::Compact(node.into())
+                });
+            }
+            Some(b'-') if self.at_sequence_entry() => {
+                return self.error("sequence entry not allowed here", self.pos);
+            }
+            Some(b'"' | b'\'') => {
+                let node = self.parse_scalar(false)?;
+                nodes.push(node);
+            }
+            _ => {
+                let start = self.pos;
+                let node = self.parse_scalar(false)?;
+                nodes.push(self.continue_plain(start, indent, node));
+            }
+        }
+        self.end_of_line(nodes)
+    }
+
+    fn parse_block_mapping(&mut self, indent: usize, parent: isize) -> Result<Document<'static>> {
+        let mut entries = Vec::new();
+        loop {
+            let mark = self.pos;
+            let mut nodes = Vec::new();
+            self.skip_to_content(&mut nodes);
+            if self.at_end() {
+                if !nodes.is_empty() {
+                    entries.push(Document::Fragment(nodes));
+                }
+                break;
+            }
+            let col = self.column();
+            if col < indent || (col == indent && self.at_sequence_entry()) {
+                self.pos = mark;
+                self.end_block(indent, parent, &mut entries);
+                break;
+            }
+            if col > indent {
+                return self.error("bad indentation of a mapping entry", self.pos);
+            }
+            let start = self.pos;
+            let key = match self.parse_key()? {
+                Some(key) => key,
+                None => return self.error("expected a mapping key", start),
+            };
+            self.skip_space();
+            if self.peek() == Some(b'#') || self.at_eol() {
+                // A comment after the key trails the value, which is where
+                // the emitters put the trailing comments of a nested
+                // collection back.
+                let mut kc = Vec::new();
+                self.end_of_line(&mut kc)?;
+                nodes.push(key);
+                if !self.parse_nested(indent as isize, true, &mut nodes)? {
+                    nodes.push(Document::Null);
+                }
+                nodes.extend(kc);
+            } else {
+                nodes.push(key);
+                self.parse_inline(indent as isize, &mut nodes)?;
+            }
+            entries.push(Document::Fragment(nodes));
+        }
+        Ok(Document::Mapping(entries))
+    }
+
+    fn parse_block_sequence(&mut self, indent: usize, parent: isize) -> Result<Document<'static>> {
+        let mut items = Vec::new();
+        loop {
+            let mark = self.pos;
+            let mut nodes = Vec::new();
+            self.skip_to_content(&mut nodes);
+            if self.at_end() {
+                if !nodes.is_empty() {
+                    items.push(Document::Fragment(nodes));
+                }
+                break;
+            }
+            let col = self.column();
+            if col < indent || (col == indent && !self.at_sequence_entry()) {
+                self.pos = mark;
+                self.end_block(indent, parent, &mut items);
+                break;
+            }
+            if col > indent {
+                return self.error("bad indentation of a sequence entry", self.pos);
+            }
+            // Skip the `-` indicator.
+            self.pos += 1;
+            self.skip_space();
+            if self.peek() == Some(b'#') || self.at_eol() {
+                // As for a mapping key, a comment after the indicator trails
+                // the value.
+                let mut ic = Vec::new();
+                self.end_of_line(&mut ic)?;
+                if !self.parse_nested(indent as isize, false, &mut nodes)? {
+                    nodes.push(Document::Null);
+                }
+                nodes.extend(ic);
+            } else {
+                self.parse_block_node(indent as isize, &mut nodes)?;
+            }
+            // Like the `Relax` parser, a sequence element without comments is
+            // not wrapped in a fragment.
+            if nodes.len() == 1 && nodes[0].comment().is_none() {
+                items.push(nodes.pop().unwrap());
+            } else {
+                items.push(Document::Fragment(nodes));
+            }
+        }
+        Ok(Document::Sequence(items))
+    }
+
+    // Returns whether a mapping key begins at the current position.
+    fn is_key(&mut self) -> Result<bool> {
+        let mark = self.pos;
+        let key = self.parse_key()?;
+        self.pos = mark;
+        Ok(key.is_some())
+    }
+
+    // Parses a mapping key and its `:` indicator.  Returns `None` and leaves the
+    // position unchanged if there is no key at the current position.
+    fn parse_key(&mut self) -> Result<Option<Document<'static>>> {
+        let start = self.pos;
+        let key = match self.peek() {
+            Some(b'"' | b'\'') => self.parse_scalar(false)?,
+            Some(b'[' | b'{' | b'#' | b'|' | b'>') | None => return Ok(None),
+            _ => {
+                let s = self.scan_plain(false);
+                if s.is_empty() {
+                    return Ok(None);
+                }
+                Self::resolve_plain(s)
+            }
+        };
+        self.skip_space();
+        if self.peek() == Some(b':') && Self::is_blank(self.peek_at(1)) {
+            self.pos += 1;
+            Ok(Some(key))
+        } else {
+            self.pos = start;
+            Ok(None)
+        }
+    }
+
+    // Scans a plain (unquoted) scalar and returns its text.
+    fn scan_plain(&mut self, flow: bool) -> &'a str {
+        let start = self.pos;
+        while let Some(ch) = self.peek() {
+            let next = self.peek_at(1);
+            let stop = match ch {
+                b'\r' | b'\n' => true,
+                b':' => {
+                    Self::is_blank(next)
+                        || (flow && matches!(next, Some(b',' | b'[' | b']' | b'{' | b'}')))
+                }
+                b'#' => self.pos > start && matches!(self.bytes()[self.pos - 1], b' ' | b'\t'),
+                b',' | b'[' | b']' | b'{' | b'}' => flow,
+                _ => false,
+            };
+            if stop {
+                break;
+            }
+            self.pos += 1;
+        }
+        let text = self.text[start..self.pos].trim_end();
+        self.pos = start + text.len();
+        text
+    }
+
+    // Folds the continuation lines of the plain scalar `node`, which starts
+    // at `start`, into it.  Continuation lines must be indented deeper than
+    // the parent at `indent`.
+    fn continue_plain(
+        &mut self,
+        start: usize,
+        indent: isize,
+        node: Document<'static>,
+    ) -> Document<'static> {
+        let mut text = self.text[start..self.pos].to_string();
+        let mut folded = false;
+        loop {
+            let mark = self.pos;
+            self.skip_space();
+            if !self.at_eol() {
+                // A comment ends the scalar.
+                self.pos = mark;
+                break;
+            }
+            let mut breaks = 0;
+            while self.at_eol() && !self.at_eof() {
+                self.skip_newline();
+                self.skip_space();
+                breaks += 1;
+            }
+            if self.at_end() || self.column() as isize <= indent || self.peek() == Some(b'#') {
+                self.pos = mark;
+                break;
+            }
+            let line = self.scan_plain(false);
+            let end = self.pos;
+            self.skip_space();
+            if line.is_empty() || self.peek() == Some(b':') {
+                // An implicit key cannot continue a scalar.
+                self.pos = mark;
+                break;
+            }
+            self.pos = end;
+            // A single line break folds into a space; each additional one is
+            // kept as a newline.
+            if breaks == 1 {
+                text.push(' ');
+            } else {
+                text.extend(std::iter::repeat_n('\n', breaks - 1));
+            }
+            text.push_str(line);
+            folded = true;
+        }
+        if folded {
+            Self::resolve_plain(&text)
+        } else {
+            node
+        }
+    }
+
+    fn parse_scalar(&mut self, flow: bool) -> Result<Document<'static>> {
+        match self.peek() {
+            Some(b'"') => Ok(Document::String(
+                self.parse_double_quoted()?,
+                StrFormat::Standard,
+            )),
+            Some(b'\'') => Ok(Document::String(
+                self.parse_single_quoted()?,
+                StrFormat::Standard,
+            )),
+            Some(b'&' | b'*' | b'!') => {
+                self.error("anchors, aliases and tags are not supported", self.pos)
+            }
+            Some(b'?') if Self::is_blank(self.peek_at(1)) => {
+                self.error("complex mapping keys are not supported", self.pos)
+            }
+            Some(b'@' | b'`') => self.error("reserved indicator", self.pos),
+            _ => {
+                let start = self.pos;
+                let s = self.scan_plain(flow);
+                if s.is_empty() {
+                    return self.error("expected a value", start);
+                }
+                Ok(Self::resolve_plain(s))
+            }
+        }
+    }
+
+    // Resolves the type of a plain scalar with the YAML 1.2 core schema
+    // (plus the `0b` binary integers emitted by `Yaml`).
+    fn resolve_plain(s: &str) -> Document<'static> {
+        static INT: OnceCell<Regex> = OnceCell::new();
+        static FLOAT: OnceCell<Regex> = OnceCell::new();
+        let int = INT.get_or_init(|| {
+            Regex::new(r"^[-+]?(?:[0-9]+|0x[0-9a-fA-F]+|0o[0-7]+|0b[01]+)$").unwrap()
+        });
+        let float = FLOAT.get_or_init(|| {
+            Regex::new(r"^[-+]?(?:\.[0-9]+|[0-9]+(?:\.[0-9]*)?)(?:[eE][-+]?[0-9]+)?$").unwrap()
+        });
+        match s {
+            "null" | "Null" | "NULL" | "~" => Document::Null,
+            "true" | "True" | "TRUE" => Document::Boolean(true),
+            "false" | "False" | "FALSE" => Document::Boolean(false),
+            ".inf" | ".Inf" | ".INF" | "+.inf" | "+.Inf" | "+.INF" => {
+                Document::Float(f64::INFINITY)
+            }
+            "-.inf" | "-.Inf" | "-.INF" => Document::Float(f64::NEG_INFINITY),
+            ".nan" | ".NaN" | ".NAN" => Document::Float(f64::NAN),
+            _ if int.is_match(s) => match Int::from_str_radix(s, 0) {
+                Ok(i) => Document::Int(i),
+                // Integers too large for an `Int` are kept as floats.
+                Err(_) => s
+                    .parse::<f64>()
+                    .map(Document::Float)
+                    .unwrap_or_else(|_| Document::String(s.into(), StrFormat::Unquoted)),
+            },
+            _ if float.is_match(s) => match s.parse::<f64>() {
+                Ok(f) => Document::Float(f),
+                Err(_) => Document::String(s.into(), StrFormat::Unquoted),
+            },
+            _ => Document::String(s.into(), StrFormat::Unquoted),
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        let ch = self.text[self.pos..].chars().next()?;
+        self.pos += ch.len_utf8();
+        Some(ch)
+    }
+
+    // Folds a line break inside a quoted scalar: a single line break becomes
+    // a space and each additional empty line becomes a newline.
+    fn fold_line_break(&mut self, s: &mut String) {
+        s.truncate(s.trim_end_matches([' ', '\t']).len());
+        let mut breaks = 0;
+        loop {
+            self.skip_space();
+            if matches!(self.peek(), Some(b'\r' | b'\n')) {
+                self.skip_newline();
+                breaks += 1;
+            } else {
+                break;
+            }
+        }
+        if breaks == 1 {
+            s.push(' ');
+        } else {
+            for _ in 1..breaks {
+                s.push('\n');
+            }
+        }
+    }
+
+    fn hex_escape(&mut self, digits: usize) -> Result<char> {
+        let start = self.pos;
+        let text = self
+            .text
+            .get(self.pos..self.pos + digits)
+            .filter(|t| t.bytes().all(|b| b.is_ascii_hexdigit()));
+        match text {
+            Some(t) => {
+                self.pos += digits;
+                Ok(char::try_from(u32::from_str_radix(t, 16)?)?)
+            }
+            None => self.error("bad escape sequence", start),
+        }
+    }
+
+    fn parse_double_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return self.error("unterminated string", start),
+                Some(b'"') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\r' | b'\n') => self.fold_line_break(&mut s),
+                Some(b'\\') => {
+                    self.pos += 1;
+                    let ch = match self.next_char() {
+                        Some(ch) => ch,
+                        None => return self.error("unterminated string", start),
+                    };
+                    let decoded = match ch {
+                        '0' => '\0',
+                        'a' => '\x07',
+                        'b' => '\x08',
+                        't' | '\t' => '\t',
+                        'n' => '\n',
+                        'v' => '\x0b',
+                        'f' => '\x0c',
+                        'r' => '\r',
+                        'e' => '\x1b',
+                        ' ' => ' ',
+                        '"' => '"',
+                        '/' => '/',
+                        '\\' => '\\',
+                        'N' => '\u{85}',
+                        '_' => '\u{a0}',
+                        'L' => '\u{2028}',
+                        'P' => '\u{2029}',
+                        'x' => self.hex_escape(2)?,
+                        'u' => self.hex_escape(4)?,
+                        'U' => self.hex_escape(8)?,
+                        '\r' | '\n' => {
+                            // An escaped line break joins the lines without a space.
+                            if ch == '\r' && self.peek() == Some(b'\n') {
+                                self.pos += 1;
+                            }
+                            self.skip_space();
+                            continue;
+                        }
+                        _ => return Err(Error::EscapeError(ch)),
+                    };
+                    s.push(decoded);
+                }
+                Some(_) => {
+                    let ch = self.next_char().unwrap();
+                    s.push(ch);
+                }
+            }
+        }
+        Ok(s)
+    }
+
+    fn parse_single_quoted(&mut self) -> Result<String> {
+        let start = self.pos;
+        self.pos += 1;
+        let mut s = String::new();
+        loop {
+            match self.peek() {
+                None => return self.error("unterminated string", start),
+                Some(b'\'') if self.peek_at(1) == Some(b'\'') => {
+                    self.pos += 2;
+                    s.push('\'');
+                }
+                Some(b'\'') => {
+                    self.pos += 1;
+                    break;
+                }
+                Some(b'\r' | b'\n') => self.fold_line_break(&mut s),
+                Some(_) => {
+                    let ch = self.next_char().unwrap();
+                    s.push(ch);
+                }
+            }
+        }
+        Ok(s)
+    }
+
+    fn parse_block_scalar(
+        &mut self,
+        indent: isize,
+        nodes: &mut Vec<Document<'static>>,
+    ) -> Result<()> {
+        let literal = self.peek() == Some(b'|');
+        self.pos += 1;
+        let mut chomp = Chomp::Clip;
+        let mut explicit = None;
+        for _ in 0..2 {
+            match self.peek() {
+                Some(b'-') => chomp = Chomp::Strip,
+                Some(b'+') => chomp = Chomp::Keep,
+                Some(ch @ b'1'..=b'9') => explicit = Some((ch - b'0') as isize),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+        let mut trailing = Vec::new();
+        self.end_of_line(&mut trailing)?;
+
+        let mut content_indent = explicit.map(|n| (indent.max(0) + n) as usize);
+        let mut lines = Vec::new();
+        while !self.at_eof() {
+            let start = self.pos;
+            let end = self.text[start..]
+                .find('\n')
+                .map(|e| start + e)
+                .unwrap_or(self.text.len());
+            let line = self.text[start..end].trim_end_matches('\r');
+            let spaces = line.len() - line.trim_start_matches(' ').len();
+            if line.trim().is_empty() {
+                // Blank lines may be shorter than the content indentation,
+                // or be indented with other whitespace, so only the leading
+                // spaces are removed.
+                let ci = content_indent.unwrap_or(usize::MAX).min(spaces);
+                lines.push(&line[ci..]);
+            } else {
+                let ci = *content_indent.get_or_insert(spaces);
+                if spaces < ci || spaces as isize <= indent {
+                    break;
+                }
+                lines.push(&line[ci..]);
+            }
+            self.pos = end;
+            self.skip_newline();
+        }
+
+        let end = lines.len()
+            - lines
+                .iter()
+                .rev()
+                .take_while(|line| line.trim().is_empty())
+                .count();
+        let mut value = if literal {
+            lines[..end].join("\n")
+        } else {
+            Self::fold(&lines[..end])
+        };
+        match chomp {
+            Chomp::Strip => {}
+            Chomp::Clip if end > 0 => value.push('\n'),
+            Chomp::Clip => {}
+            Chomp::Keep => {
+                let breaks = lines.len() - end + usize::from(end > 0);
+                value.push_str(&"\n".repeat(breaks));
+            }
+        }
+        nodes.push(Document::String(value, StrFormat::Multiline));
+        nodes.extend(trailing);
+        Ok(())
+    }
+
+    // Folds the lines of a `>` block scalar.
+    fn fold(lines: &[&str]) -> String {
+        let mut s = String::new();
+        let mut empty = 0;
+        let mut prev: Option<&str> = None;
+        for line in lines {
+            if line.is_empty() {
+                empty += 1;
+                continue;
+            }
+            let more_indented = |l: &str| l.starts_with([' ', '\t']);
+            match prev {
+                Some(p) if more_indented(p) || more_indented(line) => {
+                    s.push_str(&"\n".repeat(empty + 1))
+                }
+                Some(_) if empty == 0 => s.push(' '),
+                _ => s.push_str(&"\n".repeat(empty)),
+            }
+            s.push_str(line);
+            empty = 0;
+            prev = Some(line);
+        }
+        s
+    }
+
+    // Skips whitespace, line breaks and comments inside a flow collection.
+    fn skip_flow_space(&mut self) {
+        loop {
+            match self.peek() {
+                Some(b' ' | b'\t' | b'\r' | b'\n') => self.pos += 1,
+                Some(b'#') => {
+                    while !self.at_eol() {
+                        self.pos += 1;
+                    }
+                }
+                _ => break,
+            }
+        }
+    }
+
+    fn parse_flow(&mut self) -> Result<Document<'static>> {
+        let start = self.pos;
+        match self.peek() {
+            Some(b'[') => {
+                self.pos += 1;
+                let mut items = Vec::new();
+                loop {
+                    self.skip_flow_space();
+                    match self.peek() {
+                        Some(b']') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        None => return self.error("unterminated flow sequence", start),
+                        _ => {}
+                    }
+                    items.push(self.parse_flow()?);
+                    self.skip_flow_space();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b']') => {}
+                        _ => return self.error("expected `,` or `]`", self.pos),
+                    }
+                }
+                Ok(Document::Sequence(items))
+            }
+            Some(b'{') => {
+                self.pos += 1;
+                let mut entries = Vec::new();
+                loop {
+                    self.skip_flow_space();
+                    match self.peek() {
+                        Some(b'}') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        None => return self.error("unterminated flow mapping", start),
+                        _ => {}
+                    }
+                    let key = self.parse_scalar(true)?;
+                    self.skip_flow_space();
+                    let value = if self.peek() == Some(b':') {
+                        self.pos += 1;
+                        self.skip_flow_space();
+                        if matches!(self.peek(), Some(b',' | b'}')) {
+                            Document::Null
+                        } else {
+                            self.parse_flow()?
+                        }
+                    } else {
+                        Document::Null
+                    };
+                    entries.push(Document::Fragment(vec![key, value]));
+                    self.skip_flow_space();
+                    match self.peek() {
+                        Some(b',') => self.pos += 1,
+                        Some(b'}') => {}
+                        _ => return self.error("expected `,` or `}`", self.pos),
+                    }
+                }
+                Ok(Document::Mapping(entries))
+            }
+            _ => self.parse_scalar(true),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::integer::Base;
+    use anyhow::{anyhow, Result};
+
+    fn parse(text: &str) -> Result<Document<'static>> {
+        Ok(YamlParser::new(text).parse()?)
+    }
+
+    fn parse_integer(text: &str) -> Result<(i128, Base)> {
+        if let Document::Int(int) = parse(text)? {
+            Ok((int.clone().into(), int.base()))
+        } else {
+            Err(anyhow!("Didn't return Document::Int()"))
+        }
+    }
+
+    fn parse_string(text: &str) -> Result<(String, StrFormat)> {
+        if let Document::String(s, f) = parse(text)? {
+            Ok((s, f))
+        } else {
+            Err(anyhow!("Didn't return Document::String()"))
+        }
+    }
+
+    fn kv_extract<'a>(kv: &'a Document) -> Result<(&'a str, &'a Document<'a>)> {
+        let (k, v) = kv.as_kv()?;
+        Ok((k.as_str()?, v))
+    }
+
+    #[test]
+    fn test_scalars() -> Result<()> {
+        assert!(matches!(parse("null")?, Document::Null));
+        assert!(matches!(parse("~")?, Document::Null));
+        assert!(matches!(parse("")?, Document::Null));
+        assert!(matches!(parse("true")?, Document::Boolean(true)));
+        assert!(matches!(parse("False")?, Document::Boolean(false)));
+        assert!(matches!(parse("3.5")?, Document::Float(f) if f == 3.5));
+        assert!(matches!(parse("-.inf")?, Document::Float(f) if f == f64::NEG_INFINITY));
+        assert!(matches!(parse(".nan")?, Document::Float(f) if f.is_nan()));
+        assert_eq!(
+            parse_string("hello world")?,
+            ("hello world".into(), StrFormat::Unquoted)
+        );
+        assert_eq!(parse_string("'it''s'")?.0, "it's");
+        assert_eq!(parse_string(r#""a\tb™\"""#)?.0, "a\tb\u{2122}\"");
+        assert_eq!(
+            parse_string("\"one\n  two\n\n  three\"")?.0,
+            "one two\nthree"
+        );
+        assert_eq!(parse_string("2014-12-31")?.0, "2014-12-31");
+        // Only the core schema forms are numbers.
+        assert_eq!(parse_string("1_000")?.0, "1_000");
+        assert_eq!(parse_string("0x00_FF")?.0, "0x00_FF");
+        assert_eq!(parse_string("inf")?.0, "inf");
+        assert_eq!(parse_string("-inf")?.0, "-inf");
+        assert_eq!(parse_string("NaN")?.0, "NaN");
+        assert_eq!(parse_string("1e")?.0, "1e");
+        assert!(matches!(parse("1e3")?, Document::Float(f) if f == 1000.0));
+        assert!(matches!(parse("-.5")?, Document::Float(f) if f == -0.5));
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip_plain_strings() -> Result<()> {
+        let text = "---
+a: 1_000
+b: inf
+c: NaN
+d: .inf
+e: -.inf
+f: .nan";
+        let doc = parse(text)?;
+        // The strings are quoted for YAML 1.1 readers, which read them as numbers.
+        let quoted = "---\na: \"1_000\"\nb: \"inf\"\nc: \"NaN\"\nd: .inf\ne: -.inf\nf: .nan";
+        assert_eq!(doc.clone().to_yaml().to_string(), quoted);
+        assert_eq!(parse(quoted)?.to_yaml().to_string(), quoted);
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Expected a mapping"));
+        };
+        let values = m
+            .iter()
+            .map(|kv| kv_extract(kv).map(|(_, v)| v))
+            .collect::<Result<Vec<_>>>()?;
+        assert!(matches!(values[0], Document::String(s, _) if s == "1_000"));
+        assert!(matches!(values[1], Document::String(s, _) if s == "inf"));
+        assert!(matches!(values[2], Document::String(s, _) if s == "NaN"));
+        assert!(matches!(values[3], Document::Float(f) if *f == f64::INFINITY));
+        assert!(matches!(values[4], Document::Float(f) if *f == f64::NEG_INFINITY));
+        assert!(matches!(values[5], Document::Float(f) if f.is_nan()));
+        Ok(())
+    }
+
+    #[test]
+    fn test_integers() -> Result<()> {
+        assert_eq!(parse_integer("1234")?, (1234, Base::Dec));
+        assert_eq!(parse_integer("-5678")?, (-5678, Base::Dec));
+        assert_eq!(parse_integer("0x50444653")?, (0x50444653, Base::Hex));
+        assert_eq!(parse_integer("0o755")?, (0o755, Base::Oct));
+        assert_eq!(parse_integer("0b11111111")?, (255, Base::Bin));
+        // Leading zeros are preserved.
+        assert_eq!(
+            parse("0x00FF")?.to_yaml().header(false).to_string(),
+            "0x00FF"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_scalars() -> Result<()> {
+        let doc = parse("a: |-\n  Look, Mom!\n  No newline\nb: |+\n  kept\n\nc: |\n  clip\n\n\nd: >\n  folded\n  text\n\n  para\n")?;
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Expected a mapping"));
+        };
+        let expect = [
+            ("a", "Look, Mom!\nNo newline"),
+            ("b", "kept\n\n"),
+            ("c", "clip\n"),
+            ("d", "folded text\npara\n"),
+        ];
+        for (kv, (key, value)) in m.iter().zip(expect) {
+            let (k, v) = kv_extract(kv)?;
+            assert_eq!(k, key);
+            assert!(matches!(v, Document::String(s, StrFormat::Multiline) if s == value));
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_scalar_blank_lines() -> Result<()> {
+        // Blank lines may be short or contain multi-byte whitespace.
+        let doc = parse("a: |\n  x\n\u{3000}\n \n  y\n")?;
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Expected a mapping"));
+        };
+        let (_, v) = kv_extract(&m[0])?;
+        assert!(matches!(v, Document::String(s, _) if s == "x\n\u{3000}\n\ny\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_plain_continuation() -> Result<()> {
+        assert_eq!(parse_string("one\ntwo\n\n  three")?.0, "one two\nthree");
+        let doc = parse("a: b\n  c # d\ne:\n  - f\n    g\n  - h\n")?;
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Expected a mapping"));
+        };
+        let (_, v) = kv_extract(&m[0])?;
+        assert!(matches!(v, Document::String(s, _) if s == "b c"));
+        assert_eq!(
+            m[0].fragments()?
+                .last()
+                .and_then(|c| c.comment())
+                .map(|c| c.0),
+            Some("d")
+        );
+        let (_, v) = kv_extract(&m[1])?;
+        let Document::Sequence(s) = v else {
+            return Err(anyhow!("Expected a sequence"));
+        };
+        assert!(matches!(&s[0], Document::String(s, _) if s == "f g"));
+        assert!(matches!(&s[1], Document::String(s, _) if s == "h"));
+        // Continuation lines must be indented deeper than the parent.
+        assert!(parse("a: b\nc\n").is_err());
+        // Nor can a mapping key.
+        assert!(parse("- a\n  b: c\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_mapping() -> Result<()> {
+        let doc = parse(
+            r#"
+            # Leading comment
+            name: Fred  # trailing comment
+            "quoted key": 'value'
+            nested:
+              inner: 1
+            list:
+            - 1
+            - 2
+            empty:
+            flow: {prg: [1, 0x8000]}
+            "#,
+        )?;
+        let Document::Mapping(m) = doc else {
+            return Err(anyhow!("Expected a mapping"));
+        };
+        assert_eq!(m.len(), 6);
+        let nodes = m[0].fragments()?;
+        assert!(
+            matches!(&nodes[0], Document::Comment(c, CommentFormat::Hash) if c == "Leading comment")
+        );
+        assert!(matches!(&nodes[3], Document::Comment(c, _) if c == "trailing comment"));
+        let (k, v) = kv_extract(&m[0])?;
+        assert_eq!((k, v.as_str()?), ("name", "Fred"));
+        let (k, v) = kv_extract(&m[1])?;
+        assert_eq!((k, v.as_str()?), ("quoted key", "value"));
+        let (k, v) = kv_extract(&m[2])?;
+        assert_eq!(k, "nested");
+        assert!(matches!(v, Document::Mapping(n) if n.len() == 1));
+        let (k, v) = kv_extract(&m[3])?;
+        assert_eq!(k, "list");
+        assert!(matches!(v, Document::Sequence(s) if s.len() == 2));
+        let (k, v) = kv_extract(&m[4])?;
+        assert_eq!(k, "empty");
+        assert!(matches!(v, Document::Null));
+        let (k, v) = kv_extract(&m[5])?;
+        assert_eq!(k, "flow");
+        assert!(matches!(v, Document::Compact(_)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sequence() -> Result<()> {
+        let doc = parse(
+            r#"---
+- a: 1
+  b: 2
+-
+  # A comment
+  c: 3
+- - x
+  - y
+- [1, 2,
+3,]
+"#,
+        )?;
+        let Document::Sequence(s) = doc else {
+            return Err(anyhow!("Expected a sequence"));
+        };
+        assert_eq!(s.len(), 4);
+        assert!(matches!(&s[0], Document::Mapping(m) if m.len() == 2));
+        assert!(matches!(&s[1], Document::Mapping(m) if m[0].fragments()?[0].comment().is_some()));
+        assert!(matches!(&s[2], Document::Sequence(v) if v.len() == 2));
+        assert!(
+            matches!(&s[3], Document::Compact(c) if matches!(&**c, Document::Sequence(v) if v.len() == 3))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_errors() -> Result<()> {
+        let e = parse("a: 1\n   b: 2").unwrap_err();
+        assert_eq!(
+            e.to_string(),
+            "syntax error: bad indentation of a mapping entry at 2:4\n|    b: 2\n|    ^"
+        );
+        assert!(parse("a: [1, 2").is_err());
+        assert!(parse("a: \"unterminated").is_err());
+        assert!(parse("a: *alias").is_err());
+        assert!(parse("a: - 1").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_roundtrip() -> Result<()> {
+        let text = r#"---
+# Signature
+signature: 0x50444653
+# Version
+minor: 6
+list:
+  - 0b0101
+  - "needs: quotes"
+  -
+    # Inner
+    a: 1
+    b: []
+hexdump: |-
+  00000000  45 76 65 72 79 77 68 65  72 65 20 74 68 61 74 20  |Everywhere that |
+  00000010  4d 61 72 79 20 77 65 6e  74                       |Mary went|
+nes: {prg: [1, 0x8000]}
+none: null"#;
+        let doc = parse(text)?;
+        assert_eq!(doc.to_yaml().to_string(), text);
+        Ok(())
+    }
+}
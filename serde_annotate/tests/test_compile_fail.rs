@@ -0,0 +1,18 @@
+// Checks the compile errors for misuse of the annotations.  These drive
+// cargo through trybuild, so they have no Bazel target.
+
+// A value borrowed for `'a` must not reach an `Annotate` impl which assumes
+// the borrow is `'static`.
+#[test]
+fn test_lookup_compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/lookup/*.rs");
+}
+
+// Bad field references, format specs and braces in comment templates are
+// reported at the template.
+#[test]
+fn test_template_compile_fail() {
+    let t = trybuild::TestCases::new();
+    t.compile_fail("tests/ui/template/*.rs");
+}
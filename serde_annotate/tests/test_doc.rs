@@ -0,0 +1,84 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+
+/// An SFDP parameter header.
+///
+/// See JESD216 for details.
+#[derive(Serialize, Annotate)]
+#[annotate(doc_comments)]
+struct ParamHeader {
+    /// The parameter ID.
+    #[annotate(format = hex)]
+    id: u8,
+    /// The parameter revision.
+    #[annotate(comment = "Revision")]
+    revision: u8,
+    /**
+     * The length in dwords.
+     *   Must be non-zero.
+     */
+    length: u8,
+    pointer: u32,
+}
+
+#[test]
+fn test_doc_comments() -> Result<()> {
+    let h = ParamHeader {
+        id: 0xFF,
+        revision: 1,
+        length: 9,
+        pointer: 0x30,
+    };
+    let s = serialize(&h)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"// An SFDP parameter header.
+//
+// See JESD216 for details.
+{
+  // The parameter ID.
+  id: 0xFF,
+  // Revision
+  revision: 1,
+  // The length in dwords.
+  //   Must be non-zero.
+  length: 9,
+  pointer: 48
+}"#
+    );
+    Ok(())
+}
+
+/// Commands for the device.
+#[derive(Serialize, Annotate)]
+enum Command {
+    /// Reads a register.
+    #[annotate(comment = doc)]
+    Read {
+        /// The register.
+        #[annotate(format = hex, comment = doc)]
+        reg: u16,
+    },
+    /// Resets the device.
+    Reset,
+}
+
+#[test]
+fn test_doc_comment_fields() -> Result<()> {
+    let s = serialize(&vec![Command::Read { reg: 0x10 }, Command::Reset])?
+        .to_yaml()
+        .to_string();
+    assert_eq!(
+        s,
+        r#"---
+-
+  # Reads a register.
+  Read:
+    # The register.
+    reg: 0x10
+- Reset"#
+    );
+    Ok(())
+}
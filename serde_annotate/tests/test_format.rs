@@ -2,6 +2,7 @@ use anyhow::Result;
 use serde::{Deserialize, Serialize};
 use serde_annotate::serialize;
 use serde_annotate::Annotate;
+use serde_annotate::{Deserializer, Document, Json, Yaml};
 
 fn fixdoc(doc: &str) -> String {
     let mut s = String::new();
@@ -14,8 +15,8 @@ fn fixdoc(doc: &str) -> String {
             dedent = line.find(|c: char| !c.is_whitespace());
             assert!(dedent.is_some());
         }
-        let (_, v) = line.split_at(dedent.unwrap());
-        s.push_str(v);
+        // Blank lines within the document may be shorter than the indent.
+        s.push_str(line.get(dedent.unwrap()..).unwrap_or_default());
         s.push('\n');
     }
     s.pop();
@@ -29,6 +30,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_json().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::default(), $value, &string);
         let decode: $t = serde_json::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -36,6 +38,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_json5().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::json5(), $value, &string);
         let decode: $t = json5::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -43,6 +46,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_hjson().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::hjson(), $value, &string);
         let decode: $t = deser_hjson::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -50,6 +54,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_json().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::default(), $value, &string);
         let decode: $t = serde_annotate::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -57,6 +62,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_json5().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::json5(), $value, &string);
         let decode: $t = serde_annotate::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -64,6 +70,7 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_hjson().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Json::hjson(), $value, &string);
         let decode: $t = serde_annotate::from_str(&string)?;
         assert_eq!($value, &decode);
     }};
@@ -71,13 +78,39 @@ macro_rules! tester {
         let doc = serialize($value)?;
         let string = doc.to_yaml().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Yaml::default(), $value, &string);
         let decode: $t = serde_yaml::from_str(&string)?;
         assert_eq!($value, &decode);
+        tester!(@annotate_yaml, $t, $value, &string);
     }};
-    (ser_yaml, $t:ty, $value:expr, $expect:expr) => {{
+    (annotate_yaml, $t:ty, $value:expr, $expect:expr) => {{
         let doc = serialize($value)?;
         let string = doc.to_yaml().to_string();
         assert_eq!(string, fixdoc($expect));
+        tester!(@stream, Yaml::default(), $value, &string);
+        tester!(@annotate_yaml, $t, $value, &string);
+    }};
+    (toml, $t:ty, $value:expr, $expect:expr) => {{
+        let doc = serialize($value)?;
+        let string = doc.to_toml().try_to_string()?;
+        assert_eq!(string, fixdoc($expect));
+        let decode: $t = toml::from_str(&string)?;
+        assert_eq!($value, &decode);
+        let doc = Document::from_toml(&string)?;
+        let mut ds = Deserializer::from_document(&doc)?;
+        let decode = <$t>::deserialize(&mut ds)?;
+        assert_eq!($value, &decode);
+    }};
+    (@stream, $format:expr, $value:expr, $string:expr) => {{
+        let mut buf = Vec::new();
+        $format.serialize_to($value, &mut buf)?;
+        assert_eq!(&String::from_utf8(buf)?, $string);
+    }};
+    (@annotate_yaml, $t:ty, $value:expr, $string:expr) => {{
+        let doc = Document::from_yaml($string)?;
+        let mut ds = Deserializer::from_document(&doc)?;
+        let decode = <$t>::deserialize(&mut ds)?;
+        assert_eq!($value, &decode);
     }};
 }
 
@@ -150,6 +183,19 @@ fn test_coordinate() -> Result<()> {
         z: 0o10"#
     );
 
+    tester!(
+        toml,
+        Coordinate,
+        &value,
+        r#"
+        # X-coordinate
+        x = 0x10
+        # Y-coordinate
+        y = 10
+        # Z-coordinate
+        z = 0o10"#
+    );
+
     Ok(())
 }
 
@@ -251,6 +297,23 @@ fn test_sfdp() -> Result<()> {
           reserved: 0b11111111"#
     );
 
+    tester!(
+        toml,
+        Sfdp,
+        &value,
+        r#"
+        [header]
+        # Signature value='SFDP' (should be 'SFDP')
+        signature = 0x50444653
+        # SFDP Version
+        minor = 6
+        major = 1
+        # Number of parameter headers (minus 1)
+        nph = 2
+        # Reserved field should be all ones
+        reserved = 0b11111111"#
+    );
+
     Ok(())
 }
 
@@ -262,12 +325,10 @@ enum NesAddress {
     Prg(#[annotate(format=hex)] u8, #[annotate(format=hex)] u16),
     #[annotate(format=compact, comment="NES CHR bank:address")]
     Chr(#[annotate(format=hex)] u8, #[annotate(format=hex)] u16),
-    // TODO(serde-annotate#6): Currently, we do not emit comments for unit variants.
     #[annotate(comment = "Bad Address")]
     Invalid,
 }
 
-// TODO(serde-annotate#6): Currently, we do not emit comments for newtype structs.
 #[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
 struct CpuAddress(#[annotate(format=hex, comment="CPU Address")] u16);
 
@@ -336,10 +397,14 @@ fn test_nes_addresses() -> Result<()> {
             Chr: [0x2, 0x400]
           },
           vectors: [
+            // CPU Address
             0xFFFA,
+            // CPU Address
             0xFFFC,
+            // CPU Address
             0xFFFE
           ],
+          // Bad Address
           inv: "Invalid"
         }"#
     );
@@ -360,12 +425,45 @@ fn test_nes_addresses() -> Result<()> {
             # NES CHR bank:address
             Chr: [0x2, 0x400]
           vectors:
+            # CPU Address
             - 0xFFFA
+            # CPU Address
             - 0xFFFC
+            # CPU Address
             - 0xFFFE
+          # Bad Address
           inv: Invalid"#
     );
 
+    tester!(
+        toml,
+        Addresses,
+        &value,
+        r#"
+        vectors = [
+          # CPU Address
+          0xFFFA,
+          # CPU Address
+          0xFFFC,
+          # CPU Address
+          0xFFFE,
+        ]
+        # Bad Address
+        inv = "Invalid"
+
+        [a]
+        # NES file offset
+        File = 16400
+
+        [b]
+        # NES PRG bank:address
+        Prg = [0x1, 0x8000]
+
+        [c]
+        # NES CHR bank:address
+        Chr = [0x2, 0x400]"#
+    );
+
     Ok(())
 }
 
@@ -394,11 +492,10 @@ fn test_bytes() -> Result<()> {
         fourth_stanza: "the lamb was sure to go".into(),
     };
 
-    // We can't test deserialization of the yaml data yet because we don't yet
-    // have a parser or adapter for yaml that emits a `serde_annotate::Document`
-    // to feed to our deserializer implementation.
+    // The multiline flow sequence isn't accepted by `serde_yaml`, so we can
+    // only test deserialization of the yaml data with our own yaml parser.
     tester!(
-        ser_yaml,
+        annotate_yaml,
         Poem,
         &value,
         r#"
@@ -571,3 +668,451 @@ fn test_untagged_data() -> Result<()> {
     );
     Ok(())
 }
+
+// Comments placed at the end of the line.
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+struct Register {
+    #[annotate(format=hex, comment="Base address", placement=trailing)]
+    address: u32,
+    #[annotate(comment = "Width in bits", placement = trailing)]
+    width: u8,
+    #[annotate(comment = "Access permissions")]
+    access: String,
+    #[annotate(comment = "Reset values", placement = trailing)]
+    reset: Vec<u8>,
+}
+
+#[test]
+fn test_trailing_comments() -> Result<()> {
+    let value = Register {
+        address: 0x1000,
+        width: 32,
+        access: "rw".into(),
+        reset: vec![1, 2],
+    };
+    tester!(
+        json5,
+        Register,
+        &value,
+        r#"
+        {
+          address: 0x1000, // Base address
+          width: 32, // Width in bits
+          // Access permissions
+          access: "rw",
+          reset: [
+            1,
+            2
+          ] // Reset values
+        }"#
+    );
+
+    tester!(
+        hjson,
+        Register,
+        &value,
+        r#"
+        {
+          address: 4096, # Base address
+          width: 32, # Width in bits
+          # Access permissions
+          access: "rw",
+          reset: [
+            1,
+            2
+          ] # Reset values
+        }"#
+    );
+
+    tester!(
+        yaml,
+        Register,
+        &value,
+        r#"
+        ---
+        address: 0x1000 # Base address
+        width: 32 # Width in bits
+        # Access permissions
+        access: rw
+        reset: # Reset values
+          - 1
+          - 2"#
+    );
+
+    tester!(
+        toml,
+        Register,
+        &value,
+        r#"
+        address = 0x1000 # Base address
+        width = 32 # Width in bits
+        # Access permissions
+        access = "rw"
+        reset = [1, 2] # Reset values"#
+    );
+
+    let doc = serialize(&value)?;
+    assert_eq!(
+        doc.clone().to_json5().align_comments(true).to_string(),
+        fixdoc(
+            r#"
+            {
+              address: 0x1000, // Base address
+              width: 32,       // Width in bits
+              // Access permissions
+              access: "rw",
+              reset: [
+                1,
+                2
+              ] // Reset values
+            }"#
+        )
+    );
+    assert_eq!(
+        doc.to_yaml().align_comments(true).to_string(),
+        fixdoc(
+            r#"
+            ---
+            address: 0x1000 # Base address
+            width: 32       # Width in bits
+            # Access permissions
+            access: rw
+            reset:          # Reset values
+              - 1
+              - 2"#
+        )
+    );
+    Ok(())
+}
+
+// Trailing comments on block scalars, which can't follow the last line.
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+struct Note {
+    #[annotate(format=block, comment="first\nsecond", placement=trailing)]
+    long: String,
+    #[annotate(format=block, comment="note", placement=trailing)]
+    text: String,
+}
+
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+struct Dump {
+    #[serde(with = "serde_bytes")]
+    #[annotate(format=hexdump, comment="data", placement=trailing)]
+    data: Vec<u8>,
+    #[annotate(comment = "notes", placement = trailing)]
+    notes: Vec<Note>,
+}
+
+#[test]
+fn test_block_trailing_comments() -> Result<()> {
+    let value = Note {
+        long: "one\ntwo\n".into(),
+        text: "Hello\nWorld".into(),
+    };
+    tester!(
+        yaml,
+        Note,
+        &value,
+        r#"
+        ---
+        # first
+        # second
+        long: |+
+          one
+          two
+        text: |- # note
+          Hello
+          World"#
+    );
+
+    let value = Dump {
+        data: "Mary had a little lamb".into(),
+        notes: vec![value],
+    };
+    tester!(
+        annotate_yaml,
+        Dump,
+        &value,
+        r#"
+        ---
+        data: |- # data
+          00000000  4d 61 72 79 20 68 61 64  20 61 20 6c 69 74 74 6c  |Mary had a littl|
+          00000010  65 20 6c 61 6d 62                                 |e lamb|
+        notes: # notes
+          -
+            # first
+            # second
+            long: |+
+              one
+              two
+            text: |- # note
+              Hello
+              World"#
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+#[annotate(comment = "SFDP header, see JESD216", format = hex)]
+struct ParamHeader {
+    signature: u32,
+    #[annotate(format = dec)]
+    version: u8,
+}
+
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+#[annotate(comment = "The bus speed")]
+enum Speed {
+    #[annotate(comment = "Up to 50MHz")]
+    Slow,
+    Custom(u32),
+}
+
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+struct Flash {
+    #[annotate(comment = "The first header")]
+    header: ParamHeader,
+    others: Vec<ParamHeader>,
+    speed: Speed,
+    custom: Speed,
+}
+
+#[test]
+fn test_container_annotations() -> Result<()> {
+    let value = Flash {
+        header: ParamHeader {
+            signature: 0x50444653,
+            version: 6,
+        },
+        others: vec![ParamHeader {
+            signature: 0x1234,
+            version: 1,
+        }],
+        speed: Speed::Slow,
+        custom: Speed::Custom(80),
+    };
+
+    tester!(
+        json5,
+        Flash,
+        &value,
+        r#"
+        {
+          // The first header
+          // SFDP header, see JESD216
+          header: {
+            signature: 0x50444653,
+            version: 6
+          },
+          others: [
+            // SFDP header, see JESD216
+            {
+              signature: 0x1234,
+              version: 1
+            }
+          ],
+          // The bus speed
+          // Up to 50MHz
+          speed: "Slow",
+          // The bus speed
+          custom: {
+            Custom: 80
+          }
+        }"#
+    );
+
+    tester!(
+        yaml,
+        Flash,
+        &value,
+        r#"
+        ---
+        # The first header
+        # SFDP header, see JESD216
+        header:
+          signature: 0x50444653
+          version: 6
+        others:
+          # SFDP header, see JESD216
+          -
+            signature: 0x1234
+            version: 1
+        # The bus speed
+        # Up to 50MHz
+        speed: Slow
+        # The bus speed
+        custom:
+          Custom: 80"#
+    );
+
+    tester!(
+        toml,
+        Flash,
+        &value,
+        r#"
+        # The bus speed
+        # Up to 50MHz
+        speed = "Slow"
+
+        # The first header
+        # SFDP header, see JESD216
+        [header]
+        signature = 0x50444653
+        version = 6
+
+        # SFDP header, see JESD216
+        [[others]]
+        signature = 0x1234
+        version = 1
+
+        # The bus speed
+        [custom]
+        Custom = 80"#
+    );
+
+    tester!(
+        json5,
+        ParamHeader,
+        &value.header,
+        r#"
+        // SFDP header, see JESD216
+        {
+          signature: 0x50444653,
+          version: 6
+        }"#
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Annotate, Debug, PartialEq)]
+#[annotate(format = hex, width = natural)]
+struct Control {
+    ctrl: u32,
+    #[annotate(format = bin, group = 4)]
+    mask: u8,
+    #[annotate(format = hex, width = 8, group = 4)]
+    addr: u64,
+    #[annotate(format = dec, group = 3)]
+    count: i64,
+}
+
+#[test]
+fn test_digits() -> Result<()> {
+    let value = Control {
+        ctrl: 0x40,
+        mask: 0xA5,
+        addr: 0x1234,
+        count: -1234567,
+    };
+
+    tester!(
+        json,
+        Control,
+        &value,
+        r#"
+        {
+          "ctrl": 64,
+          "mask": 165,
+          "addr": 4660,
+          "count": -1234567
+        }"#
+    );
+
+    tester!(
+        json5,
+        Control,
+        &value,
+        r#"
+        {
+          ctrl: 0x00000040,
+          mask: 165,
+          addr: 0x00001234,
+          count: -1234567
+        }"#
+    );
+
+    tester!(
+        annotate_yaml,
+        Control,
+        &value,
+        r#"
+        ---
+        ctrl: 0x00000040
+        mask: 0b10100101
+        addr: 0x00001234
+        count: -1234567"#
+    );
+
+    tester!(
+        toml,
+        Control,
+        &value,
+        r#"
+        ctrl = 0x00000040
+        mask = 0b1010_0101
+        addr = 0x0000_1234
+        count = -1_234_567"#
+    );
+
+    // The other formats have no digit separators, but can emit them when
+    // enabled for readers which accept them.
+    let json5 = Json::json5().digit_separators(true);
+    let string = serialize(&value)?
+        .to_json5()
+        .digit_separators(true)
+        .to_string();
+    assert_eq!(
+        string,
+        fixdoc(
+            r#"
+            {
+              ctrl: 0x00000040,
+              mask: 165,
+              addr: 0x0000_1234,
+              count: -1_234_567
+            }"#
+        )
+    );
+    tester!(@stream, json5, &value, &string);
+    assert_eq!(serde_annotate::from_str::<Control>(&string)?, value);
+
+    let hjson = Json::hjson().digit_separators(true);
+    let string = serialize(&value)?
+        .to_hjson()
+        .digit_separators(true)
+        .to_string();
+    assert_eq!(
+        string,
+        fixdoc(
+            r#"
+            {
+              ctrl: 64,
+              mask: 165,
+              addr: 4660,
+              count: -1_234_567
+            }"#
+        )
+    );
+    tester!(@stream, hjson, &value, &string);
+    assert_eq!(serde_annotate::from_str::<Control>(&string)?, value);
+
+    let yaml = Yaml::default().digit_separators(true);
+    let string = serialize(&value)?
+        .to_yaml()
+        .digit_separators(true)
+        .to_string();
+    assert_eq!(
+        string,
+        fixdoc(
+            r#"
+            ---
+            ctrl: 0x00000040
+            mask: 0b1010_0101
+            addr: 0x0000_1234
+            count: -1_234_567"#
+        )
+    );
+    tester!(@stream, yaml, &value, &string);
+    tester!(@annotate_yaml, Control, &value, &string);
+    Ok(())
+}
@@ -0,0 +1,96 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+
+#[derive(Serialize, Annotate)]
+struct Register<T> {
+    #[annotate(format=hex, comment="Register value")]
+    value: T,
+}
+
+#[derive(Serialize, Annotate)]
+struct Registers {
+    byte: Register<u8>,
+    word: Register<u32>,
+}
+
+#[test]
+fn test_monomorphizations() -> Result<()> {
+    let r = Registers {
+        byte: Register { value: 255 },
+        word: Register { value: 0x1234 },
+    };
+    let s = serialize(&r)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  byte: {
+    // Register value
+    value: 0xFF
+  },
+  word: {
+    // Register value
+    value: 0x1234
+  }
+}"#
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+struct Packet<T>
+where
+    T: Serialize,
+{
+    #[annotate(format=hex)]
+    flags: u8,
+    #[annotate(comment = "Payload")]
+    payload: Vec<T>,
+}
+
+#[derive(Serialize, Annotate)]
+enum Message<T> {
+    #[annotate(comment = "A short message")]
+    Short(#[annotate(format=hex)] T),
+}
+
+#[test]
+fn test_where_clause_and_enum() -> Result<()> {
+    let p = Packet::<u16> {
+        flags: 5,
+        payload: vec![1, 2, 3, 4],
+    };
+    let s = serialize(&p)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        "{\n  flags: 0x5,\n  // Payload\n  payload: [\n    1,\n    2,\n    3,\n    4\n  ]\n}"
+    );
+
+    let m = Message::Short(31u32);
+    let s = serialize(&m)?.to_json5().to_string();
+    assert_eq!(s, "{\n  // A short message\n  Short: 0x1F\n}");
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+struct Named<'a> {
+    name: &'a str,
+    #[annotate(format=hex, comment=name)]
+    value: u32,
+}
+
+#[test]
+fn test_lifetimes() -> Result<()> {
+    let name = String::from("The answer");
+    let n = Named {
+        name: &name,
+        value: 42,
+    };
+    let s = serialize(&n)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        "{\n  name: \"The answer\",\n  // The answer\n  value: 0x2A\n}"
+    );
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use anyhow::Result;
+use serde::{Serialize, Serializer};
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+use std::rc::Rc;
+use std::sync::Arc;
+
+#[derive(Serialize, Annotate)]
+struct Sensor {
+    name: String,
+    #[annotate(format=hex, comment=name)]
+    reading: u32,
+}
+
+impl Sensor {
+    fn new(name: &str, reading: u32) -> Self {
+        Sensor {
+            name: name.into(),
+            reading,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct Pointers<'a> {
+    boxed: Box<Sensor>,
+    rc: Rc<Sensor>,
+    arc: Arc<Sensor>,
+    by_ref: &'a Sensor,
+}
+
+#[test]
+fn test_smart_pointers() -> Result<()> {
+    let sensor = Sensor::new("ref", 4);
+    let p = Pointers {
+        boxed: Box::new(Sensor::new("boxed", 1)),
+        rc: Rc::new(Sensor::new("rc", 2)),
+        arc: Arc::new(Sensor::new("arc", 3)),
+        by_ref: &sensor,
+    };
+    let s = serialize(&p)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  boxed: {
+    name: "boxed",
+    // boxed
+    reading: 0x1
+  },
+  rc: {
+    name: "rc",
+    // rc
+    reading: 0x2
+  },
+  arc: {
+    name: "arc",
+    // arc
+    reading: 0x3
+  },
+  by_ref: {
+    name: "ref",
+    // ref
+    reading: 0x4
+  }
+}"#
+    );
+    Ok(())
+}
+
+// Serializes a `u32` as a `Sensor`.  The value passed to the serializer is
+// not a `Sensor`, so only the annotations which don't depend on the value
+// can be used.
+fn as_sensor<S: Serializer>(reading: &u32, serializer: S) -> Result<S::Ok, S::Error> {
+    Sensor::new("proxy", *reading).serialize(serializer)
+}
+
+#[derive(Serialize)]
+struct Proxied {
+    #[serde(serialize_with = "as_sensor")]
+    sensor: u32,
+}
+
+#[test]
+fn test_serialize_with() -> Result<()> {
+    let p = Proxied { sensor: 10 };
+    let s = serialize(&p)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  sensor: {
+    name: "proxy",
+    reading: 0xA
+  }
+}"#
+    );
+    Ok(())
+}
+
+mod first {
+    use super::*;
+
+    #[derive(Serialize, Annotate)]
+    pub struct Config {
+        #[annotate(format=hex, comment="First")]
+        pub value: u32,
+    }
+}
+
+mod second {
+    use super::*;
+
+    #[derive(Serialize, Annotate)]
+    pub struct Config {
+        #[annotate(format=dec, comment="Second")]
+        pub value: u32,
+    }
+}
+
+#[derive(Serialize)]
+struct Both {
+    first: first::Config,
+    second: Box<second::Config>,
+}
+
+#[test]
+fn test_same_name() -> Result<()> {
+    let b = Both {
+        first: first::Config { value: 5 },
+        second: Box::new(second::Config { value: 5 }),
+    };
+    let s = serialize(&b)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  first: {
+    // First
+    value: 0x5
+  },
+  second: {
+    // Second
+    value: 5
+  }
+}"#
+    );
+    Ok(())
+}
@@ -5,7 +5,7 @@ use serde_annotate::{Document, StrFormat};
 #[derive(Debug, serde::Serialize, serde::Deserialize)]
 struct Partial {
     n: i32,
-    doc: Document,
+    doc: Document<'static>,
 }
 
 const SERIALIZE_RESULT: &str = r#"{
@@ -0,0 +1,126 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::annotate::{self, Format, MemberId};
+use serde_annotate::{caster, register, serialize, Annotate};
+use std::time::Duration;
+
+#[derive(Serialize)]
+struct Address {
+    value: u32,
+}
+
+// Small addresses are easier to read in decimal.
+impl Annotate for Address {
+    fn format(&self, _variant: Option<&str>, field: &MemberId) -> Option<Format> {
+        match field {
+            MemberId::Name("value") if self.value > 255 => Some(Format::Hex),
+            _ => None,
+        }
+    }
+    fn comment(&self, _variant: Option<&str>, _field: &MemberId) -> Option<String> {
+        None
+    }
+}
+register!(Address);
+
+#[derive(Serialize)]
+#[serde(rename = "Reg")]
+struct Register<'a> {
+    name: &'a str,
+    value: u32,
+}
+
+impl Annotate for Register<'_> {
+    fn format(&self, _variant: Option<&str>, field: &MemberId) -> Option<Format> {
+        match field {
+            MemberId::Name("value") => Some(Format::Hex),
+            _ => None,
+        }
+    }
+    fn comment(&self, _variant: Option<&str>, field: &MemberId) -> Option<String> {
+        match field {
+            MemberId::Name("value") => Some(format!("The {} register", self.name)),
+            _ => None,
+        }
+    }
+}
+register!(Register<'_>, "Reg");
+
+#[test]
+fn test_register_macro() -> Result<()> {
+    let addrs = vec![Address { value: 16 }, Address { value: 4096 }];
+    let s = serialize(&addrs)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        "[\n  {\n    value: 16\n  },\n  {\n    value: 0x1000\n  }\n]"
+    );
+
+    let r = Register {
+        name: "status",
+        value: 0x80,
+    };
+    let s = serialize(&r)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        "{\n  name: \"status\",\n  // The status register\n  value: 0x80\n}"
+    );
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct Plugin {
+    id: u32,
+}
+
+impl Annotate for Plugin {
+    fn format(&self, _variant: Option<&str>, _field: &MemberId) -> Option<Format> {
+        Some(Format::Hex)
+    }
+    fn comment(&self, _variant: Option<&str>, field: &MemberId) -> Option<String> {
+        match field {
+            MemberId::Name("id") => Some("Plugin id".into()),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_register_runtime() -> Result<()> {
+    let p = Plugin { id: 42 };
+    let s = serialize(&p)?.to_json5().to_string();
+    assert_eq!(s, "{\n  id: 42\n}");
+
+    annotate::register("Plugin", caster!(Plugin));
+    let s = serialize(&p)?.to_json5().to_string();
+    assert_eq!(s, "{\n  // Plugin id\n  id: 0x2A\n}");
+    Ok(())
+}
+
+// Annotations for `std::time::Duration`, which serde serializes as the struct
+// `Duration { secs, nanos }`.
+struct DurationAnnotations;
+
+impl Annotate for DurationAnnotations {
+    fn format(&self, _variant: Option<&str>, _field: &MemberId) -> Option<Format> {
+        None
+    }
+    fn comment(&self, _variant: Option<&str>, field: &MemberId) -> Option<String> {
+        match field {
+            MemberId::Name("secs") => Some("Seconds".into()),
+            MemberId::Name("nanos") => Some("Nanoseconds".into()),
+            _ => None,
+        }
+    }
+}
+
+#[test]
+fn test_register_static() -> Result<()> {
+    annotate::register_static("Duration", &DurationAnnotations);
+    let d = Duration::from_millis(1500);
+    let s = serialize(&d)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        "{\n  // Seconds\n  secs: 1,\n  // Nanoseconds\n  nanos: 500000000\n}"
+    );
+    Ok(())
+}
@@ -0,0 +1,95 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+
+#[derive(Serialize, Annotate)]
+#[serde(rename_all = "camelCase")]
+struct Header {
+    #[serde(rename = "sig")]
+    #[annotate(format=hex, comment="Signature")]
+    signature: u32,
+    #[annotate(format=hex, comment="Number of headers")]
+    num_headers: u8,
+    #[serde(rename(serialize = "kind", deserialize = "type"))]
+    #[annotate(format=hex)]
+    r#type: u8,
+}
+
+#[test]
+fn test_struct_rename() -> Result<()> {
+    let h = Header {
+        signature: 0x50444653,
+        num_headers: 2,
+        r#type: 10,
+    };
+    let s = serialize(&h)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  // Signature
+  sig: 0x50444653,
+  // Number of headers
+  numHeaders: 0x2,
+  kind: 0xA
+}"#
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+#[serde(rename_all = "snake_case", rename_all_fields = "SCREAMING_SNAKE_CASE")]
+enum Command {
+    #[annotate(comment = "Read a register")]
+    ReadRegister {
+        #[annotate(format=hex)]
+        reg_addr: u16,
+    },
+    #[serde(rename = "write", rename_all = "kebab-case")]
+    #[annotate(comment = "Write a register")]
+    WriteRegister {
+        #[annotate(format=hex)]
+        reg_addr: u16,
+        #[annotate(format=hex, comment = "Value to write")]
+        reg_value: u32,
+    },
+    #[annotate(comment = "Reset the device")]
+    ResetDevice(#[annotate(format=hex)] u8),
+}
+
+#[test]
+fn test_enum_rename() -> Result<()> {
+    let c = Command::ReadRegister { reg_addr: 0x40 };
+    let s = serialize(&c)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  // Read a register
+  read_register: {
+    REG_ADDR: 0x40
+  }
+}"#
+    );
+
+    let c = Command::WriteRegister {
+        reg_addr: 0x40,
+        reg_value: 0xFF,
+    };
+    let s = serialize(&c)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"{
+  // Write a register
+  write: {
+    "reg-addr": 0x40,
+    // Value to write
+    "reg-value": 0xFF
+  }
+}"#
+    );
+
+    let c = Command::ResetDevice(5);
+    let s = serialize(&c)?.to_json5().to_string();
+    assert_eq!(s, "{\n  // Reset the device\n  reset_device: 0x5\n}");
+    Ok(())
+}
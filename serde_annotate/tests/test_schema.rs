@@ -0,0 +1,425 @@
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use serde_annotate::{serialize, Annotate, Schema};
+
+#[derive(Serialize, Deserialize, Annotate)]
+struct Register {
+    #[annotate(comment = "Register name")]
+    name: String,
+    #[annotate(format=hex, comment="Offset from the block base")]
+    offset: u16,
+    reset: Option<u32>,
+    #[annotate(format=bin)]
+    mask: u8,
+    fields: Vec<Field>,
+}
+
+#[derive(Serialize, Deserialize, Annotate)]
+struct Field {
+    #[annotate(comment = "Lowest bit\nof the field")]
+    lsb: i8,
+    enabled: bool,
+}
+
+fn sample() -> Register {
+    Register {
+        name: "ctrl".into(),
+        offset: 0x10,
+        reset: None,
+        mask: 0b101,
+        fields: vec![Field {
+            lsb: 0,
+            enabled: true,
+        }],
+    }
+}
+
+#[test]
+fn test_describe_value() -> Result<()> {
+    let schema = Schema::describe_value(&sample())?;
+    assert_eq!(
+        schema.to_json5().to_string(),
+        r#"{
+  $schema: "https://json-schema.org/draft/2020-12/schema",
+  title: "Register",
+  type: "object",
+  properties: {
+    // Register name
+    name: {
+      description: "Register name",
+      type: "string"
+    },
+    // Offset from the block base
+    offset: {
+      description: "Offset from the block base",
+      type: ["integer", "string"],
+      format: "hex",
+      pattern: "^[+-]?0[xX][0-9A-Fa-f]+(_[0-9A-Fa-f]+)*$",
+      minimum: 0,
+      maximum: 65535
+    },
+    reset: {},
+    mask: {
+      type: ["integer", "string"],
+      format: "bin",
+      pattern: "^[+-]?0[bB][01]+(_[01]+)*$",
+      minimum: 0,
+      maximum: 255
+    },
+    fields: {
+      type: "array",
+      items: {
+        type: "object",
+        properties: {
+          // Lowest bit
+          // of the field
+          lsb: {
+            description: "Lowest bit\nof the field",
+            type: "integer",
+            minimum: -128,
+            maximum: 127
+          },
+          enabled: {
+            type: "boolean"
+          }
+        },
+        required: ["lsb", "enabled"]
+      }
+    }
+  },
+  required: ["name", "offset", "mask", "fields"]
+}"#
+    );
+    Ok(())
+}
+
+#[test]
+fn test_described_schema_validates() -> Result<()> {
+    let schema = Schema::from_document(&Schema::describe_value(&sample())?)?;
+
+    // Hex and binary integers are emitted as strings in strict json.
+    let doc = serialize(&sample())?;
+    for text in [
+        doc.clone().to_json().to_string(),
+        doc.clone().to_json5().to_string(),
+        doc.to_hjson().to_string(),
+    ] {
+        let parsed = serde_annotate::Document::parse(&text)?;
+        assert!(schema.validate(&parsed).is_ok(), "{}", text);
+    }
+
+    // Integers with digit grouping are emitted as strings in strict json.
+    let grouped = serde_annotate::Document::parse(
+        r#"{"name": "ctrl", "offset": "0x00_10", "mask": "0b0000_0101", "fields": []}"#,
+    )?;
+    assert!(schema.validate(&grouped).is_ok());
+
+    let bad = serde_annotate::Document::parse(
+        r#"{name: "ctrl", offset: 0x10000, mask: 1, fields: [{lsb: 0, enabled: 1}]}"#,
+    )?;
+    let errors = schema
+        .validate(&bad)
+        .unwrap_err()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "at offset: schema violation: 65536 is greater than the maximum 65535",
+            "at fields[0].enabled: schema violation: expected boolean but got integer",
+        ]
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Annotate)]
+#[annotate(comment = "A block of registers")]
+struct Block {
+    #[annotate(format=hex, comment="Base address")]
+    base: u32,
+    alias: Option<String>,
+    access: Access,
+    #[serde(default)]
+    tags: Vec<String>,
+    children: Vec<Block>,
+}
+
+#[derive(Serialize, Deserialize, Annotate)]
+enum Access {
+    #[annotate(comment = "Read only")]
+    Ro,
+    Rw,
+    Mask(#[annotate(format=bin)] u8),
+    Range(#[annotate(format=hex)] u16, u16),
+    Window {
+        start: u16,
+        end: Option<u16>,
+    },
+}
+
+#[test]
+fn test_describe_type() -> Result<()> {
+    let schema = Schema::describe_type::<Block>()?;
+    assert_eq!(
+        schema.to_json5().to_string(),
+        r##"{
+  $schema: "https://json-schema.org/draft/2020-12/schema",
+  title: "Block",
+  description: "A block of registers",
+  type: "object",
+  properties: {
+    // Base address
+    base: {
+      description: "Base address",
+      type: ["integer", "string"],
+      format: "hex",
+      pattern: "^[+-]?0[xX][0-9A-Fa-f]+(_[0-9A-Fa-f]+)*$",
+      minimum: 0,
+      maximum: 4294967295
+    },
+    alias: {
+      type: ["string", "null"]
+    },
+    access: {
+      oneOf: [
+        // Read only
+        {
+          description: "Read only",
+          type: "string",
+          "enum": ["Ro"]
+        },
+        {
+          type: "string",
+          "enum": ["Rw"]
+        },
+        {
+          type: "object",
+          properties: {
+            Mask: {
+              type: ["integer", "string"],
+              format: "bin",
+              pattern: "^[+-]?0[bB][01]+(_[01]+)*$",
+              minimum: 0,
+              maximum: 255
+            }
+          },
+          required: ["Mask"],
+          additionalProperties: false
+        },
+        {
+          type: "object",
+          properties: {
+            Range: {
+              type: "array",
+              prefixItems: [
+                {
+                  type: ["integer", "string"],
+                  format: "hex",
+                  pattern: "^[+-]?0[xX][0-9A-Fa-f]+(_[0-9A-Fa-f]+)*$",
+                  minimum: 0,
+                  maximum: 65535
+                },
+                {
+                  type: "integer",
+                  minimum: 0,
+                  maximum: 65535
+                }
+              ],
+              minItems: 2,
+              maxItems: 2
+            }
+          },
+          required: ["Range"],
+          additionalProperties: false
+        },
+        {
+          type: "object",
+          properties: {
+            Window: {
+              type: "object",
+              properties: {
+                start: {
+                  type: "integer",
+                  minimum: 0,
+                  maximum: 65535
+                },
+                end: {
+                  type: ["integer", "null"],
+                  minimum: 0,
+                  maximum: 65535
+                }
+              },
+              required: ["start"]
+            }
+          },
+          required: ["Window"],
+          additionalProperties: false
+        }
+      ]
+    },
+    tags: {
+      type: "array",
+      items: {
+        type: "string"
+      }
+    },
+    children: {
+      type: "array",
+      items: {
+        $ref: "#"
+      }
+    }
+  },
+  required: ["base", "access", "children"]
+}"##
+    );
+    Ok(())
+}
+
+#[test]
+fn test_described_type_validates() -> Result<()> {
+    let schema = Schema::from_document(&Schema::describe_type::<Register>()?)?;
+    // `reset` may be left out, but is typed when present.
+    let doc = serde_annotate::Document::parse(
+        r#"{name: "ctrl", offset: 0x10, mask: 0b1, fields: [{lsb: 0, enabled: true}]}"#,
+    )?;
+    assert!(schema.validate(&doc).is_ok());
+    let bad = serde_annotate::Document::parse(
+        r#"{name: "ctrl", offset: 0x10, reset: "x", mask: 0b1, fields: [{lsb: 0}]}"#,
+    )?;
+    let errors = schema
+        .validate(&bad)
+        .unwrap_err()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "at reset: schema violation: expected one of integer, null but got string",
+            "at fields[0]: schema violation: missing required property `enabled`",
+        ]
+    );
+    Ok(())
+}
+
+// The recursive variant comes first, so it can only be traced once `Lit` has
+// been.
+#[derive(Serialize, Deserialize)]
+enum Expr {
+    Add(Box<Expr>, Box<Expr>),
+    Lit(i8),
+}
+
+#[test]
+fn test_describe_recursive_enum() -> Result<()> {
+    let schema = Schema::describe_type::<Expr>()?;
+    assert_eq!(
+        schema.to_json5().to_string(),
+        r##"{
+  $schema: "https://json-schema.org/draft/2020-12/schema",
+  title: "Expr",
+  oneOf: [
+    {
+      type: "object",
+      properties: {
+        Add: {
+          type: "array",
+          items: {
+            $ref: "#"
+          },
+          minItems: 2,
+          maxItems: 2
+        }
+      },
+      required: ["Add"],
+      additionalProperties: false
+    },
+    {
+      type: "object",
+      properties: {
+        Lit: {
+          type: "integer",
+          minimum: -128,
+          maximum: 127
+        }
+      },
+      required: ["Lit"],
+      additionalProperties: false
+    }
+  ]
+}"##
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Untagged {
+    Int(u32),
+    Str(String),
+}
+
+#[test]
+fn test_describe_untraceable_type() {
+    let err = Schema::describe_type::<Untagged>().unwrap_err();
+    assert!(err.to_string().contains("can't be traced"), "{}", err);
+}
+
+#[derive(Serialize)]
+struct Big {
+    v: u64,
+}
+
+#[test]
+fn test_quoted_integers_validate() -> Result<()> {
+    // The json emitter quotes integers beyond 2^53.
+    let json = serialize(&Big { v: 1 << 60 })?.to_json().to_string();
+    assert_eq!(json, "{\n  \"v\": \"1152921504606846976\"\n}");
+    let schema = Schema::parse(r#"{"properties": {"v": {"type": "integer", "minimum": 0}}}"#)?;
+    assert!(schema
+        .validate(&serde_annotate::Document::parse(&json)?)
+        .is_ok());
+    Ok(())
+}
+
+#[derive(Serialize, Deserialize, Annotate)]
+struct Wide {
+    count: u64,
+    delta: i64,
+    #[annotate(format=hex)]
+    addr: u32,
+    #[annotate(format=hex)]
+    wide_addr: u64,
+}
+
+#[test]
+fn test_described_bounds_parse() -> Result<()> {
+    let json = Schema::describe_type::<Wide>()?.to_json().to_string();
+    let schema = Schema::parse(&json)?;
+    // Bounds are decimal json numbers; those beyond 2^53 are left out.
+    assert!(json.contains(r#""maximum": 4294967295"#), "{}", json);
+    assert!(!json.contains("0x"), "{}", json);
+    let doc = serde_annotate::Document::parse(
+        r#"{"count": "18446744073709551615", "delta": -1, "addr": "0xFFFFFFFF", "wide_addr": "0x1"}"#,
+    )?;
+    assert!(schema.validate(&doc).is_ok());
+    let bad = serde_annotate::Document::parse(
+        r#"{"count": -1, "delta": 0, "addr": "0x100000000", "wide_addr": 0}"#,
+    )?;
+    let errors = schema
+        .validate(&bad)
+        .unwrap_err()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>();
+    assert_eq!(
+        errors,
+        vec![
+            "at count: schema violation: -1 is less than the minimum 0",
+            "at addr: schema violation: 4294967296 is greater than the maximum 4294967295",
+        ]
+    );
+    Ok(())
+}
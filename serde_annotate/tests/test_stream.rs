@@ -0,0 +1,260 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+use serde_annotate::{ColorProfile, Json, Yaml};
+use std::collections::BTreeMap;
+
+// Checks that streaming `value` produces the same output as serializing it
+// into a `Document` and emitting that.
+fn check<T: Serialize>(value: &T) -> Result<()> {
+    let doc = serialize(value)?;
+    let expected = [
+        doc.clone().to_json().to_string(),
+        doc.clone().to_json5().to_string(),
+        doc.clone().to_hjson().to_string(),
+        doc.clone().to_json5().compact(true).to_string(),
+        doc.clone().to_yaml().to_string(),
+        doc.clone().to_yaml().compact(true).to_string(),
+        doc.to_yaml().color(ColorProfile::basic()).to_string(),
+    ];
+    let mut actual = Vec::new();
+    for json in [
+        Json::default(),
+        Json::json5(),
+        Json::hjson(),
+        Json::json5().compact(true),
+    ] {
+        let mut buf = Vec::new();
+        json.serialize_to(value, &mut buf)?;
+        actual.push(String::from_utf8(buf)?);
+    }
+    for yaml in [
+        Yaml::default(),
+        Yaml::default().compact(true),
+        Yaml::default().color(ColorProfile::basic()),
+    ] {
+        let mut buf = Vec::new();
+        yaml.serialize_to(value, &mut buf)?;
+        actual.push(String::from_utf8(buf)?);
+    }
+    assert_eq!(actual, expected);
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+struct Pair(
+    #[annotate(format=hex, comment = "First")] u32,
+    #[annotate(comment = "Second")] String,
+);
+
+#[derive(Serialize, Annotate)]
+struct Wrapper(#[annotate(format=bin)] u8);
+
+#[derive(Serialize, Annotate)]
+enum Shape {
+    Empty,
+    #[annotate(comment = "A circle", format=compact)]
+    Circle(u32),
+    #[annotate(comment = "A point", format=compact)]
+    Point(u32, u32),
+    #[annotate(comment = "A square")]
+    Square {
+        side: u32,
+    },
+    Line(u32, u32),
+    #[annotate(comment = "A triangle", placement = trailing)]
+    Triangle(
+        u32,
+        #[annotate(comment = "Apex", placement = trailing)] u32,
+        u32,
+    ),
+}
+
+#[derive(Serialize, Annotate)]
+struct Everything {
+    #[annotate(comment = "Nothing at all")]
+    empty_list: Vec<u32>,
+    empty_map: BTreeMap<String, u32>,
+    #[annotate(comment = "Multi-line\ncomment")]
+    pair: Pair,
+    wrapper: Wrapper,
+    shapes: Vec<Shape>,
+    #[annotate(format=hex)]
+    by_number: BTreeMap<u32, Option<bool>>,
+    nested: Vec<Vec<Vec<u8>>>,
+    #[annotate(format=block)]
+    text: String,
+    #[annotate(format=hexdump)]
+    #[serde(with = "serde_bytes")]
+    data: Vec<u8>,
+    #[serde(with = "serde_bytes")]
+    raw: Vec<u8>,
+    unit: (),
+    letter: char,
+    float: f32,
+    #[annotate(comment = "Ends the value", placement = trailing)]
+    last: Vec<u8>,
+}
+
+#[test]
+fn test_stream_matches_document() -> Result<()> {
+    let value = Everything {
+        empty_list: vec![],
+        empty_map: BTreeMap::new(),
+        pair: Pair(0x1234, "two".into()),
+        wrapper: Wrapper(5),
+        shapes: vec![
+            Shape::Empty,
+            Shape::Circle(3),
+            Shape::Point(1, 2),
+            Shape::Square { side: 4 },
+            Shape::Line(5, 6),
+            Shape::Triangle(1, 2, 3),
+        ],
+        by_number: BTreeMap::from([(10, Some(true)), (20, None)]),
+        nested: vec![vec![], vec![vec![1, 2], vec![]]],
+        text: "Hello\nWorld\n".into(),
+        data: (0u8..40).collect(),
+        raw: vec![1, 2, 3],
+        unit: (),
+        letter: 'x',
+        float: 1.5,
+        last: vec![],
+    };
+    check(&value)?;
+    check(&value.shapes)?;
+    check(&value.pair)?;
+    check(&Shape::Circle(7))?;
+    check(&Vec::<u32>::new())?;
+    check(&"just a string")?;
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+struct Dumps {
+    #[annotate(format=hexdump)]
+    #[serde(with = "serde_bytes")]
+    hexdump: Vec<u8>,
+    #[annotate(format=xxd)]
+    #[serde(with = "serde_bytes")]
+    xxd: Vec<u8>,
+    #[annotate(format=hexdump)]
+    #[serde(with = "serde_bytes")]
+    empty: Vec<u8>,
+}
+
+#[test]
+fn test_stream_hexdump_lines() -> Result<()> {
+    let data = (0u8..=255).cycle().take(1000).collect::<Vec<u8>>();
+    check(&Dumps {
+        hexdump: data.clone(),
+        xxd: data,
+        empty: vec![],
+    })
+}
+
+#[derive(Serialize, Annotate)]
+enum Signal {
+    #[annotate(comment = "Stop")]
+    Red,
+    #[annotate(comment = "Go", placement = trailing)]
+    Green,
+}
+
+#[derive(Serialize, Annotate)]
+struct Flags(#[annotate(comment = "Enabled flags")] Vec<u8>);
+
+#[derive(Serialize, Annotate, PartialEq, Eq, PartialOrd, Ord)]
+struct Id(#[annotate(comment = "An identifier", placement = trailing)] u32);
+
+#[derive(Serialize, Annotate)]
+enum Panel {
+    #[annotate(format=compact)]
+    Row(Vec<Signal>, Id),
+}
+
+#[derive(Serialize, Annotate)]
+struct Lights {
+    #[annotate(comment = "Main light")]
+    main: Signal,
+    backup: Signal,
+    flags: Flags,
+    no_flags: Flags,
+    id: Id,
+    panel: Panel,
+    sequence: Vec<Signal>,
+    ids: Vec<Id>,
+    by_id: BTreeMap<Id, Signal>,
+}
+
+#[test]
+fn test_stream_value_comments() -> Result<()> {
+    let value = Lights {
+        main: Signal::Red,
+        backup: Signal::Green,
+        flags: Flags(vec![1, 2]),
+        no_flags: Flags(vec![]),
+        id: Id(7),
+        panel: Panel::Row(vec![Signal::Red, Signal::Green], Id(3)),
+        sequence: vec![Signal::Red, Signal::Green],
+        ids: vec![Id(1), Id(2)],
+        by_id: BTreeMap::from([(Id(1), Signal::Red), (Id(2), Signal::Green)]),
+    };
+    check(&value)?;
+    check(&Signal::Red)?;
+    check(&Signal::Green)?;
+    check(&Flags(vec![3]))?;
+    check(&vec![Flags(vec![4, 5]), Flags(vec![])])?;
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+#[annotate(comment = "A register block", format = hex)]
+struct Block {
+    base: u32,
+    #[annotate(format = dec)]
+    count: u32,
+}
+
+#[derive(Serialize, Annotate)]
+#[annotate(comment = "Marks the end", placement = trailing)]
+struct Marker;
+
+#[derive(Serialize, Annotate)]
+#[annotate(comment = "A bank of blocks", placement = trailing)]
+struct Bank(Vec<Block>);
+
+#[derive(Serialize, Annotate)]
+#[annotate(comment = "A device")]
+enum Device {
+    #[annotate(comment = "Nothing attached")]
+    Absent,
+    Present(Block),
+}
+
+#[derive(Serialize, Annotate)]
+struct Layout {
+    #[annotate(comment = "The first block")]
+    first: Block,
+    bank: Bank,
+    devices: Vec<Device>,
+    end: Marker,
+}
+
+#[test]
+fn test_stream_container_comments() -> Result<()> {
+    let block = |base| Block { base, count: 16 };
+    let value = Layout {
+        first: block(0x1000),
+        bank: Bank(vec![block(0x2000), block(0x3000)]),
+        devices: vec![Device::Absent, Device::Present(block(0x4000))],
+        end: Marker,
+    };
+    check(&value)?;
+    check(&value.first)?;
+    check(&value.bank)?;
+    check(&value.devices)?;
+    check(&Marker)?;
+    Ok(())
+}
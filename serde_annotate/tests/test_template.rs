@@ -0,0 +1,92 @@
+use anyhow::Result;
+use serde::Serialize;
+use serde_annotate::serialize;
+use serde_annotate::Annotate;
+
+#[derive(Serialize, Annotate)]
+#[annotate(comment_template = "Header for {{{nph + 1}}} parameters")]
+struct SfdpHeader {
+    #[annotate(format = hex, comment_template = "value={signature:#010x} ascii={signature:fourcc}")]
+    signature: u32,
+    #[annotate(comment_template = "Version {major}.{minor}")]
+    minor: u8,
+    major: u8,
+    #[annotate(comment_template = "Number of headers: {nph + 1}")]
+    nph: u8,
+    #[annotate(comment_template = "{name.len()} bytes")]
+    name: String,
+    // Only `comment_template` interpolates; braces in a comment are literal.
+    #[annotate(comment = "Flags {in braces}")]
+    flags: u8,
+}
+
+#[test]
+fn test_template_fields() -> Result<()> {
+    let h = SfdpHeader {
+        signature: 0x50444653,
+        minor: 6,
+        major: 1,
+        nph: 2,
+        name: "flash".into(),
+        flags: 0,
+    };
+    let s = serialize(&h)?.to_json5().to_string();
+    assert_eq!(
+        s,
+        r#"// Header for {3} parameters
+{
+  // value=0x50444653 ascii=SFDP
+  signature: 0x50444653,
+  // Version 1.6
+  minor: 6,
+  major: 1,
+  // Number of headers: 3
+  nph: 2,
+  // 5 bytes
+  name: "flash",
+  // Flags {in braces}
+  flags: 0
+}"#
+    );
+    Ok(())
+}
+
+#[derive(Serialize, Annotate)]
+enum Command {
+    #[annotate(comment_template = "Read {len} bytes")]
+    Read {
+        #[annotate(format = hex, comment_template = "{addr:fourcc}")]
+        addr: u32,
+        len: usize,
+    },
+    #[annotate(comment_template = "Erase {{sector}}")]
+    Erase { sector: u32 },
+}
+
+#[test]
+fn test_template_variants() -> Result<()> {
+    let s = serialize(&vec![
+        Command::Read {
+            addr: 0x0a4b4f42,
+            len: 16,
+        },
+        Command::Erase { sector: 3 },
+    ])?
+    .to_yaml()
+    .to_string();
+    assert_eq!(
+        s,
+        r#"---
+-
+  # Read 16 bytes
+  Read:
+    # BOK.
+    addr: 0xA4B4F42
+    len: 16
+-
+  # Erase {sector}
+  Erase:
+    sector: 3"#
+    );
+    Ok(())
+}
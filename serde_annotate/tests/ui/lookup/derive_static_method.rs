@@ -0,0 +1,21 @@
+use serde::Serialize;
+use serde_annotate::Annotate;
+use std::sync::Mutex;
+
+static LEAKED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+#[derive(Serialize, Annotate)]
+struct Borrowed<'a> {
+    #[annotate(comment = leak())]
+    name: &'a str,
+}
+
+// Only defined for `'static`, so the derived annotations can't call it.
+impl Borrowed<'static> {
+    fn leak(&self) -> Option<String> {
+        LEAKED.lock().unwrap().push(self.name);
+        None
+    }
+}
+
+fn main() {}
@@ -0,0 +1,26 @@
+use serde::Serialize;
+use serde_annotate::annotate::{Format, MemberId};
+use serde_annotate::{register, Annotate};
+use std::sync::Mutex;
+
+static LEAKED: Mutex<Vec<&'static str>> = Mutex::new(Vec::new());
+
+#[derive(Serialize)]
+struct Borrowed<'a> {
+    name: &'a str,
+}
+
+// Only implemented for `'static`, so `name` could outlive the value.
+impl Annotate for Borrowed<'static> {
+    fn format(&self, _variant: Option<&str>, _field: &MemberId) -> Option<Format> {
+        LEAKED.lock().unwrap().push(self.name);
+        None
+    }
+    fn comment(&self, _variant: Option<&str>, _field: &MemberId) -> Option<String> {
+        None
+    }
+}
+
+register!(Borrowed<'_>, "Borrowed");
+
+fn main() {}
@@ -0,0 +1,10 @@
+use serde::Serialize;
+use serde_annotate::Annotate;
+
+#[derive(Serialize, Annotate)]
+struct Header {
+    #[annotate(comment_template = "Signature {signature:q}")]
+    signature: u32,
+}
+
+fn main() {}
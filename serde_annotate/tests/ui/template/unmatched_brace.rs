@@ -0,0 +1,10 @@
+use serde::Serialize;
+use serde_annotate::Annotate;
+
+#[derive(Serialize, Annotate)]
+struct Header {
+    #[annotate(comment_template = "Number of headers: nph}")]
+    nph: u8,
+}
+
+fn main() {}
@@ -0,0 +1,12 @@
+use serde::Serialize;
+use serde_annotate::Annotate;
+
+// A variant's template may only refer to the variant's own fields.
+#[derive(Serialize, Annotate)]
+enum Command {
+    #[annotate(comment_template = "Read {len} bytes")]
+    Read { offset: u32 },
+    Write { len: u32 },
+}
+
+fn main() {}
@@ -20,9 +20,11 @@ pub struct Struct<'a> {
 
 #[derive(Debug)]
 pub struct Field<'a> {
+    #[allow(dead_code)]
     pub original: &'a syn::Field,
     pub attrs: Attrs<'a>,
     pub member: Member,
+    #[allow(dead_code)]
     pub ty: &'a Type,
 }
 
@@ -36,6 +38,7 @@ pub struct Enum<'a> {
 
 #[derive(Debug)]
 pub struct Variant<'a> {
+    #[allow(dead_code)]
     pub original: &'a syn::Variant,
     pub attrs: Attrs<'a>,
     pub ident: Ident,
@@ -54,9 +57,13 @@ impl<'a> Input<'a> {
 
 impl<'a> Struct<'a> {
     fn from_syn(node: &'a DeriveInput, data: &'a DataStruct) -> Result<Self> {
-        let attrs = attr::get(&node.attrs)?;
+        let mut attrs = attr::get(&node.attrs)?;
         let span = Span::call_site();
-        let fields = Field::multiple_from_syn(&data.fields, span)?;
+        let mut fields = Field::multiple_from_syn(&data.fields, span)?;
+        if attrs.doc_comments {
+            attrs.use_doc();
+            fields.iter_mut().for_each(|f| f.attrs.use_doc());
+        }
         Ok(Struct {
             original: node,
             attrs,
@@ -68,16 +75,23 @@ impl<'a> Struct<'a> {
 
 impl<'a> Enum<'a> {
     fn from_syn(node: &'a DeriveInput, data: &'a DataEnum) -> Result<Self> {
-        let attrs = attr::get(&node.attrs)?;
+        let mut attrs = attr::get(&node.attrs)?;
         let span = Span::call_site();
-        let variants = data
+        let mut variants = data
             .variants
             .iter()
             .map(|node| {
                 let v = Variant::from_syn(node, span)?;
                 Ok(v)
             })
-            .collect::<Result<_>>()?;
+            .collect::<Result<Vec<_>>>()?;
+        if attrs.doc_comments {
+            attrs.use_doc();
+            for v in variants.iter_mut() {
+                v.attrs.use_doc();
+                v.fields.iter_mut().for_each(|f| f.attrs.use_doc());
+            }
+        }
         Ok(Enum {
             original: node,
             attrs,
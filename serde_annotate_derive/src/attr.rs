@@ -1,6 +1,10 @@
+use crate::case::RenameRule;
+use crate::template::Template;
 use proc_macro2::TokenTree;
 use syn::parse::ParseStream;
-use syn::{parenthesized, Attribute, Error, Ident, LitStr, Result, Token};
+use syn::{
+    parenthesized, Attribute, Error, Expr, ExprLit, Ident, Lit, LitInt, LitStr, Meta, Result, Token,
+};
 
 #[derive(Debug, PartialEq)]
 pub enum Format {
@@ -19,39 +23,129 @@ pub enum Format {
 #[derive(Debug, PartialEq)]
 pub enum Comment {
     None,
+    Doc,
     Field(Ident),
     Function(Ident),
     Static(String),
+    Template(Template),
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Width {
+    None,
+    Digits(usize),
+    Natural,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Placement {
+    Leading,
+    Trailing,
 }
 
 #[derive(Debug)]
 pub struct Attrs<'a> {
     pub rename: Option<String>,
+    pub rename_all: RenameRule,
+    pub rename_all_fields: RenameRule,
     pub annotate: Option<&'a Attribute>,
     pub format: Format,
     pub comment: Comment,
+    pub placement: Placement,
+    pub width: Width,
+    pub group: Option<usize>,
+    // The doc comment, and whether the doc comments of the container and its
+    // members are used as their comments.
+    pub doc: Option<String>,
+    pub doc_comments: bool,
 }
 
-pub fn get(input: &[Attribute]) -> Result<Attrs> {
+impl Attrs<'_> {
+    // Uses the doc comment as the comment unless there is another comment.
+    pub fn use_doc(&mut self) {
+        if self.comment == Comment::None && self.doc.is_some() {
+            self.comment = Comment::Doc;
+        }
+    }
+}
+
+pub fn get(input: &[Attribute]) -> Result<Attrs<'_>> {
     let mut attrs = Attrs {
         rename: None,
+        rename_all: RenameRule::None,
+        rename_all_fields: RenameRule::None,
         annotate: None,
         format: Format::None,
         comment: Comment::None,
+        placement: Placement::Leading,
+        width: Width::None,
+        group: None,
+        doc: None,
+        doc_comments: false,
     };
 
+    let mut doc = Vec::new();
     for attr in input {
-        if attr.path().is_ident("annotate") {
+        if attr.path().is_ident("doc") {
+            if let Meta::NameValue(nv) = &attr.meta {
+                if let Expr::Lit(ExprLit {
+                    lit: Lit::Str(s), ..
+                }) = &nv.value
+                {
+                    doc.push(s.value());
+                }
+            }
+        } else if attr.path().is_ident("annotate") {
             attrs.annotate = Some(attr);
             parse_annotate_attribute(&mut attrs, attr)?;
         } else if attr.path().is_ident("serde") {
-            // If there is a `serde` attribute, look for `rename = "..."`.
+            // If there is a `serde` attribute, look for the `rename` options
+            // which affect the names serde emits.
             parse_serde_attribute(&mut attrs, attr)?;
         }
     }
+    attrs.doc = doc_comment(&doc);
+    if attrs.comment == Comment::Doc && attrs.doc.is_none() {
+        return Err(Error::new_spanned(
+            attrs.annotate,
+            "`comment = doc` requires a doc comment",
+        ));
+    }
     Ok(attrs)
 }
 
+// Joins the lines of a doc comment, removing the indentation the lines have
+// in common (e.g. the space after `///`) and surrounding blank lines.  As in
+// rustdoc, the `*` starting every line of a block comment is removed.
+fn doc_comment(doc: &[String]) -> Option<String> {
+    let mut lines = doc
+        .iter()
+        .flat_map(|d| d.split('\n'))
+        .map(str::trim_end)
+        .collect::<Vec<_>>();
+    if lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .all(|l| l.trim_start().starts_with('*'))
+    {
+        for line in lines.iter_mut().filter(|l| !l.is_empty()) {
+            *line = &line.trim_start()[1..];
+        }
+    }
+    let indent = lines
+        .iter()
+        .filter(|l| !l.is_empty())
+        .map(|l| l.len() - l.trim_start().len())
+        .min()?;
+    let lines = lines
+        .iter()
+        .map(|l| l.get(indent..).unwrap_or_default())
+        .collect::<Vec<_>>();
+    let first = lines.iter().position(|l| !l.is_empty())?;
+    let last = lines.iter().rposition(|l| !l.is_empty())?;
+    Some(lines[first..=last].join("\n"))
+}
+
 fn function_call(input: ParseStream) -> Result<bool> {
     let content;
     let _result = parenthesized!(content in input);
@@ -61,6 +155,11 @@ fn function_call(input: ParseStream) -> Result<bool> {
 fn parse_annotate_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> Result<()> {
     syn::custom_keyword!(format);
     syn::custom_keyword!(comment);
+    syn::custom_keyword!(comment_template);
+    syn::custom_keyword!(placement);
+    syn::custom_keyword!(width);
+    syn::custom_keyword!(group);
+    syn::custom_keyword!(doc_comments);
 
     attr.parse_args_with(|input: ParseStream| {
         let mut more = true;
@@ -97,12 +196,52 @@ fn parse_annotate_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> R
                         Ok(false) => {
                             return Err(Error::new_spanned(attr, "Function args not permitted"));
                         }
+                        Err(_) if ident == "doc" => Comment::Doc,
                         Err(_) => Comment::Field(ident.clone()),
                     };
                 } else {
                     let comment: LitStr = input.parse()?;
                     attrs.comment = Comment::Static(comment.value());
                 }
+            } else if input.peek(comment_template) {
+                let _kw = input.parse::<comment_template>()?;
+                let _eq: Token![=] = input.parse()?;
+                let template: LitStr = input.parse()?;
+                attrs.comment = Comment::Template(Template::parse(&template)?);
+            } else if input.peek(doc_comments) {
+                let _kw = input.parse::<doc_comments>()?;
+                attrs.doc_comments = true;
+            } else if input.peek(placement) {
+                let _kw = input.parse::<placement>()?;
+                let _eq: Token![=] = input.parse()?;
+                let ident: Ident = input.parse()?;
+                attrs.placement = match ident.to_string().as_str() {
+                    "leading" => Placement::Leading,
+                    "trailing" => Placement::Trailing,
+                    _ => return Err(Error::new_spanned(ident, "unknown placement")),
+                };
+            } else if input.peek(width) {
+                let _kw = input.parse::<width>()?;
+                let _eq: Token![=] = input.parse()?;
+                attrs.width = if input.peek(Ident) {
+                    let ident: Ident = input.parse()?;
+                    if ident != "natural" {
+                        return Err(Error::new_spanned(ident, "unknown width"));
+                    }
+                    Width::Natural
+                } else {
+                    let digits: LitInt = input.parse()?;
+                    Width::Digits(digits.base10_parse()?)
+                };
+            } else if input.peek(group) {
+                let _kw = input.parse::<group>()?;
+                let _eq: Token![=] = input.parse()?;
+                let digits: LitInt = input.parse()?;
+                let n = digits.base10_parse()?;
+                if n == 0 {
+                    return Err(Error::new_spanned(digits, "group must be at least 1"));
+                }
+                attrs.group = Some(n);
             } else {
                 return Err(Error::new_spanned(attr, "parse error"));
             }
@@ -125,16 +264,54 @@ fn parse_serde_attribute<'a>(attrs: &mut Attrs<'a>, attr: &'a Attribute) -> Resu
                     return Err(cursor.error("no `rename` found"));
                 };
                 match &tt {
-                    TokenTree::Ident(r) if r == "rename" => Ok(((true), next)),
-                    _ => Ok(((false), next)),
+                    TokenTree::Ident(r)
+                        if r == "rename" || r == "rename_all" || r == "rename_all_fields" =>
+                    {
+                        Ok((Some(r.to_string()), next))
+                    }
+                    _ => Ok((None, next)),
                 }
             })?;
-            if found {
-                let _eq: Token![=] = input.parse()?;
-                let name: LitStr = input.parse()?;
-                attrs.rename = Some(name.value());
+            let Some(option) = found else {
+                continue;
+            };
+            let Some(name) = serialize_name(input)? else {
+                continue;
+            };
+            match option.as_str() {
+                "rename" => attrs.rename = Some(name.value()),
+                "rename_all" => attrs.rename_all = RenameRule::from_lit(&name)?,
+                _ => attrs.rename_all_fields = RenameRule::from_lit(&name)?,
             }
         }
         Ok(())
     })
 }
+
+// Parses the value of a serde `rename` option: either `= "name"` or
+// `(serialize = "name", deserialize = "name")`.  Only the serialize name
+// matters for annotations.
+fn serialize_name(input: ParseStream) -> Result<Option<LitStr>> {
+    syn::custom_keyword!(serialize);
+
+    if input.peek(Token![=]) {
+        let _eq: Token![=] = input.parse()?;
+        return Ok(Some(input.parse()?));
+    }
+    let content;
+    let _paren = parenthesized!(content in input);
+    let mut name = None;
+    while !content.is_empty() {
+        let is_serialize = content.peek(serialize);
+        let _kw: Ident = content.parse()?;
+        let _eq: Token![=] = content.parse()?;
+        let value: LitStr = content.parse()?;
+        if is_serialize {
+            name = Some(value);
+        }
+        if !content.is_empty() {
+            let _comma: Token![,] = content.parse()?;
+        }
+    }
+    Ok(name)
+}
@@ -1,15 +1,17 @@
 use crate::ast::{Enum, Field, Input, Struct, Variant};
-use crate::attr::{Attrs, Comment, Format};
-use proc_macro2::TokenStream;
+use crate::attr::{Attrs, Comment, Format, Placement, Width};
+use crate::case::RenameRule;
+use proc_macro2::{Span, TokenStream};
 use quote::quote;
-use syn::{DeriveInput, Index, Member, Result};
+use syn::ext::IdentExt;
+use syn::{DeriveInput, Error, GenericParam, Ident, Index, Member, Result};
 
 pub fn derive(node: &DeriveInput) -> Result<TokenStream> {
     let input = Input::from_syn(node)?;
 
     Ok(match input {
-        Input::Struct(input) => impl_struct(input),
-        Input::Enum(input) => impl_enum(input),
+        Input::Struct(input) => impl_struct(input)?,
+        Input::Enum(input) => impl_enum(input)?,
     })
 }
 
@@ -28,63 +30,196 @@ fn impl_format(a: &Attrs) -> TokenStream {
     }
 }
 
-fn impl_field_format(fields: &[Field]) -> Vec<TokenStream> {
+fn has_digits(a: &Attrs) -> bool {
+    a.width != Width::None || a.group.is_some()
+}
+
+fn impl_digits(a: &Attrs) -> TokenStream {
+    if !has_digits(a) {
+        return quote! { None };
+    }
+    let width = match a.width {
+        Width::None => quote! { None },
+        Width::Digits(n) => quote! { Some(Width::Digits(#n)) },
+        Width::Natural => quote! { Some(Width::Natural) },
+    };
+    let group = match a.group {
+        Some(n) => quote! { Some(#n) },
+        None => quote! { None },
+    };
+    quote! { Some(Digits { width: #width, group: #group }) }
+}
+
+// Returns the `MemberId` pattern matching the name serde gives the field.
+fn member_id(f: &Field, rule: RenameRule) -> TokenStream {
+    match &f.member {
+        Member::Named(id) => {
+            let id = f
+                .attrs
+                .rename
+                .clone()
+                .unwrap_or_else(|| rule.apply_to_field(&id.unraw().to_string()));
+            quote! { MemberId::Name(#id) }
+        }
+        Member::Unnamed(Index { index: i, .. }) => {
+            quote! { MemberId::Index(#i) }
+        }
+    }
+}
+
+fn impl_field_format(fields: &[Field], rule: RenameRule) -> Vec<TokenStream> {
     fields
         .iter()
         .map(|f| {
             let format = impl_format(&f.attrs);
-            match &f.member {
-                Member::Named(id) => {
-                    let id = id.to_string();
-                    quote! { MemberId::Name(#id) => #format }
-                }
-                Member::Unnamed(Index { index: i, .. }) => {
-                    quote! { MemberId::Index(#i) => #format }
-                }
-            }
+            let id = member_id(f, rule);
+            quote! { #id => #format }
         })
         .collect::<Vec<_>>()
 }
 
-fn impl_comment(a: &Attrs) -> TokenStream {
+// The fields a comment template may refer to by name.
+enum Scope<'a> {
+    // The fields of a struct, which are fields of `self`.
+    Struct(&'a [Field<'a>]),
+    // The fields of an enum variant, which are bound by matching `self`.
+    Variant(&'a Ident, &'a [Field<'a>]),
+    // No fields (e.g. for the comment on an enum).
+    None,
+}
+
+// Generates the comment for `a`.  When `statics` is set, the comment must
+// not depend on the value, so comments computed from `self` are omitted.
+fn impl_comment(a: &Attrs, statics: bool, scope: &Scope) -> TokenStream {
     match &a.comment {
         Comment::None => quote! { None },
+        Comment::Field(_) | Comment::Function(_) | Comment::Template(_) if statics => {
+            quote! { None }
+        }
         Comment::Static(s) => quote! {
             Some(#s.to_string())
         },
+        Comment::Doc => {
+            let doc = a.doc.as_deref().unwrap_or_default();
+            quote! {
+                Some(#doc.to_string())
+            }
+        }
         Comment::Field(id) => quote! {
             Some(self.#id.to_string())
         },
         Comment::Function(id) => quote! {
             self.#id()
         },
+        Comment::Template(t) => {
+            let named = |fields: &'_ [Field]| {
+                let names = fields.iter().filter_map(|f| match &f.member {
+                    Member::Named(id) => Some(id),
+                    Member::Unnamed(_) => None,
+                });
+                t.fields(names.collect::<Vec<_>>())
+            };
+            let bindings = match scope {
+                Scope::Struct(fields) => {
+                    let used = named(fields);
+                    quote! { #(let #used = &self.#used;)* }
+                }
+                Scope::Variant(variant, fields) => {
+                    let used = named(fields);
+                    if used.is_empty() {
+                        quote! {}
+                    } else {
+                        quote! {
+                            let Self::#variant { #(#used,)* .. } = self else {
+                                return None;
+                            };
+                        }
+                    }
+                }
+                Scope::None => quote! {},
+            };
+            let format = t.expand();
+            quote! {{
+                #bindings
+                Some(#format)
+            }}
+        }
     }
 }
 
-fn impl_field_comment(fields: &[Field]) -> Vec<TokenStream> {
+fn impl_field_comment(
+    fields: &[Field],
+    rule: RenameRule,
+    statics: bool,
+    scope: &Scope,
+) -> Vec<TokenStream> {
     fields
         .iter()
         .map(|f| {
-            let comment = impl_comment(&f.attrs);
-            match &f.member {
-                Member::Named(id) => {
-                    let id = id.to_string();
-                    quote! { MemberId::Name(#id) => #comment }
-                }
-                Member::Unnamed(Index { index: i, .. }) => {
-                    quote! { MemberId::Index(#i) => #comment }
-                }
-            }
+            let comment = impl_comment(&f.attrs, statics, scope);
+            let id = member_id(f, rule);
+            quote! { #id => #comment }
+        })
+        .collect::<Vec<_>>()
+}
+
+// Generates the match arms for the fields with trailing comments.  The
+// placement doesn't depend on the value, so it is the same for `statics`.
+fn impl_field_placement(fields: &[Field], rule: RenameRule) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .filter(|f| f.attrs.placement == Placement::Trailing)
+        .map(|f| {
+            let id = member_id(f, rule);
+            quote! { #id => Placement::Trailing }
         })
         .collect::<Vec<_>>()
 }
 
-fn impl_variants(variants: &[Variant]) -> (Vec<TokenStream>, Vec<TokenStream>) {
-    let formats = variants
+// Generates the match arms for the fields with digit annotations.
+fn impl_field_digits(fields: &[Field], rule: RenameRule) -> Vec<TokenStream> {
+    fields
+        .iter()
+        .filter(|f| has_digits(&f.attrs))
+        .map(|f| {
+            let digits = impl_digits(&f.attrs);
+            let id = member_id(f, rule);
+            quote! { #id => #digits }
+        })
+        .collect::<Vec<_>>()
+}
+
+// Returns the name serde gives the variant and the rule serde applies to
+// the names of its fields.
+fn variant_names(input: &Enum, v: &Variant) -> (String, RenameRule) {
+    let name = v.attrs.rename.clone().unwrap_or_else(|| {
+        input
+            .attrs
+            .rename_all
+            .apply_to_variant(&v.ident.unraw().to_string())
+    });
+    let rule = match v.attrs.rename_all {
+        RenameRule::None => input.attrs.rename_all_fields,
+        rule => rule,
+    };
+    (name, rule)
+}
+
+fn impl_variants(
+    input: &Enum,
+    statics: bool,
+) -> (
+    Vec<TokenStream>,
+    Vec<TokenStream>,
+    Vec<TokenStream>,
+    Vec<TokenStream>,
+) {
+    let formats = input
+        .variants
         .iter()
         .map(|v| {
-            let variant = v.ident.to_string();
-            let formats = impl_field_format(&v.fields);
+            let (variant, rule) = variant_names(input, v);
+            let formats = impl_field_format(&v.fields, rule);
             let vformat = impl_format(&v.attrs);
             quote! {
                 #variant => match field {
@@ -95,12 +230,14 @@ fn impl_variants(variants: &[Variant]) -> (Vec<TokenStream>, Vec<TokenStream>) {
             }
         })
         .collect::<Vec<_>>();
-    let comments = variants
+    let comments = input
+        .variants
         .iter()
         .map(|v| {
-            let variant = v.ident.to_string();
-            let comments = impl_field_comment(&v.fields);
-            let vcomment = impl_comment(&v.attrs);
+            let (variant, rule) = variant_names(input, v);
+            let scope = Scope::Variant(&v.ident, &v.fields);
+            let comments = impl_field_comment(&v.fields, rule, statics, &scope);
+            let vcomment = impl_comment(&v.attrs, statics, &scope);
             quote! {
                 #variant => match field {
                     MemberId::Variant => #vcomment,
@@ -110,89 +247,258 @@ fn impl_variants(variants: &[Variant]) -> (Vec<TokenStream>, Vec<TokenStream>) {
             }
         })
         .collect::<Vec<_>>();
+    let placements = input
+        .variants
+        .iter()
+        .map(|v| {
+            let (variant, rule) = variant_names(input, v);
+            let mut placements = impl_field_placement(&v.fields, rule);
+            if v.attrs.placement == Placement::Trailing {
+                placements.push(quote! { MemberId::Variant => Placement::Trailing });
+            }
+            quote! {
+                #variant => match field {
+                    #(#placements,)*
+                    _ => Placement::Leading,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+    let digits = input
+        .variants
+        .iter()
+        .map(|v| {
+            let (variant, rule) = variant_names(input, v);
+            let mut digits = impl_field_digits(&v.fields, rule);
+            if has_digits(&v.attrs) {
+                let vdigits = impl_digits(&v.attrs);
+                digits.push(quote! { MemberId::Variant => #vdigits });
+            }
+            quote! {
+                #variant => match field {
+                    #(#digits,)*
+                    _ => None,
+                }
+            }
+        })
+        .collect::<Vec<_>>();
 
-    (formats, comments)
+    (formats, comments, placements, digits)
 }
 
-fn impl_struct(input: Struct) -> TokenStream {
-    let formats = impl_field_format(&input.fields);
-    let comments = impl_field_comment(&input.fields);
-    let name = &input.ident;
-    let name_str = name.to_string();
-    let typename = input.attrs.rename.as_deref().unwrap_or(name_str.as_str());
-    quote! {
-        const _: () = {
-            extern crate serde_annotate;
-            use serde_annotate::annotate::{Annotate, Format, MemberId, private};
+// Generates the match arms for the annotations on the container itself.
+fn impl_container(
+    a: &Attrs,
+    statics: bool,
+    scope: &Scope,
+) -> (TokenStream, TokenStream, TokenStream, TokenStream) {
+    let format = impl_format(a);
+    let digits = impl_digits(a);
+    let comment = impl_comment(a, statics, scope);
+    let placement = match a.placement {
+        Placement::Leading => quote! { Placement::Leading },
+        Placement::Trailing => quote! { Placement::Trailing },
+    };
+    (
+        quote! { MemberId::Container => #format },
+        quote! { MemberId::Container => #comment },
+        quote! { MemberId::Container => #placement },
+        quote! { MemberId::Container => #digits },
+    )
+}
 
-            impl Annotate for #name {
-                fn format(&self, _variant: Option<&str>, field: &MemberId) -> Option<Format> {
-                    match field {
-                        #(#formats,)*
-                        _ => None,
-                    }
-                }
-                fn comment(&self, _variant: Option<&str>, field: &MemberId) -> Option<String> {
-                    match field {
-                        #(#comments,)*
-                        _ => None,
-                    }
-                }
+fn struct_methods(input: &Struct, statics: bool) -> TokenStream {
+    let formats = impl_field_format(&input.fields, input.attrs.rename_all);
+    let scope = Scope::Struct(&input.fields);
+    let comments = impl_field_comment(&input.fields, input.attrs.rename_all, statics, &scope);
+    let placements = impl_field_placement(&input.fields, input.attrs.rename_all);
+    let digits = impl_field_digits(&input.fields, input.attrs.rename_all);
+    let (cformat, ccomment, cplacement, cdigits) = impl_container(&input.attrs, statics, &scope);
+    quote! {
+        fn format(&self, _variant: Option<&str>, field: &MemberId) -> Option<Format> {
+            match field {
+                #cformat,
+                #(#formats,)*
+                _ => None,
             }
-            impl #name {
-                unsafe fn into_annotate(object: *const ()) -> &'static dyn Annotate {
-                    // Safety: the caller is required to pass a pointer to an object
-                    // of this type to be cast into a `&dyn Annotate` reference.
-                    &*(object as *const #name)
-                }
+        }
+        fn comment(&self, _variant: Option<&str>, field: &MemberId) -> Option<String> {
+            match field {
+                #ccomment,
+                #(#comments,)*
+                _ => None,
             }
-            private::inventory::submit! {
-                unsafe {
-                    private::Annotator::new(#typename, #name::into_annotate)
-                }
+        }
+        fn placement(&self, _variant: Option<&str>, field: &MemberId) -> Placement {
+            match field {
+                #cplacement,
+                #(#placements,)*
+                _ => Placement::Leading,
             }
-        };
+        }
+        fn digits(&self, _variant: Option<&str>, field: &MemberId) -> Option<Digits> {
+            match field {
+                #cdigits,
+                #(#digits,)*
+                _ => None,
+            }
+        }
     }
 }
 
-fn impl_enum(input: Enum) -> TokenStream {
-    let (formats, comments) = impl_variants(&input.variants);
-    let name = &input.ident;
-    let name_str = name.to_string();
+fn impl_struct(input: Struct) -> Result<TokenStream> {
+    let methods = struct_methods(&input, false);
+    let static_methods = struct_methods(&input, true);
+    let dynamic = dynamic_comment(&input.attrs)
+        .or_else(|| input.fields.iter().find_map(|f| dynamic_comment(&f.attrs)));
+    let name_str = input.ident.to_string();
     let typename = input.attrs.rename.as_deref().unwrap_or(name_str.as_str());
+    impl_annotate(input.original, typename, methods, static_methods, dynamic)
+}
+
+fn enum_methods(input: &Enum, statics: bool) -> TokenStream {
+    let (formats, comments, placements, digits) = impl_variants(input, statics);
+    let (cformat, ccomment, cplacement, cdigits) =
+        impl_container(&input.attrs, statics, &Scope::None);
     quote! {
+        fn format(&self, variant: Option<&str>, field: &MemberId) -> Option<Format> {
+            let Some(variant) = variant else {
+                return match field {
+                    #cformat,
+                    _ => None,
+                };
+            };
+            match variant {
+                #(#formats,)*
+                _ => None,
+            }
+        }
+        fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<String> {
+            let Some(variant) = variant else {
+                return match field {
+                    #ccomment,
+                    _ => None,
+                };
+            };
+            match variant {
+                #(#comments,)*
+                _ => None,
+            }
+        }
+        fn placement(&self, variant: Option<&str>, field: &MemberId) -> Placement {
+            let Some(variant) = variant else {
+                return match field {
+                    #cplacement,
+                    _ => Placement::Leading,
+                };
+            };
+            match variant {
+                #(#placements,)*
+                _ => Placement::Leading,
+            }
+        }
+        fn digits(&self, variant: Option<&str>, field: &MemberId) -> Option<Digits> {
+            let Some(variant) = variant else {
+                return match field {
+                    #cdigits,
+                    _ => None,
+                };
+            };
+            match variant {
+                #(#digits,)*
+                _ => None,
+            }
+        }
+    }
+}
+
+fn impl_enum(input: Enum) -> Result<TokenStream> {
+    let methods = enum_methods(&input, false);
+    let static_methods = enum_methods(&input, true);
+    let dynamic = dynamic_comment(&input.attrs).or_else(|| {
+        input.variants.iter().find_map(|v| {
+            dynamic_comment(&v.attrs)
+                .or_else(|| v.fields.iter().find_map(|f| dynamic_comment(&f.attrs)))
+        })
+    });
+    let name_str = input.ident.to_string();
+    let typename = input.attrs.rename.as_deref().unwrap_or(name_str.as_str());
+    impl_annotate(input.original, typename, methods, static_methods, dynamic)
+}
+
+// Returns the identifier of a comment which must be computed from `self`.
+fn dynamic_comment(a: &Attrs) -> Option<Span> {
+    match &a.comment {
+        Comment::Field(id) | Comment::Function(id) => Some(id.span()),
+        Comment::Template(t) => Some(t.lit.span()),
+        _ => None,
+    }
+}
+
+fn impl_annotate(
+    node: &DeriveInput,
+    typename: &str,
+    methods: TokenStream,
+    static_methods: TokenStream,
+    dynamic: Option<Span>,
+) -> Result<TokenStream> {
+    let name = &node.ident;
+    let (impl_generics, ty_generics, where_clause) = node.generics.split_for_impl();
+    let lifetimes_only = node
+        .generics
+        .params
+        .iter()
+        .all(|p| matches!(p, GenericParam::Lifetime(_)));
+
+    let annotator = if lifetimes_only {
+        // The registered type is the `'static` form of the type.  This is
+        // sound because the derived implementation is generic over every
+        // lifetime, so it can't rely on borrows living for `'static`.
+        let lifetimes = node.generics.lifetimes().map(|_| quote! { 'static });
+        let static_ty = if node.generics.params.is_empty() {
+            quote! { #name }
+        } else {
+            quote! { #name<#(#lifetimes),*> }
+        };
+        quote! {
+            unsafe { private::Annotator::new::<#static_ty>(#typename, &__SerdeAnnotateStatic) }
+        }
+    } else {
+        // The registry is keyed by the serde name, which is shared by every
+        // monomorphization of a generic type.  We can't register all of
+        // them, so only register annotations which don't depend on the type
+        // parameters.
+        if let Some(span) = dynamic {
+            return Err(Error::new(
+                span,
+                "comments computed from fields or functions are not supported on types with generic type or const parameters",
+            ));
+        }
+        quote! {
+            private::Annotator::new_static(#typename, &__SerdeAnnotateStatic)
+        }
+    };
+
+    Ok(quote! {
         const _: () = {
             extern crate serde_annotate;
-            use serde_annotate::annotate::{Annotate, Format, MemberId, private};
-
-            impl Annotate for #name {
-                fn format(&self, variant: Option<&str>, field: &MemberId) -> Option<Format> {
-                    let variant = variant?;
-                    match variant {
-                        #(#formats,)*
-                        _ => None,
-                    }
-                }
-                fn comment(&self, variant: Option<&str>, field: &MemberId) -> Option<String> {
-                    let variant = variant?;
-                    match variant {
-                        #(#comments,)*
-                        _ => None,
-                    }
-                }
+            #[allow(unused_imports)]
+            use serde_annotate::annotate::{
+                Annotate, Digits, Format, MemberId, Placement, Width, private,
+            };
+
+            impl #impl_generics Annotate for #name #ty_generics #where_clause {
+                #methods
             }
-            impl #name {
-                unsafe fn into_annotate(object: *const ()) -> &'static dyn Annotate {
-                    // Safety: the caller is required to pass a pointer to an object
-                    // of this type to be cast into a `&dyn Annotate` reference.
-                    &*(object as *const #name)
-                }
+            // The annotations which don't depend on the value, for values
+            // whose type can't be identified when they're serialized.
+            struct __SerdeAnnotateStatic;
+            impl Annotate for __SerdeAnnotateStatic {
+                #static_methods
             }
             private::inventory::submit! {
-                unsafe {
-                    private::Annotator::new(#typename, #name::into_annotate)
-                }
+                #annotator
             }
         };
-    }
+    })
 }
@@ -2,7 +2,9 @@ extern crate proc_macro;
 
 mod ast;
 mod attr;
+mod case;
 mod expand;
+mod template;
 
 use proc_macro::TokenStream;
 use syn::{parse_macro_input, DeriveInput};
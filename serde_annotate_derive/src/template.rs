@@ -0,0 +1,187 @@
+use proc_macro2::{Group, Span, TokenStream, TokenTree};
+use quote::quote;
+use std::ops::Range;
+use syn::{Error, Ident, LitStr, Result};
+
+/// A comment template: a format string whose placeholders hold expressions
+/// over the fields of the annotated value (e.g. `"{nph + 1} headers"`),
+/// given with `comment_template = "..."`.
+#[derive(Debug)]
+pub struct Template {
+    pub lit: LitStr,
+    format: String,
+    args: Vec<TokenStream>,
+}
+
+impl PartialEq for Template {
+    fn eq(&self, other: &Self) -> bool {
+        self.lit == other.lit
+    }
+}
+
+impl Template {
+    /// Parses a comment template literal.
+    pub fn parse(lit: &LitStr) -> Result<Self> {
+        let value = lit.value();
+        let mut format = String::new();
+        let mut args = Vec::new();
+        let mut chars = value.char_indices().peekable();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' if matches!(chars.peek(), Some((_, '{'))) => {
+                    chars.next();
+                    format.push_str("{{");
+                }
+                '}' if matches!(chars.peek(), Some((_, '}'))) => {
+                    chars.next();
+                    format.push_str("}}");
+                }
+                '{' => {
+                    let Some(placeholder) = placeholder(&mut chars) else {
+                        let span = subspan(lit, &value, i..value.len());
+                        return Err(Error::new(span, "unterminated `{` in comment template"));
+                    };
+                    // Errors in the expression point at the placeholder.
+                    let span = subspan(lit, &value, i + 1..i + 1 + placeholder.len());
+                    let (expr, spec) = split_spec(&placeholder);
+                    let tokens = expr.parse::<TokenStream>().map_err(|e| {
+                        Error::new(span, format!("invalid expression `{}`: {}", expr, e))
+                    })?;
+                    if tokens.is_empty() {
+                        return Err(Error::new(span, "empty `{}` in comment template"));
+                    }
+                    let tokens = respan(tokens, span);
+                    match spec {
+                        Some("fourcc") => {
+                            format.push_str("{}");
+                            args.push(quote! { private::fourcc(&(#tokens)) });
+                        }
+                        Some(spec) => {
+                            format.push_str(&format!("{{:{}}}", spec));
+                            args.push(quote! { (#tokens) });
+                        }
+                        None => {
+                            format.push_str("{}");
+                            args.push(quote! { (#tokens) });
+                        }
+                    }
+                }
+                '}' => {
+                    return Err(Error::new(
+                        subspan(lit, &value, i..i + 1),
+                        "unmatched `}` in comment template; use `}}` for a literal brace",
+                    ));
+                }
+                c => format.push(c),
+            }
+        }
+        Ok(Template {
+            lit: lit.clone(),
+            format,
+            args,
+        })
+    }
+
+    /// Returns the identifiers in the template's expressions which name
+    /// one of `fields`.
+    pub fn fields<'f>(&self, fields: impl IntoIterator<Item = &'f Ident>) -> Vec<Ident> {
+        let mut idents = Vec::new();
+        for arg in &self.args {
+            variables(arg.clone(), &mut idents);
+        }
+        fields
+            .into_iter()
+            .filter(|f| idents.iter().any(|i| i == *f))
+            .cloned()
+            .collect()
+    }
+
+    /// Generates the expression which formats the comment.
+    pub fn expand(&self) -> TokenStream {
+        let format = LitStr::new(&self.format, self.lit.span());
+        let args = &self.args;
+        quote! { format!(#format, #(#args),*) }
+    }
+}
+
+// Reads the contents of a placeholder up to its closing brace.  Braces
+// within the placeholder (e.g. a block expression) must be balanced.
+fn placeholder(chars: &mut impl Iterator<Item = (usize, char)>) -> Option<String> {
+    let mut depth = 0;
+    let mut s = String::new();
+    loop {
+        match chars.next()?.1 {
+            '}' if depth == 0 => return Some(s),
+            c => {
+                match c {
+                    '{' => depth += 1,
+                    '}' => depth -= 1,
+                    _ => {}
+                }
+                s.push(c);
+            }
+        }
+    }
+}
+
+// Splits a placeholder into its expression and format spec at the first
+// `:` which isn't part of a path separator.
+fn split_spec(placeholder: &str) -> (&str, Option<&str>) {
+    let bytes = placeholder.as_bytes();
+    for (i, &b) in bytes.iter().enumerate() {
+        let path = (i > 0 && bytes[i - 1] == b':') || bytes.get(i + 1) == Some(&b':');
+        if b == b':' && !path {
+            return (placeholder[..i].trim(), Some(placeholder[i + 1..].trim()));
+        }
+    }
+    (placeholder.trim(), None)
+}
+
+// Returns the span of `range` of the literal's value, or the span of the
+// whole literal where the compiler can't point within it (on stable, or if
+// the literal has escapes).
+fn subspan(lit: &LitStr, value: &str, range: Range<usize>) -> Span {
+    let token = lit.token();
+    let repr = token.to_string();
+    repr.find('"')
+        .map(|quote| quote + 1)
+        .filter(|&start| repr[start..].starts_with(value))
+        .and_then(|start| token.subspan(start + range.start..start + range.end))
+        .unwrap_or_else(|| lit.span())
+}
+
+// Sets the span of every token in `tokens` to `span`.
+fn respan(tokens: TokenStream, span: Span) -> TokenStream {
+    tokens
+        .into_iter()
+        .map(|mut tt| {
+            if let TokenTree::Group(g) = &tt {
+                let mut group = Group::new(g.delimiter(), respan(g.stream(), span));
+                group.set_span(span);
+                tt = TokenTree::Group(group);
+            } else {
+                tt.set_span(span);
+            }
+            tt
+        })
+        .collect()
+}
+
+// Collects the identifiers in `tokens` which may be variables: those which
+// aren't a field or method (after `.`) or a path segment (after `::`).
+fn variables(tokens: TokenStream, idents: &mut Vec<Ident>) {
+    let mut prior = None;
+    for tt in tokens {
+        match &tt {
+            TokenTree::Group(g) => variables(g.stream(), idents),
+            TokenTree::Ident(id) if !matches!(prior, Some('.') | Some(':')) => {
+                idents.push(id.clone())
+            }
+            _ => {}
+        }
+        prior = match tt {
+            TokenTree::Punct(p) => Some(p.as_char()),
+            _ => None,
+        };
+    }
+}